@@ -0,0 +1,478 @@
+//! A fingerprint-echo server, in the spirit of tls.peet.ws, for checking a client's
+//! TLS and HTTP/2 fingerprint without leaving the machine.
+//!
+//! The server captures each ClientHello with the `Acceptor` API, completes the
+//! handshake, and answers the first request with a JSON description of what the
+//! client sent:
+//!
+//! - `tls`: JA3 and JA4, cipher suites and extensions in wire order, GREASE
+//!   positions, groups, key shares, signature algorithms, ALPN and padding
+//! - `http2` (when `h2` was negotiated): the Akamai fingerprint, from the
+//!   SETTINGS, WINDOW_UPDATE and PRIORITY frames and the pseudo-header order
+//! - `http1` (otherwise): the request line and header order
+//!
+//! It generates its own CA and a certificate for `localhost` and `127.0.0.1`,
+//! and writes the CA certificate to `--ca-path` for clients to trust. With
+//! `--port 0` a free port is chosen; the address is printed on startup as
+//! `listening on <addr>`.
+//!
+//! Key material is only logged when the `SSLKEYLOGFILE` env var is set, to a
+//! log file (insecure) for debugging purposes. See [`rustls::KeyLog`] for more
+//! information.
+//!
+//! Usage: cargo r --bin fingerprint-echo -- --port 8443
+
+use core::error::Error as StdError;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+use clap::Parser;
+use rcgen::{Issuer, KeyPair};
+use rustls::crypto::Identity;
+use rustls::custls::{ClientHelloView, akamai_fingerprint_of_preface};
+use rustls::enums::ApplicationProtocol;
+use rustls::pki_types::PrivatePkcs8KeyDer;
+use rustls::server::{Acceptor, ServerConfig};
+use rustls_aws_lc_rs::DEFAULT_PROVIDER;
+use serde_json::{Value, json};
+
+fn main() -> Result<(), Box<dyn StdError>> {
+    let args = Args::parse();
+
+    if args.verbose {
+        env_logger::Builder::new()
+            .parse_filters("trace")
+            .init();
+    }
+
+    let (ca_pem, config) = echo_server_config()?;
+    fs::write(&args.ca_path, ca_pem)?;
+
+    let listener = TcpListener::bind((args.address.as_str(), args.port))?;
+    println!("listening on {}", listener.local_addr()?);
+    io::stdout().flush()?;
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let config = config.clone();
+        thread::spawn(move || {
+            if let Err(err) = serve(stream, config) {
+                eprintln!("connection failed: {err}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Capture the ClientHello, complete the handshake and answer one request.
+fn serve(mut stream: TcpStream, config: Arc<ServerConfig>) -> Result<(), Box<dyn StdError>> {
+    let mut acceptor = Acceptor::default();
+    let accepted = loop {
+        if acceptor.read_tls(&mut stream)? == 0 {
+            return Err("connection closed before the ClientHello".into());
+        }
+
+        match acceptor.accept() {
+            Ok(Some(accepted)) => break accepted,
+            Ok(None) => continue,
+            Err((err, mut alert)) => {
+                alert.write_all(&mut stream)?;
+                return Err(err.into());
+            }
+        }
+    };
+
+    let tls = tls_fingerprint(&accepted.client_hello().view()?);
+    let mut conn = match accepted.into_connection(config) {
+        Ok(conn) => conn,
+        Err((err, mut alert)) => {
+            alert.write_all(&mut stream)?;
+            return Err(err.into());
+        }
+    };
+    conn.complete_io(&mut stream)?;
+
+    let h2 = matches!(conn.alpn_protocol(), Some(ApplicationProtocol::Http2));
+    let mut tls_stream = rustls::Stream::new(&mut conn, &mut stream);
+    match h2 {
+        true => serve_h2(&mut tls_stream, tls)?,
+        false => serve_http1(&mut tls_stream, tls)?,
+    }
+
+    tls_stream.conn.send_close_notify();
+    tls_stream.flush()?;
+    Ok(())
+}
+
+/// Describe the ClientHello the way fingerprinting services do.
+fn tls_fingerprint(view: &ClientHelloView) -> Value {
+    let capture = view.to_browser_capture("");
+    json!({
+        "ja3": view.ja3_string(),
+        "ja3_hash": md5_hex(view.ja3_string().as_bytes()),
+        "ja4": view.ja4(&DEFAULT_PROVIDER),
+        "ja4_r": view.ja4_r(),
+        "legacy_version": view.legacy_version(),
+        "cipher_suites": view.cipher_suites(),
+        "extensions": view
+            .extensions()
+            .iter()
+            .map(|ext| json!({ "id": ext.extension_type, "name": ext.name }))
+            .collect::<Vec<_>>(),
+        "grease_cipher_positions": capture.grease_cipher_positions,
+        "grease_extension_positions": capture.grease_extension_positions,
+        "supported_versions": view.supported_versions(),
+        "supported_groups": view.supported_groups(),
+        "key_share_groups": capture.key_share_groups,
+        "signature_algorithms": capture.signature_algorithms,
+        "alpn": capture.alpn_protocols,
+        "padding_length": view.padding_length(),
+    })
+}
+
+/// MD5 as lowercase hex (RFC 1321), for the JA3 hash.
+///
+/// The crypto provider has no MD5, and JA3 only uses it as an identifier.
+fn md5_hex(data: &[u8]) -> String {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for chunk in padded.chunks_exact(64) {
+        let m = chunk
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect::<Vec<_>>();
+
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            // K[i] = floor(abs(sin(i + 1)) * 2^32)
+            let k = ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32;
+            let f = f
+                .wrapping_add(a)
+                .wrapping_add(k)
+                .wrapping_add(m[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(SHIFTS[(i / 16) * 4 + i % 4]));
+        }
+
+        for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    state
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn serve_http1(stream: &mut impl ReadWrite, tls: Value) -> Result<(), Box<dyn StdError>> {
+    let request = read_until(stream, |buf| {
+        buf.windows(4)
+            .any(|window| window == b"\r\n\r\n")
+    })?;
+    let request = String::from_utf8_lossy(&request);
+    let mut lines = request
+        .split("\r\n")
+        .take_while(|line| !line.is_empty());
+    let request_line = lines.next().unwrap_or_default();
+    let header_order = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+
+    let body = json!({
+        "http_version": "http/1.1",
+        "tls": tls,
+        "http1": {
+            "request_line": request_line,
+            "header_order": header_order,
+        },
+    })
+    .to_string();
+
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+fn serve_h2(stream: &mut impl ReadWrite, tls: Value) -> Result<(), Box<dyn StdError>> {
+    let request = read_until(stream, |buf| first_headers_frame(buf).is_some())?;
+    let (stream_id, header_block) =
+        first_headers_frame(&request).ok_or("no HEADERS frame in request")?;
+    let frames = akamai_fingerprint_of_preface(&request)?;
+    let pseudo_headers = pseudo_header_order(header_block);
+
+    let body = json!({
+        "http_version": "h2",
+        "tls": tls,
+        "http2": {
+            "akamai_fingerprint": format!("{frames}|{}", pseudo_headers.join(",")),
+            "frames": frames,
+            "pseudo_header_order": pseudo_headers,
+        },
+    })
+    .to_string();
+
+    let mut response = Vec::new();
+    // Our (empty) SETTINGS, then the acknowledgement of the client's.
+    write_frame(&mut response, FRAME_SETTINGS, 0, 0, &[]);
+    write_frame(&mut response, FRAME_SETTINGS, FLAG_ACK, 0, &[]);
+
+    // `:status: 200` is static table entry 8; `content-type` is a literal
+    // without indexing, with the name from static table entry 31.
+    let mut headers = vec![0x88, 0x0f, 0x10, CONTENT_TYPE_JSON.len() as u8];
+    headers.extend_from_slice(CONTENT_TYPE_JSON);
+    write_frame(&mut response, FRAME_HEADERS, FLAG_END_HEADERS, stream_id, &headers);
+
+    let chunks = body
+        .as_bytes()
+        .chunks(DEFAULT_MAX_FRAME_SIZE)
+        .collect::<Vec<_>>();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let flags = match i + 1 == chunks.len() {
+            true => FLAG_END_STREAM,
+            false => 0,
+        };
+        write_frame(&mut response, FRAME_DATA, flags, stream_id, chunk);
+    }
+
+    stream.write_all(&response)?;
+    Ok(())
+}
+
+/// The stream ID and header block fragment of the first HEADERS frame.
+fn first_headers_frame(buf: &[u8]) -> Option<(u32, &[u8])> {
+    let mut rest = buf.strip_prefix(HTTP2_PREFACE)?;
+    while rest.len() >= FRAME_HEADER_LEN {
+        let len = u32::from_be_bytes([0, rest[0], rest[1], rest[2]]) as usize;
+        let payload = rest.get(FRAME_HEADER_LEN..FRAME_HEADER_LEN + len)?;
+
+        if rest[3] == FRAME_HEADERS {
+            let flags = rest[4];
+            let stream_id = u32::from_be_bytes([rest[5], rest[6], rest[7], rest[8]]) & 0x7fff_ffff;
+            let (pad_len, payload) = match flags & FLAG_PADDED != 0 {
+                true => (usize::from(*payload.first()?), payload.get(1..)?),
+                false => (0, payload),
+            };
+            let payload = payload.get(..payload.len().checked_sub(pad_len)?)?;
+            let block = match flags & FLAG_PRIORITY != 0 {
+                true => payload.get(5..)?,
+                false => payload,
+            };
+            return Some((stream_id, block));
+        }
+
+        rest = &rest[FRAME_HEADER_LEN + len..];
+    }
+    None
+}
+
+/// Pseudo-header order of a request header block, as Akamai letters (`m,a,s,p`).
+///
+/// Clients encode pseudo-headers with names from the HPACK static table, so the
+/// order can be read without decoding values or keeping a dynamic table.
+fn pseudo_header_order(block: &[u8]) -> Vec<&'static str> {
+    let mut hpack = Hpack(block);
+    let mut order = Vec::new();
+
+    while let Some(&first) = hpack.0.first() {
+        let (index, literal) = if first & 0x80 != 0 {
+            // Indexed header field
+            (hpack.int(7), false)
+        } else if first & 0x40 != 0 {
+            // Literal with incremental indexing
+            (hpack.int(6), true)
+        } else if first & 0x20 != 0 {
+            // Dynamic table size update
+            if hpack.int(5).is_none() {
+                break;
+            }
+            continue;
+        } else {
+            // Literal without indexing, or never indexed
+            (hpack.int(4), true)
+        };
+
+        let letter = match index {
+            Some(1) => "a",
+            Some(2 | 3) => "m",
+            Some(4 | 5) => "p",
+            Some(6 | 7) => "s",
+            // Pseudo-headers come first; stop at the first regular header.
+            _ => break,
+        };
+        if literal && hpack.skip_string().is_none() {
+            break;
+        }
+        order.push(letter);
+    }
+
+    order
+}
+
+/// Cursor over an HPACK header block.
+struct Hpack<'a>(&'a [u8]);
+
+impl Hpack<'_> {
+    /// Read an integer with a `prefix_bits`-bit prefix (RFC 7541 Section 5.1).
+    fn int(&mut self, prefix_bits: u32) -> Option<usize> {
+        let (&first, mut rest) = self.0.split_first()?;
+        let max = (1usize << prefix_bits) - 1;
+        let mut value = usize::from(first) & max;
+        if value == max {
+            let mut shift = 0;
+            loop {
+                let (&byte, tail) = rest.split_first()?;
+                rest = tail;
+                value = value.checked_add(usize::from(byte & 0x7f).checked_shl(shift)?)?;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+        }
+        self.0 = rest;
+        Some(value)
+    }
+
+    /// Skip a string literal, Huffman-coded or not (RFC 7541 Section 5.2).
+    fn skip_string(&mut self) -> Option<()> {
+        let len = self.int(7)?;
+        self.0 = self.0.get(len..)?;
+        Some(())
+    }
+}
+
+fn write_frame(out: &mut Vec<u8>, frame_type: u8, flags: u8, stream_id: u32, payload: &[u8]) {
+    out.extend_from_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+    out.push(frame_type);
+    out.push(flags);
+    out.extend_from_slice(&stream_id.to_be_bytes());
+    out.extend_from_slice(payload);
+}
+
+/// Read from `stream` until `done` accepts what has arrived.
+fn read_until(stream: &mut impl Read, done: impl Fn(&[u8]) -> bool) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    while !done(&buf) {
+        let len = stream.read(&mut chunk)?;
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed mid-request",
+            ));
+        }
+        buf.extend_from_slice(&chunk[..len]);
+        if buf.len() > MAX_REQUEST_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request too large",
+            ));
+        }
+    }
+    Ok(buf)
+}
+
+trait ReadWrite: Read + Write {}
+
+impl<T: Read + Write> ReadWrite for T {}
+
+/// Generate a CA and a server certificate for `localhost` and `127.0.0.1`.
+///
+/// Returns the CA certificate PEM and a server config offering `h2` and `http/1.1`.
+fn echo_server_config() -> Result<(String, Arc<ServerConfig>), Box<dyn StdError>> {
+    let alg = &rcgen::PKCS_ECDSA_P256_SHA256;
+    let mut ca_params = rcgen::CertificateParams::new(Vec::new())?;
+    ca_params
+        .distinguished_name
+        .push(rcgen::DnType::CommonName, "Fingerprint Echo CA");
+    ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    ca_params.key_usages = vec![
+        rcgen::KeyUsagePurpose::KeyCertSign,
+        rcgen::KeyUsagePurpose::DigitalSignature,
+    ];
+    let ca_key = KeyPair::generate_for(alg)?;
+    let ca_cert = ca_params.self_signed(&ca_key)?;
+    let ca = Issuer::new(ca_params, ca_key);
+
+    let mut ee_params =
+        rcgen::CertificateParams::new(vec!["localhost".to_string(), "127.0.0.1".to_string()])?;
+    ee_params.is_ca = rcgen::IsCa::NoCa;
+    ee_params.extended_key_usages = vec![rcgen::ExtendedKeyUsagePurpose::ServerAuth];
+    let ee_key = KeyPair::generate_for(alg)?;
+    let ee_cert = ee_params.signed_by(&ee_key, &ca)?;
+
+    let mut config = ServerConfig::builder(Arc::new(DEFAULT_PROVIDER))
+        .with_no_client_auth()
+        .with_single_cert(
+            Arc::new(Identity::from_cert_chain(vec![ee_cert.der().clone()])?),
+            PrivatePkcs8KeyDer::from(ee_key.serialize_der()).into(),
+        )?;
+    config.alpn_protocols = vec![ApplicationProtocol::Http2, ApplicationProtocol::Http11];
+    if env::var_os("SSLKEYLOGFILE").is_some() {
+        config.key_log = Arc::new(rustls::KeyLogFile::new());
+    }
+
+    Ok((ca_cert.pem(), Arc::new(config)))
+}
+
+const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+const FRAME_HEADER_LEN: usize = 9;
+const FRAME_DATA: u8 = 0x0;
+const FRAME_HEADERS: u8 = 0x1;
+const FRAME_SETTINGS: u8 = 0x4;
+const FLAG_END_STREAM: u8 = 0x1;
+const FLAG_ACK: u8 = 0x1;
+const FLAG_END_HEADERS: u8 = 0x4;
+const FLAG_PADDED: u8 = 0x8;
+const FLAG_PRIORITY: u8 = 0x20;
+const DEFAULT_MAX_FRAME_SIZE: usize = 16_384;
+const CONTENT_TYPE_JSON: &[u8] = b"application/json";
+const MAX_REQUEST_LEN: usize = 1 << 20;
+
+/// Runs a fingerprint-echo server on ADDRESS:PORT (default 127.0.0.1:8443).
+#[derive(Debug, Parser)]
+#[clap(version)]
+struct Args {
+    /// Listen on this port; 0 picks a free one
+    #[clap(long, default_value = "8443")]
+    port: u16,
+
+    /// Listen on this address
+    #[clap(long, default_value = "127.0.0.1")]
+    address: String,
+
+    /// Write the CA cert PEM to this path
+    #[clap(long, default_value = "echo-ca.pem")]
+    ca_path: String,
+
+    /// Emit log output
+    #[clap(long)]
+    verbose: bool,
+}
@@ -13,6 +13,8 @@ mod tests_with_ring {
     mod compress;
     #[path = "api/crypto.rs"]
     mod crypto;
+    #[path = "api/custls.rs"]
+    mod custls;
     #[path = "api/ffdhe.rs"]
     mod ffdhe;
     #[path = "api/io.rs"]
@@ -48,6 +50,8 @@ mod tests_with_aws_lc_rs {
     mod compress;
     #[path = "api/crypto.rs"]
     mod crypto;
+    #[path = "api/custls.rs"]
+    mod custls;
     #[path = "api/ffdhe.rs"]
     mod ffdhe;
     #[path = "api/io.rs"]
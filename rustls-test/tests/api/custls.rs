@@ -0,0 +1,757 @@
+//! Loopback tests of the ClientHellos custls templates put on the wire.
+
+#![allow(clippy::disallowed_types, clippy::duplicate_mod)]
+
+use std::sync::Arc;
+
+use rustls::client::UnbufferedClientConnection;
+use rustls::crypto::kx::NamedGroup;
+use rustls::crypto::{CipherSuite, SelectedCredential};
+use rustls::custls::capture::is_grease_value;
+use rustls::custls::{
+    BrowserTemplate, ClientHelloView, CustlsConfig, DefaultCustomizer, HelloRejection,
+    PaddingDistribution, RandomizationLevel, RetryPlanner, RetryStep, TargetKey, TemplateData,
+    lookup_template, register_template,
+};
+use rustls::error::{Error, PeerIncompatible};
+use rustls::internal::msgs::ExtensionType;
+use rustls::server::{ClientHello, ServerCredentialResolver};
+use rustls::unbuffered::{ConnectionState, EncodeError};
+use rustls::{ClientConfig, HandshakeKind, ServerConfig, ServerConnection};
+#[cfg(feature = "aws-lc-rs")]
+use rustls::{
+    ClientConnection,
+    client::{EchConfig, EchMode},
+    pki_types::EchConfigListBytes,
+};
+#[cfg(feature = "aws-lc-rs")]
+use rustls_aws_lc_rs::hpke::ALL_SUPPORTED_SUITES;
+#[cfg(feature = "aws-lc-rs")]
+use rustls_test::ClientConfigExt;
+use rustls_test::{
+    ErrorFromPeer, KeyType, do_handshake, do_handshake_until_error, make_client_config,
+    make_pair_for_arc_configs, make_server_config, make_server_config_with_kx_groups, server_name,
+    transfer,
+};
+
+use super::provider;
+
+const LEVELS: [RandomizationLevel; 4] = [
+    RandomizationLevel::None,
+    RandomizationLevel::Light,
+    RandomizationLevel::Medium,
+    RandomizationLevel::High,
+];
+
+/// Extensions the randomizer never moves from their template position.
+const PINNED_EXTENSIONS: [ExtensionType; 2] =
+    [ExtensionType::SupportedVersions, ExtensionType::KeyShare];
+
+/// Extensions the encoder always places last, in this order.
+const TRAILING_EXTENSIONS: [ExtensionType; 3] = [
+    ExtensionType::EncryptedClientHelloOuterExtensions,
+    ExtensionType::EncryptedClientHello,
+    ExtensionType::PreSharedKey,
+];
+
+#[test]
+fn templates_on_the_wire_follow_their_shape() {
+    let provider = provider::DEFAULT_PROVIDER;
+    let server_config = Arc::new(make_server_config(KeyType::Rsa2048, &provider));
+
+    for browser in browser_templates() {
+        for level in LEVELS {
+            let (client_config, template) = custls_client_config(browser.clone(), level);
+            let client_config = Arc::new(client_config);
+
+            let (mut client, mut server) =
+                make_pair_for_arc_configs(&client_config, &server_config);
+            do_handshake(&mut client, &mut server);
+            assert_eq!(client.handshake_kind(), Some(HandshakeKind::Full));
+            assert!(!client.sent_client_hellos().is_empty());
+            for hello in client.sent_client_hellos() {
+                check_hello(&hello.view().unwrap(), &template, level);
+            }
+        }
+    }
+}
+
+#[test]
+fn grease_and_padding_reach_the_wire() {
+    let provider = provider::DEFAULT_PROVIDER;
+    let server_config = Arc::new(make_server_config(KeyType::Rsa2048, &provider));
+
+    let mut template = lookup_template("chrome_130").unwrap();
+    template
+        .grease_pattern
+        .cipher_suite_probability = 1.0;
+    template
+        .grease_pattern
+        .extension_probability = 1.0;
+    template.padding_distribution = PaddingDistribution {
+        pmf: vec![(128, 1.0)],
+        min_length: 128,
+        max_length: 128,
+        power_of_2_bias: 0.0,
+    };
+    register_template("loopback_padded", template);
+
+    for level in LEVELS {
+        let browser = BrowserTemplate::Named("loopback_padded".to_string());
+        let (client_config, template) = custls_client_config(browser, level);
+        let client_config = Arc::new(client_config);
+
+        let (mut client, mut server) = make_pair_for_arc_configs(&client_config, &server_config);
+        do_handshake(&mut client, &mut server);
+        let view = client.sent_client_hellos()[0]
+            .view()
+            .unwrap();
+        check_hello(&view, &template, level);
+
+        assert_eq!(view.padding_length(), Some(128), "{level:?}");
+        let grease = match level {
+            RandomizationLevel::None => 0,
+            _ => 1,
+        };
+        assert_eq!(
+            view.cipher_suites().len() - without_grease(view.cipher_suites()).len(),
+            grease,
+            "{level:?}: GREASE cipher suites"
+        );
+        let extensions = view.extension_types();
+        assert_eq!(
+            extensions.len() - without_grease(&extensions).len(),
+            grease,
+            "{level:?}: GREASE extensions"
+        );
+    }
+}
+
+#[cfg(feature = "aws-lc-rs")]
+#[test]
+fn templates_shape_the_outer_hello_with_ech() {
+    let suite = ALL_SUPPORTED_SUITES[0];
+    let config_path = format!(
+        "tests/data/{:?}-{:?}-{:?}-echconfigs.bin",
+        suite.suite().kem,
+        suite.suite().sym.kdf_id,
+        suite.suite().sym.aead_id
+    );
+    let ech_config = EchConfig::new(
+        EchConfigListBytes::from(std::fs::read(&config_path).unwrap()),
+        &[suite],
+    )
+    .unwrap();
+    let ech = u16::from(ExtensionType::EncryptedClientHello);
+
+    for browser in browser_templates() {
+        for level in LEVELS {
+            let customizer = DefaultCustomizer::new_arc(
+                CustlsConfig::builder()
+                    .with_template(browser.clone())
+                    .with_randomization_level(level)
+                    .build(),
+            );
+            let template = customizer.template().unwrap().clone();
+            let mut config = ClientConfig::builder(provider::DEFAULT_TLS13_PROVIDER.into())
+                .with_ech(EchMode::Enable(ech_config.clone()))
+                .finish(KeyType::Rsa2048);
+            config.enable_custls(customizer);
+
+            // The outer hello is the one on the wire, and follows the template
+            let client =
+                ClientConnection::new(Arc::new(config), server_name("example.org")).unwrap();
+            let hello = client.sent_client_hellos()[0]
+                .view()
+                .unwrap();
+            assert!(
+                hello.extension_types().contains(&ech),
+                "{browser:?} at {level:?}: no ECH offer"
+            );
+            check_hello(&hello, &template, level);
+        }
+    }
+}
+
+#[test]
+fn templates_offer_psk_last_when_resuming() {
+    let provider = provider::DEFAULT_PROVIDER;
+    let server_config = Arc::new(make_server_config(KeyType::Rsa2048, &provider));
+    let psk = u16::from(ExtensionType::PreSharedKey);
+
+    for browser in browser_templates() {
+        for level in LEVELS {
+            let (client_config, template) = custls_client_config(browser.clone(), level);
+            let client_config = Arc::new(client_config);
+
+            let (mut client, mut server) =
+                make_pair_for_arc_configs(&client_config, &server_config);
+            do_handshake(&mut client, &mut server);
+
+            // The second connection offers the ticket the first one obtained
+            let (mut client, mut server) =
+                make_pair_for_arc_configs(&client_config, &server_config);
+            do_handshake(&mut client, &mut server);
+            for hello in client.sent_client_hellos() {
+                check_hello(&hello.view().unwrap(), &template, level);
+            }
+
+            if !template.handshake_behavior.psk_dhe_ke {
+                continue;
+            }
+
+            assert_eq!(
+                client.handshake_kind(),
+                Some(HandshakeKind::Resumed),
+                "{} at {level:?}",
+                template.name
+            );
+            let hello = client
+                .sent_client_hellos()
+                .last()
+                .unwrap()
+                .view()
+                .unwrap();
+            assert_eq!(
+                hello.extension_types().last(),
+                Some(&psk),
+                "{} at {level:?}: pre_shared_key must be last",
+                template.name
+            );
+        }
+    }
+}
+
+#[test]
+fn unrandomized_templates_send_identical_shapes() {
+    let provider = provider::DEFAULT_PROVIDER;
+    let server_config = Arc::new(make_server_config(KeyType::Rsa2048, &provider));
+
+    for browser in browser_templates() {
+        let (client_config, _) = custls_client_config(browser, RandomizationLevel::None);
+        let client_config = Arc::new(client_config);
+
+        let shapes = (0..2)
+            .map(|_| {
+                let (mut client, mut server) =
+                    make_pair_for_arc_configs(&client_config, &server_config);
+                do_handshake(&mut client, &mut server);
+                let view = client.sent_client_hellos()[0]
+                    .view()
+                    .unwrap();
+                (
+                    view.cipher_suites().to_vec(),
+                    view.extension_types(),
+                    view.signature_algorithms().to_vec(),
+                    view.padding_length(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(shapes[0], shapes[1]);
+    }
+}
+
+#[test]
+fn templates_replay_their_hello_after_hrr() {
+    let provider = provider::DEFAULT_PROVIDER;
+    // No template sends a secp384r1 key share, so this server always asks for one
+    let server_config = Arc::new(make_server_config_with_kx_groups(
+        KeyType::Rsa2048,
+        vec![provider::kx_group::SECP384R1],
+        &provider,
+    ));
+    let cookie = u16::from(ExtensionType::Cookie);
+
+    for browser in browser_templates() {
+        for level in LEVELS {
+            let (client_config, _) = custls_client_config(browser.clone(), level);
+            let client_config = Arc::new(client_config);
+            let context = format!("{browser:?} at {level:?}");
+
+            let (mut client, mut server) =
+                make_pair_for_arc_configs(&client_config, &server_config);
+            do_handshake(&mut client, &mut server);
+            assert_eq!(
+                client.handshake_kind(),
+                Some(HandshakeKind::FullWithHelloRetryRequest),
+                "{context}"
+            );
+
+            let [first, retried] = client.sent_client_hellos() else {
+                panic!("{context}: expected two hellos");
+            };
+            let lengths = (first.bytes().len(), retried.bytes().len());
+            let (first, retried) = (first.view().unwrap(), retried.view().unwrap());
+
+            // Only the key share, padding and a cookie, had the server sent one, may change
+            assert_eq!(
+                retried.key_share_groups(),
+                &[u16::from(NamedGroup::secp384r1)],
+                "{context}"
+            );
+            let mut extensions = retried.extension_types();
+            extensions.retain(|ext| *ext != cookie);
+            assert_eq!(extensions, first.extension_types(), "{context}: extensions");
+            assert_eq!(retried.cipher_suites(), first.cipher_suites(), "{context}");
+            assert_eq!(
+                retried.session_id_len(),
+                first.session_id_len(),
+                "{context}"
+            );
+            assert_eq!(
+                retried.supported_versions(),
+                first.supported_versions(),
+                "{context}"
+            );
+            assert_eq!(
+                retried.supported_groups(),
+                first.supported_groups(),
+                "{context}"
+            );
+            assert_eq!(
+                retried.signature_algorithms(),
+                first.signature_algorithms(),
+                "{context}"
+            );
+            assert_eq!(
+                retried.alpn_protocols(),
+                first.alpn_protocols(),
+                "{context}"
+            );
+
+            // Padding keeps the retry at the length of the first hello, unless the
+            // larger key share leaves nothing to pad
+            match (first.padding_length(), retried.padding_length()) {
+                (Some(_), Some(0)) => assert!(lengths.1 >= lengths.0, "{context}: hello length"),
+                (Some(_), Some(_)) => assert_eq!(lengths.1, lengths.0, "{context}: hello length"),
+                (first, retried) => assert_eq!(retried, first, "{context}"),
+            }
+        }
+    }
+}
+
+#[test]
+fn retry_planner_recovers_from_rejected_hellos() {
+    let server_config = Arc::new(chrome_rejecting_server_config());
+    let base = make_client_config(KeyType::Rsa2048, &provider::DEFAULT_PROVIDER);
+    let planner = RetryPlanner::new(
+        CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .build(),
+    );
+    let target = TargetKey::new("localhost".to_string(), 443);
+
+    let mut attempt = planner.first_attempt(&target);
+    loop {
+        let client_config = Arc::new(attempt.client_config(&base));
+        let (mut client, mut server) = make_pair_for_arc_configs(&client_config, &server_config);
+        match do_handshake_until_error(&mut client, &mut server) {
+            Ok(()) => break,
+            Err(ErrorFromPeer::Server(_)) => {
+                // The client learns of the rejection from the server's alert
+                transfer(&mut server, &mut client);
+                let error = client
+                    .process_new_packets()
+                    .unwrap_err();
+                let rejection = HelloRejection::from_error(&error).unwrap();
+                attempt = planner
+                    .next_attempt(&target, &attempt, rejection)
+                    .expect("attempts exhausted");
+            }
+            Err(error) => panic!("unexpected client error: {error:?}"),
+        }
+    }
+    planner.record_success(&target, &attempt);
+
+    assert_eq!(
+        attempt.steps,
+        vec![RetryStep::ChangeTemplate(BrowserTemplate::Firefox135)]
+    );
+    assert_eq!(
+        planner.working_template(&target),
+        Some(BrowserTemplate::Firefox135)
+    );
+    assert_eq!(
+        planner
+            .stats(&target)
+            .map(|(successes, failures, _)| (successes, failures)),
+        Some((1, 1))
+    );
+}
+
+#[test]
+fn retry_planner_drives_unbuffered_connections() {
+    let server_config = Arc::new(chrome_rejecting_server_config());
+    let base = make_client_config(KeyType::Rsa2048, &provider::DEFAULT_PROVIDER);
+    let planner = RetryPlanner::new(
+        CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .build(),
+    );
+    let target = TargetKey::new("localhost".to_string(), 443);
+
+    let first = planner.first_attempt(&target);
+    let error = unbuffered_hello_rejection(Arc::new(first.client_config(&base)), &server_config)
+        .expect("Chrome's hello is rejected");
+    let rejection = HelloRejection::from_error(&error);
+    assert_eq!(rejection, Some(HelloRejection::HandshakeFailure));
+
+    let second = planner
+        .next_attempt(&target, &first, rejection.unwrap())
+        .unwrap();
+    assert!(
+        unbuffered_hello_rejection(Arc::new(second.client_config(&base)), &server_config).is_none()
+    );
+
+    // The plain fallback sends rustls's own hello, which this server accepts too
+    let plain = RetryPlanner::new(CustlsConfig::default());
+    let mut attempt = plain.first_attempt(&target);
+    while let Some(next) = plain.next_attempt(&target, &attempt, HelloRejection::ConnectionReset) {
+        attempt = next;
+    }
+    assert_eq!(attempt.last_step(), Some(&RetryStep::Plain));
+    let config = attempt.client_config(&base);
+    assert!(!config.is_custls_enabled());
+    assert!(unbuffered_hello_rejection(Arc::new(config), &server_config).is_none());
+}
+
+/// A server that answers Chrome's cipher suite order with `handshake_failure`.
+fn chrome_rejecting_server_config() -> ServerConfig {
+    let mut server_config = make_server_config(KeyType::Rsa2048, &provider::DEFAULT_PROVIDER);
+    server_config.cert_resolver = Arc::new(RejectsChromeOrder(server_config.cert_resolver.clone()));
+    server_config
+}
+
+#[derive(Debug)]
+struct RejectsChromeOrder(Arc<dyn ServerCredentialResolver>);
+
+impl ServerCredentialResolver for RejectsChromeOrder {
+    fn resolve(&self, client_hello: &ClientHello<'_>) -> Result<SelectedCredential, Error> {
+        let suites = client_hello
+            .cipher_suites()
+            .iter()
+            .filter(|suite| !is_grease_value(u16::from(**suite)))
+            .copied()
+            .collect::<Vec<_>>();
+        if suites.starts_with(&[
+            CipherSuite::TLS13_AES_128_GCM_SHA256,
+            CipherSuite::TLS13_AES_256_GCM_SHA384,
+            CipherSuite::TLS13_CHACHA20_POLY1305_SHA256,
+        ]) {
+            return Err(Error::PeerIncompatible(
+                PeerIncompatible::NoCipherSuitesInCommon,
+            ));
+        }
+        self.0.resolve(client_hello)
+    }
+}
+
+/// Send the first ClientHello of an unbuffered client to a server, and return
+/// the error the client reads from the server's alert if it is rejected.
+fn unbuffered_hello_rejection(
+    client_config: Arc<ClientConfig>,
+    server_config: &Arc<ServerConfig>,
+) -> Option<Error> {
+    let mut client =
+        UnbufferedClientConnection::new(client_config, server_name("localhost")).unwrap();
+    let mut hello = Vec::new();
+    loop {
+        match client
+            .process_tls_records(&mut [])
+            .state
+            .unwrap()
+        {
+            ConnectionState::EncodeTlsData(mut data) => {
+                let required = match data.encode(&mut []) {
+                    Err(EncodeError::InsufficientSize(err)) => err.required_size,
+                    _ => unreachable!(),
+                };
+                let start = hello.len();
+                hello.resize(start + required, 0);
+                data.encode(&mut hello[start..])
+                    .unwrap();
+            }
+            ConnectionState::TransmitTlsData(data) => data.done(),
+            ConnectionState::BlockedHandshake => break,
+            state => unreachable!("unexpected client state: {state:?}"),
+        }
+    }
+
+    let mut server = ServerConnection::new(server_config.clone()).unwrap();
+    server
+        .read_tls(&mut &hello[..])
+        .unwrap();
+    if server.process_new_packets().is_ok() {
+        return None;
+    }
+
+    let mut alert = Vec::new();
+    server.write_tls(&mut alert).unwrap();
+    client
+        .process_tls_records(&mut alert)
+        .state
+        .err()
+}
+
+/// Every built-in TCP template, including the ones only reachable by name.
+fn browser_templates() -> Vec<BrowserTemplate> {
+    vec![
+        BrowserTemplate::Chrome130,
+        BrowserTemplate::Firefox135,
+        BrowserTemplate::Safari17,
+        BrowserTemplate::Edge130,
+        BrowserTemplate::Named("safari_17_legacy".to_string()),
+        BrowserTemplate::Named("chrome_131".to_string()),
+        BrowserTemplate::Named("firefox_136".to_string()),
+        BrowserTemplate::Named("edge_131".to_string()),
+        BrowserTemplate::Named("okhttp_4".to_string()),
+        BrowserTemplate::Named("ios_17".to_string()),
+        BrowserTemplate::Named("curl_8".to_string()),
+        BrowserTemplate::Named("go_1_22".to_string()),
+    ]
+}
+
+fn custls_client_config(
+    browser: BrowserTemplate,
+    level: RandomizationLevel,
+) -> (ClientConfig, TemplateData) {
+    let customizer = DefaultCustomizer::new_arc(
+        CustlsConfig::builder()
+            .with_template(browser)
+            .with_randomization_level(level)
+            .build(),
+    );
+    let template = customizer.template().unwrap().clone();
+
+    let mut config = make_client_config(KeyType::Rsa2048, &provider::DEFAULT_PROVIDER);
+    config.enable_custls(customizer);
+    (config, template)
+}
+
+/// Assert the invariants every hello sent with `template` must hold.
+fn check_hello(view: &ClientHelloView, template: &TemplateData, level: RandomizationLevel) {
+    let context = format!("{} at {level:?}", template.name);
+
+    // Cipher suites and signature schemes are sent verbatim, around any GREASE
+    let suites = template
+        .cipher_suites
+        .iter()
+        .map(|suite| u16::from(*suite))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        without_grease(view.cipher_suites()),
+        suites,
+        "{context}: cipher suites"
+    );
+    let schemes = template
+        .signature_algorithms
+        .iter()
+        .map(|scheme| u16::from(*scheme))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        without_grease(view.signature_algorithms()),
+        schemes,
+        "{context}: signature algorithms"
+    );
+
+    let pattern = &template.grease_pattern;
+    check_grease(
+        view.cipher_suites(),
+        level,
+        pattern.cipher_suite_probability,
+        &pattern.cipher_suite_positions,
+        &format!("{context}: cipher suites"),
+    );
+    let extensions = view.extension_types();
+    let padding = u16::from(ExtensionType::Padding);
+    let body = check_extension_order(&extensions, template, level, &context);
+    let body = match body.last() == Some(&padding) {
+        true => &body[..body.len() - 1],
+        false => body,
+    };
+    assert!(
+        !body.contains(&padding),
+        "{context}: padding must come right before ECH and PSK, got {extensions:04x?}"
+    );
+    check_grease(
+        body,
+        level,
+        pattern.extension_probability,
+        &pattern.extension_positions,
+        &format!("{context}: extensions"),
+    );
+
+    let dist = &template.padding_distribution;
+    match (level, view.padding_length()) {
+        (RandomizationLevel::None, len) => assert_eq!(
+            len,
+            dist.pmf
+                .first()
+                .map(|(len, _)| *len)
+                .filter(|len| *len > 0),
+            "{context}: padding"
+        ),
+        (_, Some(len)) => assert!(
+            (dist.min_length..=dist.max_length).contains(&len),
+            "{context}: padding of {len} bytes outside {}..={}",
+            dist.min_length,
+            dist.max_length
+        ),
+        (_, None) => {}
+    }
+}
+
+/// Extensions the template lists appear in template order after all the others,
+/// up to the shuffles `level` allows; ECH and PSK come last and in order.
+///
+/// Returns the extensions ahead of ECH and PSK.
+fn check_extension_order<'a>(
+    extensions: &'a [u16],
+    template: &TemplateData,
+    level: RandomizationLevel,
+    context: &str,
+) -> &'a [u16] {
+    let trailing = TRAILING_EXTENSIONS
+        .iter()
+        .map(|ext| u16::from(*ext))
+        .collect::<Vec<_>>();
+    let padding = u16::from(ExtensionType::Padding);
+    let pinned = PINNED_EXTENSIONS
+        .iter()
+        .map(|ext| u16::from(*ext))
+        .collect::<Vec<_>>();
+
+    let trailing_start = extensions
+        .iter()
+        .position(|ext| trailing.contains(ext))
+        .unwrap_or(extensions.len());
+    let (body, tail) = extensions.split_at(trailing_start);
+    assert!(
+        tail.iter()
+            .all(|ext| trailing.contains(ext)),
+        "{context}: ECH and PSK must be last, got {extensions:04x?}"
+    );
+    let tail_positions = tail
+        .iter()
+        .map(|ext| trailing.iter().position(|t| t == ext))
+        .collect::<Vec<_>>();
+    assert!(
+        tail_positions.is_sorted(),
+        "{context}: trailing extensions out of order: {tail:04x?}"
+    );
+
+    let listed = template
+        .extension_order
+        .iter()
+        .map(|ext| u16::from(*ext))
+        .filter(|ext| !trailing.contains(ext) && *ext != padding)
+        .collect::<Vec<_>>();
+    let mut sent = without_grease(body);
+    sent.retain(|ext| *ext != padding);
+    let first_listed = sent
+        .iter()
+        .position(|ext| listed.contains(ext))
+        .unwrap_or(sent.len());
+    let in_template = &sent[first_listed..];
+    assert!(
+        in_template
+            .iter()
+            .all(|ext| listed.contains(ext)),
+        "{context}: unlisted extension among the template's: {sent:04x?}"
+    );
+
+    // The template's order of the extensions sent. Pinned extensions keep their
+    // place in it; the others may only be moved by the level's shuffle.
+    let expected = listed
+        .iter()
+        .copied()
+        .filter(|ext| in_template.contains(ext))
+        .collect::<Vec<_>>();
+    for (sent, expected) in in_template.iter().zip(&expected) {
+        if pinned.contains(expected) {
+            assert_eq!(
+                sent, expected,
+                "{context}: pinned extension moved: {in_template:04x?}"
+            );
+        }
+    }
+    let moved = in_template
+        .iter()
+        .filter(|ext| !pinned.contains(ext))
+        .map(|ext| {
+            expected
+                .iter()
+                .position(|e| e == ext)
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let inversions = (0..moved.len())
+        .flat_map(|i| (i + 1..moved.len()).map(move |j| (i, j)))
+        .filter(|(i, j)| moved[*i] > moved[*j])
+        .count();
+    let allowed = match level {
+        RandomizationLevel::None => 0,
+        RandomizationLevel::Light => (moved.len() / 4).max(1),
+        RandomizationLevel::Medium => (moved.len() / 2).max(1),
+        _ => usize::MAX,
+    };
+    assert!(
+        inversions <= allowed,
+        "{context}: {inversions} extensions out of template order, {allowed} allowed: {in_template:04x?}"
+    );
+    body
+}
+
+/// GREASE values only appear when the template injects them, at one of its
+/// normalized positions in the list they were inserted into.
+///
+/// Templates that always inject GREASE must have exactly one value in `list`,
+/// and none may appear without randomization.
+fn check_grease(
+    list: &[u16],
+    level: RandomizationLevel,
+    probability: f64,
+    positions: &[f64],
+    context: &str,
+) {
+    let found = list
+        .iter()
+        .enumerate()
+        .filter(|(_, value)| is_grease_value(**value))
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    if level == RandomizationLevel::None || probability <= 0.0 {
+        assert!(
+            found.is_empty(),
+            "{context}: unexpected GREASE at {found:?}"
+        );
+        return;
+    }
+    match probability >= 1.0 {
+        true => assert_eq!(found.len(), 1, "{context}: expected one GREASE value"),
+        false => assert!(found.len() <= 1, "{context}: GREASE at {found:?}"),
+    }
+
+    let len = list.len() - found.len();
+    let allowed = match positions.is_empty() {
+        true => vec![0],
+        false => positions
+            .iter()
+            .map(|position| ((position * len as f64) as usize).min(len))
+            .collect(),
+    };
+    for index in found {
+        assert!(
+            allowed.contains(&index),
+            "{context}: GREASE at {index}, allowed {allowed:?}"
+        );
+    }
+}
+
+fn without_grease(values: &[u16]) -> Vec<u16> {
+    values
+        .iter()
+        .copied()
+        .filter(|value| !is_grease_value(*value))
+        .collect()
+}
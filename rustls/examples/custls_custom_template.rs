@@ -65,7 +65,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_randomization_level(RandomizationLevel::High)
         .build();
     
-    println!("  ✓ Custom template configured\n");
+    println!("  ✓ Custom template configured");
+    println!("  ! It carries no template data yet, so a handshake using it fails");
+    println!("    with CustlsError::TemplateError; register TemplateData and use");
+    println!("    BrowserTemplate::Named for a working custom fingerprint\n");
 
     // Part 3: Template characteristics
     println!("Part 3: Template Characteristics");
@@ -4,6 +4,7 @@ use core::marker::PhantomData;
 
 use crate::client::EchMode;
 use crate::crypto::CryptoProvider;
+use crate::custls::BrowserTemplate;
 use crate::sync::Arc;
 use crate::time_provider::TimeProvider;
 #[cfg(doc)]
@@ -160,6 +161,7 @@ impl<Side: ConfigSide, State: fmt::Debug> fmt::Debug for ConfigBuilder<Side, Sta
 #[derive(Clone, Debug)]
 pub struct WantsVerifier {
     pub(crate) client_ech_mode: Option<EchMode>,
+    pub(crate) client_browser_profile: Option<BrowserTemplate>,
 }
 
 /// Helper trait to abstract [`ConfigBuilder`] over building a [`ClientConfig`] or [`ServerConfig`].
@@ -19,6 +19,7 @@ use crate::crypto::{
     CipherSuite, Credentials, CryptoProvider, Identity, SelectedCredential, SignatureScheme,
     SingleCredential, hash,
 };
+use crate::custls::{BrowserProfile, BrowserTemplate, profile};
 use crate::enums::{ApplicationProtocol, CertificateType, ProtocolVersion};
 use crate::error::{ApiMisuse, Error};
 use crate::key_log::NoKeyLog;
@@ -174,6 +175,9 @@ pub struct ClientConfig {
     /// Optional ClientHello customizer for fingerprint simulation.
     /// The default is None (no customization).
     pub custls_customizer: Option<Arc<dyn crate::custls::ClientHelloCustomizer>>,
+
+    /// Browser profile the config was built with, if any.
+    pub(crate) browser_profile: Option<BrowserProfile>,
 }
 
 impl ClientConfig {
@@ -203,6 +207,7 @@ impl ClientConfig {
         ConfigBuilder {
             state: WantsVerifier {
                 client_ech_mode: None,
+                client_browser_profile: None,
             },
             provider,
             time_provider,
@@ -335,6 +340,14 @@ impl ClientConfig {
     pub fn is_custls_enabled(&self) -> bool {
         self.custls_customizer.is_some()
     }
+
+    /// The browser profile this config was built with.
+    ///
+    /// Set by [`ConfigBuilder::with_browser_profile()`], and lists the ways the
+    /// ClientHello differs from the template because of the crypto provider.
+    pub fn browser_profile(&self) -> Option<&BrowserProfile> {
+        self.browser_profile.as_ref()
+    }
 }
 
 struct HashAdapter<'a>(&'a mut dyn hash::Context);
@@ -413,6 +426,13 @@ pub struct ClientSessionKey<'a> {
 
     /// Transport-level identity of the server.
     pub server_name: ServerName<'a>,
+
+    /// Fingerprint identity the session belongs to.
+    ///
+    /// This is the `session_partition` a custls customizer chose in phase 1, so
+    /// sessions established with one ClientHello fingerprint are not resumed
+    /// with another. `None` when no customizer set one.
+    pub partition: Option<Vec<u8>>,
 }
 
 impl ClientSessionKey<'_> {
@@ -421,10 +441,12 @@ impl ClientSessionKey<'_> {
         let Self {
             config_hash,
             server_name,
+            partition,
         } = self;
         ClientSessionKey {
             config_hash: *config_hash,
             server_name: server_name.to_owned(),
+            partition: partition.clone(),
         }
     }
 }
@@ -706,6 +728,7 @@ impl ConfigBuilder<ClientConfig, WantsVerifier> {
             state: WantsClientCert {
                 verifier,
                 client_ech_mode: self.state.client_ech_mode,
+                browser_profile: self.state.client_browser_profile,
             },
             provider: self.provider,
             time_provider: self.time_provider,
@@ -728,6 +751,23 @@ impl ConfigBuilder<ClientConfig, WantsVerifier> {
         self
     }
 
+    /// Make the ClientHello look like the browser or client of `template`.
+    ///
+    /// When the config is built, every setting the template determines is derived
+    /// from it: the custls customizer, [`ClientConfig::alpn_protocols`],
+    /// [`ClientConfig::enable_sni`], [`ClientConfig::resumption`] and
+    /// [`ClientConfig::cert_decompressors`]. The crypto provider is narrowed to the
+    /// template's cipher suites and key exchange groups, in template order.
+    ///
+    /// Differences the provider cannot avoid are listed by
+    /// [`ClientConfig::browser_profile()`]. Building fails if the provider shares no
+    /// cipher suite or group with the template, or if `template` names a template
+    /// that is not registered.
+    pub fn with_browser_profile(mut self, template: BrowserTemplate) -> Self {
+        self.state.client_browser_profile = Some(template);
+        self
+    }
+
     /// Access configuration options whose use is dangerous and requires
     /// extra care.
     pub fn dangerous(self) -> danger::DangerousClientConfigBuilder {
@@ -743,6 +783,7 @@ impl ConfigBuilder<ClientConfig, WantsVerifier> {
 pub struct WantsClientCert {
     verifier: Arc<dyn verify::ServerVerifier>,
     client_ech_mode: Option<EchMode>,
+    browser_profile: Option<BrowserTemplate>,
 }
 
 impl ConfigBuilder<ClientConfig, WantsClientCert> {
@@ -771,9 +812,23 @@ impl ConfigBuilder<ClientConfig, WantsClientCert> {
 
     /// Sets a custom [`ClientCredentialResolver`].
     pub fn with_client_credential_resolver(
-        self,
+        mut self,
         client_auth_cert_resolver: Arc<dyn ClientCredentialResolver>,
     ) -> Result<ClientConfig, Error> {
+        let profile = match self.state.browser_profile.take() {
+            Some(browser) => {
+                let template = profile::profile_template(&browser)?;
+                let (provider, mismatches) = profile::tailor_provider(
+                    &template,
+                    &self.provider,
+                    self.state.client_ech_mode.is_none(),
+                )?;
+                self.provider = Arc::new(provider);
+                Some((browser, template, mismatches))
+            }
+            None => None,
+        };
+
         self.provider.consistency_check()?;
 
         if self.state.client_ech_mode.is_some() {
@@ -792,7 +847,7 @@ impl ConfigBuilder<ClientConfig, WantsClientCert> {
         }
 
         let require_ems = !matches!(self.provider.fips(), FipsStatus::Unvalidated);
-        Ok(ClientConfig {
+        let mut config = ClientConfig {
             alpn_protocols: Vec::new(),
             resumption: Resumption::default(),
             max_fragment_size: None,
@@ -812,7 +867,13 @@ impl ConfigBuilder<ClientConfig, WantsClientCert> {
             cert_compression_cache: Arc::new(compress::CompressionCache::default()),
             ech_mode: self.state.client_ech_mode,
             custls_customizer: None,
-        })
+            browser_profile: None,
+        };
+
+        if let Some((browser, template, mismatches)) = profile {
+            profile::apply_profile(&mut config, browser, &template, mismatches);
+        }
+        Ok(config)
     }
 }
 
@@ -857,6 +918,7 @@ pub(super) mod danger {
                 state: WantsClientCert {
                     verifier,
                     client_ech_mode: self.cfg.state.client_ech_mode,
+                    browser_profile: self.cfg.state.client_browser_profile,
                 },
                 provider: self.cfg.provider,
                 time_provider: self.cfg.time_provider,
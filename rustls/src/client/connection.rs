@@ -1,5 +1,6 @@
 use alloc::vec::Vec;
 use core::ops::{Deref, DerefMut};
+use core::time::Duration;
 use core::{fmt, mem};
 
 use pki_types::ServerName;
@@ -25,6 +26,7 @@ mod buffered {
     use alloc::vec::Vec;
     use core::fmt;
     use core::ops::{Deref, DerefMut};
+    use core::time::Duration;
     use std::io;
 
     use pki_types::{FipsStatus, ServerName};
@@ -76,6 +78,32 @@ mod buffered {
             })
         }
 
+        /// Make a new ClientConnection to `name` on TCP port `port`.
+        ///
+        /// rustls does not open the transport, so it otherwise does not know the
+        /// port. A custls customizer sees it in its target, which routing rules,
+        /// sticky template rotation and the fingerprint cache key on. Connections
+        /// made with [`ClientConnection::new`] report port 443.
+        pub fn new_with_port(
+            config: Arc<ClientConfig>,
+            name: ServerName<'static>,
+            port: u16,
+        ) -> Result<Self, Error> {
+            let exts = ClientExtensionsInput {
+                target_port: Some(port),
+                ..ClientExtensionsInput::from_alpn(config.alpn_protocols.clone())
+            };
+
+            Ok(Self {
+                inner: ConnectionCommon::from(ConnectionCore::for_client(
+                    config,
+                    name,
+                    exts,
+                    Protocol::Tcp,
+                )?),
+            })
+        }
+
         /// Returns an `io::Write` implementer you can write bytes to
         /// to send TLS1.3 early data (a.k.a. "0-RTT data") to the server.
         ///
@@ -128,6 +156,53 @@ mod buffered {
             self.inner.core.side.ech_status
         }
 
+        /// Return the HTTP/2 profile of the custls template that shaped this connection.
+        ///
+        /// This is `Some` only once `h2` has been negotiated via ALPN and the ClientHello
+        /// customizer reported a profile. An HTTP/2 client should build its connection
+        /// preface and request headers from it, so that the HTTP/2 layer matches the TLS
+        /// fingerprint.
+        pub fn http2_profile(&self) -> Option<&crate::custls::Http2Profile> {
+            self.inner
+                .core
+                .side
+                .negotiated_http2_profile(&self.inner.core.common_state)
+        }
+
+        /// Returns the ClientHellos this connection has sent, oldest first.
+        ///
+        /// There is one entry once the first flight has been produced, and a second
+        /// one if the server answered with a HelloRetryRequest. Each entry holds the
+        /// handshake-message bytes and a parsed [`crate::custls::ClientHelloView`];
+        /// with ECH this is the outer hello, which is what the server sees.
+        pub fn sent_client_hellos(&self) -> &[crate::custls::SentClientHello] {
+            &self.inner.core.side.sent_client_hellos
+        }
+
+        /// Returns how long to wait before writing the pending first flight.
+        ///
+        /// A ClientHello customizer may ask for the first flight to be delayed (custls
+        /// timing jitter). This returns that delay while the first flight is waiting in
+        /// the send buffer, and `None` once it has been written or the server has
+        /// responded. Async callers should await a timer for this long before calling
+        /// `write_tls`; synchronous callers can use [`Self::wait_for_write_delay`].
+        pub fn wants_write_after(&self) -> Option<Duration> {
+            match self.inner.wants_write() {
+                true => self.inner.core.side.first_flight_delay,
+                false => None,
+            }
+        }
+
+        /// Blocking adapter for [`Self::wants_write_after`]: sleeps on the current thread.
+        ///
+        /// Call this before the first `write_tls` (or `complete_io`). It must not be
+        /// used from an async runtime's worker thread.
+        pub fn wait_for_write_delay(&self) {
+            if let Some(delay) = self.wants_write_after() {
+                std::thread::sleep(delay);
+            }
+        }
+
         /// Returns the number of TLS1.3 tickets that have been received.
         pub fn tls13_tickets_received(&self) -> u32 {
             self.inner.tls13_tickets_received
@@ -326,6 +401,24 @@ impl UnbufferedClientConnection {
         )
     }
 
+    /// Make a new UnbufferedClientConnection to `name` on TCP port `port`.
+    ///
+    /// See `ClientConnection::new_with_port`.
+    pub fn new_with_port(
+        config: Arc<ClientConfig>,
+        name: ServerName<'static>,
+        port: u16,
+    ) -> Result<Self, Error> {
+        Self::new_with_extensions(
+            config.clone(),
+            name,
+            ClientExtensionsInput {
+                target_port: Some(port),
+                ..ClientExtensionsInput::from_alpn(config.alpn_protocols.clone())
+            },
+        )
+    }
+
     fn new_with_extensions(
         config: Arc<ClientConfig>,
         name: ServerName<'static>,
@@ -370,6 +463,32 @@ impl UnbufferedClientConnection {
     pub fn tls13_tickets_received(&self) -> u32 {
         self.inner.tls13_tickets_received
     }
+
+    /// Return the HTTP/2 profile of the custls template that shaped this connection.
+    ///
+    /// See [`crate::client::ClientConnection::http2_profile`].
+    pub fn http2_profile(&self) -> Option<&crate::custls::Http2Profile> {
+        self.inner
+            .core
+            .side
+            .negotiated_http2_profile(&self.inner.core.common_state)
+    }
+
+    /// Returns the ClientHellos this connection has sent, oldest first.
+    ///
+    /// See [`crate::client::ClientConnection::sent_client_hellos`].
+    pub fn sent_client_hellos(&self) -> &[crate::custls::SentClientHello] {
+        &self.inner.core.side.sent_client_hellos
+    }
+
+    /// Returns how long to wait before transmitting the first flight.
+    ///
+    /// A ClientHello customizer may ask for the first flight to be delayed (custls
+    /// timing jitter). Wait this long before sending the bytes of the first
+    /// `TransmitTlsData` state. Returns `None` once the server has responded.
+    pub fn wants_write_after(&self) -> Option<Duration> {
+        self.inner.core.side.first_flight_delay
+    }
 }
 
 impl Deref for UnbufferedClientConnection {
@@ -566,6 +685,9 @@ impl core::error::Error for EarlyDataError {}
 pub struct ClientConnectionData {
     pub(super) early_data: EarlyData,
     pub(super) ech_status: EchStatus,
+    pub(super) http2_profile: Option<crate::custls::Http2Profile>,
+    pub(super) first_flight_delay: Option<Duration>,
+    pub(crate) sent_client_hellos: Vec<crate::custls::SentClientHello>,
 }
 
 impl ClientConnectionData {
@@ -573,6 +695,20 @@ impl ClientConnectionData {
         Self {
             early_data: EarlyData::new(),
             ech_status: EchStatus::NotOffered,
+            http2_profile: None,
+            first_flight_delay: None,
+            sent_client_hellos: Vec::new(),
+        }
+    }
+
+    /// The customizer's HTTP/2 profile, once `h2` has been negotiated
+    pub(crate) fn negotiated_http2_profile(
+        &self,
+        common: &CommonState,
+    ) -> Option<&crate::custls::Http2Profile> {
+        match common.alpn_protocol() {
+            Some(ApplicationProtocol::Http2) => self.http2_profile.as_ref(),
+            _ => None,
         }
     }
 }
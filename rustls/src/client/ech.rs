@@ -16,6 +16,7 @@ use crate::crypto::hpke::{
     EncapsulatedSecret, Hpke, HpkeKem, HpkePublicKey, HpkeSealer, HpkeSuite,
     HpkeSymmetricCipherSuite,
 };
+use crate::custls::{ClientHelloCustomizer, HookPhase};
 use crate::enums::ProtocolVersion;
 use crate::error::{EncryptedClientHelloError, Error, PeerMisbehaved, RejectedEch};
 use crate::hash_hs::{HandshakeHash, HandshakeHashBuffer};
@@ -271,7 +272,7 @@ impl EchGreaseConfig {
 
         // Construct an inner hello using the outer hello - this allows us to know the size of
         // dummy payload we should use for the GREASE extension.
-        let encoded_inner_hello = grease_state.encode_inner_hello(outer_hello, None, None);
+        let encoded_inner_hello = grease_state.encode_inner_hello(outer_hello, None, None, None)?;
 
         // Generate a payload of random data equivalent in length to a real inner hello.
         let payload_len = encoded_inner_hello.len()
@@ -295,6 +296,55 @@ impl EchGreaseConfig {
     }
 }
 
+/// Fix the encoding order of a customized inner hello, and choose which of its extensions
+/// to compress into an `ech_outer_extensions` reference.
+///
+/// Compressed extensions must be identical to the outer hello's, contiguous in the inner
+/// hello, and referenced in the order they appear in the outer hello. The longest run of
+/// inner extensions meeting those rules is chosen; the inner order is pinned so that the
+/// `ech_outer_extensions` marker takes exactly the place of that run.
+fn pin_compressed_block(
+    outer_hello: &ClientHelloPayload,
+    inner_hello: &mut ClientHelloPayload,
+) -> Vec<ExtensionType> {
+    let outer_order = outer_hello.used_extensions_in_encoding_order();
+    let mut inner_order = inner_hello.used_extensions_in_encoding_order();
+    inner_order.retain(|ext| {
+        !matches!(
+            ext,
+            ExtensionType::PreSharedKey
+                | ExtensionType::EncryptedClientHello
+                | ExtensionType::EncryptedClientHelloOuterExtensions
+        )
+    });
+
+    let outer_position = |ext: &ExtensionType| {
+        match ext.ech_compress() && inner_hello.has_identical(outer_hello, *ext) {
+            true => outer_order.iter().position(|e| e == ext),
+            false => None,
+        }
+    };
+
+    let mut best = 0..0;
+    let mut start = 0;
+    let mut last_position = None;
+    for (i, ext) in inner_order.iter().enumerate() {
+        match (outer_position(ext), last_position) {
+            (Some(pos), Some(last)) if pos > last => {}
+            (Some(_), _) => start = i,
+            (None, _) => start = i + 1,
+        }
+        last_position = outer_position(ext);
+        if i + 1 - start > best.len() {
+            best = start..i + 1;
+        }
+    }
+
+    let compressed = inner_order[best].to_vec();
+    inner_hello.contiguous_extensions = inner_order;
+    compressed
+}
+
 /// An enum representing ECH offer status.
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -347,6 +397,9 @@ pub(crate) struct EchState {
     enable_sni: bool,
     // The extensions sent in the inner hello.
     sent_extensions: Vec<ExtensionType>,
+    // The first inner hello as the customizer shaped it. An inner hello for a retry
+    // replays it.
+    first_inner_hello: Option<ClientHelloPayload>,
 }
 
 impl EchState {
@@ -393,6 +446,7 @@ impl EchState {
             enc,
             enable_sni,
             sent_extensions: Vec::new(),
+            first_inner_hello: None,
         })
     }
 
@@ -404,11 +458,15 @@ impl EchState {
     /// If `retry_req` is `Some`, then the outer hello will be constructed for a hello retry request.
     ///
     /// If `resuming` is `Some`, then the inner hello will be constructed for a resumption handshake.
+    ///
+    /// If `customizer` is `Some`, it is given the chance to shape the inner hello before it
+    /// is encoded and encrypted.
     pub(crate) fn ech_hello(
         &mut self,
         mut outer_hello: ClientHelloPayload,
         retry_req: Option<&HelloRetryRequest>,
         resuming: Option<&Retrieved<&Tls13ClientSessionValue>>,
+        customizer: Option<&dyn ClientHelloCustomizer>,
     ) -> Result<ClientHelloPayload, Error> {
         trace!(
             "Preparing ECH offer {}",
@@ -416,7 +474,8 @@ impl EchState {
         );
 
         // Construct the encoded inner hello and update the transcript.
-        let encoded_inner_hello = self.encode_inner_hello(&outer_hello, retry_req, resuming);
+        let encoded_inner_hello =
+            self.encode_inner_hello(&outer_hello, retry_req, resuming, customizer)?;
 
         // Complete the ClientHelloOuterAAD with an ech extension, the payload should be a placeholder
         // of size L, all zeroes. L == length of encrypting encoded client hello inner w/ the selected
@@ -587,7 +646,8 @@ impl EchState {
         outer_hello: &ClientHelloPayload,
         retryreq: Option<&HelloRetryRequest>,
         resuming: Option<&Retrieved<&Tls13ClientSessionValue>>,
-    ) -> Vec<u8> {
+        customizer: Option<&dyn ClientHelloCustomizer>,
+    ) -> Result<Vec<u8>, Error> {
         // Start building an inner hello using the outer_hello as a template.
         let mut inner_hello = ClientHelloPayload {
             // Some information is copied over as-is.
@@ -647,6 +707,12 @@ impl EchState {
                 continue;
             }
 
+            // The inner hello is padded as ECH requires (Section 6.1.3), so a padding
+            // extension a customizer added to the outer hello stays there.
+            if ext == ExtensionType::Padding {
+                continue;
+            }
+
             if ext == ExtensionType::ServerName {
                 // We may want to replace the outer hello SNI with our own inner hello specific SNI.
                 if let Some(sni_value) = inner_sni {
@@ -669,6 +735,25 @@ impl EchState {
         // block of to-be-compressed extensions.
         inner_hello.contiguous_extensions = compressed_exts.clone();
 
+        // Let a customizer shape the inner hello independently of the outer hello. It may
+        // have changed the inner extension order or values, so work out again which
+        // extensions can be compressed. Like the outer hello, the inner hello for a
+        // retry replays the first one rather than being shaped again.
+        if let Some(customizer) = customizer {
+            match (retryreq, self.first_inner_hello.take()) {
+                (Some(_), Some(first)) => {
+                    inner_hello = crate::custls::hrr::replay_for_retry(&first, inner_hello);
+                }
+                _ => {
+                    customizer
+                        .on_ech_inner_ready(&mut inner_hello)
+                        .map_err(|e| HookPhase::EchInnerReady.attribute(e))?;
+                    self.first_inner_hello = Some(inner_hello.clone());
+                }
+            }
+            compressed_exts = pin_compressed_block(outer_hello, &mut inner_hello);
+        }
+
         // Note which extensions we're sending in the inner hello. This may differ from
         // the outer hello (e.g. the inner hello may omit SNI while the outer hello will
         // always have the ECH cover name in SNI).
@@ -749,7 +834,7 @@ impl EchState {
         self.inner_hello_transcript
             .add_message(&inner_hello_msg);
 
-        encoded_hello
+        Ok(encoded_hello)
     }
 
     // See https://datatracker.ietf.org/doc/html/draft-ietf-tls-esni-18#name-grease-psk
@@ -854,7 +939,11 @@ pub(crate) struct EchAccepted {
 mod tests {
     use super::*;
     use crate::crypto::CipherSuite;
-    use crate::msgs::{Compression, Random, ServerExtensions, SessionId};
+    use crate::crypto::SignatureScheme;
+    use crate::crypto::kx::NamedGroup;
+    use crate::msgs::{
+        Compression, Random, ServerExtensions, SessionId, SupportedProtocolVersions,
+    };
 
     #[test]
     fn server_hello_conf_alters_server_hello_random() {
@@ -909,4 +998,87 @@ mod tests {
             "                          afterwards those bytes are zeroed ^^^^^^^^^^^^^^^^            "
         );
     }
+
+    #[test]
+    fn pin_compressed_block_follows_customized_inner_order() {
+        let mut outer = ClientHelloPayload {
+            client_version: ProtocolVersion::TLSv1_2,
+            random: Random([0u8; 32]),
+            session_id: SessionId::empty(),
+            cipher_suites: vec![CipherSuite::TLS13_AES_128_GCM_SHA256],
+            compression_methods: vec![Compression::Null],
+            extensions: Box::new(ClientExtensions::default()),
+        };
+        outer.supported_versions = Some(SupportedProtocolVersions {
+            tls13: true,
+            tls12: false,
+        });
+        outer.named_groups = Some(vec![NamedGroup::X25519]);
+        outer.signature_schemes = Some(vec![SignatureScheme::ED25519]);
+        outer.contiguous_extensions = vec![
+            ExtensionType::SupportedVersions,
+            ExtensionType::EllipticCurves,
+            ExtensionType::SignatureAlgorithms,
+        ];
+
+        // signature_algorithms moved to the front: it can no longer be referenced in outer
+        // order together with the others.
+        let mut inner = outer.clone();
+        inner.encrypted_client_hello = Some(EncryptedClientHello::Inner);
+        inner.contiguous_extensions = vec![
+            ExtensionType::SignatureAlgorithms,
+            ExtensionType::SupportedVersions,
+            ExtensionType::EllipticCurves,
+        ];
+
+        let compressed = pin_compressed_block(&outer, &mut inner);
+        assert_eq!(
+            compressed,
+            vec![
+                ExtensionType::SupportedVersions,
+                ExtensionType::EllipticCurves
+            ]
+        );
+        assert_eq!(
+            inner.contiguous_extensions,
+            vec![
+                ExtensionType::SignatureAlgorithms,
+                ExtensionType::SupportedVersions,
+                ExtensionType::EllipticCurves,
+            ]
+        );
+
+        // The marker takes the place of the compressed block.
+        let encoded = inner.ech_inner_encoding(compressed.clone());
+        let decoded = ClientHelloPayload::read(&mut Reader::init(&encoded)).unwrap();
+        assert_eq!(decoded.encrypted_client_hello_outer, Some(compressed));
+        assert!(decoded.signature_schemes.is_some());
+        assert!(decoded.supported_versions.is_none());
+
+        // version, random, session_id, cipher_suites, compression_methods, extensions length
+        let exts = &encoded[2 + 32 + 1 + 4 + 2 + 2..];
+        let second = 4 + usize::from(u16::from_be_bytes([exts[2], exts[3]]));
+        assert_eq!(&exts[..2], &[0x00, 0x0d]);
+        assert_eq!(&exts[second..second + 2], &[0xfd, 0x00]);
+    }
+
+    #[test]
+    fn pin_compressed_block_skips_modified_extensions() {
+        let mut outer = ClientHelloPayload {
+            client_version: ProtocolVersion::TLSv1_2,
+            random: Random([0u8; 32]),
+            session_id: SessionId::empty(),
+            cipher_suites: vec![CipherSuite::TLS13_AES_128_GCM_SHA256],
+            compression_methods: vec![Compression::Null],
+            extensions: Box::new(ClientExtensions::default()),
+        };
+        outer.named_groups = Some(vec![NamedGroup::X25519, NamedGroup::secp256r1]);
+        outer.signature_schemes = Some(vec![SignatureScheme::ED25519]);
+
+        let mut inner = outer.clone();
+        inner.named_groups = Some(vec![NamedGroup::X25519]);
+
+        let compressed = pin_compressed_block(&outer, &mut inner);
+        assert_eq!(compressed, vec![ExtensionType::SignatureAlgorithms]);
+    }
 }
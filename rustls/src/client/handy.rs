@@ -223,6 +223,7 @@ mod tests {
         let key = ClientSessionKey {
             config_hash: Default::default(),
             server_name,
+            partition: None,
         };
         let now = UnixTime::now();
 
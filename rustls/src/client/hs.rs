@@ -15,8 +15,9 @@ use super::{
 use crate::check::inappropriate_handshake_message;
 use crate::common_state::{Event, Input, Output, Protocol, State};
 use crate::crypto::cipher::Payload;
-use crate::crypto::kx::{KeyExchangeAlgorithm, StartedKeyExchange, SupportedKxGroup};
-use crate::crypto::{CipherSuite, CryptoProvider, rand};
+use crate::crypto::kx::{KeyExchangeAlgorithm, NamedGroup, StartedKeyExchange, SupportedKxGroup};
+use crate::crypto::{CipherSuite, CryptoProvider, SignatureScheme, rand};
+use crate::custls::HookPhase;
 use crate::enums::{
     ApplicationProtocol, CertificateType, ContentType, HandshakeType, ProtocolVersion,
 };
@@ -54,6 +55,9 @@ pub(crate) struct ExpectServerHello {
     // If this is `None` then we do not support early data.
     pub(super) early_data_key_schedule: Option<KeyScheduleEarlyClient>,
     pub(super) offered_key_share: Option<GroupAndKeyShare>,
+    // Key shares sent in addition to `offered_key_share` (and its hybrid component),
+    // when a custls template asked for them.
+    pub(super) extra_key_shares: Vec<GroupAndKeyShare>,
     pub(super) suite: Option<SupportedCipherSuite>,
     pub(super) ech_state: Option<EchState>,
     pub(super) done_retry: bool,
@@ -105,10 +109,20 @@ impl ExpectServerHello {
             }
         }
 
+        // A template may advertise suites we have no implementation for; the
+        // server picking one of those is not misbehaviour, but we cannot continue.
+        let sent_cipher_suites = &self.input.hello.sent_cipher_suites;
         let suite = <CryptoProvider as Borrow<[&'static T]>>::borrow(self.input.config.provider())
             .iter()
             .find(|cs| cs.common().suite == server_hello.cipher_suite)
-            .ok_or(PeerMisbehaved::SelectedUnofferedCipherSuite)?;
+            .ok_or_else(|| match sent_cipher_suites.contains(&server_hello.cipher_suite) {
+                true => Error::from(PeerIncompatible::ServerSelectedAdvertiseOnlyCipherSuite),
+                false => PeerMisbehaved::SelectedUnofferedCipherSuite.into(),
+            })?;
+
+        if !sent_cipher_suites.contains(&suite.common().suite) {
+            return Err(PeerMisbehaved::SelectedUnofferedCipherSuite.into());
+        }
 
         match self.suite {
             Some(prev_suite) if prev_suite.suite() != suite.common().suite => {
@@ -131,6 +145,7 @@ impl ExpectServerHello {
 
 impl State<ClientConnectionData> for ExpectServerHello {
     fn handle(self: Box<Self>, cx: &mut ClientContext<'_>, input: Input<'_>) -> NextStateOrError {
+        cx.data.first_flight_delay = None;
         let server_hello = require_handshake_msg!(
             &input.message,
             HandshakeType::ServerHello,
@@ -215,7 +230,16 @@ impl ExpectServerHelloOrHelloRetryRequest {
                 .as_hybrid_checked(&config.provider().kx_groups, ProtocolVersion::TLSv1_3)
                 .map(|(hybrid, _)| hybrid.component().0);
 
-            if req_group == offered_key_share.share.group() || Some(req_group) == offered_hybrid {
+            let offered_extra = self
+                .next
+                .extra_key_shares
+                .iter()
+                .any(|ks| ks.share.group() == req_group);
+
+            if req_group == offered_key_share.share.group()
+                || Some(req_group) == offered_hybrid
+                || offered_extra
+            {
                 return Err(PeerMisbehaved::IllegalHelloRetryRequestWithOfferedGroup.into());
             }
         }
@@ -260,8 +284,17 @@ impl ExpectServerHelloOrHelloRetryRequest {
         }
 
         // Or asks us to use a ciphersuite we didn't offer.
-        let Some(cs) = config.find_cipher_suite(hrr.cipher_suite) else {
-            return Err(PeerMisbehaved::IllegalHelloRetryRequestWithUnofferedCipherSuite.into());
+        let sent_cipher_suites = &self.next.input.hello.sent_cipher_suites;
+        let cs = match config.find_cipher_suite(hrr.cipher_suite) {
+            Some(cs) if sent_cipher_suites.contains(&hrr.cipher_suite) => cs,
+            None if sent_cipher_suites.contains(&hrr.cipher_suite) => {
+                return Err(PeerIncompatible::ServerSelectedAdvertiseOnlyCipherSuite.into());
+            }
+            _ => {
+                return Err(
+                    PeerMisbehaved::IllegalHelloRetryRequestWithUnofferedCipherSuite.into(),
+                );
+            }
         };
 
         // Or offers ECH related extensions when we didn't offer ECH.
@@ -307,7 +340,7 @@ impl ExpectServerHelloOrHelloRetryRequest {
             cx.data.early_data.rejected();
         }
 
-        let key_share = match hrr.key_share {
+        let (key_share, extra_key_shares) = match hrr.key_share {
             Some(group) if group != offered_key_share.share.group() => {
                 let Some(skxg) = config
                     .provider()
@@ -318,15 +351,18 @@ impl ExpectServerHelloOrHelloRetryRequest {
                     );
                 };
 
-                GroupAndKeyShare::new(skxg)?
+                // Only the requested group is sent now, whatever the template asked for.
+                self.next.input.key_share_groups.clear();
+                (GroupAndKeyShare::new(skxg)?, Vec::new())
             }
-            _ => offered_key_share,
+            _ => (offered_key_share, self.next.extra_key_shares),
         };
 
         emit_client_hello_for_retry(
             transcript_buffer,
             Some(hrr),
             Some(key_share),
+            extra_key_shares,
             self.extra_exts,
             Some(cs),
             self.next.input,
@@ -338,6 +374,7 @@ impl ExpectServerHelloOrHelloRetryRequest {
 
 impl State<ClientConnectionData> for ExpectServerHelloOrHelloRetryRequest {
     fn handle(self: Box<Self>, cx: &mut ClientContext<'_>, input: Input<'_>) -> NextStateOrError {
+        cx.data.first_flight_delay = None;
         match input.message.payload {
             MessagePayload::Handshake {
                 parsed: HandshakeMessagePayload(HandshakePayload::ServerHello(..)),
@@ -368,6 +405,21 @@ pub(crate) struct ClientHelloInput {
     pub(super) session_id: SessionId,
     pub(super) session_key: ClientSessionKey<'static>,
     pub(super) prev_ech_ext: Option<EncryptedClientHello>,
+    // Groups sent in the key_share extension, in order, when a custls template dictated
+    // them. Empty when rustls chose the key shares itself.
+    pub(super) key_share_groups: Vec<NamedGroup>,
+    // Phase 1 result for the initial ClientHello, resolved before the session lookup.
+    // Taken when that hello is emitted.
+    pub(super) custls_params: Option<crate::custls::ConfigParams>,
+    // Session and compatibility behavior a custls template chose for this handshake.
+    // `None` keeps rustls' own behavior.
+    pub(super) handshake_behavior: Option<crate::custls::HandshakeBehavior>,
+    // QUIC transport parameters as sent in the first ClientHello, after any custls
+    // shaping. A retried hello repeats them.
+    pub(super) prev_transport_parameters: Option<Payload<'static>>,
+    // The first ClientHello as the customizer shaped it, before ECH. A retried hello
+    // replays it instead of running the hooks again.
+    pub(super) custls_first_hello: Option<ClientHelloPayload>,
 }
 
 impl ClientHelloInput {
@@ -378,11 +430,30 @@ impl ClientHelloInput {
         cx: &mut ClientContext<'_>,
         config: Arc<ClientConfig>,
     ) -> Result<Self, Error> {
+        // Phase 1 runs before the session lookup, as the session partition it picks
+        // decides which cached sessions this ClientHello may offer
+        let custls_params = resolve_custls_params(&config, &server_name, extra_exts, protocol)?;
         let session_key = ClientSessionKey {
             config_hash: config.config_hash(),
             server_name,
+            partition: custls_params
+                .as_ref()
+                .and_then(|params| params.session_partition.clone()),
         };
+        let handshake_behavior = custls_params
+            .as_ref()
+            .and_then(|params| params.handshake_behavior);
         let mut resuming = ClientSessionValue::retrieve(&session_key, &config, cx);
+        let declined = match (&config.custls_customizer, &resuming) {
+            (Some(customizer), Some(found)) => !customizer
+                .on_resumption_offer(found.value.identity(), session_key.partition.as_deref())
+                .map_err(|e| HookPhase::ResumptionOffer.attribute(e))?,
+            _ => false,
+        };
+        if declined {
+            debug!("Customizer declined to resume session");
+            resuming = None;
+        }
         let session_id = match &mut resuming {
             Some(resuming) => {
                 debug!("Resuming session");
@@ -392,7 +463,17 @@ impl ClientHelloInput {
                         // we're  doing an abbreviated handshake.  See section 3.4 in
                         // RFC5077.
                         if !inner.ticket().is_empty() {
-                            inner.session_id = SessionId::random(config.provider().secure_random)?;
+                            // The ID must not be empty for the server's echo to be recognized
+                            let len = match handshake_behavior {
+                                Some(behavior) if behavior.legacy_session_id_len > 0 => {
+                                    usize::from(behavior.legacy_session_id_len)
+                                }
+                                _ => 32,
+                            };
+                            inner.session_id = SessionId::random_with_len(
+                                config.provider().secure_random,
+                                len,
+                            )?;
                         }
                         Some(inner.session_id)
                     }
@@ -410,8 +491,14 @@ impl ClientHelloInput {
         let session_id = match session_id {
             Some(session_id) => session_id,
             None if protocol.is_quic() => SessionId::empty(),
-            None if !config.supports_version(ProtocolVersion::TLSv1_3) => SessionId::empty(),
-            None => SessionId::random(config.provider().secure_random)?,
+            None => match handshake_behavior {
+                Some(behavior) => SessionId::random_with_len(
+                    config.provider().secure_random,
+                    usize::from(behavior.legacy_session_id_len),
+                )?,
+                None if !config.supports_version(ProtocolVersion::TLSv1_3) => SessionId::empty(),
+                None => SessionId::random(config.provider().secure_random)?,
+            },
         };
 
         let hello = ClientHelloDetails::new(
@@ -427,12 +514,20 @@ impl ClientHelloInput {
             config,
             resuming,
             random,
-            sent_tls13_fake_ccs: false,
+            // Without middlebox compatibility mode the fake CCS is treated as already
+            // sent, so it never is
+            sent_tls13_fake_ccs: handshake_behavior
+                .is_some_and(|behavior| !behavior.middlebox_compat_ccs),
             hello,
             protocol,
             session_id,
             session_key,
             prev_ech_ext: None,
+            key_share_groups: Vec::new(),
+            custls_params,
+            handshake_behavior,
+            prev_transport_parameters: None,
+            custls_first_hello: None,
         })
     }
 
@@ -473,6 +568,7 @@ impl ClientHelloInput {
             transcript_buffer,
             None,
             key_share,
+            Vec::new(),
             extra_exts,
             None,
             self,
@@ -482,6 +578,43 @@ impl ClientHelloInput {
     }
 }
 
+/// Runs phase 1 of the custls customizer, if one is configured.
+///
+/// rustls fills in the target (with the port the connection was created for, or
+/// [`DEFAULT_TARGET_PORT`](crate::custls::hooks::DEFAULT_TARGET_PORT)), offered
+/// ALPN protocols and fresh entropy before
+/// handing the parameters to the customizer.
+fn resolve_custls_params(
+    config: &ClientConfig,
+    server_name: &ServerName<'_>,
+    extra_exts: &ClientExtensionsInput,
+    protocol: Protocol,
+) -> Result<Option<crate::custls::ConfigParams>, Error> {
+    let Some(customizer) = &config.custls_customizer else {
+        return Ok(None);
+    };
+
+    let mut config_params = crate::custls::ConfigParams::new();
+    config_params.target = Some(crate::custls::TargetKey::new(
+        server_name.to_str().into_owned(),
+        extra_exts
+            .target_port
+            .unwrap_or(crate::custls::hooks::DEFAULT_TARGET_PORT),
+    ));
+    if let Some(protocols) = &extra_exts.protocols {
+        config_params.alpn_protocols = protocols
+            .iter()
+            .map(|protocol| protocol.as_ref().to_vec())
+            .collect();
+    }
+    config_params.quic = protocol.is_quic();
+    config_params.entropy = Some(rand::random_u64(config.provider().secure_random)?);
+    customizer
+        .on_config_resolve(&mut config_params)
+        .map_err(|e| HookPhase::ConfigResolve.attribute(e))?;
+    Ok(Some(config_params))
+}
+
 /// Emits the initial ClientHello or a ClientHello in response to
 /// a HelloRetryRequest.
 ///
@@ -491,18 +624,42 @@ fn emit_client_hello_for_retry(
     mut transcript_buffer: HandshakeHashBuffer,
     retryreq: Option<&HelloRetryRequest>,
     key_share: Option<GroupAndKeyShare>,
+    extra_key_shares: Vec<GroupAndKeyShare>,
     extra_exts: ClientExtensionsInput,
     suite: Option<SupportedCipherSuite>,
     mut input: ClientHelloInput,
     cx: &mut ClientContext<'_>,
     mut ech_state: Option<EchState>,
 ) -> NextStateOrError {
+    // A browser answers a HRR with the hello it already sent, changed only where
+    // RFC 8446 Section 4.1.2 requires. The hooks ran for the first hello; running them
+    // again could pick another template, reshuffle it or draw new GREASE, so a
+    // customized hello is replayed instead.
+    let first_hello = match retryreq {
+        Some(_) => input.custls_first_hello.take(),
+        None => None,
+    };
+
+    // Phase 1 Hook: Pre-build configuration. The initial ClientHello uses the
+    // parameters resolved before the session lookup; a replayed retry needs none.
+    let config_params = match input.custls_params.take() {
+        Some(params) => Some(params),
+        None if first_hello.is_some() => None,
+        None => resolve_custls_params(
+            &input.config,
+            &input.session_key.server_name,
+            &extra_exts,
+            input.protocol,
+        )?,
+    };
+    let mut config_params = config_params.unwrap_or_default();
     let config = &input.config;
     
-    // Phase 1 Hook: Pre-build configuration
-    if let Some(customizer) = &config.custls_customizer {
-        let mut config_params = crate::custls::ConfigParams::new();
-        customizer.on_config_resolve(&mut config_params)?;
+    // The HTTP/2 profile and send delay follow the first ClientHello; a retry must
+    // not change them
+    if retryreq.is_none() {
+        cx.data.http2_profile = config_params.http2_profile.take();
+        cx.data.first_flight_delay = config_params.send_delay;
     }
     
     // Defense in depth: the ECH state should be None if ECH is disabled based on config
@@ -545,7 +702,10 @@ fn emit_client_hello_for_retry(
                 .supported_verify_schemes(),
         ),
         protocols: extra_exts.protocols.clone(),
-        extended_master_secret_request: Some(()),
+        extended_master_secret_request: match input.handshake_behavior {
+            Some(behavior) if !behavior.extended_master_secret && !config.require_ems => None,
+            _ => Some(()),
+        },
         supported_versions: Some(supported_versions),
         ..Default::default()
     });
@@ -588,11 +748,31 @@ fn emit_client_hello_for_retry(
         (None, false) => None,
     };
 
+    // A template may dictate exactly which key shares the initial hello carries.
+    // After a HRR, the shares chosen for the first hello (or the single requested one)
+    // are kept.
+    let (key_share, extra_key_shares) = match (config_params.key_share_groups, key_share) {
+        (Some(groups), Some(key_share)) if retryreq.is_none() => {
+            let (key_share, extra_key_shares, sent) =
+                template_key_shares(&groups, key_share, config)?;
+            input.key_share_groups = sent;
+            (Some(key_share), extra_key_shares)
+        }
+        (_, key_share) => (key_share, extra_key_shares),
+    };
+
     if let Some(GroupAndKeyShare { share, .. }) = &key_share {
         debug_assert!(supported_versions.tls13);
         let mut shares = vec![KeyShareEntry::new(share.group(), share.pub_key())];
 
-        if !retryreq
+        if !input.key_share_groups.is_empty() {
+            shares = template_key_share_entries(
+                &input.key_share_groups,
+                share,
+                &extra_key_shares,
+                config,
+            );
+        } else if !retryreq
             .map(|rr| rr.key_share.is_some())
             .unwrap_or_default()
         {
@@ -618,10 +798,18 @@ fn emit_client_hello_for_retry(
     if supported_versions.tls13 {
         // We could support PSK_KE here too. Such connections don't
         // have forward secrecy, and are similar to TLS1.2 resumption.
-        exts.preshared_key_modes = Some(PskKeyExchangeModes {
-            psk_dhe: true,
-            psk: false,
-        });
+        // A template may still advertise it, or omit the extension altogether.
+        exts.preshared_key_modes = match input.handshake_behavior {
+            Some(behavior) if !behavior.psk_dhe_ke && !behavior.psk_ke => None,
+            Some(behavior) => Some(PskKeyExchangeModes {
+                psk_dhe: behavior.psk_dhe_ke,
+                psk: behavior.psk_ke,
+            }),
+            None => Some(PskKeyExchangeModes {
+                psk_dhe: true,
+                psk: false,
+            }),
+        };
     }
 
     input.hello.offered_cert_compression =
@@ -669,7 +857,14 @@ fn emit_client_hello_for_retry(
     }
 
     // Do we have a SessionID or ticket cached for this host?
-    let tls13_session = prepare_resumption(&input.resuming, &mut exts, suite, cx, config);
+    let tls13_session = prepare_resumption(
+        &input.resuming,
+        &mut exts,
+        suite,
+        cx,
+        config,
+        input.handshake_behavior,
+    );
 
     // Extensions MAY be randomized
     // but they also need to keep the same order as the previous ClientHello
@@ -689,12 +884,20 @@ fn emit_client_hello_for_retry(
         cipher_suites.push(CipherSuite::TLS_EMPTY_RENEGOTIATION_INFO_SCSV);
     }
 
+    // A replayed hello is not shaped again
+    let customizer = match first_hello {
+        Some(_) => None,
+        None => config.custls_customizer.as_ref(),
+    };
+
     // Phase 2 Hook: Mid-build - modify cipher suites and extensions
     // Note: Extension modifications are limited since ClientExtensions is a struct with individual fields
     // This hook can modify cipher_suites, but extension modifications require Phase 3
-    if let Some(customizer) = &config.custls_customizer {
+    if let Some(customizer) = customizer {
         let mut extensions_placeholder = vec![]; // Placeholder for now
-        customizer.on_components_ready(&mut cipher_suites, &mut extensions_placeholder)?;
+        customizer
+            .on_components_ready(&mut cipher_suites, &mut extensions_placeholder)
+            .map_err(|e| HookPhase::ComponentsReady.attribute(e))?;
     }
 
     let mut chp_payload = ClientHelloPayload {
@@ -706,6 +909,47 @@ fn emit_client_hello_for_retry(
         extensions: exts,
     };
 
+    if let Some(first_hello) = &first_hello {
+        chp_payload = crate::custls::hrr::replay_for_retry(first_hello, chp_payload);
+    }
+
+    // Phase 3 Hook: Pre-marshal - modify complete ClientHelloPayload
+    // This runs before ECH is applied, so that when offering ECH the shaped payload
+    // becomes the outer hello and is covered by the ECH AAD. The inner hello gets
+    // its own hook while it is being encoded.
+    if let Some(customizer) = customizer {
+        customizer
+            .on_struct_ready(&mut chp_payload)
+            .map_err(|e| HookPhase::StructReady.attribute(e))?;
+
+        // QUIC is TLS 1.3 only and has no compatibility mode (RFC 9001 Sections 4.2
+        // and 8.4), whatever the customizer asked for
+        if input.protocol.is_quic() {
+            let implemented: Vec<CipherSuite> = config
+                .provider()
+                .iter_cipher_suites()
+                .filter(|cs| cs.usable_for_protocol(input.protocol))
+                .map(|cs| cs.suite())
+                .collect();
+            crate::custls::quic::drop_forbidden_in_quic(&mut chp_payload, &implemented);
+        }
+
+        if supported_versions.tls13 && retryreq.is_none() {
+            input.custls_first_hello = Some(chp_payload.clone());
+        }
+    }
+
+    // The transport parameters may have been shaped randomly; a retried hello
+    // must send the same ones
+    match (retryreq, &input.prev_transport_parameters) {
+        (Some(_), Some(prev)) => chp_payload.transport_parameters = Some(prev.clone()),
+        _ => {
+            input.prev_transport_parameters = chp_payload
+                .transport_parameters
+                .clone()
+        }
+    }
+
     let ech_grease_ext = config
         .ech_mode
         .as_ref()
@@ -724,7 +968,12 @@ fn emit_client_hello_for_retry(
         // we need to replace the client hello payload with an ECH client hello payload.
         (EchStatus::NotOffered | EchStatus::Offered, Some(ech_state)) => {
             // Replace the client hello payload with an ECH client hello payload.
-            chp_payload = ech_state.ech_hello(chp_payload, retryreq, tls13_session.as_ref())?;
+            chp_payload = ech_state.ech_hello(
+                chp_payload,
+                retryreq,
+                tls13_session.as_ref(),
+                config.custls_customizer.as_deref(),
+            )?;
             cx.data.ech_status = EchStatus::Offered;
             // Store the ECH extension in case we need to carry it forward in a subsequent hello.
             input.prev_ech_ext = chp_payload
@@ -747,13 +996,18 @@ fn emit_client_hello_for_retry(
         _ => {}
     }
 
-    // Phase 3 Hook: Pre-marshal - modify complete ClientHelloPayload
-    if let Some(customizer) = &config.custls_customizer {
-        customizer.on_struct_ready(&mut chp_payload)?;
+    // A template may have added GREASE ECH in phase 3. Track it like config-level GREASE
+    // so that a subsequent hello after HRR carries forward the identical extension.
+    if cx.data.ech_status == EchStatus::NotOffered && ech_state.is_none() {
+        if let Some(grease_ext) = &chp_payload.encrypted_client_hello {
+            cx.data.ech_status = EchStatus::Grease;
+            input.prev_ech_ext = Some(grease_ext.clone());
+        }
     }
 
-    // Note what extensions we sent.
+    // Note what extensions and cipher suites we sent.
     input.hello.sent_extensions = chp_payload.collect_used();
+    input.hello.sent_cipher_suites = chp_payload.cipher_suites.clone();
 
     let mut chp = HandshakeMessagePayload(HandshakePayload::ClientHello(chp_payload));
 
@@ -781,7 +1035,7 @@ fn emit_client_hello_for_retry(
         _ => None,
     };
 
-    let ch = Message {
+    let mut ch = Message {
         version: match retryreq {
             // <https://datatracker.ietf.org/doc/html/rfc8446#section-5.1>:
             // "This value MUST be set to 0x0303 for all records generated
@@ -806,9 +1060,21 @@ fn emit_client_hello_for_retry(
     trace!("Sending ClientHello {ch:#?}");
 
     // Phase 4 Hook: Post-marshal byte transformation
-    // Note: This hook would need to be implemented at the message sending layer
-    // where wire bytes are actually generated. For now, this is a placeholder.
-    // The hook should intercept after Message::encode() is called.
+    // The transformed bytes are the ones sent and hashed into the transcript
+    if let (Some(customizer), MessagePayload::Handshake { encoded, .. }) =
+        (customizer, &mut ch.payload)
+    {
+        let bytes = customizer
+            .transform_wire_bytes(encoded.bytes().to_vec())
+            .map_err(|e| HookPhase::TransformWireBytes.attribute(e))?;
+        *encoded = Payload::Owned(bytes);
+    }
+
+    if let MessagePayload::Handshake { encoded, .. } = &ch.payload {
+        cx.data
+            .sent_client_hellos
+            .push(crate::custls::SentClientHello::new(encoded.bytes().to_vec()));
+    }
 
     transcript_buffer.add_message(&ch);
     cx.emit(Event::PlainMessage(ch));
@@ -847,6 +1113,7 @@ fn emit_client_hello_for_retry(
         transcript_buffer,
         early_data_key_schedule,
         offered_key_share: key_share,
+        extra_key_shares,
         suite,
         ech_state,
         done_retry: false,
@@ -874,6 +1141,94 @@ impl GroupAndKeyShare {
     }
 }
 
+/// Starts key shares for the `groups` a custls template asked for.
+///
+/// Groups the provider does not support for TLS 1.3 are skipped. The first usable
+/// group becomes the primary share, reusing `default` if it is for that group. A later
+/// group that is the primary's hybrid component is sent using that component, as
+/// rustls does for its "free" second share; any other group gets its own share.
+///
+/// Returns the primary share, the extra shares, and the groups sent in order. If no
+/// group is usable, `default` is returned and the groups sent are left to rustls.
+fn template_key_shares(
+    groups: &[NamedGroup],
+    default: GroupAndKeyShare,
+    config: &ClientConfig,
+) -> Result<(GroupAndKeyShare, Vec<GroupAndKeyShare>, Vec<NamedGroup>), Error> {
+    let mut usable: Vec<&'static dyn SupportedKxGroup> = Vec::with_capacity(groups.len());
+    for group in groups {
+        let Some(skxg) = config
+            .provider()
+            .find_kx_group(*group, ProtocolVersion::TLSv1_3)
+        else {
+            continue;
+        };
+
+        if !usable.iter().any(|g| g.name() == *group) {
+            usable.push(skxg);
+        }
+    }
+
+    let Some((first, rest)) = usable.split_first() else {
+        return Ok((default, Vec::new(), Vec::new()));
+    };
+
+    let primary = match default.share.group() == first.name() {
+        true => default,
+        false => GroupAndKeyShare::new(*first)?,
+    };
+
+    let component = primary
+        .share
+        .as_hybrid_checked(&config.provider().kx_groups, ProtocolVersion::TLSv1_3)
+        .map(|(hybrid, _)| hybrid.component().0);
+
+    let mut extra = Vec::new();
+    for skxg in rest {
+        if Some(skxg.name()) != component {
+            extra.push(GroupAndKeyShare::new(*skxg)?);
+        }
+    }
+
+    let sent = usable.iter().map(|g| g.name()).collect();
+    Ok((primary, extra, sent))
+}
+
+/// Builds the key_share entries for the groups in `order`, taking each from the
+/// primary share, its hybrid component, or the extra shares.
+fn template_key_share_entries(
+    order: &[NamedGroup],
+    primary: &StartedKeyExchange,
+    extra: &[GroupAndKeyShare],
+    config: &ClientConfig,
+) -> Vec<KeyShareEntry> {
+    let component = primary
+        .as_hybrid_checked(&config.provider().kx_groups, ProtocolVersion::TLSv1_3)
+        .map(|(hybrid, _)| hybrid.component());
+
+    order
+        .iter()
+        .filter_map(|group| {
+            if *group == primary.group() {
+                return Some(KeyShareEntry::new(*group, primary.pub_key()));
+            }
+
+            if let Some(ks) = extra
+                .iter()
+                .find(|ks| ks.share.group() == *group)
+            {
+                return Some(KeyShareEntry::new(*group, ks.share.pub_key()));
+            }
+
+            component
+                .filter(|(component_group, _)| component_group == group)
+                .map(|(component_group, component_share)| {
+                    KeyShareEntry::new(component_group, component_share)
+                })
+        })
+        .collect()
+}
+
 /// Prepares `exts` and `cx` with TLS 1.2 or TLS 1.3 session
 /// resumption.
 ///
@@ -895,6 +1250,7 @@ fn prepare_resumption<'a>(
     suite: Option<SupportedCipherSuite>,
     cx: &mut ClientContext<'_>,
     config: &ClientConfig,
+    behavior: Option<crate::custls::HandshakeBehavior>,
 ) -> Option<Retrieved<&'a Tls13ClientSessionValue>> {
     // Check whether we're resuming with a non-empty ticket.
     let resuming = match resuming {
@@ -902,6 +1258,7 @@ fn prepare_resumption<'a>(
         _ => {
             if config.supports_version(ProtocolVersion::TLSv1_2)
                 && config.resumption.tls12_resumption == Tls12Resumption::SessionIdOrTickets
+                && behavior.is_none_or(|behavior| behavior.session_ticket_request)
             {
                 // If we don't have a ticket, request one.
                 exts.session_ticket = Some(ClientSessionTicket::Request);
@@ -920,7 +1277,8 @@ fn prepare_resumption<'a>(
         return None; // TLS 1.2, so nothing to return here
     };
 
-    if !config.supports_version(ProtocolVersion::TLSv1_3) {
+    // A ticket cannot be offered without PSK key exchange modes (RFC 8446 Section 4.2.9)
+    if !config.supports_version(ProtocolVersion::TLSv1_3) || exts.preshared_key_modes.is_none() {
         return None;
     }
 
@@ -936,10 +1294,32 @@ fn prepare_resumption<'a>(
         suite.can_resume_from(tls13.suite())?;
     }
 
-    tls13::prepare_resumption(config, cx, &tls13, exts, suite.is_some());
+    let offer_early_data = behavior.is_none_or(|behavior| behavior.early_data);
+    tls13::prepare_resumption(config, cx, &tls13, exts, suite.is_some(), offer_early_data);
     Some(tls13)
 }
 
+/// Reject a server signature made with a scheme we advertised but cannot verify.
+///
+/// Without a customizer we only advertise what the verifier supports. A template may
+/// add legacy schemes such as `rsa_pkcs1_sha1` for fingerprinting; a server choosing
+/// one of those must not reach a verifier that might be more permissive.
+pub(super) fn check_server_signature_scheme(
+    config: &ClientConfig,
+    scheme: SignatureScheme,
+) -> Result<(), Error> {
+    if config.custls_customizer.is_some()
+        && !config
+            .verifier()
+            .supported_verify_schemes()
+            .contains(&scheme)
+    {
+        return Err(PeerIncompatible::ServerSignedWithAdvertiseOnlySignatureScheme.into());
+    }
+
+    Ok(())
+}
+
 pub(super) fn process_alpn_protocol(
     output: &mut dyn Output,
     offered_protocols: &[ApplicationProtocol<'_>],
@@ -1015,6 +1395,15 @@ impl ClientSessionValue {
         found
     }
 
+    /// Identity of the session reported to custls hooks: the ticket if there is
+    /// one, otherwise the TLS 1.2 session ID.
+    fn identity(&self) -> &[u8] {
+        match self {
+            Self::Tls12(inner) if inner.common.ticket().is_empty() => inner.session_id.as_ref(),
+            _ => self.common().ticket(),
+        }
+    }
+
     fn common(&self) -> &ClientSessionCommon {
         match self {
             Self::Tls13(inner) => &inner.common,
@@ -6,7 +6,7 @@ use pki_types::UnixTime;
 use zeroize::Zeroizing;
 
 use crate::crypto::cipher::Payload;
-use crate::crypto::{Identity, SelectedCredential, SignatureScheme};
+use crate::crypto::{CipherSuite, Identity, SelectedCredential, SignatureScheme};
 use crate::enums::{ApplicationProtocol, CertificateType};
 use crate::log::{debug, trace};
 use crate::msgs::{
@@ -223,6 +223,7 @@ impl ServerCertDetails {
 struct ClientHelloDetails {
     alpn_protocols: Vec<ApplicationProtocol<'static>>,
     sent_extensions: Vec<ExtensionType>,
+    sent_cipher_suites: Vec<CipherSuite>,
     extension_order_seed: u16,
     offered_cert_compression: bool,
 }
@@ -232,6 +233,7 @@ impl ClientHelloDetails {
         Self {
             alpn_protocols,
             sent_extensions: Vec::new(),
+            sent_cipher_suites: Vec::new(),
             extension_order_seed,
             offered_cert_compression: false,
         }
@@ -1,31 +1,44 @@
 use alloc::borrow::Cow;
 use core::hash::Hasher;
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::prelude::v1::*;
 use std::sync::OnceLock;
 use std::vec;
 
-use pki_types::{CertificateDer, FipsStatus, ServerName};
+use pki_types::{CertificateDer, FipsStatus, ServerName, UnixTime};
 
-use crate::client::{ClientConfig, ClientConnection, Resumption, Tls12Resumption};
+use crate::client::{
+    ClientConfig, ClientConnection, ClientSessionKey, Resumption, Tls12ClientSessionValue,
+    Tls12Resumption, UnbufferedClientConnection,
+};
 use crate::crypto::cipher::{EncodedMessage, MessageEncrypter, Payload};
 use crate::crypto::kx::{self, NamedGroup, SharedSecret, StartedKeyExchange, SupportedKxGroup};
 use crate::crypto::test_provider::FakeKeyExchangeGroup;
 use crate::crypto::tls13::OkmBlock;
+use crate::custls::browser_validation::{ValidationResult, validate_template};
+use crate::custls::templates::{chrome_130, firefox_135, register_template};
+use crate::custls::{
+    BrowserTemplate, ClientExtension, ClientHelloCustomizer, ConfigParams, CustlsConfig,
+    CustlsError, CustomizerChain, DefaultCustomizer, HandshakeBehavior, HookPhase, HostPattern,
+    Http2Profile, ProfileMismatch, RandomizationLevel, RouteAction, RoutingRule,
+};
+use crate::custls::capture::is_grease_value;
 use crate::crypto::{
-    CipherSuite, Credentials, CryptoProvider, Identity, SignatureScheme, SingleCredential,
-    TEST_PROVIDER, tls12_only, tls13_only, tls13_suite,
+    CertificateIdentity, CipherSuite, Credentials, CryptoProvider, Identity, SignatureScheme,
+    SingleCredential, TEST_PROVIDER, tls12_only, tls12_suite, tls13_only, tls13_suite,
 };
-use crate::enums::{CertificateType, ProtocolVersion};
+use crate::enums::{ApplicationProtocol, CertificateType, ContentType, ProtocolVersion};
 use crate::error::{Error, PeerIncompatible, PeerMisbehaved};
 use crate::msgs::{
-    CertificateChain, ClientHelloPayload, Compression, ECCurveType, EcParameters,
-    HandshakeMessagePayload, HandshakePayload, HelloRetryRequest, HelloRetryRequestExtensions,
+    CertificateChain, ClientHelloPayload, ClientSessionTicket, Compression, ECCurveType, EcParameters,
+    ExtensionType, HandshakeMessagePayload, HandshakePayload, HelloRetryRequest, HelloRetryRequestExtensions,
     KeyShareEntry, Message, MessagePayload, Random, Reader, ServerEcdhParams, ServerExtensions,
     ServerHelloPayload, ServerKeyExchange, ServerKeyExchangeParams, ServerKeyExchangePayload,
-    SessionId, SizedPayload,
+    SessionId, SingleProtocolName, SizedPayload,
 };
 use crate::pki_types::PrivateKeyDer;
+use crate::server::Acceptor;
 use crate::pki_types::pem::PemObject;
 use crate::sync::Arc;
 use crate::tls13::key_schedule::{derive_traffic_iv, derive_traffic_key};
@@ -208,13 +221,32 @@ fn test_client_with_custom_verifier_can_accept_ecdsa_sha1_signatures() {
     let mut sent = Vec::new();
     conn.write_tls(&mut sent).unwrap();
 
+    for message in tls12_server_flight(
+        CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+        SignatureScheme::ECDSA_SHA1_Legacy,
+    ) {
+        conn.read_tls(&mut message.into_wire_bytes().as_slice())
+            .unwrap();
+        conn.process_new_packets().unwrap();
+    }
+
+    assert!(
+        verifier
+            .seen_sha1_signature
+            .load(Ordering::SeqCst)
+    );
+}
+
+/// ServerHello, Certificate, ServerKeyExchange and ServerHelloDone for TLS1.2,
+/// with the key exchange signed using `scheme`.
+fn tls12_server_flight(suite: CipherSuite, scheme: SignatureScheme) -> Vec<Message<'static>> {
     let sh = Message {
         version: ProtocolVersion::TLSv1_2,
         payload: MessagePayload::handshake(HandshakeMessagePayload(HandshakePayload::ServerHello(
             ServerHelloPayload {
                 random: Random([0u8; 32]),
                 compression_method: Compression::Null,
-                cipher_suite: CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+                cipher_suite: suite,
                 legacy_version: ProtocolVersion::TLSv1_2,
                 session_id: SessionId::empty(),
                 extensions: Box::new(ServerExtensions {
@@ -224,9 +256,6 @@ fn test_client_with_custom_verifier_can_accept_ecdsa_sha1_signatures() {
             },
         ))),
     };
-    conn.read_tls(&mut sh.into_wire_bytes().as_slice())
-        .unwrap();
-    conn.process_new_packets().unwrap();
 
     let cert = Message {
         version: ProtocolVersion::TLSv1_2,
@@ -234,19 +263,13 @@ fn test_client_with_custom_verifier_can_accept_ecdsa_sha1_signatures() {
             CertificateChain(vec![CertificateDer::from(&b"does not matter"[..])]),
         ))),
     };
-    conn.read_tls(&mut cert.into_wire_bytes().as_slice())
-        .unwrap();
-    conn.process_new_packets().unwrap();
 
     let server_kx = Message {
         version: ProtocolVersion::TLSv1_2,
         payload: MessagePayload::handshake(HandshakeMessagePayload(
             HandshakePayload::ServerKeyExchange(ServerKeyExchangePayload::Known(
                 ServerKeyExchange {
-                    dss: DigitallySignedStruct::new(
-                        SignatureScheme::ECDSA_SHA1_Legacy,
-                        b"also does not matter".to_vec(),
-                    ),
+                    dss: DigitallySignedStruct::new(scheme, b"also does not matter".to_vec()),
                     params: ServerKeyExchangeParams::Ecdh(ServerEcdhParams {
                         curve_params: EcParameters {
                             curve_type: ECCurveType::NamedCurve,
@@ -258,9 +281,6 @@ fn test_client_with_custom_verifier_can_accept_ecdsa_sha1_signatures() {
             )),
         )),
     };
-    conn.read_tls(&mut server_kx.into_wire_bytes().as_slice())
-        .unwrap();
-    conn.process_new_packets().unwrap();
 
     let server_done = Message {
         version: ProtocolVersion::TLSv1_2,
@@ -268,15 +288,8 @@ fn test_client_with_custom_verifier_can_accept_ecdsa_sha1_signatures() {
             HandshakePayload::ServerHelloDone,
         )),
     };
-    conn.read_tls(&mut server_done.into_wire_bytes().as_slice())
-        .unwrap();
-    conn.process_new_packets().unwrap();
 
-    assert!(
-        verifier
-            .seen_sha1_signature
-            .load(Ordering::SeqCst)
-    );
+    vec![sh, cert, server_kx, server_done]
 }
 
 #[derive(Debug, Default)]
@@ -319,152 +332,910 @@ impl ServerVerifier for ExpectSha1EcdsaVerifier {
 }
 
 #[test]
-fn test_client_requiring_rpk_rejects_server_that_only_offers_x509_id_by_omission() {
-    client_requiring_rpk_receives_server_ee(
-        Err(PeerIncompatible::IncorrectCertificateTypeExtension.into()),
-        ServerExtensions::default(),
-        &TEST_PROVIDER,
+fn customizer_advertised_only_cipher_suite_is_not_negotiated() {
+    let advertised = CipherSuite::from(0x000a);
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_custls(Arc::new(AdvertisingCustomizer {
+        cipher_suites: vec![advertised],
+        signature_schemes: vec![],
+    }));
+
+    let mut conn =
+        ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap()).unwrap();
+    let mut sent = Vec::new();
+    conn.write_tls(&mut sent).unwrap();
+    let ch = client_hellos_in(&sent).pop().unwrap();
+    assert_eq!(ch.cipher_suites.last(), Some(&advertised));
+
+    let sh = tls12_server_flight(advertised, SignatureScheme::ECDSA_NISTP256_SHA256).remove(0);
+    conn.read_tls(&mut sh.into_wire_bytes().as_slice())
+        .unwrap();
+    assert_eq!(
+        conn.process_new_packets().unwrap_err(),
+        PeerIncompatible::ServerSelectedAdvertiseOnlyCipherSuite.into()
     );
 }
 
 #[test]
-fn test_client_requiring_rpk_rejects_server_that_only_offers_x509_id() {
-    client_requiring_rpk_receives_server_ee(
-        Err(PeerIncompatible::IncorrectCertificateTypeExtension.into()),
-        ServerExtensions {
-            server_certificate_type: Some(CertificateType::X509),
-            ..ServerExtensions::default()
-        },
-        &TEST_PROVIDER,
-    );
+fn customizer_legacy_safari_suites_are_not_negotiated() {
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_custls(Arc::new(DefaultCustomizer::new(
+        CustlsConfig::builder()
+            .with_template(BrowserTemplate::Named("safari_17_legacy".into()))
+            .with_randomization_level(RandomizationLevel::None)
+            .build(),
+    )));
+    let config = Arc::new(config);
+
+    for legacy in [0xc008, 0xc012, 0x000a].map(CipherSuite::from) {
+        let mut conn =
+            ClientConnection::new(config.clone(), ServerName::try_from("localhost").unwrap())
+                .unwrap();
+        let mut sent = Vec::new();
+        conn.write_tls(&mut sent).unwrap();
+        let ch = client_hellos_in(&sent).pop().unwrap();
+        assert!(ch.cipher_suites.contains(&legacy), "{legacy:?} not advertised");
+
+        let sh = tls12_server_flight(legacy, SignatureScheme::ECDSA_NISTP256_SHA256).remove(0);
+        conn.read_tls(&mut sh.into_wire_bytes().as_slice())
+            .unwrap();
+        assert_eq!(
+            conn.process_new_packets().unwrap_err(),
+            PeerIncompatible::ServerSelectedAdvertiseOnlyCipherSuite.into(),
+            "{legacy:?}"
+        );
+    }
 }
 
 #[test]
-fn test_client_requiring_rpk_rejects_server_that_only_demands_x509_by_omission() {
-    client_requiring_rpk_receives_server_ee(
-        Err(PeerIncompatible::IncorrectCertificateTypeExtension.into()),
-        ServerExtensions {
-            server_certificate_type: Some(CertificateType::RawPublicKey),
-            ..ServerExtensions::default()
-        },
-        &TEST_PROVIDER,
+fn customizer_advertised_only_signature_scheme_is_not_verified() {
+    let Some(provider) = x25519_provider(TEST_PROVIDER.clone()) else {
+        return;
+    };
+
+    let mut config = ClientConfig::builder(Arc::new(provider))
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnythingVerifier))
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_custls(Arc::new(AdvertisingCustomizer {
+        cipher_suites: vec![],
+        signature_schemes: vec![
+            SignatureScheme::ECDSA_NISTP256_SHA256,
+            SignatureScheme::ECDSA_SHA1_Legacy,
+        ],
+    }));
+
+    let mut conn =
+        ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap()).unwrap();
+    let mut sent = Vec::new();
+    conn.write_tls(&mut sent).unwrap();
+    let ch = client_hellos_in(&sent).pop().unwrap();
+    assert_eq!(
+        ch.signature_schemes,
+        Some(vec![
+            SignatureScheme::ECDSA_NISTP256_SHA256,
+            SignatureScheme::ECDSA_SHA1_Legacy,
+        ])
+    );
+
+    let mut flight = tls12_server_flight(
+        CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+        SignatureScheme::ECDSA_SHA1_Legacy,
+    );
+    let server_done = flight.pop().unwrap();
+    for message in flight {
+        conn.read_tls(&mut message.into_wire_bytes().as_slice())
+            .unwrap();
+        conn.process_new_packets().unwrap();
+    }
+
+    conn.read_tls(&mut server_done.into_wire_bytes().as_slice())
+        .unwrap();
+    assert_eq!(
+        conn.process_new_packets().unwrap_err(),
+        PeerIncompatible::ServerSignedWithAdvertiseOnlySignatureScheme.into()
     );
 }
 
+/// Appends cipher suites and replaces signature schemes, like a browser template would.
+#[derive(Debug)]
+struct AdvertisingCustomizer {
+    cipher_suites: Vec<CipherSuite>,
+    signature_schemes: Vec<SignatureScheme>,
+}
+
+impl ClientHelloCustomizer for AdvertisingCustomizer {
+    fn on_components_ready(
+        &self,
+        cipher_suites: &mut Vec<CipherSuite>,
+        _extensions: &mut Vec<ClientExtension>,
+    ) -> Result<(), Error> {
+        cipher_suites.extend_from_slice(&self.cipher_suites);
+        Ok(())
+    }
+
+    fn on_struct_ready(&self, payload: &mut ClientHelloPayload) -> Result<(), Error> {
+        if !self.signature_schemes.is_empty() {
+            payload.signature_schemes = Some(self.signature_schemes.clone());
+        }
+        Ok(())
+    }
+}
+
 #[test]
-fn test_client_requiring_rpk_rejects_server_that_only_demands_x509() {
-    client_requiring_rpk_receives_server_ee(
-        Err(PeerIncompatible::IncorrectCertificateTypeExtension.into()),
-        ServerExtensions {
-            client_certificate_type: Some(CertificateType::X509),
-            server_certificate_type: Some(CertificateType::RawPublicKey),
-            ..ServerExtensions::default()
-        },
-        &TEST_PROVIDER,
+fn customizer_chain_layers_shape_one_hello() {
+    let advertised = CipherSuite::from(0x000a);
+    let chain = CustomizerChain::new()
+        .with_layer(Arc::new(AdvertisingCustomizer {
+            cipher_suites: vec![advertised],
+            signature_schemes: vec![],
+        }))
+        .with_optional_layer(Arc::new(FailingCustomizer))
+        .with_layer(Arc::new(AdvertisingCustomizer {
+            cipher_suites: vec![],
+            signature_schemes: vec![SignatureScheme::ECDSA_NISTP256_SHA256],
+        }));
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_custls(Arc::new(chain.clone()));
+
+    let mut conn =
+        ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap()).unwrap();
+    let mut sent = Vec::new();
+    conn.write_tls(&mut sent).unwrap();
+    let ch = client_hellos_in(&sent).pop().unwrap();
+    assert_eq!(ch.cipher_suites.last(), Some(&advertised));
+    assert_eq!(
+        ch.signature_schemes,
+        Some(vec![SignatureScheme::ECDSA_NISTP256_SHA256])
+    );
+
+    // The same layer made required aborts the handshake
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_custls(Arc::new(chain.with_layer(Arc::new(FailingCustomizer))));
+    let err = ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap())
+        .unwrap_err();
+    assert_eq!(
+        err,
+        Error::Customization {
+            phase: Some(HookPhase::StructReady),
+            error: CustlsError::HookError("customizer failed".into()),
+        }
     );
 }
 
 #[test]
-fn test_client_requiring_rpk_accepts_rpk_server() {
-    client_requiring_rpk_receives_server_ee(
-        Ok(()),
-        ServerExtensions {
-            client_certificate_type: Some(CertificateType::RawPublicKey),
-            server_certificate_type: Some(CertificateType::RawPublicKey),
-            ..ServerExtensions::default()
-        },
-        &TEST_PROVIDER,
+fn customizer_transforms_sent_hello_bytes() {
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_custls(Arc::new(RandomOverwritingCustomizer(Ok(()))));
+
+    let mut conn =
+        ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap()).unwrap();
+    let mut sent = Vec::new();
+    conn.write_tls(&mut sent).unwrap();
+    let ch = client_hellos_in(&sent).pop().unwrap();
+    assert_eq!(ch.random, Random([0x42; 32]));
+    assert_eq!(conn.sent_client_hellos()[0].bytes(), &sent[5..]);
+
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    let failing = RandomOverwritingCustomizer(Err("transform failed"));
+    config.enable_custls(Arc::new(failing));
+    let err = ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap())
+        .unwrap_err();
+    assert_eq!(
+        err,
+        Error::Customization {
+            phase: Some(HookPhase::TransformWireBytes),
+            error: CustlsError::HookError("transform failed".into()),
+        }
     );
 }
 
-#[track_caller]
-fn client_requiring_rpk_receives_server_ee(
-    expected: Result<(), Error>,
-    encrypted_extensions: ServerExtensions<'_>,
-    provider: &CryptoProvider,
-) {
-    let Some(provider) = x25519_provider(provider.clone()) else {
-        return;
-    };
+#[test]
+fn customizer_is_not_run_again_after_hrr() {
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_custls(Arc::new(FailsOnRetryCustomizer(AtomicBool::new(false))));
 
-    let provider = Arc::new(CryptoProvider {
-        tls12_cipher_suites: Cow::default(),
-        ..provider
-    });
-    let fake_server_crypto = Arc::new(FakeServerCrypto::new(provider.clone()));
-    let mut conn = ClientConnection::new(
-        Arc::new(client_config_for_rpk(fake_server_crypto.clone(), provider)),
-        ServerName::try_from("localhost").unwrap(),
-    )
-    .unwrap();
+    let mut conn =
+        ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap()).unwrap();
     let mut sent = Vec::new();
     conn.write_tls(&mut sent).unwrap();
 
-    let sh = Message {
-        version: ProtocolVersion::TLSv1_3,
-        payload: MessagePayload::handshake(HandshakeMessagePayload(HandshakePayload::ServerHello(
-            ServerHelloPayload {
-                random: Random([0; 32]),
-                compression_method: Compression::Null,
-                cipher_suite: CipherSuite::TLS13_AES_128_GCM_SHA256,
-                legacy_version: ProtocolVersion::TLSv1_3,
-                session_id: SessionId::empty(),
-                extensions: Box::new(ServerExtensions {
-                    key_share: Some(KeyShareEntry {
-                        group: NamedGroup::X25519,
-                        payload: SizedPayload::from(vec![0xaa; 32]),
-                    }),
-                    ..ServerExtensions::default()
-                }),
-            },
-        ))),
-    };
-    conn.read_tls(&mut sh.into_wire_bytes().as_slice())
+    let ch = client_hellos_in(&sent).pop().unwrap();
+    conn.read_tls(&mut hello_retry_request(&ch, None).into_wire_bytes().as_slice())
         .unwrap();
     conn.process_new_packets().unwrap();
 
-    let ee = Message {
-        version: ProtocolVersion::TLSv1_3,
-        payload: MessagePayload::handshake(HandshakeMessagePayload(
-            HandshakePayload::EncryptedExtensions(Box::new(encrypted_extensions)),
-        )),
-    };
+    let mut sent = Vec::new();
+    conn.write_tls(&mut sent).unwrap();
+    let retried = client_hellos_in(&sent).pop().unwrap();
+    assert_eq!(retried.cipher_suites, ch.cipher_suites);
+}
 
-    let mut encrypter = fake_server_crypto.server_handshake_encrypter();
-    let enc_ee = encrypter
-        .encrypt(EncodedMessage::<Payload<'_>>::from(ee).borrow_outbound(), 0)
-        .unwrap();
-    conn.read_tls(&mut enc_ee.encode().as_slice())
-        .unwrap();
+#[test]
+fn customized_hello_replayed_after_hrr() {
+    // A cookie-only HRR, and one asking for a group the client has no share for
+    for (provider, requested) in [
+        (TEST_PROVIDER.clone(), None),
+        (THREE_GROUP_PROVIDER.clone(), Some(NamedGroup::Unknown(0xfe02))),
+    ] {
+        let mut config = ClientConfig::builder(Arc::new(provider))
+            .with_root_certificates(roots())
+            .with_no_client_auth()
+            .unwrap();
+        config.enable_custls(Arc::new(ShufflingCustomizer::default()));
 
-    assert_eq!(conn.process_new_packets().map(|_| ()), expected);
-}
+        let mut conn =
+            ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap())
+                .unwrap();
+        let mut sent = Vec::new();
+        conn.write_tls(&mut sent).unwrap();
+        let first = client_hellos_in(&sent).pop().unwrap();
 
-fn client_config_for_rpk(key_log: Arc<dyn KeyLog>, provider: Arc<CryptoProvider>) -> ClientConfig {
-    let credentials = client_credentials(&provider);
-    let mut config = ClientConfig::builder(provider)
-        .dangerous()
-        .with_custom_certificate_verifier(Arc::new(ServerVerifierRequiringRpk))
-        .with_client_credential_resolver(Arc::new(SingleCredential::from(credentials)))
-        .unwrap();
-    config.key_log = key_log;
-    config
+        let hrr = hello_retry_request(&first, requested);
+        conn.read_tls(&mut hrr.into_wire_bytes().as_slice())
+            .unwrap();
+        conn.process_new_packets().unwrap();
+        let mut sent = Vec::new();
+        conn.write_tls(&mut sent).unwrap();
+        let retried = client_hellos_in(&sent).pop().unwrap();
+
+        assert!(retried.random == first.random);
+        assert_eq!(retried.session_id, first.session_id);
+        assert_eq!(retried.cipher_suites, first.cipher_suites);
+        for ext in first.collect_used() {
+            if ext != ExtensionType::KeyShare {
+                assert!(retried.has_identical(&first, ext), "{ext:?} changed");
+            }
+        }
+        match requested {
+            Some(group) => {
+                assert_eq!(key_share_entries(&retried)[0].0, group);
+                assert!(retried.cookie.is_none());
+            }
+            None => {
+                assert_eq!(key_share_entries(&retried), key_share_entries(&first));
+                assert!(retried.cookie.is_some());
+            }
+        }
+
+        // Same extension order on the wire, the cookie aside
+        let hellos = conn.sent_client_hellos();
+        let (first, retried) = (hellos[0].view().unwrap(), hellos[1].view().unwrap());
+        let mut order = retried.extension_types();
+        order.retain(|ext| *ext != u16::from(ExtensionType::Cookie));
+        assert_eq!(order, first.extension_types());
+    }
 }
 
-fn client_credentials(provider: &CryptoProvider) -> Credentials {
-    let key = provider
-        .key_provider
-        .load_private_key(client_key())
+#[test]
+fn browser_profile_hello_matches_template() {
+    // Chrome's shape over suites and groups the test provider implements, plus
+    // one suite and one group it does not
+    let mut template = chrome_130();
+    template.cipher_suites = vec![
+        CipherSuite::Unknown(0xff13),
+        CipherSuite::TLS13_AES_128_GCM_SHA256,
+        CipherSuite::Unknown(0xff12),
+    ];
+    template.supported_groups = vec![NamedGroup::Unknown(0xfe00), NamedGroup::X25519];
+    template.key_share_groups = vec![NamedGroup::Unknown(0xfe00)];
+    register_template("test_profile", template.clone());
+
+    let browser = BrowserTemplate::Named("test_profile".into());
+    let config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_browser_profile(browser.clone())
+        .with_root_certificates(roots())
+        .with_no_client_auth()
         .unwrap();
-    let identity = Arc::from(Identity::RawPublicKey(
-        key.public_key().unwrap().into_owned(),
-    ));
-    Credentials::new_unchecked(identity, key)
-}
 
-fn client_key() -> PrivateKeyDer<'static> {
-    PrivateKeyDer::from_pem_reader(
-        &mut include_bytes!("../../../test-ca/rsa-2048/client.key").as_slice(),
+    let mut expected = vec![
+        ProfileMismatch::CipherSuiteNotImplemented(CipherSuite::TLS13_AES_128_GCM_SHA256),
+        ProfileMismatch::GroupNotImplemented(NamedGroup::X25519),
+    ];
+    if crate::compress::default_cert_decompressors().is_empty() {
+        expected.push(ProfileMismatch::CertCompressionUnavailable);
+    }
+    let profile = config.browser_profile().unwrap();
+    assert_eq!(profile.template, browser);
+    assert_eq!(profile.mismatches, expected);
+    assert!(config.is_custls_enabled());
+
+    let ch = client_hello_sent_for_config(config).unwrap();
+    let is_grease = |value: u16| value & 0x0f0f == 0x0a0a;
+    let suites = ch
+        .cipher_suites
+        .iter()
+        .filter(|cs| !is_grease(u16::from(**cs)))
+        .copied()
+        .collect::<Vec<_>>();
+    assert_eq!(suites, template.cipher_suites);
+    assert_eq!(ch.named_groups, Some(vec![NamedGroup::Unknown(0xfe00)]));
+    assert_eq!(
+        ch.key_shares
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|share| share.group)
+            .collect::<Vec<_>>(),
+        vec![NamedGroup::Unknown(0xfe00)]
+    );
+    assert_eq!(ch.signature_schemes, Some(template.signature_algorithms));
+    assert_eq!(
+        ch.protocols,
+        Some(vec![ApplicationProtocol::from(b"h2"), ApplicationProtocol::from(b"http/1.1")])
+    );
+    assert!(ch.server_name.is_some());
+    assert!(ch.session_ticket.is_some());
+    assert!(
+        ch.supported_versions
+            .is_some_and(|versions| versions.tls12 && versions.tls13)
+    );
+}
+
+#[test]
+fn browser_profile_reports_missing_protocol_version() {
+    let mut template = chrome_130();
+    template.cipher_suites = vec![CipherSuite::Unknown(0xff13), CipherSuite::Unknown(0xff12)];
+    template.supported_groups = vec![NamedGroup::Unknown(0xfe00)];
+    register_template("test_profile_tls13", template);
+
+    let config = ClientConfig::builder(Arc::new(tls13_only(TEST_PROVIDER.clone())))
+        .with_browser_profile(BrowserTemplate::Named("test_profile_tls13".into()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    assert!(
+        config
+            .browser_profile()
+            .unwrap()
+            .mismatches
+            .contains(&ProfileMismatch::VersionNotImplemented(ProtocolVersion::TLSv1_2))
+    );
+
+    let ch = client_hello_sent_for_config(config).unwrap();
+    assert!(
+        ch.supported_versions
+            .is_some_and(|versions| !versions.tls12 && versions.tls13)
+    );
+}
+
+#[test]
+fn browser_profile_rejects_unusable_templates() {
+    let build = |browser| {
+        ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+            .with_browser_profile(browser)
+            .with_root_certificates(roots())
+            .with_no_client_auth()
+    };
+
+    // No suite or group of the real Chrome template is in the test provider
+    assert!(build(BrowserTemplate::Chrome130).is_err());
+    assert!(build(BrowserTemplate::Named("not_registered".into())).is_err());
+}
+
+#[test]
+fn customizer_session_partition_keeps_templates_apart() {
+    let customizer = Arc::new(DefaultCustomizer::new(
+        CustlsConfig::builder()
+            .with_template(BrowserTemplate::Firefox135)
+            .with_randomization_level(RandomizationLevel::None)
+            .build(),
+    ));
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.resumption =
+        Resumption::in_memory_sessions(128).tls12_resumption(Tls12Resumption::SessionIdOrTickets);
+    config.enable_custls(customizer.clone());
+
+    let store = |partition: &str, ticket: &[u8]| {
+        let key = ClientSessionKey {
+            config_hash: config.config_hash(),
+            server_name: ServerName::try_from("localhost").unwrap(),
+            partition: Some(partition.as_bytes().to_vec()),
+        };
+        let value = Tls12ClientSessionValue::new(
+            tls12_suite(CipherSuite::Unknown(0xff12), &TEST_PROVIDER),
+            SessionId::empty(),
+            Arc::new(SizedPayload::from(ticket.to_vec())),
+            &[0u8; 48],
+            Identity::X509(CertificateIdentity {
+                end_entity: CertificateDer::from(&[][..]),
+                intermediates: Vec::new(),
+            }),
+            UnixTime::now(),
+            Duration::from_secs(3600),
+            true,
+        );
+        config
+            .resumption
+            .store
+            .set_tls12_session(key, value);
+    };
+    let offered_ticket = || {
+        match client_hello_sent_for_config(config.clone())
+            .unwrap()
+            .extensions
+            .session_ticket
+        {
+            Some(ClientSessionTicket::Offer(ticket)) => Some(ticket.bytes().to_vec()),
+            _ => None,
+        }
+    };
+    let firefox = firefox_135().name;
+
+    // A session from a Chrome-shaped handshake is never offered by Firefox
+    store(&chrome_130().name, b"chrome");
+    assert_eq!(offered_ticket(), None);
+
+    // Firefox offers its own sessions, as recorded when they were stored
+    store(&firefox, b"firefox");
+    customizer.on_session_stored(b"firefox", Some(firefox.as_bytes()));
+    assert_eq!(offered_ticket(), Some(b"firefox".to_vec()));
+
+    // A session in the Firefox partition without a recorded fingerprint is refused
+    store(&firefox, b"unrecorded");
+    assert_eq!(offered_ticket(), None);
+}
+
+/// Fails phase 3 after clearing the cipher suites.
+#[derive(Debug)]
+struct FailingCustomizer;
+
+impl ClientHelloCustomizer for FailingCustomizer {
+    fn on_struct_ready(&self, payload: &mut ClientHelloPayload) -> Result<(), Error> {
+        payload.cipher_suites.clear();
+        Err(Error::General("customizer failed".into()))
+    }
+}
+
+/// Overwrites the random of the encoded ClientHello in phase 4, or fails it.
+#[derive(Debug)]
+struct RandomOverwritingCustomizer(Result<(), &'static str>);
+
+impl ClientHelloCustomizer for RandomOverwritingCustomizer {
+    fn transform_wire_bytes(&self, mut bytes: Vec<u8>) -> Result<Vec<u8>, Error> {
+        self.0
+            .map_err(|msg| Error::General(msg.into()))?;
+        // Handshake header (4 bytes) and legacy_version (2 bytes) come first
+        bytes[6..38].fill(0x42);
+        Ok(bytes)
+    }
+}
+
+/// Fails phase 2 of every ClientHello after the first.
+#[derive(Debug)]
+struct FailsOnRetryCustomizer(AtomicBool);
+
+/// Shapes every ClientHello differently: the cipher suites and extensions are
+/// rotated further on each call.
+#[derive(Debug, Default)]
+struct ShufflingCustomizer(AtomicUsize);
+
+impl ClientHelloCustomizer for ShufflingCustomizer {
+    fn on_struct_ready(&self, payload: &mut ClientHelloPayload) -> Result<(), Error> {
+        let calls = self.0.fetch_add(1, Ordering::Relaxed) + 1;
+        let suites = payload.cipher_suites.len();
+        payload
+            .cipher_suites
+            .rotate_left(calls % suites);
+        let mut order = payload.collect_used();
+        let extensions = order.len();
+        order.rotate_left(calls % extensions);
+        payload.contiguous_extensions = order;
+        Ok(())
+    }
+}
+
+impl ClientHelloCustomizer for FailsOnRetryCustomizer {
+    fn on_components_ready(
+        &self,
+        _cipher_suites: &mut Vec<CipherSuite>,
+        _extensions: &mut Vec<ClientExtension>,
+    ) -> Result<(), Error> {
+        match self.0.swap(true, Ordering::Relaxed) {
+            true => Err(CustlsError::CacheError("retry refused".into()).into()),
+            false => Ok(()),
+        }
+    }
+}
+
+/// A verifier that would accept any signature, but only claims to support P-256.
+#[derive(Debug)]
+struct AcceptAnythingVerifier;
+
+impl ServerVerifier for AcceptAnythingVerifier {
+    fn verify_identity(&self, _identity: &ServerIdentity<'_>) -> Result<PeerVerified, Error> {
+        Ok(PeerVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _input: &SignatureVerificationInput<'_>,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _input: &SignatureVerificationInput<'_>,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn request_ocsp_response(&self) -> bool {
+        false
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        vec![SignatureScheme::ECDSA_NISTP256_SHA256]
+    }
+
+    fn hash_config(&self, _: &mut dyn Hasher) {}
+}
+
+#[test]
+fn test_client_requiring_rpk_rejects_server_that_only_offers_x509_id_by_omission() {
+    client_requiring_rpk_receives_server_ee(
+        Err(PeerIncompatible::IncorrectCertificateTypeExtension.into()),
+        ServerExtensions::default(),
+        &TEST_PROVIDER,
+    );
+}
+
+#[test]
+fn test_client_requiring_rpk_rejects_server_that_only_offers_x509_id() {
+    client_requiring_rpk_receives_server_ee(
+        Err(PeerIncompatible::IncorrectCertificateTypeExtension.into()),
+        ServerExtensions {
+            server_certificate_type: Some(CertificateType::X509),
+            ..ServerExtensions::default()
+        },
+        &TEST_PROVIDER,
+    );
+}
+
+#[test]
+fn test_client_requiring_rpk_rejects_server_that_only_demands_x509_by_omission() {
+    client_requiring_rpk_receives_server_ee(
+        Err(PeerIncompatible::IncorrectCertificateTypeExtension.into()),
+        ServerExtensions {
+            server_certificate_type: Some(CertificateType::RawPublicKey),
+            ..ServerExtensions::default()
+        },
+        &TEST_PROVIDER,
+    );
+}
+
+#[test]
+fn test_client_requiring_rpk_rejects_server_that_only_demands_x509() {
+    client_requiring_rpk_receives_server_ee(
+        Err(PeerIncompatible::IncorrectCertificateTypeExtension.into()),
+        ServerExtensions {
+            client_certificate_type: Some(CertificateType::X509),
+            server_certificate_type: Some(CertificateType::RawPublicKey),
+            ..ServerExtensions::default()
+        },
+        &TEST_PROVIDER,
+    );
+}
+
+#[test]
+fn test_client_requiring_rpk_accepts_rpk_server() {
+    client_requiring_rpk_receives_server_ee(
+        Ok(()),
+        ServerExtensions {
+            client_certificate_type: Some(CertificateType::RawPublicKey),
+            server_certificate_type: Some(CertificateType::RawPublicKey),
+            ..ServerExtensions::default()
+        },
+        &TEST_PROVIDER,
+    );
+}
+
+#[track_caller]
+fn client_requiring_rpk_receives_server_ee(
+    expected: Result<(), Error>,
+    encrypted_extensions: ServerExtensions<'_>,
+    provider: &CryptoProvider,
+) {
+    let Some(provider) = x25519_provider(provider.clone()) else {
+        return;
+    };
+
+    let provider = Arc::new(CryptoProvider {
+        tls12_cipher_suites: Cow::default(),
+        ..provider
+    });
+    let (_, result) = client_receives_server_ee(
+        |key_log| client_config_for_rpk(key_log, provider.clone()),
+        provider.clone(),
+        encrypted_extensions,
+    );
+    assert_eq!(result, expected);
+}
+
+/// Drive a TLS1.3 client built by `config` up to and including the server's
+/// EncryptedExtensions, returning the connection and the result of processing it.
+fn client_receives_server_ee(
+    config: impl FnOnce(Arc<dyn KeyLog>) -> ClientConfig,
+    provider: Arc<CryptoProvider>,
+    encrypted_extensions: ServerExtensions<'_>,
+) -> (ClientConnection, Result<(), Error>) {
+    let fake_server_crypto = Arc::new(FakeServerCrypto::new(provider));
+    let mut conn = ClientConnection::new(
+        Arc::new(config(fake_server_crypto.clone())),
+        ServerName::try_from("localhost").unwrap(),
+    )
+    .unwrap();
+    let mut sent = Vec::new();
+    conn.write_tls(&mut sent).unwrap();
+
+    let sh = Message {
+        version: ProtocolVersion::TLSv1_3,
+        payload: MessagePayload::handshake(HandshakeMessagePayload(HandshakePayload::ServerHello(
+            ServerHelloPayload {
+                random: Random([0; 32]),
+                compression_method: Compression::Null,
+                cipher_suite: CipherSuite::TLS13_AES_128_GCM_SHA256,
+                legacy_version: ProtocolVersion::TLSv1_3,
+                session_id: SessionId::empty(),
+                extensions: Box::new(ServerExtensions {
+                    key_share: Some(KeyShareEntry {
+                        group: NamedGroup::X25519,
+                        payload: SizedPayload::from(vec![0xaa; 32]),
+                    }),
+                    ..ServerExtensions::default()
+                }),
+            },
+        ))),
+    };
+    conn.read_tls(&mut sh.into_wire_bytes().as_slice())
+        .unwrap();
+    conn.process_new_packets().unwrap();
+
+    let ee = Message {
+        version: ProtocolVersion::TLSv1_3,
+        payload: MessagePayload::handshake(HandshakeMessagePayload(
+            HandshakePayload::EncryptedExtensions(Box::new(encrypted_extensions)),
+        )),
+    };
+
+    let mut encrypter = fake_server_crypto.server_handshake_encrypter();
+    let enc_ee = encrypter
+        .encrypt(EncodedMessage::<Payload<'_>>::from(ee).borrow_outbound(), 0)
+        .unwrap();
+    conn.read_tls(&mut enc_ee.encode().as_slice())
+        .unwrap();
+
+    let result = conn.process_new_packets().map(|_| ());
+    (conn, result)
+}
+
+#[test]
+fn customizer_http2_profile_reported_after_h2_is_negotiated() {
+    let Some(provider) = x25519_provider(TEST_PROVIDER.clone()) else {
+        return;
+    };
+    let provider = Arc::new(CryptoProvider {
+        tls12_cipher_suites: Cow::default(),
+        ..provider
+    });
+
+    for (selected, expected) in [
+        (Some(ApplicationProtocol::Http2), Some(Http2Profile::firefox())),
+        (Some(ApplicationProtocol::Http11), None),
+        (None, None),
+    ] {
+        let (conn, result) = client_receives_server_ee(
+            |key_log| {
+                let mut config = ClientConfig::builder(provider.clone())
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(AcceptAnythingVerifier))
+                    .with_no_client_auth()
+                    .unwrap();
+                config.key_log = key_log;
+                config.alpn_protocols = vec![ApplicationProtocol::Http2, ApplicationProtocol::Http11];
+                config.enable_custls(Arc::new(Http2ProfileCustomizer));
+                config
+            },
+            provider.clone(),
+            ServerExtensions {
+                selected_protocol: selected.map(SingleProtocolName::new),
+                ..ServerExtensions::default()
+            },
+        );
+        result.unwrap();
+        assert_eq!(conn.http2_profile(), expected.as_ref());
+    }
+}
+
+#[test]
+fn customizer_send_delay_applies_to_first_flight_only() {
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_custls(Arc::new(SendDelayCustomizer(Duration::from_millis(3))));
+
+    let mut conn =
+        ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap()).unwrap();
+    assert_eq!(conn.wants_write_after(), Some(Duration::from_millis(3)));
+
+    let mut sent = Vec::new();
+    conn.write_tls(&mut sent).unwrap();
+    assert_eq!(conn.wants_write_after(), None);
+
+    // A HelloRetryRequest clears the delay; the second ClientHello goes out at once
+    let ch = client_hellos_in(&sent).pop().unwrap();
+    conn.read_tls(&mut hello_retry_request(&ch, None).into_wire_bytes().as_slice())
+        .unwrap();
+    conn.process_new_packets().unwrap();
+    assert!(conn.wants_write());
+    assert_eq!(conn.wants_write_after(), None);
+}
+
+#[test]
+fn customizer_send_delay_reported_by_unbuffered_connection() {
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_custls(Arc::new(SendDelayCustomizer(Duration::from_millis(3))));
+
+    let conn = UnbufferedClientConnection::new(
+        config.into(),
+        ServerName::try_from("localhost").unwrap(),
+    )
+    .unwrap();
+    assert_eq!(conn.wants_write_after(), Some(Duration::from_millis(3)));
+}
+
+#[test]
+fn customizer_http2_profile_withheld_by_unbuffered_connection_until_h2() {
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.alpn_protocols = vec![ApplicationProtocol::Http2];
+    config.enable_custls(Arc::new(Http2ProfileCustomizer));
+
+    // The profile is recorded with the ClientHello, but nothing is negotiated yet
+    let conn = UnbufferedClientConnection::new(
+        config.into(),
+        ServerName::try_from("localhost").unwrap(),
+    )
+    .unwrap();
+    assert!(!conn.sent_client_hellos().is_empty());
+    assert_eq!(conn.http2_profile(), None);
+}
+
+#[test]
+fn customizer_routes_on_the_connection_port() {
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_custls(Arc::new(DefaultCustomizer::new(
+        CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .with_randomization_level(RandomizationLevel::None)
+            .with_routing_rule(
+                RoutingRule::new(
+                    HostPattern::Any,
+                    RouteAction::Template(BrowserTemplate::Firefox135),
+                )
+                .with_port(8443),
+            )
+            .build(),
+    )));
+    let config = Arc::new(config);
+    let name = ServerName::try_from("localhost").unwrap();
+
+    let suites = |hello: &crate::custls::SentClientHello| {
+        hello
+            .view()
+            .unwrap()
+            .cipher_suites()
+            .iter()
+            .copied()
+            .filter(|suite| !is_grease_value(*suite))
+            .collect::<Vec<u16>>()
+    };
+    let expected = |template: crate::custls::templates::TemplateData| {
+        template
+            .cipher_suites
+            .into_iter()
+            .map(u16::from)
+            .filter(|suite| !is_grease_value(*suite))
+            .collect::<Vec<u16>>()
+    };
+
+    // Connections made without a port are on 443, which the rule does not match
+    for conn in [
+        ClientConnection::new(config.clone(), name.clone()).unwrap(),
+        ClientConnection::new_with_port(config.clone(), name.clone(), 443).unwrap(),
+    ] {
+        assert_eq!(suites(&conn.sent_client_hellos()[0]), expected(chrome_130()));
+    }
+
+    let conn = ClientConnection::new_with_port(config.clone(), name.clone(), 8443).unwrap();
+    assert_eq!(suites(&conn.sent_client_hellos()[0]), expected(firefox_135()));
+
+    let conn = UnbufferedClientConnection::new_with_port(config, name, 8443).unwrap();
+    assert_eq!(suites(&conn.sent_client_hellos()[0]), expected(firefox_135()));
+}
+
+/// Asks for the first flight to be delayed, without sleeping in the hook.
+#[derive(Debug)]
+struct SendDelayCustomizer(Duration);
+
+impl ClientHelloCustomizer for SendDelayCustomizer {
+    fn on_config_resolve(&self, config: &mut ConfigParams) -> Result<(), Error> {
+        config.send_delay = Some(self.0);
+        Ok(())
+    }
+}
+
+/// Reports Firefox's HTTP/2 profile without otherwise changing the ClientHello.
+#[derive(Debug)]
+struct Http2ProfileCustomizer;
+
+impl ClientHelloCustomizer for Http2ProfileCustomizer {
+    fn on_config_resolve(&self, config: &mut ConfigParams) -> Result<(), Error> {
+        config.http2_profile = Some(Http2Profile::firefox());
+        Ok(())
+    }
+}
+
+fn client_config_for_rpk(key_log: Arc<dyn KeyLog>, provider: Arc<CryptoProvider>) -> ClientConfig {
+    let credentials = client_credentials(&provider);
+    let mut config = ClientConfig::builder(provider)
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(ServerVerifierRequiringRpk))
+        .with_client_credential_resolver(Arc::new(SingleCredential::from(credentials)))
+        .unwrap();
+    config.key_log = key_log;
+    config
+}
+
+fn client_credentials(provider: &CryptoProvider) -> Credentials {
+    let key = provider
+        .key_provider
+        .load_private_key(client_key())
+        .unwrap();
+    let identity = Arc::from(Identity::RawPublicKey(
+        key.public_key().unwrap().into_owned(),
+    ));
+    Credentials::new_unchecked(identity, key)
+}
+
+fn client_key() -> PrivateKeyDer<'static> {
+    PrivateKeyDer::from_pem_reader(
+        &mut include_bytes!("../../../test-ca/rsa-2048/client.key").as_slice(),
     )
     .unwrap()
 }
@@ -649,6 +1420,422 @@ fn hybrid_kx_component_share_not_offered_unless_supported_separately() {
     assert_eq!(key_shares[0].group, NamedGroup::Unknown(0xfe00));
 }
 
+#[derive(Debug)]
+struct KeyShareCustomizer(Vec<NamedGroup>);
+
+impl ClientHelloCustomizer for KeyShareCustomizer {
+    fn on_config_resolve(&self, config: &mut ConfigParams) -> Result<(), Error> {
+        config.key_share_groups = Some(self.0.clone());
+        Ok(())
+    }
+}
+
+fn key_share_groups_sent(groups: &[NamedGroup]) -> Vec<NamedGroup> {
+    let mut config = ClientConfig::builder(Arc::new(THREE_GROUP_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_custls(Arc::new(KeyShareCustomizer(groups.to_vec())));
+
+    client_hello_sent_for_config(config)
+        .unwrap()
+        .extensions
+        .key_shares
+        .unwrap()
+        .iter()
+        .map(|ks| ks.group)
+        .collect()
+}
+
+#[test]
+fn customizer_key_shares_hybrid_first_reuses_component() {
+    assert_eq!(
+        key_share_groups_sent(&[FAKE_HYBRID.name, FAKE_HYBRID.classical]),
+        vec![FAKE_HYBRID.name, FAKE_HYBRID.classical]
+    );
+}
+
+#[test]
+fn customizer_key_shares_in_customizer_order() {
+    let other = NamedGroup::Unknown(0xfe02);
+    assert_eq!(
+        key_share_groups_sent(&[other, FAKE_HYBRID.classical, FAKE_HYBRID.name]),
+        vec![other, FAKE_HYBRID.classical, FAKE_HYBRID.name]
+    );
+    assert_eq!(
+        key_share_groups_sent(&[FAKE_HYBRID.name, other]),
+        vec![FAKE_HYBRID.name, other]
+    );
+}
+
+#[test]
+fn customizer_key_shares_skip_unsupported_groups() {
+    assert_eq!(
+        key_share_groups_sent(&[NamedGroup::Unknown(0x0a0a), FAKE_HYBRID.classical]),
+        vec![FAKE_HYBRID.classical]
+    );
+
+    // Nothing usable: rustls' own choice is kept
+    assert_eq!(
+        key_share_groups_sent(&[NamedGroup::Unknown(0x0a0a)]),
+        vec![FAKE_HYBRID.name, FAKE_HYBRID.classical]
+    );
+}
+
+#[test]
+fn customizer_key_shares_hrr_for_offered_group_is_rejected() {
+    let (mut conn, ch) = customized_client(&[FAKE_HYBRID.name, NamedGroup::Unknown(0xfe02)]);
+    let hrr = hello_retry_request(&ch, Some(NamedGroup::Unknown(0xfe02)));
+
+    conn.read_tls(&mut hrr.into_wire_bytes().as_slice())
+        .unwrap();
+    assert_eq!(
+        conn.process_new_packets().unwrap_err(),
+        PeerMisbehaved::IllegalHelloRetryRequestWithOfferedGroup.into()
+    );
+}
+
+#[test]
+fn customizer_key_shares_hrr_sends_only_requested_group() {
+    let (mut conn, ch) = customized_client(&[FAKE_HYBRID.name, FAKE_HYBRID.classical]);
+    let hrr = hello_retry_request(&ch, Some(NamedGroup::Unknown(0xfe02)));
+
+    conn.read_tls(&mut hrr.into_wire_bytes().as_slice())
+        .unwrap();
+    conn.process_new_packets().unwrap();
+
+    let mut bytes = Vec::new();
+    conn.write_tls(&mut bytes).unwrap();
+    let retried = client_hellos_in(&bytes).pop().unwrap();
+    let groups = retried
+        .extensions
+        .key_shares
+        .unwrap()
+        .iter()
+        .map(|ks| ks.group)
+        .collect::<Vec<_>>();
+    assert_eq!(groups, vec![NamedGroup::Unknown(0xfe02)]);
+}
+
+#[test]
+fn customizer_key_shares_repeated_after_cookie_only_hrr() {
+    let groups = [NamedGroup::Unknown(0xfe02), FAKE_HYBRID.name, FAKE_HYBRID.classical];
+    let (mut conn, ch) = customized_client(&groups);
+    let first = key_share_entries(&ch);
+    let hrr = hello_retry_request(&ch, None);
+
+    conn.read_tls(&mut hrr.into_wire_bytes().as_slice())
+        .unwrap();
+    conn.process_new_packets().unwrap();
+
+    let mut bytes = Vec::new();
+    conn.write_tls(&mut bytes).unwrap();
+    let retried = client_hellos_in(&bytes).pop().unwrap();
+    assert_eq!(key_share_entries(&retried), first);
+}
+
+struct BehaviorCustomizer(HandshakeBehavior);
+
+impl ClientHelloCustomizer for BehaviorCustomizer {
+    fn on_config_resolve(&self, config: &mut ConfigParams) -> Result<(), Error> {
+        config.handshake_behavior = Some(self.0);
+        Ok(())
+    }
+}
+
+fn behavior_config(behavior: HandshakeBehavior) -> ClientConfig {
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.resumption =
+        Resumption::in_memory_sessions(128).tls12_resumption(Tls12Resumption::SessionIdOrTickets);
+    config.enable_custls(Arc::new(BehaviorCustomizer(behavior)));
+    config
+}
+
+#[test]
+fn customizer_default_handshake_behavior_matches_rustls() {
+    let ch = client_hello_sent_for_config(behavior_config(HandshakeBehavior::default())).unwrap();
+    assert_eq!(ch.session_id.as_ref().len(), 32);
+    assert!(matches!(
+        ch.extensions.session_ticket,
+        Some(ClientSessionTicket::Request)
+    ));
+    assert!(ch.extensions.extended_master_secret_request.is_some());
+    let modes = ch.extensions.preshared_key_modes.unwrap();
+    assert!(modes.psk_dhe);
+    assert!(!modes.psk);
+}
+
+#[test]
+fn customizer_handshake_behavior_shapes_session_parts_of_hello() {
+    let ch = client_hello_sent_for_config(behavior_config(HandshakeBehavior {
+        legacy_session_id_len: 16,
+        psk_ke: true,
+        session_ticket_request: false,
+        extended_master_secret: false,
+        ..HandshakeBehavior::default()
+    }))
+    .unwrap();
+    assert_eq!(ch.session_id.as_ref().len(), 16);
+    assert!(ch.extensions.session_ticket.is_none());
+    assert!(ch.extensions.extended_master_secret_request.is_none());
+    let modes = ch.extensions.preshared_key_modes.unwrap();
+    assert!(modes.psk_dhe);
+    assert!(modes.psk);
+}
+
+#[test]
+fn customizer_handshake_behavior_can_omit_session_id_and_psk_modes() {
+    let ch = client_hello_sent_for_config(behavior_config(HandshakeBehavior {
+        legacy_session_id_len: 0,
+        psk_dhe_ke: false,
+        psk_ke: false,
+        ..HandshakeBehavior::default()
+    }))
+    .unwrap();
+    assert!(ch.session_id.is_empty());
+    assert!(ch.extensions.preshared_key_modes.is_none());
+}
+
+#[test]
+fn customizer_handshake_behavior_cannot_drop_required_ems() {
+    let mut config = behavior_config(HandshakeBehavior {
+        extended_master_secret: false,
+        ..HandshakeBehavior::default()
+    });
+    config.require_ems = true;
+    let ch = client_hello_sent_for_config(config).unwrap();
+    assert!(ch.extensions.extended_master_secret_request.is_some());
+}
+
+#[test]
+fn customizer_handshake_behavior_controls_compat_ccs_after_hrr() {
+    for middlebox_compat_ccs in [true, false] {
+        let config = behavior_config(HandshakeBehavior {
+            middlebox_compat_ccs,
+            ..HandshakeBehavior::default()
+        });
+        let mut conn =
+            ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap())
+                .unwrap();
+        let mut sent = Vec::new();
+        conn.write_tls(&mut sent).unwrap();
+        let ch = client_hellos_in(&sent).pop().unwrap();
+
+        conn.read_tls(&mut hello_retry_request(&ch, None).into_wire_bytes().as_slice())
+            .unwrap();
+        conn.process_new_packets().unwrap();
+
+        let mut bytes = Vec::new();
+        conn.write_tls(&mut bytes).unwrap();
+        let first_record = ContentType::from(bytes[0]);
+        match middlebox_compat_ccs {
+            true => assert_eq!(first_record, ContentType::ChangeCipherSpec),
+            false => assert_eq!(first_record, ContentType::Handshake),
+        }
+        assert_eq!(client_hellos_in(&bytes).len(), 1);
+    }
+}
+
+#[test]
+fn sent_client_hellos_record_initial_and_retried_hello() {
+    let config = behavior_config(HandshakeBehavior {
+        middlebox_compat_ccs: false,
+        ..HandshakeBehavior::default()
+    });
+    let mut conn =
+        ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap()).unwrap();
+    let mut sent = Vec::new();
+    conn.write_tls(&mut sent).unwrap();
+    let ch = client_hellos_in(&sent).pop().unwrap();
+
+    let hellos = conn.sent_client_hellos();
+    assert_eq!(hellos.len(), 1);
+    assert_eq!(hellos[0].bytes(), &sent[5..]);
+    let view = hellos[0].view().unwrap();
+    let suites = ch
+        .cipher_suites
+        .iter()
+        .map(|suite| u16::from(*suite))
+        .collect::<Vec<_>>();
+    assert_eq!(view.cipher_suites(), &suites[..]);
+    assert_eq!(view.session_id_len(), 32);
+    assert!(view.extension_types().contains(&0x0033));
+    assert_eq!(view.supported_versions().first(), Some(&0x0304));
+
+    conn.read_tls(&mut hello_retry_request(&ch, None).into_wire_bytes().as_slice())
+        .unwrap();
+    conn.process_new_packets().unwrap();
+    let mut retried = Vec::new();
+    conn.write_tls(&mut retried).unwrap();
+
+    let hellos = conn.sent_client_hellos();
+    assert_eq!(hellos.len(), 2);
+    assert_eq!(hellos[1].bytes(), &retried[5..]);
+    assert_ne!(hellos[0].bytes(), hellos[1].bytes());
+    let (first, retried) = (hellos[0].view().unwrap(), hellos[1].view().unwrap());
+    assert_eq!(retried.key_share_groups(), first.key_share_groups());
+    assert!(
+        retried
+            .extension_types()
+            .contains(&0x002c)
+    );
+}
+
+#[test]
+fn sent_client_hellos_reported_by_unbuffered_connection() {
+    let conn = UnbufferedClientConnection::new(
+        behavior_config(HandshakeBehavior::default()).into(),
+        ServerName::try_from("localhost").unwrap(),
+    )
+    .unwrap();
+    let hellos = conn.sent_client_hellos();
+    assert_eq!(hellos.len(), 1);
+    assert_eq!(hellos[0].bytes()[0], 0x01);
+    let view = hellos[0].view().unwrap();
+    assert!(!view.cipher_suites().is_empty());
+}
+
+#[test]
+fn sent_client_hello_validates_against_browser_capture() {
+    let customizer = Arc::new(DefaultCustomizer::new(
+        CustlsConfig::builder()
+            .with_template(BrowserTemplate::Firefox135)
+            .with_randomization_level(RandomizationLevel::None)
+            .build(),
+    ));
+    let mut config = ClientConfig::builder(Arc::new(TEST_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_custls(customizer);
+
+    let mut conn =
+        ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap()).unwrap();
+    let mut sent = Vec::new();
+    conn.write_tls(&mut sent).unwrap();
+
+    let capture = conn.sent_client_hellos()[0]
+        .view()
+        .unwrap()
+        .to_browser_capture("Firefox");
+    let report = validate_template(&firefox_135(), &capture, RandomizationLevel::None);
+    assert_eq!(report.browser, "Firefox");
+    assert!(!matches!(report.cipher_suites, ValidationResult::Mismatch { .. }));
+    assert!(!matches!(report.extension_order, ValidationResult::Mismatch { .. }));
+}
+
+#[test]
+fn acceptor_sees_the_client_hello_the_client_sent() {
+    let mut conn = ClientConnection::new(
+        behavior_config(HandshakeBehavior::default()).into(),
+        ServerName::try_from("localhost").unwrap(),
+    )
+    .unwrap();
+    let mut sent = Vec::new();
+    conn.write_tls(&mut sent).unwrap();
+
+    let mut acceptor = Acceptor::default();
+    acceptor
+        .read_tls(&mut sent.as_slice())
+        .unwrap();
+    let Ok(Some(accepted)) = acceptor.accept() else {
+        panic!("ClientHello not accepted");
+    };
+    let received = accepted.client_hello();
+    let view = received.view().unwrap();
+
+    let hello = &conn.sent_client_hellos()[0];
+    assert_eq!(received.bytes(), hello.bytes());
+    let sent = hello.view().unwrap();
+    assert_eq!(view, sent);
+    assert_eq!(view.ja3_string(), sent.ja3_string());
+    assert!(view.ja3_string().starts_with("771,"));
+    assert!(view.ja4_r().starts_with("t13d"));
+}
+
+fn key_share_entries(ch: &ClientHelloPayload) -> Vec<(NamedGroup, Vec<u8>)> {
+    ch.extensions
+        .key_shares
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|ks| (ks.group, ks.payload.bytes().to_vec()))
+        .collect()
+}
+
+fn customized_client(groups: &[NamedGroup]) -> (ClientConnection, ClientHelloPayload) {
+    let mut config = ClientConfig::builder(Arc::new(THREE_GROUP_PROVIDER.clone()))
+        .with_root_certificates(roots())
+        .with_no_client_auth()
+        .unwrap();
+    config.enable_custls(Arc::new(KeyShareCustomizer(groups.to_vec())));
+
+    let mut conn =
+        ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap()).unwrap();
+    let mut bytes = Vec::new();
+    conn.write_tls(&mut bytes).unwrap();
+    let ch = client_hellos_in(&bytes).pop().unwrap();
+    (conn, ch)
+}
+
+fn hello_retry_request(ch: &ClientHelloPayload, key_share: Option<NamedGroup>) -> Message<'static> {
+    Message {
+        version: ProtocolVersion::TLSv1_3,
+        payload: MessagePayload::handshake(HandshakeMessagePayload(
+            HandshakePayload::HelloRetryRequest(HelloRetryRequest {
+                cipher_suite: CipherSuite::TLS13_AES_128_GCM_SHA256,
+                legacy_version: ProtocolVersion::TLSv1_2,
+                session_id: ch.session_id,
+                extensions: HelloRetryRequestExtensions {
+                    key_share,
+                    cookie: match key_share {
+                        Some(_) => None,
+                        None => Some(SizedPayload::from(vec![1, 2, 3, 4])),
+                    },
+                    supported_versions: Some(ProtocolVersion::TLSv1_3),
+                    ..HelloRetryRequestExtensions::default()
+                },
+            }),
+        )),
+    }
+}
+
+fn client_hellos_in(mut bytes: &[u8]) -> Vec<ClientHelloPayload> {
+    let mut hellos = Vec::new();
+    while !bytes.is_empty() {
+        let mut reader = Reader::init(bytes);
+        let message = EncodedMessage::<Payload<'_>>::read(&mut reader)
+            .unwrap()
+            .into_owned();
+        bytes = &bytes[bytes.len() - reader.left()..];
+
+        if let Message {
+            payload:
+                MessagePayload::Handshake {
+                    parsed: HandshakeMessagePayload(HandshakePayload::ClientHello(ch)),
+                    ..
+                },
+            ..
+        } = Message::try_from(&message).unwrap()
+        {
+            hellos.push(ch);
+        }
+    }
+    hellos
+}
+
+const THREE_GROUP_PROVIDER: CryptoProvider = CryptoProvider {
+    kx_groups: Cow::Borrowed(&[
+        FAKE_HYBRID,
+        FAKE_KX_GROUP,
+        &FakeKeyExchangeGroup(NamedGroup::Unknown(0xfe02)),
+    ]),
+    ..TEST_PROVIDER
+};
+
 fn client_hello_sent_for_config(config: ClientConfig) -> Result<ClientHelloPayload, Error> {
     let mut conn =
         ClientConnection::new(config.into(), ServerName::try_from("localhost").unwrap())?;
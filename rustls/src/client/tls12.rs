@@ -775,6 +775,8 @@ impl ExpectServerDone {
                 return Err(PeerMisbehaved::SignedKxWithWrongAlgorithm.into());
             }
 
+            hs::check_server_signature_scheme(&self.config, signature.scheme)?;
+
             self.config
                 .verifier()
                 .verify_tls12_signature(&SignatureVerificationInput {
@@ -1056,6 +1058,14 @@ impl ExpectFinished {
             return;
         };
 
+        if let Some(customizer) = &self.config.custls_customizer {
+            let identity = match ticket.bytes() {
+                [] => self.session_id.as_ref(),
+                ticket => ticket,
+            };
+            customizer.on_session_stored(identity, self.session_key.partition.as_deref());
+        }
+
         let session_value = Tls12ClientSessionValue::new(
             self.secrets.suite(),
             self.session_id,
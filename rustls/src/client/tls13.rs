@@ -100,8 +100,16 @@ impl ClientHandler<Tls13CipherSuite> for Handler {
             _ => None,
         };
 
-        // We always send a key share when TLS 1.3 is enabled.
-        let our_key_share = st.offered_key_share.unwrap();
+        // We always send a key share when TLS 1.3 is enabled. The server may instead have
+        // picked one of the extra shares a template asked for.
+        let mut extra_key_shares = st.extra_key_shares;
+        let our_key_share = match extra_key_shares
+            .iter()
+            .position(|ks| ks.share.group() == their_key_share.group)
+        {
+            Some(index) => extra_key_shares.swap_remove(index),
+            None => st.offered_key_share.unwrap(),
+        };
         let our_key_share = KeyExchangeChoice::new(&config, cx, our_key_share, their_key_share)
             .map_err(|_| PeerMisbehaved::WrongGroupForKeyShare)?;
 
@@ -348,13 +356,14 @@ pub(super) fn prepare_resumption(
     resuming_session: &Retrieved<&Tls13ClientSessionValue>,
     exts: &mut ClientExtensions<'_>,
     doing_retry: bool,
+    offer_early_data: bool,
 ) {
     let resuming_suite = resuming_session.suite();
     cx.emit(Event::CipherSuite(resuming_suite.into()));
     // The EarlyData extension MUST be supplied together with the
     // PreSharedKey extension.
     let max_early_data_size = resuming_session.max_early_data_size();
-    if config.enable_early_data && max_early_data_size > 0 && !doing_retry {
+    if config.enable_early_data && offer_early_data && max_early_data_size > 0 && !doing_retry {
         cx.data
             .early_data
             .enable(max_early_data_size as usize);
@@ -1122,6 +1131,7 @@ impl State<ClientConnectionData> for ExpectCertificateVerify {
             })?;
 
         // 2. Verify their signature on the handshake.
+        hs::check_server_signature_scheme(&self.config, cert_verify.scheme)?;
         let handshake_hash = self.transcript.current_hash();
         let sig_verified = self
             .config
@@ -1432,6 +1442,10 @@ impl ExpectTraffic {
             }
         }
 
+        if let Some(customizer) = &self.config.custls_customizer {
+            customizer.on_session_stored(nst.ticket.bytes(), self.session_key.partition.as_deref());
+        }
+
         self.session_storage
             .insert_tls13_ticket(self.session_key.clone(), value);
         Ok(())
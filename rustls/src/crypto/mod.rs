@@ -386,6 +386,11 @@ pub(crate) mod rand {
         Ok(u32::from_be_bytes(random_array(secure_random)?))
     }
 
+    /// Return a uniformly random [`u64`].
+    pub(crate) fn random_u64(secure_random: &dyn SecureRandom) -> Result<u64, GetRandomFailed> {
+        Ok(u64::from_be_bytes(random_array(secure_random)?))
+    }
+
     /// Return a uniformly random [`u16`].
     pub(crate) fn random_u16(secure_random: &dyn SecureRandom) -> Result<u16, GetRandomFailed> {
         Ok(u16::from_be_bytes(random_array(secure_random)?))
@@ -7,6 +7,7 @@
 
 use crate::custls::templates::TemplateData;
 use crate::custls::{RandomizationLevel, BrowserTemplate};
+pub use crate::custls::capture::{BrowserCapture, ClientHelloView, ExtensionInfo, is_grease_value};
 use alloc::vec::Vec;
 use alloc::vec;
 use alloc::string::{String, ToString};
@@ -16,29 +17,6 @@ use alloc::collections::BTreeSet as HashSet;
 #[cfg(test)]
 use std::println;
 
-/// Browser capture analysis data
-#[derive(Debug, Clone)]
-pub struct BrowserCapture {
-    pub browser: String,
-    pub version: String,
-    pub platform: String,
-    pub cipher_suites: Vec<u16>,
-    pub grease_cipher_positions: Vec<usize>,
-    pub extensions: Vec<ExtensionInfo>,
-    pub grease_extension_positions: Vec<usize>,
-    pub padding_length: u16,
-    pub padding_samples: Vec<u16>,
-    pub key_share_groups: Vec<String>,
-    pub signature_algorithms: Vec<String>,
-    pub alpn_protocols: Vec<String>,
-}
-
-#[derive(Debug, Clone)]
-pub struct ExtensionInfo {
-    pub extension_type: u16,
-    pub name: String,
-}
-
 /// Validation result for a single field
 #[derive(Debug, Clone, PartialEq)]
 pub enum ValidationResult {
@@ -222,10 +200,42 @@ pub fn load_browser_capture(browser: &str) -> Result<BrowserCapture, String> {
             ],
             alpn_protocols: vec!["h2".to_string(), "http/1.1".to_string()],
         }),
+        "curl_8" => {
+            let hello = decode_capture_hex(include_str!(
+                "test_data/browser_captures/curl_8/linux_clienthello.hex"
+            ))?;
+            let view = ClientHelloView::parse(&hello).map_err(|e| format!("{e:?}"))?;
+            Ok(BrowserCapture {
+                version: "7.88.1 (OpenSSL 3.0.19)".to_string(),
+                platform: "Debian 12".to_string(),
+                ..view.to_browser_capture("curl")
+            })
+        }
         _ => Err(format!("Unknown browser: {}", browser)),
     }
 }
 
+/// Decode a recorded `*_clienthello.hex` file, skipping `#` comment lines
+fn decode_capture_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<u8> = text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(|line| line.trim().bytes())
+        .collect();
+    if digits.len() % 2 != 0 {
+        return Err("odd number of hex digits".to_string());
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            core::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| format!("invalid hex digits {pair:?}"))
+        })
+        .collect()
+}
+
 /// Validate custls template against browser capture
 pub fn validate_template(
     template: &TemplateData,
@@ -415,25 +425,6 @@ fn validate_alpn(template_alpn: &[Vec<u8>], capture_alpn: &[String]) -> Validati
     }
 }
 
-fn is_grease_value(value: u16) -> bool {
-    // GREASE values follow pattern 0x?A?A where both bytes have the same high nibble
-    // Valid GREASE values: 0x0a0a, 0x1a1a, 0x2a2a, 0x3a3a, 0x4a4a, 0x5a5a, 0x6a6a, 0x7a7a, 0x8a8a, 0x9a9a, 0xaaaa, 0xbaba, 0xcaca, 0xdada, 0xeaea, 0xfafa
-    let high_byte = (value >> 8) as u8;
-    let low_byte = (value & 0xFF) as u8;
-    
-    // Both bytes must end in 0xA (low nibble = 0xA)
-    // Both bytes must have the same high nibble
-    let high_nibble_high = (high_byte >> 4) & 0x0F;
-    let low_nibble_high = high_byte & 0x0F;
-    let high_nibble_low = (low_byte >> 4) & 0x0F;
-    let low_nibble_low = low_byte & 0x0F;
-    
-    // Check: both low nibbles are 0xA and both high nibbles match
-    low_nibble_high == 0x0A && 
-    low_nibble_low == 0x0A &&
-    high_nibble_high == high_nibble_low
-}
-
 fn result_to_score(result: &ValidationResult) -> f64 {
     match result {
         ValidationResult::Match => 1.0,
@@ -534,6 +525,47 @@ mod tests {
         assert!(report.overall_fidelity > 0.5, "Edge template fidelity too low");
     }
 
+    #[test]
+    fn test_load_curl_capture() {
+        let capture = load_browser_capture("curl_8").unwrap();
+        assert_eq!(capture.browser, "curl");
+        assert_eq!(capture.cipher_suites.len(), 31);
+        assert_eq!(capture.key_share_groups, ["x25519"]);
+        assert_eq!(capture.alpn_protocols, ["h2", "http/1.1"]);
+        
+        // OpenSSL pads the hello to 512 bytes
+        assert_eq!(capture.padding_length, 180);
+    }
+    
+    #[test]
+    fn test_curl_template_matches_recorded_codepoints() {
+        let template = templates::curl_8();
+        let capture = load_browser_capture("curl_8").unwrap();
+        let hello = decode_capture_hex(include_str!(
+            "test_data/browser_captures/curl_8/linux_clienthello.hex"
+        ))
+        .unwrap();
+        let view = ClientHelloView::parse(&hello).unwrap();
+        
+        let suites: Vec<u16> = template.cipher_suites.iter().map(|cs| u16::from(*cs)).collect();
+        assert_eq!(suites, capture.cipher_suites);
+        
+        // Padding is OpenSSL's, not part of the template's order
+        let extensions: Vec<u16> = template.extension_order.iter().map(|ext| u16::from(*ext)).collect();
+        let mut recorded = view.extension_types();
+        assert_eq!(recorded.pop(), Some(0x0015));
+        assert_eq!(extensions, recorded);
+        
+        let groups: Vec<u16> = template.supported_groups.iter().map(|g| u16::from(*g)).collect();
+        assert_eq!(groups, view.supported_groups());
+        let schemes: Vec<u16> = template.signature_algorithms.iter().map(|s| u16::from(*s)).collect();
+        assert_eq!(schemes, view.signature_algorithms());
+        
+        let report = validate_template(&template, &capture, RandomizationLevel::None);
+        println!("{}", report.summary());
+        assert!(report.overall_fidelity > 0.5, "curl template fidelity too low");
+    }
+    
     #[test]
     fn test_is_grease_value() {
         assert!(is_grease_value(0x0a0a));
@@ -0,0 +1,562 @@
+//! ClientHello capture and inspection
+//!
+//! A client connection records the handshake-message bytes of every ClientHello
+//! it sends: the initial one and, after a HelloRetryRequest, the second one.
+//! [`SentClientHello`] holds those bytes and parses them on request into a
+//! [`ClientHelloView`], the fields that make up a TLS fingerprint.
+//!
+//! The view uses the same vocabulary as a browser capture, so a live connection
+//! can be compared against a recorded browser directly:
+//!
+//! ```rust,ignore
+//! for hello in conn.sent_client_hellos() {
+//!     let view = hello.view()?;
+//!     println!("{:04x?}", view.cipher_suites());
+//!     let capture = view.to_browser_capture("Chrome");
+//! }
+//! ```
+//!
+//! [`ClientHelloView::parse`] accepts any ClientHello handshake message, so the
+//! same view can be built from bytes captured elsewhere, and a server sees the
+//! same view through `server::ClientHello::view()`. The JA3 and JA4 fingerprints
+//! of a view are computed in [`super::fingerprint`].
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::CustlsError;
+use crate::error::{Error, InvalidMessage};
+use crate::msgs::{Codec, Reader};
+
+const CLIENT_HELLO: u8 = 0x01;
+
+const EXT_SIGNATURE_ALGORITHMS: u16 = 0x000d;
+const EXT_SUPPORTED_GROUPS: u16 = 0x000a;
+const EXT_EC_POINT_FORMATS: u16 = 0x000b;
+const EXT_ALPN: u16 = 0x0010;
+const EXT_PADDING: u16 = 0x0015;
+const EXT_SUPPORTED_VERSIONS: u16 = 0x002b;
+const EXT_KEY_SHARE: u16 = 0x0033;
+
+/// Browser capture analysis data
+///
+/// Describes one ClientHello in terms of its fingerprint-relevant fields. The
+/// browser validation suite holds recorded browser captures in this form, and
+/// [`ClientHelloView::to_browser_capture`] produces one from a live hello.
+#[derive(Debug, Clone)]
+pub struct BrowserCapture {
+    /// Browser family, e.g. `"Chrome"`
+    pub browser: String,
+    /// Browser version the capture was taken from
+    pub version: String,
+    /// Platform the capture was taken on
+    pub platform: String,
+    /// Cipher suites in wire order, GREASE included
+    pub cipher_suites: Vec<u16>,
+    /// Indexes of GREASE values in `cipher_suites`
+    pub grease_cipher_positions: Vec<usize>,
+    /// Extensions in wire order, GREASE included
+    pub extensions: Vec<ExtensionInfo>,
+    /// Indexes of GREASE extensions in `extensions`
+    pub grease_extension_positions: Vec<usize>,
+    /// Length of the padding extension body, 0 when none was sent
+    pub padding_length: u16,
+    /// Padding lengths seen across several captures
+    pub padding_samples: Vec<u16>,
+    /// Groups offered in the key_share extension, by IANA name
+    pub key_share_groups: Vec<String>,
+    /// Signature schemes offered, by IANA name
+    pub signature_algorithms: Vec<String>,
+    /// ALPN protocols offered
+    pub alpn_protocols: Vec<String>,
+}
+
+/// One extension of a ClientHello
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionInfo {
+    /// Extension type codepoint
+    pub extension_type: u16,
+    /// IANA name of the extension, `"grease"` for GREASE values
+    pub name: String,
+}
+
+impl ExtensionInfo {
+    /// Describe the extension with codepoint `extension_type`
+    pub fn new(extension_type: u16) -> Self {
+        Self {
+            extension_type,
+            name: extension_name(extension_type),
+        }
+    }
+}
+
+/// A ClientHello sent by a client connection
+#[derive(Debug, Clone)]
+pub struct SentClientHello {
+    bytes: Vec<u8>,
+}
+
+impl SentClientHello {
+    pub(crate) fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    /// The handshake message as sent, including the 4-byte handshake header
+    ///
+    /// This is the message as it enters the transcript hash. It is carried in
+    /// TLS records over TCP, or in CRYPTO frames for QUIC.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Parse this hello into a [`ClientHelloView`]
+    ///
+    /// The hello is parsed on each call, so a connection that never looks at its
+    /// hellos does not pay for parsing them.
+    pub fn view(&self) -> Result<ClientHelloView, Error> {
+        Ok(ClientHelloView::parse(&self.bytes)?)
+    }
+}
+
+/// Read-only parsed view of a ClientHello
+///
+/// All values are raw codepoints in wire order, GREASE included, so the view
+/// shows exactly what a server or a passive observer sees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientHelloView {
+    legacy_version: u16,
+    session_id_len: usize,
+    cipher_suites: Vec<u16>,
+    extensions: Vec<ExtensionInfo>,
+    supported_versions: Vec<u16>,
+    supported_groups: Vec<u16>,
+    ec_point_formats: Vec<u8>,
+    key_share_groups: Vec<u16>,
+    signature_algorithms: Vec<u16>,
+    alpn_protocols: Vec<Vec<u8>>,
+    padding_length: Option<u16>,
+}
+
+impl ClientHelloView {
+    /// Parse a ClientHello handshake message, including its 4-byte header
+    pub fn parse(bytes: &[u8]) -> Result<Self, CustlsError> {
+        Self::read(&mut Reader::init(bytes)).map_err(|err| {
+            CustlsError::ValidationError(format!("malformed ClientHello: {err:?}"))
+        })
+    }
+
+    fn read(r: &mut Reader<'_>) -> Result<Self, InvalidMessage> {
+        if u8::read(r)? != CLIENT_HELLO {
+            return Err(InvalidMessage::UnexpectedMessage("expected ClientHello"));
+        }
+        let len = r
+            .take(3)
+            .ok_or(InvalidMessage::MissingData("handshake length"))?;
+        let len = (usize::from(len[0]) << 16) | (usize::from(len[1]) << 8) | usize::from(len[2]);
+        let mut body = r.sub(len)?;
+        r.expect_empty("ClientHello")?;
+
+        let legacy_version = u16::read(&mut body)?;
+        body.take(32)
+            .ok_or(InvalidMessage::MissingData("random"))?;
+        let session_id_len = usize::from(u8::read(&mut body)?);
+        body.take(session_id_len)
+            .ok_or(InvalidMessage::MissingData("session_id"))?;
+
+        let mut view = Self {
+            legacy_version,
+            session_id_len,
+            cipher_suites: read_u16_list(&mut body)?,
+            extensions: Vec::new(),
+            supported_versions: Vec::new(),
+            supported_groups: Vec::new(),
+            ec_point_formats: Vec::new(),
+            key_share_groups: Vec::new(),
+            signature_algorithms: Vec::new(),
+            alpn_protocols: Vec::new(),
+            padding_length: None,
+        };
+
+        let compression_len = usize::from(u8::read(&mut body)?);
+        body.take(compression_len)
+            .ok_or(InvalidMessage::MissingData("compression_methods"))?;
+
+        // A hello without extensions is valid TLS 1.2.
+        if !body.any_left() {
+            return Ok(view);
+        }
+
+        let extensions_len = usize::from(u16::read(&mut body)?);
+        let mut extensions = body.sub(extensions_len)?;
+        body.expect_empty("ClientHello")?;
+
+        while extensions.any_left() {
+            let extension_type = u16::read(&mut extensions)?;
+            let len = usize::from(u16::read(&mut extensions)?);
+            let mut data = extensions.sub(len)?;
+            view.extensions
+                .push(ExtensionInfo::new(extension_type));
+
+            match extension_type {
+                EXT_SUPPORTED_GROUPS => view.supported_groups = read_u16_list(&mut data)?,
+                EXT_EC_POINT_FORMATS => {
+                    let len = usize::from(u8::read(&mut data)?);
+                    view.ec_point_formats = data
+                        .take(len)
+                        .ok_or(InvalidMessage::MissingData("ec_point_formats"))?
+                        .to_vec();
+                }
+                EXT_SIGNATURE_ALGORITHMS => {
+                    view.signature_algorithms = read_u16_list(&mut data)?
+                }
+                EXT_SUPPORTED_VERSIONS => {
+                    let len = usize::from(u8::read(&mut data)?);
+                    view.supported_versions = read_u16s(&mut data.sub(len)?)?;
+                }
+                EXT_KEY_SHARE => {
+                    let len = usize::from(u16::read(&mut data)?);
+                    let mut entries = data.sub(len)?;
+                    while entries.any_left() {
+                        view.key_share_groups
+                            .push(u16::read(&mut entries)?);
+                        let key_len = usize::from(u16::read(&mut entries)?);
+                        entries
+                            .take(key_len)
+                            .ok_or(InvalidMessage::MissingData("key_exchange"))?;
+                    }
+                }
+                EXT_ALPN => {
+                    let len = usize::from(u16::read(&mut data)?);
+                    let mut protocols = data.sub(len)?;
+                    while protocols.any_left() {
+                        let len = usize::from(u8::read(&mut protocols)?);
+                        let protocol = protocols
+                            .take(len)
+                            .ok_or(InvalidMessage::MissingData("alpn protocol"))?;
+                        view.alpn_protocols
+                            .push(protocol.to_vec());
+                    }
+                }
+                EXT_PADDING => view.padding_length = Some(len as u16),
+                _ => {}
+            }
+        }
+
+        Ok(view)
+    }
+
+    /// The `legacy_version` field
+    pub fn legacy_version(&self) -> u16 {
+        self.legacy_version
+    }
+
+    /// Length of the legacy session ID, 0 when it is empty
+    pub fn session_id_len(&self) -> usize {
+        self.session_id_len
+    }
+
+    /// Cipher suites in wire order
+    pub fn cipher_suites(&self) -> &[u16] {
+        &self.cipher_suites
+    }
+
+    /// Extensions in wire order
+    pub fn extensions(&self) -> &[ExtensionInfo] {
+        &self.extensions
+    }
+
+    /// Extension type codepoints in wire order
+    pub fn extension_types(&self) -> Vec<u16> {
+        self.extensions
+            .iter()
+            .map(|ext| ext.extension_type)
+            .collect()
+    }
+
+    /// Versions from the supported_versions extension
+    pub fn supported_versions(&self) -> &[u16] {
+        &self.supported_versions
+    }
+
+    /// Groups from the supported_groups extension
+    pub fn supported_groups(&self) -> &[u16] {
+        &self.supported_groups
+    }
+
+    /// Formats from the ec_point_formats extension
+    pub fn ec_point_formats(&self) -> &[u8] {
+        &self.ec_point_formats
+    }
+
+    /// Groups that have an entry in the key_share extension
+    pub fn key_share_groups(&self) -> &[u16] {
+        &self.key_share_groups
+    }
+
+    /// Schemes from the signature_algorithms extension
+    pub fn signature_algorithms(&self) -> &[u16] {
+        &self.signature_algorithms
+    }
+
+    /// Protocols from the ALPN extension
+    pub fn alpn_protocols(&self) -> &[Vec<u8>] {
+        &self.alpn_protocols
+    }
+
+    /// Length of the padding extension body, `None` when no padding was sent
+    pub fn padding_length(&self) -> Option<u16> {
+        self.padding_length
+    }
+
+    /// Describe this hello as a [`BrowserCapture`] of `browser`
+    ///
+    /// Version and platform are left empty; the single padding length seen is
+    /// the only padding sample.
+    pub fn to_browser_capture(&self, browser: &str) -> BrowserCapture {
+        let padding_length = self.padding_length.unwrap_or(0);
+        BrowserCapture {
+            browser: browser.to_string(),
+            version: String::new(),
+            platform: String::new(),
+            cipher_suites: self.cipher_suites.clone(),
+            grease_cipher_positions: grease_positions(self.cipher_suites.iter().copied()),
+            extensions: self.extensions.clone(),
+            grease_extension_positions: grease_positions(
+                self.extensions
+                    .iter()
+                    .map(|ext| ext.extension_type),
+            ),
+            padding_length,
+            padding_samples: vec![padding_length],
+            key_share_groups: self
+                .key_share_groups
+                .iter()
+                .map(|group| group_name(*group))
+                .collect(),
+            signature_algorithms: self
+                .signature_algorithms
+                .iter()
+                .map(|scheme| signature_scheme_name(*scheme))
+                .collect(),
+            alpn_protocols: self
+                .alpn_protocols
+                .iter()
+                .map(|protocol| String::from_utf8_lossy(protocol).to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Whether `value` is a GREASE codepoint (RFC 8701): `0x?a?a` with equal bytes
+pub fn is_grease_value(value: u16) -> bool {
+    (value & 0x0f0f) == 0x0a0a && (value >> 8) == (value & 0xff)
+}
+
+/// IANA name of a TLS extension type
+pub fn extension_name(extension_type: u16) -> String {
+    let name = match extension_type {
+        _ if is_grease_value(extension_type) => "grease",
+        0x0000 => "server_name",
+        0x0005 => "status_request",
+        0x000a => "supported_groups",
+        0x000b => "ec_point_formats",
+        0x000d => "signature_algorithms",
+        0x0010 => "alpn",
+        0x0012 => "sct",
+        0x0015 => "padding",
+        0x0016 => "encrypt_then_mac",
+        0x0017 => "extended_master_secret",
+        0x001b => "compress_certificate",
+        0x001c => "record_size_limit",
+        0x0022 => "delegated_credentials",
+        0x0023 => "session_ticket",
+        0x0029 => "pre_shared_key",
+        0x002a => "early_data",
+        0x002b => "supported_versions",
+        0x002c => "cookie",
+        0x002d => "psk_key_exchange_modes",
+        0x0031 => "post_handshake_auth",
+        0x0032 => "signature_algorithms_cert",
+        0x0033 => "key_share",
+        0x0039 => "quic_transport_parameters",
+        0x4469 | 0x44cd => "application_settings",
+        0xfe0d => "encrypted_client_hello",
+        0xff01 => "renegotiation_info",
+        other => return format!("0x{other:04x}"),
+    };
+    name.to_string()
+}
+
+/// IANA name of a named group
+pub fn group_name(group: u16) -> String {
+    let name = match group {
+        _ if is_grease_value(group) => "grease",
+        0x0017 => "secp256r1",
+        0x0018 => "secp384r1",
+        0x0019 => "secp521r1",
+        0x001d => "x25519",
+        0x001e => "x448",
+        0x0100 => "ffdhe2048",
+        0x0101 => "ffdhe3072",
+        0x0200 => "MLKEM512",
+        0x0201 => "MLKEM768",
+        0x0202 => "MLKEM1024",
+        0x11eb => "SecP256r1MLKEM768",
+        0x11ec => "X25519MLKEM768",
+        0x6399 => "X25519Kyber768Draft00",
+        other => return format!("0x{other:04x}"),
+    };
+    name.to_string()
+}
+
+/// IANA name of a signature scheme
+pub fn signature_scheme_name(scheme: u16) -> String {
+    let name = match scheme {
+        _ if is_grease_value(scheme) => "grease",
+        0x0201 => "rsa_pkcs1_sha1",
+        0x0203 => "ecdsa_sha1",
+        0x0401 => "rsa_pkcs1_sha256",
+        0x0403 => "ecdsa_secp256r1_sha256",
+        0x0501 => "rsa_pkcs1_sha384",
+        0x0503 => "ecdsa_secp384r1_sha384",
+        0x0601 => "rsa_pkcs1_sha512",
+        0x0603 => "ecdsa_secp521r1_sha512",
+        0x0804 => "rsa_pss_rsae_sha256",
+        0x0805 => "rsa_pss_rsae_sha384",
+        0x0806 => "rsa_pss_rsae_sha512",
+        0x0807 => "ed25519",
+        0x0808 => "ed448",
+        0x0809 => "rsa_pss_pss_sha256",
+        0x080a => "rsa_pss_pss_sha384",
+        0x080b => "rsa_pss_pss_sha512",
+        0x0904 => "mldsa44",
+        0x0905 => "mldsa65",
+        0x0906 => "mldsa87",
+        other => return format!("0x{other:04x}"),
+    };
+    name.to_string()
+}
+
+fn grease_positions(values: impl Iterator<Item = u16>) -> Vec<usize> {
+    values
+        .enumerate()
+        .filter(|(_, value)| is_grease_value(*value))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn read_u16_list(r: &mut Reader<'_>) -> Result<Vec<u16>, InvalidMessage> {
+    let len = usize::from(u16::read(r)?);
+    read_u16s(&mut r.sub(len)?)
+}
+
+fn read_u16s(r: &mut Reader<'_>) -> Result<Vec<u16>, InvalidMessage> {
+    let mut values = Vec::new();
+    while r.any_left() {
+        values.push(u16::read(r)?);
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello(extensions: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0u8; 32]);
+        body.push(0);
+        body.extend_from_slice(&[0x00, 0x04, 0x1a, 0x1a, 0x13, 0x01]);
+        body.extend_from_slice(&[0x01, 0x00]);
+
+        let mut exts = Vec::new();
+        for (typ, data) in extensions {
+            exts.extend_from_slice(&typ.to_be_bytes());
+            exts.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            exts.extend_from_slice(data);
+        }
+        body.extend_from_slice(&(exts.len() as u16).to_be_bytes());
+        body.extend_from_slice(&exts);
+
+        let mut msg = vec![CLIENT_HELLO, 0, (body.len() >> 8) as u8, body.len() as u8];
+        msg.extend_from_slice(&body);
+        msg
+    }
+
+    #[test]
+    fn test_parse_client_hello_fields() {
+        let bytes = hello(&[
+            (0x2a2a, &[]),
+            (EXT_SUPPORTED_GROUPS, &[0x00, 0x04, 0x11, 0xec, 0x00, 0x1d]),
+            (EXT_KEY_SHARE, &[0x00, 0x06, 0x00, 0x1d, 0x00, 0x02, 0xaa, 0xbb]),
+            (EXT_SIGNATURE_ALGORITHMS, &[0x00, 0x02, 0x04, 0x03]),
+            (EXT_SUPPORTED_VERSIONS, &[0x02, 0x03, 0x04]),
+            (EXT_ALPN, &[0x00, 0x03, 0x02, b'h', b'2']),
+            (EXT_PADDING, &[0u8; 5]),
+        ]);
+        let view = ClientHelloView::parse(&bytes).unwrap();
+
+        assert_eq!(view.legacy_version(), 0x0303);
+        assert_eq!(view.session_id_len(), 0);
+        assert_eq!(view.cipher_suites(), &[0x1a1a, 0x1301]);
+        assert_eq!(
+            view.extension_types(),
+            vec![
+                0x2a2a,
+                EXT_SUPPORTED_GROUPS,
+                EXT_KEY_SHARE,
+                EXT_SIGNATURE_ALGORITHMS,
+                EXT_SUPPORTED_VERSIONS,
+                EXT_ALPN,
+                EXT_PADDING
+            ]
+        );
+        assert_eq!(view.extensions()[0].name, "grease");
+        assert_eq!(view.supported_groups(), &[0x11ec, 0x001d]);
+        assert_eq!(view.key_share_groups(), &[0x001d]);
+        assert_eq!(view.signature_algorithms(), &[0x0403]);
+        assert_eq!(view.supported_versions(), &[0x0304]);
+        assert_eq!(view.alpn_protocols(), &[b"h2".to_vec()]);
+        assert_eq!(view.padding_length(), Some(5));
+    }
+
+    #[test]
+    fn test_browser_capture_from_view() {
+        let bytes = hello(&[
+            (0x0000, &[]),
+            (0x4a4a, &[]),
+            (EXT_KEY_SHARE, &[0x00, 0x04, 0x00, 0x1d, 0x00, 0x00]),
+            (EXT_SIGNATURE_ALGORITHMS, &[0x00, 0x02, 0x08, 0x04]),
+        ]);
+        let capture = ClientHelloView::parse(&bytes)
+            .unwrap()
+            .to_browser_capture("Chrome");
+
+        assert_eq!(capture.browser, "Chrome");
+        assert_eq!(capture.grease_cipher_positions, vec![0]);
+        assert_eq!(capture.grease_extension_positions, vec![1]);
+        assert_eq!(capture.extensions[0].name, "server_name");
+        assert_eq!(capture.key_share_groups, vec!["x25519".to_string()]);
+        assert_eq!(
+            capture.signature_algorithms,
+            vec!["rsa_pss_rsae_sha256".to_string()]
+        );
+        assert_eq!(capture.padding_length, 0);
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_hellos() {
+        let bytes = hello(&[(EXT_ALPN, &[0x00, 0x03, 0x02, b'h', b'2'])]);
+        assert!(ClientHelloView::parse(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut server_hello = bytes.clone();
+        server_hello[0] = 0x02;
+        assert!(ClientHelloView::parse(&server_hello).is_err());
+
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(ClientHelloView::parse(&trailing).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+//! Layering several ClientHello customizers
+//!
+//! `ClientConfig` holds a single [`ClientHelloCustomizer`]. A [`CustomizerChain`]
+//! is a customizer that runs a list of layers in order for every phase, so small
+//! customizers (an extra extension, a logging tap) can be stacked on top of
+//! [`DefaultCustomizer`](super::DefaultCustomizer) without forking it.
+//!
+//! ## Ordering
+//!
+//! Every phase runs the layers in the order they were added, each working on the
+//! output of the previous one. In phase 1 all layers share the connection's
+//! [`ConfigParams`], so a later layer sees the template choices an earlier one
+//! made (`key_share_groups`, `http2_profile`, ...) along with the target, ALPN and
+//! entropy rustls filled in.
+//!
+//! ## Errors
+//!
+//! Each layer has a [`LayerPolicy`]:
+//!
+//! - [`LayerPolicy::Required`]: an error aborts the handshake with that error and
+//!   the remaining layers do not run
+//! - [`LayerPolicy::Optional`]: an error is discarded, the changes the layer made
+//!   in that phase are rolled back, and the next layer runs on the input the
+//!   failing layer received
+//!
+//! Rolling back only covers the hook arguments. State a layer keeps itself is not
+//! restored.
+//!
+//! ## Usage
+//!
+//! ```rust,ignore
+//! use std::sync::Arc;
+//! use rustls::custls::{CustlsConfig, CustomizerChain, DefaultCustomizer};
+//!
+//! let chain = CustomizerChain::new()
+//!     .with_layer(Arc::new(DefaultCustomizer::new(CustlsConfig::default())))
+//!     .with_optional_layer(Arc::new(LoggingTap::default()));
+//! client_config.enable_custls(Arc::new(chain));
+//! ```
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::hooks::{ClientExtension, ClientHelloCustomizer, ConfigParams};
+use crate::crypto::CipherSuite;
+use crate::error::Error;
+use crate::msgs::ClientHelloPayload;
+
+/// How a [`CustomizerChain`] handles errors from one of its layers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LayerPolicy {
+    /// An error aborts the handshake
+    #[default]
+    Required,
+
+    /// An error is ignored and the layer's changes in that phase are undone
+    Optional,
+}
+
+/// A [`ClientHelloCustomizer`] running several customizers in order
+#[derive(Debug, Clone, Default)]
+pub struct CustomizerChain {
+    layers: Vec<(Arc<dyn ClientHelloCustomizer>, LayerPolicy)>,
+}
+
+impl CustomizerChain {
+    /// Create an empty chain, which leaves the ClientHello unchanged
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a layer whose errors abort the handshake
+    pub fn with_layer(self, customizer: Arc<dyn ClientHelloCustomizer>) -> Self {
+        self.with_layer_policy(customizer, LayerPolicy::Required)
+    }
+
+    /// Append a layer whose errors are ignored
+    pub fn with_optional_layer(self, customizer: Arc<dyn ClientHelloCustomizer>) -> Self {
+        self.with_layer_policy(customizer, LayerPolicy::Optional)
+    }
+
+    /// Append a layer with the given error policy
+    pub fn with_layer_policy(
+        mut self,
+        customizer: Arc<dyn ClientHelloCustomizer>,
+        policy: LayerPolicy,
+    ) -> Self {
+        self.layers.push((customizer, policy));
+        self
+    }
+
+    /// Number of layers in the chain
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Whether the chain has no layers
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Run `hook` on every layer in order over `state`
+    ///
+    /// Optional layers work on a copy that only replaces `state` when they succeed.
+    fn run<T: Clone>(
+        &self,
+        state: &mut T,
+        hook: impl Fn(&dyn ClientHelloCustomizer, &mut T) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        for (customizer, policy) in &self.layers {
+            match policy {
+                LayerPolicy::Required => hook(customizer.as_ref(), state)?,
+                LayerPolicy::Optional => {
+                    let mut attempt = state.clone();
+                    if hook(customizer.as_ref(), &mut attempt).is_ok() {
+                        *state = attempt;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ClientHelloCustomizer for CustomizerChain {
+    fn on_config_resolve(&self, config: &mut ConfigParams) -> Result<(), Error> {
+        self.run(config, |customizer, config| customizer.on_config_resolve(config))
+    }
+
+    fn on_components_ready(
+        &self,
+        cipher_suites: &mut Vec<CipherSuite>,
+        extensions: &mut Vec<ClientExtension>,
+    ) -> Result<(), Error> {
+        let mut components = (core::mem::take(cipher_suites), core::mem::take(extensions));
+        let result = self.run(&mut components, |customizer, (cipher_suites, extensions)| {
+            customizer.on_components_ready(cipher_suites, extensions)
+        });
+        (*cipher_suites, *extensions) = components;
+        result
+    }
+
+    fn on_struct_ready(&self, payload: &mut ClientHelloPayload) -> Result<(), Error> {
+        self.run(payload, |customizer, payload| customizer.on_struct_ready(payload))
+    }
+
+    fn on_ech_inner_ready(&self, inner: &mut ClientHelloPayload) -> Result<(), Error> {
+        self.run(inner, |customizer, inner| customizer.on_ech_inner_ready(inner))
+    }
+
+    fn transform_wire_bytes(&self, mut bytes: Vec<u8>) -> Result<Vec<u8>, Error> {
+        self.run(&mut bytes, |customizer, bytes| {
+            *bytes = customizer.transform_wire_bytes(core::mem::take(bytes))?;
+            Ok(())
+        })?;
+        Ok(bytes)
+    }
+
+    /// The session is offered only if every layer agrees
+    ///
+    /// An optional layer that fails is treated as agreeing.
+    fn on_resumption_offer(&self, session: &[u8], partition: Option<&[u8]>) -> Result<bool, Error> {
+        let mut offer = true;
+        self.run(&mut offer, |customizer, offer| {
+            *offer &= customizer.on_resumption_offer(session, partition)?;
+            Ok(())
+        })?;
+        Ok(offer)
+    }
+
+    fn on_session_stored(&self, session: &[u8], partition: Option<&[u8]>) {
+        for (customizer, _) in &self.layers {
+            customizer.on_session_stored(session, partition);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use std::sync::Mutex;
+
+    use crate::custls::{BrowserTemplate, CustlsConfig, DefaultCustomizer, RandomizationLevel};
+    use crate::custls::templates::firefox_135;
+
+    /// Appends a cipher suite and a wire byte, or fails after partially doing so
+    #[derive(Debug)]
+    struct Appending {
+        suite: u16,
+        fail: bool,
+    }
+
+    impl ClientHelloCustomizer for Appending {
+        fn on_config_resolve(&self, config: &mut ConfigParams) -> Result<(), Error> {
+            config.alpn_protocols.push(self.suite.to_be_bytes().to_vec());
+            self.result()
+        }
+
+        fn on_components_ready(
+            &self,
+            cipher_suites: &mut Vec<CipherSuite>,
+            _extensions: &mut Vec<ClientExtension>,
+        ) -> Result<(), Error> {
+            cipher_suites.push(CipherSuite::from(self.suite));
+            self.result()
+        }
+
+        fn transform_wire_bytes(&self, mut bytes: Vec<u8>) -> Result<Vec<u8>, Error> {
+            bytes.push(self.suite as u8);
+            self.result().map(|()| bytes)
+        }
+    }
+
+    impl Appending {
+        fn layer(suite: u16, fail: bool) -> Arc<dyn ClientHelloCustomizer> {
+            Arc::new(Self { suite, fail })
+        }
+
+        fn result(&self) -> Result<(), Error> {
+            if self.fail {
+                return Err(Error::General("layer failed".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    /// Records the cipher suites it is given in phase 2
+    #[derive(Debug, Default)]
+    struct Tap {
+        seen: Mutex<Vec<CipherSuite>>,
+    }
+
+    impl ClientHelloCustomizer for Tap {
+        fn on_components_ready(
+            &self,
+            cipher_suites: &mut Vec<CipherSuite>,
+            _extensions: &mut Vec<ClientExtension>,
+        ) -> Result<(), Error> {
+            self.seen.lock().unwrap().clone_from(cipher_suites);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_empty_chain_is_transparent() {
+        let chain = CustomizerChain::new();
+        assert!(chain.is_empty());
+
+        let mut suites = vec![CipherSuite::from(1)];
+        chain.on_components_ready(&mut suites, &mut Vec::new()).unwrap();
+        assert_eq!(suites, vec![CipherSuite::from(1)]);
+        assert_eq!(chain.transform_wire_bytes(vec![1, 2]).unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_layers_run_in_order() {
+        let chain = CustomizerChain::new()
+            .with_layer(Appending::layer(1, false))
+            .with_layer(Appending::layer(2, false));
+        assert_eq!(chain.len(), 2);
+
+        let mut params = ConfigParams::new();
+        chain.on_config_resolve(&mut params).unwrap();
+        assert_eq!(params.alpn_protocols, vec![vec![0, 1], vec![0, 2]]);
+
+        let mut suites = Vec::new();
+        chain.on_components_ready(&mut suites, &mut Vec::new()).unwrap();
+        assert_eq!(suites, vec![CipherSuite::from(1), CipherSuite::from(2)]);
+
+        assert_eq!(chain.transform_wire_bytes(vec![0]).unwrap(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_required_layer_error_stops_the_chain() {
+        let chain = CustomizerChain::new()
+            .with_layer(Appending::layer(1, true))
+            .with_layer(Appending::layer(2, false));
+
+        let mut suites = Vec::new();
+        let err = chain.on_components_ready(&mut suites, &mut Vec::new()).unwrap_err();
+        assert_eq!(err, Error::General("layer failed".to_string()));
+        assert_eq!(suites, vec![CipherSuite::from(1)]);
+
+        assert!(chain.transform_wire_bytes(vec![0]).is_err());
+    }
+
+    #[test]
+    fn test_optional_layer_error_is_rolled_back() {
+        let chain = CustomizerChain::new()
+            .with_layer(Appending::layer(1, false))
+            .with_optional_layer(Appending::layer(2, true))
+            .with_layer_policy(Appending::layer(3, false), LayerPolicy::Required);
+
+        let mut params = ConfigParams::new();
+        chain.on_config_resolve(&mut params).unwrap();
+        assert_eq!(params.alpn_protocols, vec![vec![0, 1], vec![0, 3]]);
+
+        let mut suites = Vec::new();
+        chain.on_components_ready(&mut suites, &mut Vec::new()).unwrap();
+        assert_eq!(suites, vec![CipherSuite::from(1), CipherSuite::from(3)]);
+
+        assert_eq!(chain.transform_wire_bytes(vec![0]).unwrap(), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn test_later_layers_see_default_customizer_output() {
+        let config = CustlsConfig::builder()
+            .with_template(BrowserTemplate::Firefox135)
+            .with_randomization_level(RandomizationLevel::None)
+            .build();
+        let tap = Arc::new(Tap::default());
+        let chain = CustomizerChain::new()
+            .with_layer(Arc::new(DefaultCustomizer::new(config)))
+            .with_layer(tap.clone());
+
+        let mut params = ConfigParams::new();
+        chain.on_config_resolve(&mut params).unwrap();
+        assert_eq!(params.http2_profile, Some(firefox_135().http2_profile));
+
+        let mut suites = Vec::new();
+        chain.on_components_ready(&mut suites, &mut Vec::new()).unwrap();
+        assert_eq!(*tap.seen.lock().unwrap(), firefox_135().cipher_suites);
+    }
+}
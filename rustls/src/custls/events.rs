@@ -0,0 +1,368 @@
+//! Decisions and outcomes reported by the default customizer
+//!
+//! [`DefaultCustomizer`](super::DefaultCustomizer) reports every choice it makes
+//! for a ClientHello, and every handshake result recorded with it, as a
+//! [`CustlsEvent`]. Events go to the [`CustlsEventSink`] configured with
+//! [`CustlsConfigBuilder::with_event_sink`](super::CustlsConfigBuilder::with_event_sink),
+//! and are also aggregated into the [`CustlsStats`] snapshot returned by
+//! `DefaultCustomizer::stats`.
+//!
+//! ## Events
+//!
+//! For each ClientHello, in order:
+//!
+//! 1. [`CustlsEvent::TemplateSelected`] or [`CustlsEvent::CustomizationDisabled`]
+//! 2. [`CustlsEvent::CacheLookup`], when the cache is enabled and the target is known
+//! 3. [`CustlsEvent::ClientHelloShaped`], once the hello has its final shape
+//!
+//! A hello sent after a HelloRetryRequest replays the first one and produces no
+//! events. Recording a result
+//! with `DefaultCustomizer::record_handshake_result` produces a
+//! [`CustlsEvent::HandshakeOutcome`], followed by a
+//! [`CustlsEvent::TargetQuarantined`] when the result starts a quarantine under
+//! the cache policy.
+//!
+//! Sinks are called synchronously while the ClientHello is being built, so they
+//! should hand events off rather than block.
+//!
+//! ## Usage
+//!
+//! ```rust,ignore
+//! use std::sync::Arc;
+//! use rustls::custls::{CustlsConfig, CustlsEvent, CustlsEventSink, DefaultCustomizer};
+//!
+//! #[derive(Debug)]
+//! struct LogSink;
+//!
+//! impl CustlsEventSink for LogSink {
+//!     fn on_event(&self, event: &CustlsEvent) {
+//!         log::info!("custls: {event:?}");
+//!     }
+//! }
+//!
+//! let customizer = DefaultCustomizer::new(
+//!     CustlsConfig::builder()
+//!         .with_event_sink(Arc::new(LogSink))
+//!         .build(),
+//! );
+//! // ... later, export the aggregate
+//! let stats = customizer.stats();
+//! ```
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::state::{REPUTATION_BUCKETS, TargetKey};
+use crate::msgs::ExtensionType;
+
+/// Receives the events of a [`DefaultCustomizer`](super::DefaultCustomizer)
+pub trait CustlsEventSink: Send + Sync + fmt::Debug {
+    /// Called for every decision and recorded outcome, on the thread that made it
+    fn on_event(&self, event: &CustlsEvent);
+}
+
+/// A decision or outcome reported by the default customizer
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub enum CustlsEvent {
+    /// A template was chosen to shape the ClientHello
+    TemplateSelected {
+        /// The server being connected to, if known
+        target: Option<TargetKey>,
+        /// Name of the chosen template
+        template: String,
+        /// What chose it
+        source: TemplateSource,
+    },
+
+    /// A routing rule left the ClientHello to rustls
+    CustomizationDisabled {
+        /// The server being connected to, if known
+        target: Option<TargetKey>,
+    },
+
+    /// The target was looked up in the fingerprint cache
+    CacheLookup {
+        /// The server being connected to
+        target: TargetKey,
+        /// Whether a working fingerprint was cached for it
+        hit: bool,
+    },
+
+    /// A ClientHello has its final shape
+    ClientHelloShaped {
+        /// The server being connected to, if known
+        target: Option<TargetKey>,
+        /// Name of the template that shaped it
+        template: String,
+        /// GREASE values in the encoded cipher suites, then extensions
+        grease_values: Vec<u16>,
+        /// Length of the padding extension body, `None` when no padding is sent
+        padding_length: Option<u16>,
+        /// Extensions in the order they are encoded, including the randomized
+        /// positions of the ones the template does not pin
+        ///
+        /// ECH and PSK extensions added after shaping are not included.
+        extension_order: Vec<ExtensionType>,
+    },
+
+    /// A handshake result was recorded
+    HandshakeOutcome {
+        /// The server connected to
+        target: TargetKey,
+        /// Name of the template the handshake used
+        template: String,
+        /// Whether the handshake succeeded
+        success: bool,
+    },
+
+    /// Consecutive failures got a template quarantined for a target
+    ///
+    /// Until the quarantine ends, hellos to the target use another template.
+    TargetQuarantined {
+        /// The server connected to
+        target: TargetKey,
+        /// Name of the quarantined template
+        template: String,
+    },
+}
+
+/// What chose the template for a ClientHello
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateSource {
+    /// A routing rule matched the target
+    RoutingRule,
+    /// The template rotation policy
+    Rotation,
+    /// The configured template, without rotation
+    Configured,
+    /// Rotation away from a template quarantined for the target
+    Quarantine,
+}
+
+/// Successes and failures recorded for handshakes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutcomeCounts {
+    /// Handshakes recorded as successful
+    pub successes: u64,
+    /// Handshakes recorded as failed
+    pub failures: u64,
+}
+
+impl OutcomeCounts {
+    /// Fraction of recorded handshakes that succeeded, or `None` if there are none
+    pub fn success_rate(&self) -> Option<f64> {
+        let total = self.successes + self.failures;
+        match total {
+            0 => None,
+            _ => Some(self.successes as f64 / total as f64),
+        }
+    }
+
+    fn record(&mut self, success: bool) {
+        match success {
+            true => self.successes += 1,
+            false => self.failures += 1,
+        }
+    }
+}
+
+/// Aggregate for one template
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TemplateStats {
+    /// ClientHellos the template was selected for
+    pub selected: u64,
+    /// Handshake results recorded for the template
+    pub outcomes: OutcomeCounts,
+}
+
+/// Snapshot of the default customizer's aggregated events
+///
+/// Plain data, meant to be exported to a metrics system.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CustlsStats {
+    /// Per template name
+    pub templates: BTreeMap<String, TemplateStats>,
+
+    /// Handshake results per target, then per template name
+    ///
+    /// Holds at most `max_cache_size` targets; the earliest added is dropped
+    /// first.
+    pub targets: BTreeMap<TargetKey, BTreeMap<String, OutcomeCounts>>,
+
+    /// ClientHellos the routing rules left to rustls
+    pub disabled: u64,
+
+    /// Cache lookups that found a working fingerprint
+    pub cache_hits: u64,
+
+    /// Cache lookups that did not
+    pub cache_misses: u64,
+
+    /// Templates quarantined for a target
+    pub quarantines: u64,
+
+    /// Reputation scores of the cached fingerprints
+    ///
+    /// Bucket `i` counts scores in `[i / 10, (i + 1) / 10)`; a score of 1.0 is in
+    /// the last bucket. All zero when the cache is disabled.
+    pub reputation_histogram: [usize; REPUTATION_BUCKETS],
+}
+
+/// Running aggregate behind [`CustlsStats`]
+#[derive(Debug, Default)]
+pub(crate) struct StatsCollector {
+    stats: CustlsStats,
+    target_order: VecDeque<TargetKey>,
+}
+
+impl StatsCollector {
+    /// Fold `event` into the aggregate, tracking at most `max_targets` targets
+    pub(crate) fn record(&mut self, event: &CustlsEvent, max_targets: usize) {
+        let stats = &mut self.stats;
+        match event {
+            CustlsEvent::TemplateSelected { template, .. } => {
+                stats
+                    .templates
+                    .entry(template.clone())
+                    .or_default()
+                    .selected += 1;
+            }
+            CustlsEvent::CustomizationDisabled { .. } => stats.disabled += 1,
+            CustlsEvent::CacheLookup { hit: true, .. } => stats.cache_hits += 1,
+            CustlsEvent::CacheLookup { hit: false, .. } => stats.cache_misses += 1,
+            CustlsEvent::ClientHelloShaped { .. } => {}
+            CustlsEvent::TargetQuarantined { .. } => stats.quarantines += 1,
+            CustlsEvent::HandshakeOutcome {
+                target,
+                template,
+                success,
+            } => {
+                stats
+                    .templates
+                    .entry(template.clone())
+                    .or_default()
+                    .outcomes
+                    .record(*success);
+
+                if !stats.targets.contains_key(target) {
+                    while self.target_order.len() >= max_targets.max(1) {
+                        if let Some(oldest) = self.target_order.pop_front() {
+                            stats.targets.remove(&oldest);
+                        }
+                    }
+                    self.target_order.push_back(target.clone());
+                }
+                stats
+                    .targets
+                    .entry(target.clone())
+                    .or_default()
+                    .entry(template.clone())
+                    .or_default()
+                    .record(*success);
+            }
+        }
+    }
+
+    /// The aggregate so far, with the given reputation histogram
+    pub(crate) fn snapshot(&self, reputation_histogram: [usize; REPUTATION_BUCKETS]) -> CustlsStats {
+        CustlsStats {
+            reputation_histogram,
+            ..self.stats.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn outcome(host: &str, template: &str, success: bool) -> CustlsEvent {
+        CustlsEvent::HandshakeOutcome {
+            target: TargetKey::new(host.to_string(), 443),
+            template: template.to_string(),
+            success,
+        }
+    }
+
+    #[test]
+    fn test_outcomes_aggregate_per_template_and_target() {
+        let mut collector = StatsCollector::default();
+        collector.record(
+            &CustlsEvent::TemplateSelected {
+                target: None,
+                template: "Chrome 130+".to_string(),
+                source: TemplateSource::Configured,
+            },
+            10,
+        );
+        collector.record(&outcome("a.example", "Chrome 130+", true), 10);
+        collector.record(&outcome("a.example", "Chrome 130+", false), 10);
+        collector.record(&outcome("a.example", "Firefox 135+", true), 10);
+        collector.record(&outcome("b.example", "Chrome 130+", true), 10);
+
+        let stats = collector.snapshot([0; REPUTATION_BUCKETS]);
+        let chrome = stats.templates["Chrome 130+"];
+        assert_eq!(chrome.selected, 1);
+        assert_eq!(
+            chrome.outcomes,
+            OutcomeCounts {
+                successes: 2,
+                failures: 1
+            }
+        );
+        assert_eq!(stats.templates["Firefox 135+"].selected, 0);
+
+        let a = &stats.targets[&TargetKey::new("a.example".to_string(), 443)];
+        assert_eq!(a["Chrome 130+"].success_rate(), Some(0.5));
+        assert_eq!(a["Firefox 135+"].success_rate(), Some(1.0));
+        assert_eq!(OutcomeCounts::default().success_rate(), None);
+    }
+
+    #[test]
+    fn test_cache_lookups_and_disabled_hellos_counted() {
+        let mut collector = StatsCollector::default();
+        let target = TargetKey::new("a.example".to_string(), 443);
+        for hit in [true, false, false] {
+            collector.record(
+                &CustlsEvent::CacheLookup {
+                    target: target.clone(),
+                    hit,
+                },
+                10,
+            );
+        }
+        collector.record(&CustlsEvent::CustomizationDisabled { target: None }, 10);
+
+        let mut histogram = [0; REPUTATION_BUCKETS];
+        histogram[5] = 2;
+        let stats = collector.snapshot(histogram);
+        assert_eq!((stats.cache_hits, stats.cache_misses), (1, 2));
+        assert_eq!(stats.disabled, 1);
+        assert_eq!(stats.reputation_histogram, histogram);
+    }
+
+    #[test]
+    fn test_oldest_target_dropped_when_full() {
+        let mut collector = StatsCollector::default();
+        collector.record(&outcome("a.example", "Chrome 130+", true), 2);
+        collector.record(&outcome("b.example", "Chrome 130+", true), 2);
+        collector.record(&outcome("a.example", "Chrome 130+", true), 2);
+        collector.record(&outcome("c.example", "Chrome 130+", true), 2);
+
+        let stats = collector.snapshot([0; REPUTATION_BUCKETS]);
+        let hosts = stats
+            .targets
+            .keys()
+            .map(|target| target.host.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(hosts, ["b.example", "c.example"]);
+
+        // Dropping a target keeps the per-template totals
+        assert_eq!(stats.templates["Chrome 130+"].outcomes.successes, 4);
+    }
+}
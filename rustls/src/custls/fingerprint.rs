@@ -0,0 +1,316 @@
+//! JA3 and JA4 ClientHello fingerprints
+//!
+//! Bot-detection services key clients by these two fingerprints, so they are
+//! the quickest way to check what a server makes of a custls ClientHello:
+//!
+//! - JA3 joins the legacy version, cipher suites, extensions, groups and point
+//!   formats in wire order. Services key clients by the MD5 of that string;
+//!   rustls has no MD5, so the hash is left to the caller.
+//! - JA4 (FoxIO) sorts cipher suites and extensions, which makes it stable
+//!   under Chrome's extension shuffling, and hashes them with truncated SHA-256
+//!   taken from a [`CryptoProvider`].
+//!
+//! Both ignore GREASE values. They are computed from a [`ClientHelloView`], so
+//! they work the same for a hello a client sent and one a server received.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::capture::{ClientHelloView, is_grease_value};
+use crate::crypto::{CryptoProvider, HashAlgorithm, hash};
+
+const EXT_SERVER_NAME: u16 = 0x0000;
+const EXT_ALPN: u16 = 0x0010;
+const EXT_QUIC_TRANSPORT_PARAMETERS: u16 = 0x0039;
+
+impl ClientHelloView {
+    /// The JA3 string, before hashing
+    ///
+    /// `version,ciphers,extensions,groups,point_formats` with decimal values
+    /// joined by `-`, in wire order and without GREASE. The JA3 fingerprint is
+    /// the MD5 of this string, as lowercase hex.
+    pub fn ja3_string(&self) -> String {
+        format!(
+            "{},{},{},{},{}",
+            self.legacy_version(),
+            join_decimal(self.cipher_suites().iter().copied()),
+            join_decimal(
+                self.extensions()
+                    .iter()
+                    .map(|ext| ext.extension_type)
+            ),
+            join_decimal(self.supported_groups().iter().copied()),
+            self.ec_point_formats()
+                .iter()
+                .map(|format| format!("{format}"))
+                .collect::<Vec<_>>()
+                .join("-"),
+        )
+    }
+
+    /// The JA4 fingerprint, e.g. `t13d1516h2_8daaf6152771_e5627efa2ab1`
+    ///
+    /// The SHA-256 comes from one of `provider`'s cipher suites; `None` if none
+    /// of them hashes with SHA-256.
+    pub fn ja4(&self, provider: &CryptoProvider) -> Option<String> {
+        let sha256 = provider
+            .iter_cipher_suites()
+            .map(|suite| suite.hash_provider())
+            .find(|hash| hash.algorithm() == HashAlgorithm::SHA256)?;
+
+        let (ciphers, extensions) = self.ja4_lists();
+        Some(format!(
+            "{}_{}_{}",
+            self.ja4_a(),
+            ja4_hash(sha256, &ciphers),
+            ja4_hash(sha256, &extensions)
+        ))
+    }
+
+    /// The unhashed JA4 fingerprint (`ja4_r`), with the sorted lists in clear
+    pub fn ja4_r(&self) -> String {
+        let (ciphers, extensions) = self.ja4_lists();
+        format!("{}_{}_{}", self.ja4_a(), ciphers, extensions)
+    }
+
+    /// First JA4 section: transport, version, SNI, counts and ALPN
+    fn ja4_a(&self) -> String {
+        let extension_types = self.extension_types();
+        let transport = match extension_types.contains(&EXT_QUIC_TRANSPORT_PARAMETERS) {
+            true => 'q',
+            false => 't',
+        };
+        let version = self
+            .supported_versions()
+            .iter()
+            .copied()
+            .filter(|version| !is_grease_value(*version))
+            .max()
+            .unwrap_or_else(|| self.legacy_version());
+        let sni = match extension_types.contains(&EXT_SERVER_NAME) {
+            true => 'd',
+            false => 'i',
+        };
+        let ciphers = non_grease(self.cipher_suites().iter().copied()).count();
+        let extensions = non_grease(extension_types.iter().copied()).count();
+
+        format!(
+            "{transport}{}{sni}{:02}{:02}{}",
+            ja4_version(version),
+            ciphers.min(99),
+            extensions.min(99),
+            self.ja4_alpn()
+        )
+    }
+
+    /// First and last character of the first ALPN protocol, `00` without one
+    fn ja4_alpn(&self) -> String {
+        let Some(protocol) = self
+            .alpn_protocols()
+            .first()
+            .filter(|protocol| !protocol.is_empty())
+        else {
+            return String::from("00");
+        };
+
+        let (first, last) = (protocol[0], protocol[protocol.len() - 1]);
+        match first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+            true => format!("{}{}", first as char, last as char),
+            false => {
+                let hex = hex(protocol);
+                let bytes = hex.as_bytes();
+                format!("{}{}", bytes[0] as char, bytes[bytes.len() - 1] as char)
+            }
+        }
+    }
+
+    /// Sorted cipher list, and sorted extension list with signature algorithms
+    fn ja4_lists(&self) -> (String, String) {
+        let mut ciphers = non_grease(self.cipher_suites().iter().copied()).collect::<Vec<_>>();
+        ciphers.sort_unstable();
+
+        let mut extensions = non_grease(self.extension_types().into_iter())
+            .filter(|ext| *ext != EXT_SERVER_NAME && *ext != EXT_ALPN)
+            .collect::<Vec<_>>();
+        extensions.sort_unstable();
+
+        let mut extensions = join_hex(&extensions);
+        let schemes = non_grease(self.signature_algorithms().iter().copied()).collect::<Vec<_>>();
+        if !schemes.is_empty() {
+            extensions.push('_');
+            extensions.push_str(&join_hex(&schemes));
+        }
+
+        (join_hex(&ciphers), extensions)
+    }
+}
+
+fn ja4_version(version: u16) -> &'static str {
+    match version {
+        0x0304 => "13",
+        0x0303 => "12",
+        0x0302 => "11",
+        0x0301 => "10",
+        0x0300 => "s3",
+        0x0002 => "s2",
+        0xfeff => "d1",
+        0xfefd => "d2",
+        0xfefc => "d3",
+        _ => "00",
+    }
+}
+
+/// First 12 hex characters of the SHA-256 of `list`, zeros for an empty list
+fn ja4_hash(sha256: &dyn hash::Hash, list: &str) -> String {
+    match list.is_empty() {
+        true => String::from("000000000000"),
+        false => hex(sha256.hash(list.as_bytes()).as_ref())[..12].into(),
+    }
+}
+
+fn non_grease(values: impl Iterator<Item = u16>) -> impl Iterator<Item = u16> {
+    values.filter(|value| !is_grease_value(*value))
+}
+
+fn join_decimal(values: impl Iterator<Item = u16>) -> String {
+    non_grease(values)
+        .map(|value| format!("{value}"))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn join_hex(values: &[u16]) -> String {
+    values
+        .iter()
+        .map(|value| format!("{value:04x}"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    fn view() -> ClientHelloView {
+        // GREASE, TLS_AES_128_GCM_SHA256, TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256
+        hello(
+            &[0x0a0a, 0x1301, 0xc02b],
+            &[
+                (0x2a2a, &[]),
+                (0x0000, &[0x00, 0x05, 0x00, 0x00, 0x02, b'a', b'b']),
+                (0x000a, &[0x00, 0x04, 0x0a, 0x0a, 0x00, 0x1d]),
+                (0x000b, &[0x01, 0x00]),
+                (0x000d, &[0x00, 0x04, 0x04, 0x03, 0x08, 0x04]),
+                (0x0010, &[0x00, 0x03, 0x02, b'h', b'2']),
+                (0x002b, &[0x04, 0x03, 0x04, 0x03, 0x03]),
+            ],
+        )
+    }
+
+    fn hello(cipher_suites: &[u16], extensions: &[(u16, &[u8])]) -> ClientHelloView {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0u8; 32]);
+        body.push(0);
+        body.extend_from_slice(&((cipher_suites.len() * 2) as u16).to_be_bytes());
+        for suite in cipher_suites {
+            body.extend_from_slice(&suite.to_be_bytes());
+        }
+        body.extend_from_slice(&[0x01, 0x00]);
+
+        let mut exts = Vec::new();
+        for (typ, data) in extensions {
+            exts.extend_from_slice(&typ.to_be_bytes());
+            exts.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            exts.extend_from_slice(data);
+        }
+        body.extend_from_slice(&(exts.len() as u16).to_be_bytes());
+        body.extend_from_slice(&exts);
+
+        let mut msg = vec![0x01, 0, (body.len() >> 8) as u8, body.len() as u8];
+        msg.extend_from_slice(&body);
+        ClientHelloView::parse(&msg).unwrap()
+    }
+
+    #[test]
+    fn test_ja3_skips_grease() {
+        assert_eq!(view().ja3_string(), "771,4865-49195,0-10-11-13-16-43,29,0");
+    }
+
+    #[test]
+    fn test_ja4_sections() {
+        assert_eq!(
+            view().ja4_r(),
+            "t13d0206h2_1301,c02b_000a,000b,000d,002b_0403,0804"
+        );
+    }
+
+    /// The example in FoxIO's JA4 technical details
+    #[cfg(any(target_arch = "aarch64", target_arch = "x86_64"))]
+    #[test]
+    fn test_ja4_matches_foxio_reference() {
+        let view = hello(
+            &[
+                0x1a1a, 0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8,
+                0xc013, 0xc014, 0x009c, 0x009d, 0x002f, 0x0035,
+            ],
+            &[
+                (0x3a3a, &[]),
+                (0x0000, &[0x00, 0x05, 0x00, 0x00, 0x02, b'a', b'b']),
+                (0x0017, &[]),
+                (0xff01, &[0x00]),
+                (0x000a, &[0x00, 0x04, 0x3a, 0x3a, 0x00, 0x1d]),
+                (0x000b, &[0x01, 0x00]),
+                (0x0023, &[]),
+                (0x0010, &[0x00, 0x03, 0x02, b'h', b'2']),
+                (0x0005, &[0x01, 0x00, 0x00, 0x00, 0x00]),
+                (
+                    0x000d,
+                    &[
+                        0x00, 0x10, 0x04, 0x03, 0x08, 0x04, 0x04, 0x01, 0x05, 0x03, 0x08, 0x05,
+                        0x05, 0x01, 0x08, 0x06, 0x06, 0x01,
+                    ],
+                ),
+                (0x0012, &[]),
+                (0x0033, &[0x00, 0x00]),
+                (0x002d, &[0x01, 0x01]),
+                (0x002b, &[0x04, 0x03, 0x04, 0x03, 0x03]),
+                (0x001b, &[0x02, 0x00, 0x02]),
+                (0x4469, &[0x00, 0x03, 0x02, b'h', b'2']),
+                (0x0015, &[0x00; 4]),
+                (0x5a5a, &[0x00]),
+            ],
+        );
+
+        assert_eq!(
+            view.ja4_r(),
+            "t13d1516h2_002f,0035,009c,009d,1301,1302,1303,c013,c014,c02b,c02c,c02f,c030,cca8,cca9_\
+             0005,000a,000b,000d,0012,0015,0017,001b,0023,002b,002d,0033,4469,ff01_\
+             0403,0804,0401,0503,0805,0501,0806,0601"
+        );
+        assert_eq!(
+            view.ja4(&crate::crypto::TEST_PROVIDER)
+                .as_deref(),
+            Some("t13d1516h2_8daaf6152771_e5627efa2ab1")
+        );
+    }
+
+    #[test]
+    fn test_ja4_empty_lists_and_unknown_versions() {
+        assert_eq!(
+            ja4_hash(crate::crypto::test_provider::FAKE_HASH, ""),
+            "000000000000"
+        );
+        assert_eq!(ja4_version(0x0303), "12");
+        assert_eq!(ja4_version(0x1234), "00");
+    }
+}
@@ -146,14 +146,13 @@ mod property_tests {
             // The hook should return an error
             prop_assert!(result.is_err(), "Hook should return an error");
             
-            // The error should be a General error containing the custls error
+            // The error should be the custls error, unchanged
             match result {
-                Err(Error::General(msg)) => {
-                    prop_assert!(msg.contains("custls error"), 
-                        "Error message should contain 'custls error', got: {}", msg);
+                Err(Error::Customization { error: got, .. }) => {
+                    prop_assert_eq!(got, error);
                 }
                 _ => {
-                    return Err(TestCaseError::fail("Expected General error variant"));
+                    return Err(TestCaseError::fail("Expected Customization error variant"));
                 }
             }
         }
@@ -205,17 +204,16 @@ mod property_tests {
             error in arbitrary_custls_error(),
         ) {
             let error_msg = format!("{}", error);
-            let rustls_error: Error = error.into();
+            let rustls_error: Error = error.clone().into();
+            prop_assert!(format!("{}", rustls_error).contains(&error_msg),
+                "Error should preserve original message");
             
             match rustls_error {
-                Error::General(msg) => {
-                    prop_assert!(msg.contains("custls error"), 
-                        "Error should contain 'custls error'");
-                    prop_assert!(msg.contains(&error_msg), 
-                        "Error should preserve original message");
+                Error::Customization { phase: None, error: got } => {
+                    prop_assert_eq!(got, error);
                 }
                 _ => {
-                    return Err(TestCaseError::fail("Expected General error variant"));
+                    return Err(TestCaseError::fail("Expected Customization error variant"));
                 }
             }
         }
@@ -314,11 +314,10 @@ fn test_hook_error_conversion() {
     
     assert!(result.is_err());
     match result {
-        Err(Error::General(msg)) => {
-            assert!(msg.contains("custls error"));
-            assert!(msg.contains("Hook error"));
+        Err(Error::Customization { phase: None, error }) => {
+            assert_eq!(error, CustlsError::HookError("hook failed".into()));
         }
-        _ => panic!("Expected General error"),
+        _ => panic!("Expected Customization error"),
     }
 }
 
@@ -17,6 +17,8 @@
 //!    - Accesses complete ClientHelloPayload before serialization
 //!    - Allows final structural modifications
 //!    - Use for: padding addition, final validation, cross-field adjustments
+//!    - With ECH, sees the outer hello before encryption; the inner hello is
+//!      passed separately to `on_ech_inner_ready`
 //!
 //! 4. **Phase 4 (transform_wire_bytes)**: Post-marshal byte transformation
 //!    - Accesses final wire bytes after marshaling
@@ -60,8 +62,77 @@
 //! ```
 
 use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+use crate::crypto::kx::NamedGroup;
 use crate::error::Error;
 
+use super::state::TargetKey;
+use super::templates::HandshakeBehavior;
+use super::utils::Http2Profile;
+
+/// Port reported in [`ConfigParams::target`] when the connection was not given one
+///
+/// See `ClientConnection::new_with_port` and `UnbufferedClientConnection::new_with_port`.
+pub const DEFAULT_TARGET_PORT: u16 = 443;
+
+/// The customizer hook a handshake failed in
+///
+/// Carried by [`Error::Customization`] so callers can tell which phase of ClientHello
+/// construction aborted the handshake.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookPhase {
+    /// [`ClientHelloCustomizer::on_config_resolve`]
+    ConfigResolve,
+    /// [`ClientHelloCustomizer::on_components_ready`]
+    ComponentsReady,
+    /// [`ClientHelloCustomizer::on_struct_ready`]
+    StructReady,
+    /// [`ClientHelloCustomizer::on_ech_inner_ready`]
+    EchInnerReady,
+    /// [`ClientHelloCustomizer::transform_wire_bytes`]
+    TransformWireBytes,
+    /// [`ClientHelloCustomizer::on_resumption_offer`]
+    ResumptionOffer,
+}
+
+impl HookPhase {
+    /// Attribute an error returned by this phase's hook to it
+    ///
+    /// Custls errors without a phase are tagged with this one, and catch-all
+    /// [`Error::General`] failures become [`CustlsError::HookError`]s. Other errors,
+    /// such as a failure to get random bytes, are returned unchanged.
+    ///
+    /// [`CustlsError::HookError`]: super::CustlsError::HookError
+    pub(crate) fn attribute(self, error: Error) -> Error {
+        match error {
+            Error::Customization { phase: None, error } => Error::Customization {
+                phase: Some(self),
+                error,
+            },
+            Error::General(msg) => Error::Customization {
+                phase: Some(self),
+                error: super::CustlsError::HookError(msg),
+            },
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for HookPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::ConfigResolve => "on_config_resolve",
+            Self::ComponentsReady => "on_components_ready",
+            Self::StructReady => "on_struct_ready",
+            Self::EchInnerReady => "on_ech_inner_ready",
+            Self::TransformWireBytes => "transform_wire_bytes",
+            Self::ResumptionOffer => "on_resumption_offer",
+        })
+    }
+}
+
 /// Trait for customizing ClientHello construction through multi-phase hooks
 ///
 /// This trait provides four distinct callback phases that intercept ClientHello
@@ -78,8 +149,18 @@ use crate::error::Error;
 /// 1. `on_config_resolve` - Before any ClientHello components are created
 /// 2. `on_components_ready` - After cipher suites and extensions are assembled
 /// 3. `on_struct_ready` - After complete ClientHelloPayload is constructed
+///    - `on_ech_inner_ready` - When offering ECH, after the inner hello is derived
+///      from the shaped outer hello and before it is encrypted
 /// 4. `transform_wire_bytes` - After ClientHelloPayload is marshaled to bytes
 ///
+/// ## HelloRetryRequest
+///
+/// The hooks shape the first ClientHello only. The hello answering a
+/// HelloRetryRequest replays it, outer and ECH inner alike, changing only what
+/// RFC 8446 Section 4.1.2 requires: the key shares, the cookie, early_data and the
+/// pre_shared_key offer. Browsers do the same, so a customizer cannot make the two
+/// hellos differ.
+///
 /// ## Error Propagation
 ///
 /// If any hook returns an error, the handshake is immediately aborted and the
@@ -89,7 +170,7 @@ use crate::error::Error;
 ///
 /// Implementations must be `Send + Sync` to allow sharing across threads.
 /// Use interior mutability (e.g., `Mutex`, `RwLock`) if mutable state is needed.
-pub trait ClientHelloCustomizer: Send + Sync + core::fmt::Debug {
+pub trait ClientHelloCustomizer: Send + Sync + fmt::Debug {
     /// Phase 1: Pre-build configuration hook
     ///
     /// This hook executes before ClientHelloPayload initialization, allowing
@@ -220,6 +301,38 @@ pub trait ClientHelloCustomizer: Send + Sync + core::fmt::Debug {
         Ok(())
     }
 
+    /// Phase 3 (ECH inner): Pre-encryption inner ClientHello modification hook
+    ///
+    /// When ECH is offered, `on_struct_ready` shapes the outer ClientHello
+    /// before the inner hello is derived from it and sealed. This hook then
+    /// receives the inner (protected) ClientHello, so that it can be shaped
+    /// independently of the outer one.
+    ///
+    /// # Use Cases
+    ///
+    /// - Apply the template extension order to the protected hello
+    /// - Remove outer-only extensions the browser would not send inside ECH
+    ///
+    /// # Parameters
+    ///
+    /// - `inner`: Mutable reference to the inner ClientHelloPayload
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` to continue with encryption
+    /// - `Err(error)` to abort the handshake with the given error
+    ///
+    /// # Important Constraints
+    ///
+    /// - Do not add a padding extension: the inner hello is padded as required
+    ///   by the ECH specification after this hook returns
+    /// - `encrypted_client_hello_outer` compression is recomputed after this hook,
+    ///   so that only extensions identical to the outer hello and contiguous in
+    ///   the final inner order are compressed
+    fn on_ech_inner_ready(&self, _inner: &mut crate::msgs::ClientHelloPayload) -> Result<(), Error> {
+        Ok(())
+    }
+
     /// Phase 4: Post-marshal byte transformation hook
     ///
     /// This hook accesses the final wire bytes after ClientHelloPayload has been
@@ -234,7 +347,8 @@ pub trait ClientHelloCustomizer: Send + Sync + core::fmt::Debug {
     ///
     /// # Parameters
     ///
-    /// - `bytes`: The marshaled ClientHello wire bytes
+    /// - `bytes`: The encoded ClientHello handshake message (type, length and
+    ///   body), without the record header
     ///
     /// # Returns
     ///
@@ -246,6 +360,10 @@ pub trait ClientHelloCustomizer: Send + Sync + core::fmt::Debug {
     /// - Modifications must maintain valid TLS wire format
     /// - Length fields must be updated if content is modified
     /// - Invalid modifications will cause handshake failures
+    /// - The returned bytes are sent and hashed into the transcript as they are;
+    ///   changing a hello that offers a PSK invalidates its binders
+    /// - Only the first ClientHello is passed here: a retry after a
+    ///   HelloRetryRequest is a replay of it and is sent as rustls encodes it
     ///
     /// # Example
     ///
@@ -268,12 +386,41 @@ pub trait ClientHelloCustomizer: Send + Sync + core::fmt::Debug {
     fn transform_wire_bytes(&self, bytes: Vec<u8>) -> Result<Vec<u8>, Error> {
         Ok(bytes)
     }
+
+    /// Resumption hook: decide whether a cached session may be offered
+    ///
+    /// Called after phase 1, once rustls has found a session for the
+    /// connection's [`ConfigParams::session_partition`], and before the
+    /// ClientHello is built.
+    ///
+    /// # Parameters
+    ///
+    /// - `session`: Identity of the cached session: the ticket, or the TLS 1.2
+    ///   session ID when there is no ticket
+    /// - `partition`: Session partition of the ClientHello being built
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(true)` to offer the session
+    /// - `Ok(false)` to discard it and perform a full handshake
+    /// - `Err(error)` to abort the handshake with the given error
+    fn on_resumption_offer(&self, _session: &[u8], _partition: Option<&[u8]>) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    /// Resumption hook: a session was handed to the session store
+    ///
+    /// Called for every TLS 1.3 ticket and every TLS 1.2 session the client
+    /// stores, with the same identity later passed to `on_resumption_offer`
+    /// and the partition it was stored under. This runs after the handshake,
+    /// possibly on a different thread from the one that built the ClientHello.
+    fn on_session_stored(&self, _session: &[u8], _partition: Option<&[u8]>) {}
 }
 
-/// Placeholder for configuration parameters passed to Phase 1 hook
+/// Configuration parameters passed to Phase 1 hook
 ///
-/// This structure will be expanded in future implementations to include
-/// actual configuration parameters that influence ClientHello construction.
+/// Fields left at their default value keep the behavior rustls would have
+/// without a customizer.
 ///
 /// # Future Fields
 ///
@@ -284,14 +431,91 @@ pub trait ClientHelloCustomizer: Send + Sync + core::fmt::Debug {
 /// - `feature_flags`: Feature enablement flags
 #[derive(Debug, Clone)]
 pub struct ConfigParams {
-    /// Placeholder field - will be expanded in future implementations
-    _placeholder: (),
+    /// Server the connection is for
+    ///
+    /// Filled in by rustls before phase 1. The host is the server name the
+    /// connection was created with. The port is the one given to
+    /// `ClientConnection::new_with_port`, or [`DEFAULT_TARGET_PORT`] for
+    /// connections created without one. `None` only when parameters are built by
+    /// hand, e.g. in tests.
+    pub target: Option<TargetKey>,
+
+    /// ALPN protocols the connection offers, filled in by rustls before phase 1
+    pub alpn_protocols: Vec<Vec<u8>>,
+
+    /// Whether the ClientHello is for a QUIC connection, filled in by rustls before phase 1
+    ///
+    /// QUIC hellos are TLS 1.3 only and carry transport parameters. Whatever the
+    /// hooks set, rustls drops TLS 1.2 cipher suites and extensions and the legacy
+    /// session ID from them after phase 3.
+    pub quic: bool,
+
+    /// Fresh randomness from the crypto provider's `SecureRandom`
+    ///
+    /// Filled in by rustls before phase 1, for choices that must be unpredictable,
+    /// such as weighted template rotation.
+    pub entropy: Option<u64>,
+
+    /// Groups to send key shares for in the initial ClientHello, in order
+    ///
+    /// When set, exactly these groups get a key share, hybrid groups included.
+    /// Groups the crypto provider does not support for TLS 1.3 are skipped. A
+    /// group that is the classical component of an earlier hybrid group reuses
+    /// that hybrid's component share rather than starting a new key exchange.
+    ///
+    /// `None` keeps rustls' default: one share for the preferred group, plus the
+    /// classical component share if that group is hybrid.
+    pub key_share_groups: Option<Vec<NamedGroup>>,
+
+    /// HTTP/2 profile matching the ClientHello being built
+    ///
+    /// Recorded on the connection from the first ClientHello and reported by
+    /// `ClientConnection::http2_profile` once `h2` has been negotiated.
+    pub http2_profile: Option<Http2Profile>,
+
+    /// Delay to wait before sending the first flight
+    ///
+    /// Hooks must not sleep. A customizer wanting timing jitter reports the delay
+    /// here instead; the connection exposes it through `wants_write_after()` until
+    /// the server's first handshake message arrives, and the caller waits (or
+    /// awaits) before writing. Only the value from the first ClientHello is used.
+    pub send_delay: Option<Duration>,
+
+    /// Opaque identity of the fingerprint the ClientHello presents
+    ///
+    /// The client session store is partitioned by this value: sessions are
+    /// stored under the partition of the handshake that created them and only
+    /// looked up for connections in the same partition, so a ticket obtained
+    /// under one identity is never offered under another. `None` shares the
+    /// partition of connections without a customizer.
+    ///
+    /// Only the value from the first ClientHello is used; it is not changed by a
+    /// HelloRetryRequest.
+    pub session_partition: Option<Vec<u8>>,
+
+    /// Session and compatibility behavior of the handshake
+    ///
+    /// Controls the legacy session ID, PSK key exchange modes, early data, the
+    /// empty TLS 1.2 `session_ticket` extension, `extended_master_secret` and the
+    /// middlebox compatibility ChangeCipherSpec. `None` keeps rustls' behavior.
+    /// Only the value from the first ClientHello is used.
+    pub handshake_behavior: Option<HandshakeBehavior>,
 }
 
 impl ConfigParams {
     /// Create a new ConfigParams with default values
     pub fn new() -> Self {
-        Self { _placeholder: () }
+        Self {
+            target: None,
+            alpn_protocols: Vec::new(),
+            quic: false,
+            entropy: None,
+            key_share_groups: None,
+            http2_profile: None,
+            send_delay: None,
+            session_partition: None,
+            handshake_behavior: None,
+        }
     }
 }
 
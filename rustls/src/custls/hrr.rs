@@ -0,0 +1,173 @@
+//! ClientHello replay after a HelloRetryRequest
+//!
+//! A browser answers a HelloRetryRequest with the hello it already sent, changed
+//! only where RFC 8446 Section 4.1.2 requires. A customizer that ran again for the
+//! retry could pick another template, shuffle the extensions or cipher suites again
+//! or draw new GREASE values, which no browser does and which a server can see by
+//! comparing the two hellos. rustls therefore shapes a customized hello only once,
+//! and builds the retry by replaying it.
+
+use crate::custls::extensions::PaddingExtension;
+use crate::msgs::{ClientHelloPayload, Codec, ExtensionType};
+
+/// Builds the ClientHello answering a HelloRetryRequest from the first one
+///
+/// `first` is the hello as the customizer shaped it. `retry` is the hello rustls
+/// built for the retry, without running the hooks again. The result is `first` with
+/// only the changes RFC 8446 Section 4.1.2 permits taken from `retry`:
+///
+/// - the key shares, now for the group the server asked for
+/// - the cookie the server sent, right after supported_versions as Chrome and
+///   Firefox send it
+/// - early_data removed
+/// - the updated pre_shared_key offer, whose binders are filled in afterwards
+///
+/// The encrypted_client_hello extension is taken from `retry` as well: rustls
+/// carries a GREASE or rejected offer forward itself, and rebuilds a real one.
+///
+/// A padding extension is resized so the retry has the encoded length of the
+/// first hello, as far as padding can make up for the changes above. Extensions
+/// added after shaping, such as a real ECH offer, are not counted.
+pub(crate) fn replay_for_retry(
+    first: &ClientHelloPayload,
+    mut retry: ClientHelloPayload,
+) -> ClientHelloPayload {
+    let mut hello = first.clone();
+    hello.key_shares = retry.key_shares.take();
+    hello.cookie = retry.cookie.take();
+    hello.early_data_request = None;
+    hello.preshared_key_offer = retry.preshared_key_offer.take();
+    hello.encrypted_client_hello = retry.encrypted_client_hello.take();
+
+    if hello.cookie.is_some() && !hello.contiguous_extensions.is_empty() {
+        let order = &mut hello.contiguous_extensions;
+        let position = match order
+            .iter()
+            .position(|ext| *ext == ExtensionType::SupportedVersions)
+        {
+            Some(versions) => versions + 1,
+            None => order
+                .iter()
+                .position(|ext| *ext == ExtensionType::Padding)
+                .unwrap_or(order.len()),
+        };
+        order.insert(position, ExtensionType::Cookie);
+    }
+
+    if let Some(padding) = &hello.padding {
+        let target = first.get_encoding().len();
+        let current = hello.get_encoding().len();
+        let length = (usize::from(padding.length) + target).saturating_sub(current);
+        hello.padding = Some(PaddingExtension::new(
+            u16::try_from(length).unwrap_or(u16::MAX),
+        ));
+    }
+    hello
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::crypto::CipherSuite;
+    use crate::crypto::kx::NamedGroup;
+    use crate::enums::ProtocolVersion;
+    use crate::msgs::{
+        ClientExtensions, Compression, KeyShareEntry, Random, SessionId, SupportedProtocolVersions,
+    };
+
+    fn hello(cipher_suites: Vec<CipherSuite>, order: Vec<ExtensionType>) -> ClientHelloPayload {
+        let mut hello = ClientHelloPayload {
+            client_version: ProtocolVersion::TLSv1_2,
+            random: Random([7u8; 32]),
+            session_id: SessionId::empty(),
+            cipher_suites,
+            compression_methods: vec![Compression::Null],
+            extensions: Box::new(ClientExtensions::default()),
+        };
+        hello.named_groups = Some(vec![NamedGroup::X25519, NamedGroup::secp384r1]);
+        hello.key_shares = Some(vec![KeyShareEntry::new(NamedGroup::X25519, &[1u8; 32][..])]);
+        hello.supported_versions = Some(SupportedProtocolVersions {
+            tls13: true,
+            tls12: false,
+        });
+        hello.early_data_request = Some(());
+        hello.padding = Some(PaddingExtension::new(200));
+        hello.contiguous_extensions = order;
+        hello
+    }
+
+    #[test]
+    fn test_replay_keeps_shape_of_first_hello() {
+        let first = hello(
+            vec![
+                CipherSuite::TLS13_AES_128_GCM_SHA256,
+                CipherSuite::TLS13_AES_256_GCM_SHA384,
+            ],
+            vec![
+                ExtensionType::SupportedVersions,
+                ExtensionType::KeyShare,
+                ExtensionType::EllipticCurves,
+                ExtensionType::EarlyData,
+                ExtensionType::Padding,
+            ],
+        );
+
+        // What a customizer running again might have produced
+        let mut retry = hello(
+            vec![
+                CipherSuite::TLS13_AES_256_GCM_SHA384,
+                CipherSuite::TLS13_AES_128_GCM_SHA256,
+            ],
+            vec![
+                ExtensionType::EllipticCurves,
+                ExtensionType::KeyShare,
+                ExtensionType::SupportedVersions,
+            ],
+        );
+        retry.key_shares = Some(vec![KeyShareEntry::new(
+            NamedGroup::secp384r1,
+            &[2u8; 97][..],
+        )]);
+        retry.cookie = Some(vec![1, 2, 3].into());
+        retry.early_data_request = None;
+        retry.order_seed = first.order_seed.wrapping_add(1);
+
+        let replayed = replay_for_retry(&first, retry.clone());
+        assert_eq!(replayed.cipher_suites, first.cipher_suites);
+        assert_eq!(
+            replayed.contiguous_extensions,
+            vec![
+                ExtensionType::SupportedVersions,
+                ExtensionType::Cookie,
+                ExtensionType::KeyShare,
+                ExtensionType::EllipticCurves,
+                ExtensionType::EarlyData,
+                ExtensionType::Padding,
+            ]
+        );
+        assert_eq!(replayed.order_seed, first.order_seed);
+        assert!(replayed.has_identical(&retry, ExtensionType::KeyShare));
+        assert!(replayed.has_identical(&retry, ExtensionType::Cookie));
+        assert!(replayed.early_data_request.is_none());
+        assert!(replayed.has_identical(&first, ExtensionType::EllipticCurves));
+
+        // The larger key share and the cookie come out of the padding
+        assert_eq!(replayed.get_encoding().len(), first.get_encoding().len());
+        let padding = replayed
+            .padding
+            .as_ref()
+            .unwrap()
+            .length;
+        assert!(padding < 200);
+
+        // Padding never goes below empty
+        let mut small = first.clone();
+        small.padding = Some(PaddingExtension::new(10));
+        let replayed = replay_for_retry(&small, retry);
+        assert_eq!(replayed.padding, Some(PaddingExtension::new(0)));
+    }
+}
@@ -7,10 +7,13 @@
 //!
 //! This module provides:
 //! - Multi-phase hook system for ClientHello customization
-//! - Browser simulation templates (Chrome, Firefox, Safari, Edge)
+//! - Browser simulation templates (Chrome, Firefox, Safari, Edge) and non-browser
+//!   clients (OkHttp, iOS URLSession, curl, Go)
 //! - Non-uniform randomization matching real browser behavior
 //! - Working fingerprint cache with reputation tracking
+//! - Per-host template routing rules
 //! - Missing TLS extension implementations
+//! - Inspection of the ClientHellos a connection actually sent
 //!
 //! ## Design Philosophy
 //!
@@ -29,13 +32,19 @@
 
 use alloc::boxed::Box;
 use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::fmt;
 
 use crate::error::Error as RustlsError;
+use crate::msgs::ExtensionType;
+use crate::time_provider::TimeProvider;
 
 /// Core error type for custls operations
-#[derive(Debug, Clone)]
+///
+/// Converts into [`Error::Customization`](crate::Error::Customization), which also
+/// records the hook it was returned from.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CustlsError {
     /// Error occurred during hook execution
     HookError(String),
@@ -73,9 +82,14 @@ impl fmt::Display for CustlsError {
 impl std::error::Error for CustlsError {}
 
 /// Convert CustlsError to rustls::Error for seamless integration
+///
+/// The phase is filled in when the error is returned from a hook.
 impl From<CustlsError> for RustlsError {
     fn from(e: CustlsError) -> Self {
-        RustlsError::General(alloc::format!("custls error: {}", e))
+        RustlsError::Customization {
+            phase: None,
+            error: e,
+        }
     }
 }
 
@@ -114,7 +128,18 @@ pub enum TemplateRotationPolicy {
     Random,
     
     /// Weighted random selection (prefer more common browsers)
+    ///
+    /// Weights come from `CustlsConfig::rotation_weights`; without them the
+    /// rotation templates are weighted by browser share (Chrome 40, Firefox 25,
+    /// Safari 20, Edge 15, others 10).
     WeightedRandom,
+    
+    /// Weighted random selection, sticky per target
+    ///
+    /// The first connection to a host and port draws a template as
+    /// `WeightedRandom` does; later connections to that target reuse it, so a
+    /// server never sees the browser change mid-session.
+    StickyWeightedRandom,
 }
 
 /// Browser template presets for fingerprint simulation
@@ -132,7 +157,19 @@ pub enum BrowserTemplate {
     /// Edge 130+ template
     Edge130,
     
+    /// Template registered by name in the template registry
+    ///
+    /// The built-in templates are registered under their constructor names, e.g.
+    /// `"safari_17_legacy"`, `"okhttp_4"`, `"ios_17"`, `"curl_8"` and `"go_1_22"`. Additional client
+    /// families can be added with [`templates::register_template`]. An unknown
+    /// name fails the connection in phase 1 with `CustlsError::TemplateError`.
+    Named(String),
+    
     /// Custom user-defined template
+    ///
+    /// A `CustomTemplate` does not carry template data yet, so a connection using
+    /// it fails in phase 1 with `CustlsError::TemplateError`. Register the data with
+    /// [`templates::register_template`] and use `Named` instead.
     Custom(Box<CustomTemplate>),
 }
 
@@ -164,14 +201,45 @@ pub struct CustlsConfig {
     /// Maximum cache size (number of entries)
     pub max_cache_size: usize,
     
+    /// Expiry, reputation decay and quarantine of cached fingerprints
+    pub cache_policy: CachePolicy,
+    
+    /// Wall clock for `cache_policy` (optional)
+    ///
+    /// Defaults to the system clock under `std`. Without one, entries do not
+    /// expire or fade, and quarantines last until the target is invalidated.
+    pub time_provider: Option<Arc<dyn TimeProvider>>,
+    
     /// Template rotation policy
     pub rotation_policy: TemplateRotationPolicy,
     
     /// Templates to rotate through (if rotation is enabled)
     pub rotation_templates: Vec<BrowserTemplate>,
     
+    /// Template weights for the weighted rotation policies
+    ///
+    /// When non-empty, weighted policies draw only from these templates, with
+    /// probability proportional to the weight. Custom and named templates can be
+    /// listed. Entries with a non-positive weight are ignored.
+    pub rotation_weights: Vec<(BrowserTemplate, f64)>,
+    
     /// Timing jitter configuration for anti-fingerprinting (optional)
     pub timing_jitter: Option<TimingJitterConfig>,
+    
+    /// Per-host routing rules, evaluated in order (first match wins)
+    ///
+    /// Connections no rule matches use `template` and `rotation_policy`.
+    pub routing_rules: Vec<RoutingRule>,
+    
+    /// Receives the customizer's decisions and recorded outcomes (optional)
+    pub event_sink: Option<Arc<dyn CustlsEventSink>>,
+    
+    /// Extensions taken out of every template
+    ///
+    /// They lose their place in the template's extension order, and
+    /// `EncryptedClientHello` stops the template's GREASE ECH. Real ECH configured
+    /// on the `ClientConfig` is not affected.
+    pub excluded_extensions: Vec<ExtensionType>,
 }
 
 impl Default for CustlsConfig {
@@ -181,9 +249,15 @@ impl Default for CustlsConfig {
             randomization_level: RandomizationLevel::Light,
             enable_cache: true,
             max_cache_size: 1000,
+            cache_policy: CachePolicy::default(),
+            time_provider: None,
             rotation_policy: TemplateRotationPolicy::None,
             rotation_templates: Vec::new(),
+            rotation_weights: Vec::new(),
             timing_jitter: None,
+            routing_rules: Vec::new(),
+            event_sink: None,
+            excluded_extensions: Vec::new(),
         }
     }
 }
@@ -202,9 +276,15 @@ pub struct CustlsConfigBuilder {
     randomization_level: RandomizationLevel,
     enable_cache: bool,
     max_cache_size: usize,
+    cache_policy: CachePolicy,
+    time_provider: Option<Arc<dyn TimeProvider>>,
     rotation_policy: TemplateRotationPolicy,
     rotation_templates: Vec<BrowserTemplate>,
+    rotation_weights: Vec<(BrowserTemplate, f64)>,
     timing_jitter: Option<TimingJitterConfig>,
+    routing_rules: Vec<RoutingRule>,
+    event_sink: Option<Arc<dyn CustlsEventSink>>,
+    excluded_extensions: Vec<ExtensionType>,
 }
 
 impl Default for CustlsConfigBuilder {
@@ -221,9 +301,15 @@ impl CustlsConfigBuilder {
             randomization_level: RandomizationLevel::Light,
             enable_cache: true,
             max_cache_size: 1000,
+            cache_policy: CachePolicy::default(),
+            time_provider: None,
             rotation_policy: TemplateRotationPolicy::None,
             rotation_templates: Vec::new(),
+            rotation_weights: Vec::new(),
             timing_jitter: None,
+            routing_rules: Vec::new(),
+            event_sink: None,
+            excluded_extensions: Vec::new(),
         }
     }
     
@@ -251,6 +337,18 @@ impl CustlsConfigBuilder {
         self
     }
     
+    /// Set the expiry, reputation decay and quarantine policy of the cache
+    pub fn with_cache_policy(mut self, policy: CachePolicy) -> Self {
+        self.cache_policy = policy;
+        self
+    }
+    
+    /// Set the wall clock the cache policy is timed by
+    pub fn with_time_provider(mut self, time_provider: Arc<dyn TimeProvider>) -> Self {
+        self.time_provider = Some(time_provider);
+        self
+    }
+    
     /// Set the timing jitter configuration
     pub fn with_timing_jitter(mut self, config: TimingJitterConfig) -> Self {
         self.timing_jitter = Some(config);
@@ -272,6 +370,36 @@ impl CustlsConfigBuilder {
         self
     }
     
+    /// Add a template with a weight for the weighted rotation policies
+    ///
+    /// Once any weight is set, only weighted templates are drawn.
+    pub fn with_rotation_weight(mut self, template: BrowserTemplate, weight: f64) -> Self {
+        self.rotation_weights.push((template, weight));
+        self
+    }
+    
+    /// Append a per-host routing rule
+    ///
+    /// Rules are evaluated in the order they were added; the first match wins.
+    pub fn with_routing_rule(mut self, rule: RoutingRule) -> Self {
+        self.routing_rules.push(rule);
+        self
+    }
+    
+    /// Set the sink receiving the customizer's events
+    ///
+    /// See the [`events`] module for what is reported.
+    pub fn with_event_sink(mut self, sink: Arc<dyn CustlsEventSink>) -> Self {
+        self.event_sink = Some(sink);
+        self
+    }
+    
+    /// Set the extensions taken out of every template
+    pub fn with_excluded_extensions(mut self, extensions: Vec<ExtensionType>) -> Self {
+        self.excluded_extensions = extensions;
+        self
+    }
+    
     /// Build the CustlsConfig
     pub fn build(self) -> CustlsConfig {
         CustlsConfig {
@@ -279,9 +407,15 @@ impl CustlsConfigBuilder {
             randomization_level: self.randomization_level,
             enable_cache: self.enable_cache,
             max_cache_size: self.max_cache_size,
+            cache_policy: self.cache_policy,
+            time_provider: self.time_provider,
             rotation_policy: self.rotation_policy,
             rotation_templates: self.rotation_templates,
+            rotation_weights: self.rotation_weights,
             timing_jitter: self.timing_jitter,
+            routing_rules: self.routing_rules,
+            event_sink: self.event_sink,
+            excluded_extensions: self.excluded_extensions,
         }
     }
 }
@@ -293,8 +427,17 @@ pub mod templates;
 pub mod randomizer;
 pub mod state;
 pub mod utils;
+pub mod routing;
+pub mod chain;
+pub mod profile;
 pub mod orchestrator;
 pub mod security;
+pub mod quic;
+pub mod capture;
+pub mod fingerprint;
+pub mod events;
+pub mod retry;
+pub(crate) mod hrr;
 
 #[cfg(test)]
 pub mod browser_validation;
@@ -303,7 +446,7 @@ pub mod browser_validation;
 pub mod real_server_tests;
 
 // Re-export key types from hooks module
-pub use hooks::{ClientHelloCustomizer, ConfigParams, ClientExtension};
+pub use hooks::{ClientHelloCustomizer, ConfigParams, ClientExtension, HookPhase};
 
 // Re-export extension types
 pub use extensions::{
@@ -317,11 +460,16 @@ pub use extensions::{
 
 // Re-export template types
 pub use templates::{
+    register_template,
+    lookup_template,
+    registered_template_names,
     TemplateData,
     GreasePattern,
     PaddingDistribution,
     NaturalnessFilter,
     ExtensionSet,
+    HandshakeBehavior,
+    QuicProfile,
 };
 
 // Re-export state types
@@ -330,22 +478,62 @@ pub use state::{
     TargetKey,
     ClientHelloConfig,
     FingerprintEntry,
+    CachePolicy,
+    Quarantine,
+    REPUTATION_BUCKETS,
 };
 
 // Re-export utility types
 pub use utils::{
     Http2Settings,
+    Http2Profile,
     PrioritySpec,
     TimingJitterConfig,
     sample_from_pmf,
     sample_with_power_of_2_bias,
     validate_extension_order,
+    akamai_fingerprint_of_preface,
     calculate_reputation_score,
 };
 
+// Re-export routing types
+pub use routing::{HostPattern, RouteAction, RoutingRule};
+
+// Re-export customizer chaining
+pub use chain::{CustomizerChain, LayerPolicy};
+
+// Re-export browser profile types
+pub use profile::{BrowserProfile, ProfileMismatch};
+
 // Re-export orchestrator types
 pub use orchestrator::DefaultCustomizer;
 
+// Re-export event and statistics types
+pub use events::{
+    CustlsEvent,
+    CustlsEventSink,
+    CustlsStats,
+    OutcomeCounts,
+    TemplateSource,
+    TemplateStats,
+};
+
+// Re-export retry planning types
+pub use retry::{
+    HelloRejection,
+    RetryAttempt,
+    RetryPlanner,
+    RetryStep,
+    DEFAULT_MAX_ATTEMPTS,
+    RISKY_EXTENSIONS,
+};
+
+// Re-export QUIC transport parameter types
+pub use quic::{QuicTransportParameters, TransportParameter};
+
+// Re-export ClientHello capture types
+pub use capture::{BrowserCapture, ClientHelloView, ExtensionInfo, SentClientHello};
+
 // Re-export security types
 pub use security::{
     validate_downgrade_protection,
@@ -407,6 +595,24 @@ mod config_tests {
         assert!(matches!(config.template, Some(BrowserTemplate::Chrome130)));
     }
     
+    #[test]
+    fn test_custls_config_builder_with_routing_rules() {
+        let config = CustlsConfig::builder()
+            .with_routing_rule(RoutingRule::new(
+                HostPattern::Exact("api.internal".to_string()),
+                RouteAction::Disabled,
+            ))
+            .with_routing_rule(RoutingRule::new(
+                HostPattern::Any,
+                RouteAction::Template(BrowserTemplate::Firefox135),
+            ))
+            .build();
+        
+        assert_eq!(config.routing_rules.len(), 2);
+        assert_eq!(config.routing_rules[0].action, RouteAction::Disabled);
+        assert!(CustlsConfig::default().routing_rules.is_empty());
+    }
+    
     #[test]
     fn test_custls_config_builder_with_randomization_level() {
         let config = CustlsConfig::builder()
@@ -434,6 +640,22 @@ mod config_tests {
         assert_eq!(config.max_cache_size, 500);
     }
     
+    #[test]
+    fn test_custls_config_builder_with_cache_policy() {
+        let policy = CachePolicy {
+            ttl: Some(core::time::Duration::from_secs(300)),
+            quarantine_after: Some(3),
+            ..CachePolicy::default()
+        };
+        let config = CustlsConfig::builder()
+            .with_cache_policy(policy)
+            .build();
+        
+        assert_eq!(config.cache_policy, policy);
+        assert!(config.time_provider.is_none());
+        assert_eq!(CustlsConfig::default().cache_policy, CachePolicy::default());
+    }
+    
     #[test]
     fn test_custls_config_builder_with_rotation_policy() {
         let config = CustlsConfig::builder()
@@ -457,6 +679,22 @@ mod config_tests {
         assert_eq!(config.rotation_templates.len(), 2);
     }
     
+    #[test]
+    fn test_custls_config_builder_with_rotation_weights() {
+        let config = CustlsConfig::builder()
+            .with_rotation_policy(TemplateRotationPolicy::StickyWeightedRandom)
+            .with_rotation_weight(BrowserTemplate::Chrome130, 70.0)
+            .with_rotation_weight(BrowserTemplate::Named("okhttp_4".to_string()), 30.0)
+            .build();
+        
+        assert_eq!(config.rotation_policy, TemplateRotationPolicy::StickyWeightedRandom);
+        assert_eq!(config.rotation_weights, vec![
+            (BrowserTemplate::Chrome130, 70.0),
+            (BrowserTemplate::Named("okhttp_4".to_string()), 30.0),
+        ]);
+        assert!(CustlsConfig::default().rotation_weights.is_empty());
+    }
+    
     #[test]
     fn test_custls_config_builder_chaining() {
         let config = CustlsConfig::builder()
@@ -539,11 +777,10 @@ mod config_tests {
         let rustls_error: RustlsError = custls_error.into();
         
         match rustls_error {
-            RustlsError::General(msg) => {
-                assert!(msg.contains("custls error"));
-                assert!(msg.contains("Hook error"));
+            RustlsError::Customization { phase: None, error } => {
+                assert_eq!(error, CustlsError::HookError("test".to_string()));
             }
-            _ => panic!("Expected General error"),
+            _ => panic!("Expected Customization error"),
         }
     }
 }
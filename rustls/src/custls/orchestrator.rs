@@ -14,9 +14,9 @@
 //!
 //! ## Hook Phases
 //!
-//! - **Phase 1 (on_config_resolve)**: Select template, query cache
+//! - **Phase 1 (on_config_resolve)**: Apply routing rules, select template, query cache
 //! - **Phase 2 (on_components_ready)**: Apply randomization, inject GREASE
-//! - **Phase 3 (on_struct_ready)**: Add padding extension
+//! - **Phase 3 (on_struct_ready)**: Add padding extension and GREASE ECH
 //! - **Phase 4 (transform_wire_bytes)**: Final byte-level adjustments (if needed)
 //!
 //! ## Usage
@@ -35,25 +35,38 @@
 //! // Use customizer with rustls ClientConfig
 //! ```
 
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::fmt;
 
+#[cfg(feature = "std")]
+use std::collections::HashMap;
 #[cfg(feature = "std")]
 use std::sync::Mutex;
+#[cfg(feature = "std")]
+use std::thread::ThreadId;
 
 use crate::error::Error as RustlsError;
 use crate::custls::{
     CustlsConfig, BrowserTemplate, RandomizationLevel, TemplateRotationPolicy,
-    ClientHelloCustomizer, ConfigParams, ClientExtension,
+    ClientHelloCustomizer, ConfigParams, ClientExtension, CustlsError,
 };
 use crate::crypto::CipherSuite;
-use crate::custls::templates::{TemplateData, NaturalnessFilter, chrome_130, firefox_135, safari_17, edge_130};
-use crate::custls::randomizer::BrowserRandomizer;
+use crate::msgs::ExtensionType;
+use crate::custls::routing::{route, sticky_choice, RouteAction};
+use crate::custls::utils::random_unit;
+use crate::custls::capture::is_grease_value;
+use crate::custls::templates::{TemplateData, NaturalnessFilter, chrome_130, firefox_135, safari_17, edge_130, lookup_template};
+use crate::custls::randomizer::{BrowserRandomizer, TRAILING_EXTENSIONS};
 use crate::custls::state::{FingerprintManager, TargetKey, ClientHelloConfig};
 use crate::custls::extensions::PaddingExtension;
+use crate::custls::quic::QuicTransportParameters;
+use crate::crypto::cipher::Payload;
 use crate::custls::security::{SessionStateTracker, SessionId};
+use crate::custls::events::{CustlsEvent, CustlsStats, StatsCollector, TemplateSource};
 
 /// Default implementation of ClientHelloCustomizer that orchestrates all custls components.
 ///
@@ -102,6 +115,143 @@ pub struct DefaultCustomizer {
     /// Session state tracker (no_std version)
     #[cfg(not(feature = "std"))]
     session_tracker: Option<core::cell::RefCell<SessionStateTracker>>,
+    
+    /// Template decision made in phase 1, per thread
+    ///
+    /// A ClientHello is built synchronously on one thread, so keying the decision
+    /// by thread keeps concurrent handshakes sharing this customizer apart. Phases 2
+    /// and 3 reuse it rather than selecting (and rotating) again.
+    #[cfg(feature = "std")]
+    routes: Mutex<HashMap<ThreadId, Route>>,
+    
+    /// Template decision made in phase 1 (no_std version)
+    #[cfg(not(feature = "std"))]
+    routes: core::cell::RefCell<Option<Route>>,
+    
+    /// Templates assigned per target by `StickyWeightedRandom`
+    #[cfg(feature = "std")]
+    sticky_assignments: Mutex<StickyAssignments>,
+    
+    /// Templates assigned per target (no_std version)
+    #[cfg(not(feature = "std"))]
+    sticky_assignments: core::cell::RefCell<StickyAssignments>,
+    
+    /// Fingerprint of each session partition handed out in phase 1
+    ///
+    /// Sessions are recorded in the session tracker with the fingerprint of the
+    /// partition they were stored under. Only filled when session tracking is on.
+    #[cfg(feature = "std")]
+    partition_fingerprints: Mutex<BTreeMap<Vec<u8>, ClientHelloConfig>>,
+    
+    /// Fingerprint of each session partition (no_std version)
+    #[cfg(not(feature = "std"))]
+    partition_fingerprints: core::cell::RefCell<BTreeMap<Vec<u8>, ClientHelloConfig>>,
+    
+    /// Decisions for the ClientHello being built, per thread, reported once it is shaped
+    #[cfg(feature = "std")]
+    pending_hellos: Mutex<HashMap<ThreadId, PendingHello>>,
+    
+    /// Decisions for the ClientHello being built (no_std version)
+    #[cfg(not(feature = "std"))]
+    pending_hellos: core::cell::RefCell<Option<PendingHello>>,
+    
+    /// Aggregate of the events reported so far
+    #[cfg(feature = "std")]
+    stats: Mutex<StatsCollector>,
+    
+    /// Aggregate of the events reported so far (no_std version)
+    #[cfg(not(feature = "std"))]
+    stats: core::cell::RefCell<StatsCollector>,
+}
+
+/// Decisions made in phase 1, reported with the shaped ClientHello
+#[derive(Debug)]
+struct PendingHello {
+    target: Option<TargetKey>,
+    template: String,
+    /// Extension order phase 3 gave the outer hello, which the ECH inner hello follows
+    outer_order: Option<Vec<ExtensionType>>,
+}
+
+/// Template assignments of the sticky rotation policy, oldest evicted first
+#[derive(Debug, Default)]
+struct StickyAssignments {
+    by_target: BTreeMap<TargetKey, BrowserTemplate>,
+    order: VecDeque<TargetKey>,
+}
+
+impl StickyAssignments {
+    fn get(&self, target: &TargetKey) -> Option<&BrowserTemplate> {
+        self.by_target.get(target)
+    }
+    
+    fn insert(&mut self, target: TargetKey, template: BrowserTemplate, capacity: usize) {
+        while self.order.len() >= capacity.max(1) {
+            if let Some(oldest) = self.order.pop_front() {
+                self.by_target.remove(&oldest);
+            }
+        }
+        self.order.push_back(target.clone());
+        self.by_target.insert(target, template);
+    }
+}
+
+/// Templates `config` rotates through: its `rotation_templates`, or the four
+/// browsers if there are none.
+pub(crate) fn rotation_templates(config: &CustlsConfig) -> Vec<BrowserTemplate> {
+    if config.rotation_templates.is_empty() {
+        vec![
+            BrowserTemplate::Chrome130,
+            BrowserTemplate::Firefox135,
+            BrowserTemplate::Safari17,
+            BrowserTemplate::Edge130,
+        ]
+    } else {
+        config.rotation_templates.clone()
+    }
+}
+
+/// Default weight of a template in weighted rotation
+///
+/// Roughly desktop browser market share; other templates get a small weight.
+fn default_rotation_weight(template: &BrowserTemplate) -> f64 {
+    match template {
+        BrowserTemplate::Chrome130 => 40.0,
+        BrowserTemplate::Firefox135 => 25.0,
+        BrowserTemplate::Safari17 => 20.0,
+        BrowserTemplate::Edge130 => 15.0,
+        _ => 10.0,
+    }
+}
+
+/// Pick from `weights` with probability proportional to weight, given `unit` in [0, 1)
+fn weighted_choice(weights: &[(BrowserTemplate, f64)], unit: f64) -> Option<BrowserTemplate> {
+    let total: f64 = weights.iter().map(|(_, weight)| weight).sum();
+    let mut roll = unit * total;
+    for (template, weight) in weights {
+        if roll < *weight {
+            return Some(template.clone());
+        }
+        roll -= weight;
+    }
+    
+    // Floating point rounding can leave a sliver past the last weight
+    weights.last().map(|(template, _)| template.clone())
+}
+
+/// Map 64 bits of entropy to a uniform value in [0, 1)
+fn unit_from_entropy(entropy: u64) -> f64 {
+    (entropy >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Template decision for the ClientHello being built
+#[derive(Debug, Clone)]
+enum Route {
+    /// Shape the ClientHello with this template
+    Template(BrowserTemplate, TemplateData),
+    
+    /// Leave the ClientHello to rustls
+    Disabled,
 }
 
 impl DefaultCustomizer {
@@ -127,7 +277,8 @@ impl DefaultCustomizer {
     /// ```
     pub fn new(config: CustlsConfig) -> Self {
         // Resolve template from config
-        let template = config.template.as_ref().map(|t| Self::resolve_template(t));
+        // An unregistered name is left unresolved here and reported in phase 1
+        let template = config.template.as_ref().and_then(|t| Self::resolve_template(t).ok());
         
         // Create naturalness filter (for now, use default)
         let naturalness_filter = NaturalnessFilter::default();
@@ -140,7 +291,11 @@ impl DefaultCustomizer {
         
         // Create cache if enabled
         let cache = if config.enable_cache {
-            Some(FingerprintManager::new(config.max_cache_size))
+            let manager = FingerprintManager::with_policy(config.max_cache_size, config.cache_policy);
+            Some(match &config.time_provider {
+                Some(time_provider) => manager.with_time_provider(time_provider.clone()),
+                None => manager,
+            })
         } else {
             None
         };
@@ -172,6 +327,26 @@ impl DefaultCustomizer {
             session_tracker: session_tracker.map(Mutex::new),
             #[cfg(not(feature = "std"))]
             session_tracker: session_tracker.map(core::cell::RefCell::new),
+            #[cfg(feature = "std")]
+            routes: Mutex::new(HashMap::new()),
+            #[cfg(not(feature = "std"))]
+            routes: core::cell::RefCell::new(None),
+            #[cfg(feature = "std")]
+            sticky_assignments: Mutex::new(StickyAssignments::default()),
+            #[cfg(not(feature = "std"))]
+            sticky_assignments: core::cell::RefCell::new(StickyAssignments::default()),
+            #[cfg(feature = "std")]
+            partition_fingerprints: Mutex::new(BTreeMap::new()),
+            #[cfg(not(feature = "std"))]
+            partition_fingerprints: core::cell::RefCell::new(BTreeMap::new()),
+            #[cfg(feature = "std")]
+            pending_hellos: Mutex::new(HashMap::new()),
+            #[cfg(not(feature = "std"))]
+            pending_hellos: core::cell::RefCell::new(None),
+            #[cfg(feature = "std")]
+            stats: Mutex::new(StatsCollector::default()),
+            #[cfg(not(feature = "std"))]
+            stats: core::cell::RefCell::new(StatsCollector::default()),
         }
     }
     
@@ -206,19 +381,274 @@ impl DefaultCustomizer {
     ///
     /// # Returns
     ///
-    /// The corresponding `TemplateData` structure.
-    fn resolve_template(template: &BrowserTemplate) -> TemplateData {
-        match template {
+    /// The corresponding `TemplateData` structure, or `CustlsError::TemplateError`
+    /// for a `BrowserTemplate::Named` that is not in the template registry and for
+    /// a `BrowserTemplate::Custom`, which carries no template data yet.
+    pub(crate) fn resolve_template(template: &BrowserTemplate) -> Result<TemplateData, RustlsError> {
+        Ok(match template {
             BrowserTemplate::Chrome130 => chrome_130(),
             BrowserTemplate::Firefox135 => firefox_135(),
             BrowserTemplate::Safari17 => safari_17(),
             BrowserTemplate::Edge130 => edge_130(),
-            BrowserTemplate::Custom(_custom) => {
-                // TODO: Once CustomTemplate has full implementation, convert it to TemplateData
-                // For now, fall back to Chrome
-                chrome_130()
+            BrowserTemplate::Named(name) => lookup_template(name).ok_or_else(|| {
+                CustlsError::TemplateError(alloc::format!("no template registered as {name:?}"))
+            })?,
+            BrowserTemplate::Custom(custom) => {
+                return Err(CustlsError::TemplateError(alloc::format!(
+                    "custom template {:?} has no template data; register it and use BrowserTemplate::Named",
+                    custom.name
+                ))
+                .into());
+            }
+        })
+    }
+    
+    /// HTTP/3 variant of `template`, if one is registered.
+    ///
+    /// The variant of a template registered as `name` is registered as
+    /// `name_quic`, so `BrowserTemplate::Chrome130` maps to `chrome_130_quic`.
+    fn quic_template_for(template: &BrowserTemplate) -> Option<TemplateData> {
+        let name = match template {
+            BrowserTemplate::Chrome130 => "chrome_130",
+            BrowserTemplate::Firefox135 => "firefox_135",
+            BrowserTemplate::Safari17 => "safari_17",
+            BrowserTemplate::Edge130 => "edge_130",
+            BrowserTemplate::Named(name) => name.as_str(),
+            BrowserTemplate::Custom(_) => return None,
+        };
+        lookup_template(&alloc::format!("{name}_quic"))
+    }
+    
+    /// Evaluate the routing rules for the connection described by `params`.
+    ///
+    /// Returns `None` when there is no target or no rule matches, in which case the
+    /// configured template and rotation policy apply.
+    fn route_for(&self, params: &ConfigParams) -> Result<Option<Route>, RustlsError> {
+        let Some(target) = params.target.as_ref() else {
+            return Ok(None);
+        };
+        let Some(action) = route(&self.config.routing_rules, target, &params.alpn_protocols) else {
+            return Ok(None);
+        };
+        
+        let template = match action {
+            RouteAction::Disabled => return Ok(Some(Route::Disabled)),
+            RouteAction::Template(template) => Some(template),
+            RouteAction::Rotate(templates) => {
+                sticky_choice(templates, target).or(self.config.template.as_ref())
+            }
+        };
+        
+        Ok(Some(match template {
+            Some(template) => Route::Template(template.clone(), Self::resolve_template(template)?),
+            None => Route::Disabled,
+        }))
+    }
+    
+    /// Record the routing decision for the ClientHello being built on this thread.
+    #[cfg(feature = "std")]
+    fn set_route(&self, route: Option<Route>) -> Result<(), RustlsError> {
+        let mut routes = self.routes.lock()
+            .map_err(|e| RustlsError::General(alloc::format!("Failed to lock routes: {}", e)))?;
+        let thread = std::thread::current().id();
+        match route {
+            Some(route) => routes.insert(thread, route),
+            None => routes.remove(&thread),
+        };
+        Ok(())
+    }
+    
+    /// Record the routing decision for the ClientHello being built (no_std version).
+    #[cfg(not(feature = "std"))]
+    fn set_route(&self, route: Option<Route>) -> Result<(), RustlsError> {
+        *self.routes.borrow_mut() = route;
+        Ok(())
+    }
+    
+    /// Routing decision for the ClientHello being built on this thread, if any.
+    #[cfg(feature = "std")]
+    fn current_route(&self) -> Result<Option<Route>, RustlsError> {
+        let routes = self.routes.lock()
+            .map_err(|e| RustlsError::General(alloc::format!("Failed to lock routes: {}", e)))?;
+        Ok(routes.get(&std::thread::current().id()).cloned())
+    }
+    
+    /// Routing decision for the ClientHello being built (no_std version).
+    #[cfg(not(feature = "std"))]
+    fn current_route(&self) -> Result<Option<Route>, RustlsError> {
+        Ok(self.routes.borrow().clone())
+    }
+    
+    /// Run `f` on the pending decisions for the ClientHello being built on this thread.
+    #[cfg(feature = "std")]
+    fn with_pending_hello<R>(
+        &self,
+        f: impl FnOnce(&mut Option<PendingHello>) -> R,
+    ) -> Result<R, RustlsError> {
+        let mut hellos = self.pending_hellos.lock()
+            .map_err(|e| RustlsError::General(alloc::format!("Failed to lock pending hellos: {}", e)))?;
+        let thread = std::thread::current().id();
+        let mut pending = hellos.remove(&thread);
+        let result = f(&mut pending);
+        if let Some(pending) = pending {
+            hellos.insert(thread, pending);
+        }
+        Ok(result)
+    }
+    
+    /// Run `f` on the pending decisions for the ClientHello being built (no_std version).
+    #[cfg(not(feature = "std"))]
+    fn with_pending_hello<R>(
+        &self,
+        f: impl FnOnce(&mut Option<PendingHello>) -> R,
+    ) -> Result<R, RustlsError> {
+        Ok(f(&mut self.pending_hellos.borrow_mut()))
+    }
+    
+    /// Look `target` up in the fingerprint cache; `None` when the cache is disabled.
+    fn cache_lookup(&self, target: &TargetKey) -> Result<Option<bool>, RustlsError> {
+        let Some(cache) = &self.cache else {
+            return Ok(None);
+        };
+        
+        #[cfg(feature = "std")]
+        let mut cache = cache.lock()
+            .map_err(|e| RustlsError::General(alloc::format!("Failed to lock cache: {}", e)))?;
+        
+        #[cfg(not(feature = "std"))]
+        let mut cache = cache.borrow_mut();
+        
+        Ok(Some(cache.get_working_fingerprint(target).is_some()))
+    }
+    
+    /// Template the cache policy has quarantined for `target`, if any.
+    fn quarantined_template(&self, target: &TargetKey) -> Result<Option<BrowserTemplate>, RustlsError> {
+        let Some(cache) = &self.cache else {
+            return Ok(None);
+        };
+        
+        #[cfg(feature = "std")]
+        let cache = cache.lock()
+            .map_err(|e| RustlsError::General(alloc::format!("Failed to lock cache: {}", e)))?;
+        
+        #[cfg(not(feature = "std"))]
+        let cache = cache.borrow();
+        
+        Ok(cache.quarantined_template(target).cloned())
+    }
+    
+    /// Template to use while `quarantined` is quarantined: the rotation template
+    /// following it, or `None` if there is no other.
+    fn rotate_from(&self, quarantined: &BrowserTemplate) -> Option<BrowserTemplate> {
+        let templates = self.rotation_templates();
+        let start = templates
+            .iter()
+            .position(|template| template == quarantined)
+            .map_or(0, |index| index + 1);
+        templates
+            .iter()
+            .cycle()
+            .skip(start)
+            .take(templates.len())
+            .find(|template| *template != quarantined)
+            .cloned()
+    }
+    
+    /// Aggregate `event` and pass it to the configured event sink.
+    ///
+    /// Must not be called with any of our locks held, since the sink may call back
+    /// into the customizer.
+    fn emit(&self, event: CustlsEvent) {
+        #[cfg(feature = "std")]
+        {
+            if let Ok(mut stats) = self.stats.lock() {
+                stats.record(&event, self.config.max_cache_size);
             }
         }
+        
+        #[cfg(not(feature = "std"))]
+        {
+            self.stats.borrow_mut().record(&event, self.config.max_cache_size);
+        }
+        
+        if let Some(sink) = &self.config.event_sink {
+            sink.on_event(&event);
+        }
+    }
+    
+    /// Name a template is reported under in events and statistics.
+    fn template_name(template: &BrowserTemplate) -> String {
+        match template {
+            BrowserTemplate::Custom(custom) => custom.name.clone(),
+            // An unregistered name is reported as given; phase 1 fails for it
+            BrowserTemplate::Named(name) => lookup_template(name).map_or_else(|| name.clone(), |t| t.name),
+            other => Self::resolve_template(other).map(|t| t.name).unwrap_or_default(),
+        }
+    }
+    
+    /// Snapshot of the events aggregated so far.
+    ///
+    /// The reputation histogram is taken from the fingerprint cache at the time of
+    /// the call. See the [`events`](crate::custls::events) module for what is counted.
+    #[cfg(feature = "std")]
+    pub fn stats(&self) -> CustlsStats {
+        let histogram = self.cache
+            .as_ref()
+            .and_then(|cache| cache.lock().ok())
+            .map(|cache| cache.reputation_histogram())
+            .unwrap_or_default();
+        self.stats
+            .lock()
+            .map(|stats| stats.snapshot(histogram))
+            .unwrap_or_default()
+    }
+    
+    /// Snapshot of the events aggregated so far (no_std version).
+    #[cfg(not(feature = "std"))]
+    pub fn stats(&self) -> CustlsStats {
+        let histogram = self.cache
+            .as_ref()
+            .map(|cache| cache.borrow().reputation_histogram())
+            .unwrap_or_default();
+        self.stats.borrow().snapshot(histogram)
+    }
+    
+    /// Template to shape the ClientHello being built, or `None` to leave it alone.
+    ///
+    /// A routing decision from phase 1 takes precedence; otherwise the template is
+    /// selected from the configured template and rotation policy.
+    fn template_for_hello(&self) -> Result<Option<TemplateData>, RustlsError> {
+        match self.current_route()? {
+            Some(Route::Template(_, template)) => return Ok(Some(self.without_excluded(template))),
+            Some(Route::Disabled) => return Ok(None),
+            None => {}
+        }
+        
+        // Select template (either fixed or rotated)
+        let template_enum = if self.config.rotation_policy != TemplateRotationPolicy::None {
+            self.select_rotated_template()
+        } else {
+            self.config.template.clone()
+        };
+        
+        // If no template was selected, use the pre-resolved one from construction
+        Ok(match template_enum {
+            Some(t) => Some(Self::resolve_template(&t)?),
+            None => self.template.clone(),
+        }
+        .map(|template| self.without_excluded(template)))
+    }
+    
+    /// `template` without the configured `excluded_extensions`.
+    fn without_excluded(&self, mut template: TemplateData) -> TemplateData {
+        let excluded = &self.config.excluded_extensions;
+        template
+            .extension_order
+            .retain(|ext| !excluded.contains(ext));
+        if excluded.contains(&ExtensionType::EncryptedClientHello) {
+            template.ech_grease = None;
+        }
+        template
     }
     
     /// Get the current configuration.
@@ -231,6 +661,93 @@ impl DefaultCustomizer {
         self.template.as_ref()
     }
     
+    /// Templates to rotate through (defaults to the four browsers if not specified).
+    fn rotation_templates(&self) -> Vec<BrowserTemplate> {
+        rotation_templates(&self.config)
+    }
+    
+    /// Distribution the weighted rotation policies draw from.
+    ///
+    /// Configured `rotation_weights` are used as given (non-positive weights drop
+    /// the template). Without them, each rotation template gets its default weight.
+    fn rotation_weights(&self) -> Vec<(BrowserTemplate, f64)> {
+        if !self.config.rotation_weights.is_empty() {
+            return self.config.rotation_weights
+                .iter()
+                .filter(|(_, weight)| weight.is_finite() && *weight > 0.0)
+                .cloned()
+                .collect();
+        }
+        
+        self.rotation_templates()
+            .into_iter()
+            .map(|template| {
+                let weight = default_rotation_weight(&template);
+                (template, weight)
+            })
+            .collect()
+    }
+    
+    /// Select the template for a new connection in phase 1.
+    ///
+    /// Weighted policies draw from the provider's entropy in `params`, falling back
+    /// to the internal generator when there is none. The sticky policy reuses the
+    /// template already assigned to the target.
+    fn select_template_for(
+        &self,
+        params: &ConfigParams,
+    ) -> Result<Option<(BrowserTemplate, TemplateData)>, RustlsError> {
+        let configured = || {
+            self.config.template
+                .as_ref()
+                .map(|t| Self::resolve_template(t).map(|data| (t.clone(), data)))
+                .transpose()
+        };
+        let policy = self.config.rotation_policy;
+        if policy == TemplateRotationPolicy::None {
+            return configured();
+        }
+        
+        let draw = || match (policy, params.entropy) {
+            (
+                TemplateRotationPolicy::WeightedRandom | TemplateRotationPolicy::StickyWeightedRandom,
+                Some(entropy),
+            ) => weighted_choice(&self.rotation_weights(), unit_from_entropy(entropy)),
+            _ => self.select_rotated_template(),
+        };
+        
+        let selected = match (policy, &params.target) {
+            (TemplateRotationPolicy::StickyWeightedRandom, Some(target)) => {
+                #[cfg(feature = "std")]
+                let mut assignments = self.sticky_assignments.lock()
+                    .map_err(|e| RustlsError::General(alloc::format!("Failed to lock sticky assignments: {}", e)))?;
+                
+                #[cfg(not(feature = "std"))]
+                let mut assignments = self.sticky_assignments.borrow_mut();
+                
+                match assignments.get(target) {
+                    Some(template) => Some(template.clone()),
+                    None => {
+                        let template = draw();
+                        if let Some(template) = &template {
+                            assignments.insert(target.clone(), template.clone(), self.config.max_cache_size);
+                        }
+                        template
+                    }
+                }
+            }
+            _ => draw(),
+        };
+        
+        match selected {
+            Some(t) => {
+                let data = Self::resolve_template(&t)?;
+                Ok(Some((t, data)))
+            }
+            None => configured(),
+        }
+    }
+    
     /// Select the next template based on rotation policy.
     ///
     /// This method is called internally to select a template when rotation is enabled.
@@ -246,17 +763,7 @@ impl DefaultCustomizer {
             return self.config.template.clone();
         }
         
-        // Get rotation templates (use defaults if not specified)
-        let templates = if self.config.rotation_templates.is_empty() {
-            vec![
-                BrowserTemplate::Chrome130,
-                BrowserTemplate::Firefox135,
-                BrowserTemplate::Safari17,
-                BrowserTemplate::Edge130,
-            ]
-        } else {
-            self.config.rotation_templates.clone()
-        };
+        let templates = self.rotation_templates();
         
         if templates.is_empty() {
             return None;
@@ -285,30 +792,8 @@ impl DefaultCustomizer {
                 Some(templates[index].clone())
             }
             
-            TemplateRotationPolicy::WeightedRandom => {
-                // Weighted selection: Chrome 40%, Firefox 25%, Safari 20%, Edge 15%
-                // This is a simplified implementation
-                let weight = (counter * 2654435761) % 100;
-                
-                let index = if weight < 40 {
-                    // Chrome (40%)
-                    templates.iter().position(|t| matches!(t, BrowserTemplate::Chrome130))
-                        .unwrap_or(0)
-                } else if weight < 65 {
-                    // Firefox (25%)
-                    templates.iter().position(|t| matches!(t, BrowserTemplate::Firefox135))
-                        .unwrap_or(1 % templates.len())
-                } else if weight < 85 {
-                    // Safari (20%)
-                    templates.iter().position(|t| matches!(t, BrowserTemplate::Safari17))
-                        .unwrap_or(2 % templates.len())
-                } else {
-                    // Edge (15%)
-                    templates.iter().position(|t| matches!(t, BrowserTemplate::Edge130))
-                        .unwrap_or(3 % templates.len())
-                };
-                
-                Some(templates[index].clone())
+            TemplateRotationPolicy::WeightedRandom | TemplateRotationPolicy::StickyWeightedRandom => {
+                weighted_choice(&self.rotation_weights(), random_unit())
             }
         }
     }
@@ -321,17 +806,7 @@ impl DefaultCustomizer {
             return self.config.template.clone();
         }
         
-        // Get rotation templates (use defaults if not specified)
-        let templates = if self.config.rotation_templates.is_empty() {
-            vec![
-                BrowserTemplate::Chrome130,
-                BrowserTemplate::Firefox135,
-                BrowserTemplate::Safari17,
-                BrowserTemplate::Edge130,
-            ]
-        } else {
-            self.config.rotation_templates.clone()
-        };
+        let templates = self.rotation_templates();
         
         if templates.is_empty() {
             return None;
@@ -359,24 +834,8 @@ impl DefaultCustomizer {
                 Some(templates[index].clone())
             }
             
-            TemplateRotationPolicy::WeightedRandom => {
-                let weight = (counter * 2654435761) % 100;
-                
-                let index = if weight < 40 {
-                    templates.iter().position(|t| matches!(t, BrowserTemplate::Chrome130))
-                        .unwrap_or(0)
-                } else if weight < 65 {
-                    templates.iter().position(|t| matches!(t, BrowserTemplate::Firefox135))
-                        .unwrap_or(1 % templates.len())
-                } else if weight < 85 {
-                    templates.iter().position(|t| matches!(t, BrowserTemplate::Safari17))
-                        .unwrap_or(2 % templates.len())
-                } else {
-                    templates.iter().position(|t| matches!(t, BrowserTemplate::Edge130))
-                        .unwrap_or(3 % templates.len())
-                };
-                
-                Some(templates[index].clone())
+            TemplateRotationPolicy::WeightedRandom | TemplateRotationPolicy::StickyWeightedRandom => {
+                weighted_choice(&self.rotation_weights(), random_unit())
             }
         }
     }
@@ -384,7 +843,8 @@ impl DefaultCustomizer {
     /// Record a handshake result in the cache.
     ///
     /// This method should be called after a handshake completes to update
-    /// the cache with success/failure information.
+    /// the cache with success/failure information. The result is also reported
+    /// as a [`CustlsEvent::HandshakeOutcome`] and counted in [`Self::stats`].
     ///
     /// # Arguments
     ///
@@ -405,11 +865,30 @@ impl DefaultCustomizer {
         config: ClientHelloConfig,
         success: bool,
     ) {
+        let template = Self::template_name(&config.template);
+        let mut quarantined = None;
         if let Some(cache) = &self.cache {
             if let Ok(mut cache_guard) = cache.lock() {
+                let before = cache_guard.quarantined_template(target).cloned();
                 cache_guard.record_result(target, config, success);
+                quarantined = cache_guard
+                    .quarantined_template(target)
+                    .filter(|after| before.as_ref() != Some(*after))
+                    .map(Self::template_name);
             }
         }
+        
+        self.emit(CustlsEvent::HandshakeOutcome {
+            target: target.clone(),
+            template,
+            success,
+        });
+        if let Some(template) = quarantined {
+            self.emit(CustlsEvent::TargetQuarantined {
+                target: target.clone(),
+                template,
+            });
+        }
     }
     
     /// Record a handshake result in the cache (no_std version).
@@ -420,8 +899,28 @@ impl DefaultCustomizer {
         config: ClientHelloConfig,
         success: bool,
     ) {
+        let template = Self::template_name(&config.template);
+        let mut quarantined = None;
         if let Some(cache) = &self.cache {
-            cache.borrow_mut().record_result(target, config, success);
+            let mut cache = cache.borrow_mut();
+            let before = cache.quarantined_template(target).cloned();
+            cache.record_result(target, config, success);
+            quarantined = cache
+                .quarantined_template(target)
+                .filter(|after| before.as_ref() != Some(*after))
+                .map(Self::template_name);
+        }
+        
+        self.emit(CustlsEvent::HandshakeOutcome {
+            target: target.clone(),
+            template,
+            success,
+        });
+        if let Some(template) = quarantined {
+            self.emit(CustlsEvent::TargetQuarantined {
+                target: target.clone(),
+                template,
+            });
         }
     }
     
@@ -609,6 +1108,18 @@ impl DefaultCustomizer {
             tracker.borrow_mut().record_resumption(session_id);
         }
     }
+    
+    /// Fingerprint recorded in phase 1 for a session partition, if any.
+    fn partition_fingerprint(&self, partition: &[u8]) -> Result<Option<ClientHelloConfig>, RustlsError> {
+        #[cfg(feature = "std")]
+        let fingerprints = self.partition_fingerprints.lock()
+            .map_err(|e| RustlsError::General(alloc::format!("Failed to lock partition fingerprints: {}", e)))?;
+        
+        #[cfg(not(feature = "std"))]
+        let fingerprints = self.partition_fingerprints.borrow();
+        
+        Ok(fingerprints.get(partition).cloned())
+    }
 }
 
 impl fmt::Debug for DefaultCustomizer {
@@ -625,78 +1136,210 @@ impl ClientHelloCustomizer for DefaultCustomizer {
     /// Phase 1: Pre-build configuration hook.
     ///
     /// In this phase, we:
-    /// - Select template based on rotation policy (if enabled)
+    /// - Match the target against the routing rules (first match wins)
+    /// - Otherwise select template based on rotation policy (if enabled)
+    /// - Switch to the template's HTTP/3 variant for QUIC connections
+    /// - Request key shares for the template's key share groups
+    /// - Report the template's HTTP/2 profile for the connection
+    /// - Report the template's session and compatibility behavior
+    /// - Partition the session store by template
+    /// - Sample timing jitter as the first flight's send delay
     /// - Query the cache for working fingerprints (if enabled)
+    /// - Rotate away from a template the cache policy quarantined for the target
+    /// - Report the template choice and cache lookup to the event sink
     /// - Initialize per-connection state
     ///
     /// # Note
     ///
-    /// Cache lookup requires knowing the target (host, port), which is not
-    /// currently available in ConfigParams. This will be added in future
-    /// rustls integration work. For now, this phase handles template rotation.
-    fn on_config_resolve(&self, _config: &mut ConfigParams) -> Result<(), RustlsError> {
-        // Apply timing jitter if configured
-        if let Some(ref jitter_config) = self.config.timing_jitter {
-            jitter_config.apply();
+    /// The routing decision is remembered per thread and reused by phases 2 and 3,
+    /// which do not see the target. A rule with `RouteAction::Disabled` leaves the
+    /// whole ClientHello untouched.
+    fn on_config_resolve(&self, config: &mut ConfigParams) -> Result<(), RustlsError> {
+        // Routing rules see the target; the decision is kept for the later phases
+        let route = self.route_for(config)?;
+        self.set_route(route.clone())?;
+        
+        let mut source = match (&route, self.config.rotation_policy) {
+            (Some(_), _) => TemplateSource::RoutingRule,
+            (None, TemplateRotationPolicy::None) => TemplateSource::Configured,
+            (None, _) => TemplateSource::Rotation,
+        };
+        
+        let template = match route {
+            Some(Route::Disabled) => {
+                self.with_pending_hello(|pending| *pending = None)?;
+                self.emit(CustlsEvent::CustomizationDisabled {
+                    target: config.target.clone(),
+                });
+                return Ok(());
+            }
+            Some(Route::Template(browser, template)) => Some((browser, template)),
+            None => {
+                // Select once per connection; phases 2 and 3 reuse this choice
+                let selected = self.select_template_for(config)?;
+                self.set_route(
+                    selected
+                        .clone()
+                        .map(|(browser, template)| Route::Template(browser, template)),
+                )?;
+                selected
+            }
+        };
+        
+        // A template the cache policy quarantined for this target is replaced by
+        // the next rotation template until the quarantine ends
+        let template = match (template, &config.target) {
+            (Some((browser, data)), Some(target)) => {
+                let replacement = match self.quarantined_template(target)? {
+                    Some(quarantined) if quarantined == browser => self.rotate_from(&quarantined),
+                    _ => None,
+                };
+                match replacement {
+                    Some(next) => {
+                        let data = Self::resolve_template(&next)?;
+                        self.set_route(Some(Route::Template(next.clone(), data.clone())))?;
+                        source = TemplateSource::Quarantine;
+                        Some((next, data))
+                    }
+                    None => Some((browser, data)),
+                }
+            }
+            (template, _) => template,
+        };
+        
+        // QUIC connections use the template's HTTP/3 variant where there is one;
+        // otherwise rustls strips what QUIC forbids from the TCP shape
+        let template = match template {
+            Some((browser, data)) if config.quic && data.quic.is_none() => {
+                let data = Self::quic_template_for(&browser).unwrap_or(data);
+                self.set_route(Some(Route::Template(browser.clone(), data.clone())))?;
+                Some((browser, data))
+            }
+            other => other,
+        };
+        
+        // Fold the provider's fresh entropy into the randomizer, so this hello's
+        // padding and GREASE cannot be predicted from earlier connections
+        if let Some(entropy) = config.entropy {
+            #[cfg(feature = "std")]
+            let mut randomizer = self.randomizer.lock()
+                .map_err(|e| RustlsError::General(alloc::format!("Failed to lock randomizer: {}", e)))?;
+            
+            #[cfg(not(feature = "std"))]
+            let mut randomizer = self.randomizer.borrow_mut();
+            
+            randomizer.mix_entropy(entropy);
         }
         
-        // If rotation is enabled, select the next template
-        // Note: This updates the connection counter but doesn't change self.template
-        // because self is immutable. In a real implementation, we would need to
-        // store the selected template in ConfigParams or use a different approach.
+        // Timing jitter is reported as a send delay for the first flight; the
+        // caller waits before writing, so no hook blocks
+        if let Some(ref jitter_config) = self.config.timing_jitter {
+            config.send_delay = jitter_config.sample_delay();
+        }
         
-        // For now, just call select_rotated_template to increment the counter
-        // The actual template selection will happen in on_components_ready
-        if self.config.rotation_policy != TemplateRotationPolicy::None {
-            let _ = self.select_rotated_template();
+        // Key shares are generated before the ClientHello is assembled, so the
+        // template's key share groups are requested here
+        if let Some((browser, template)) = template {
+            let name = Self::template_name(&browser);
+            self.with_pending_hello(|pending| {
+                *pending = Some(PendingHello {
+                    target: config.target.clone(),
+                    template: name.clone(),
+                    outer_order: None,
+                })
+            })?;
+            self.emit(CustlsEvent::TemplateSelected {
+                target: config.target.clone(),
+                template: name,
+                source,
+            });
+            
+            if let Some(target) = &config.target {
+                if let Some(hit) = self.cache_lookup(target)? {
+                    self.emit(CustlsEvent::CacheLookup {
+                        target: target.clone(),
+                        hit,
+                    });
+                }
+            }
+            
+            if !template.key_share_groups.is_empty() {
+                config.key_share_groups = Some(template.key_share_groups.clone());
+            }
+            config.http2_profile = Some(template.http2_profile.clone());
+            config.handshake_behavior = Some(template.handshake_behavior);
+            
+            // Each template gets its own slice of the session store, so a ticket
+            // is only ever offered with the fingerprint that obtained it
+            let partition = template.name.as_bytes().to_vec();
+            if self.session_tracker.is_some() {
+                #[cfg(feature = "std")]
+                let mut fingerprints = self.partition_fingerprints.lock()
+                    .map_err(|e| RustlsError::General(alloc::format!("Failed to lock partition fingerprints: {}", e)))?;
+                
+                #[cfg(not(feature = "std"))]
+                let mut fingerprints = self.partition_fingerprints.borrow_mut();
+                
+                fingerprints
+                    .entry(partition.clone())
+                    .or_insert_with(|| ClientHelloConfig::from_template(browser, &template));
+            }
+            config.session_partition = Some(partition);
+        } else {
+            self.with_pending_hello(|pending| *pending = None)?;
         }
         
-        // TODO: Once ConfigParams includes target information:
-        // 1. Extract target (host, port) from config
-        // 2. Query cache for working fingerprint
-        // 3. If found, prepare to apply cached config with small variation
-        // 4. If not found, proceed with template-based generation
+        // TODO: On a cache hit, apply the cached config with small variation
+        // instead of generating from the template
         
         Ok(())
     }
     
     /// Phase 2: Mid-build component modification hook.
     ///
-    /// In this phase, we:
-    /// - Apply template configuration to cipher suites and extensions
-    /// - Apply randomization based on configured level
-    /// - Inject GREASE values following browser patterns
-    /// - Shuffle extensions with grouped constraints
-    ///
-    /// This is the main customization phase where most fingerprint shaping occurs.
+    /// In this phase, we replace the cipher suite list with the template's,
+    /// including advertise-only suites. Randomization and GREASE need the
+    /// extensions, so they are applied in phase 3.
     fn on_components_ready(
         &self,
         cipher_suites: &mut Vec<CipherSuite>,
         extensions: &mut Vec<ClientExtension>,
     ) -> Result<(), RustlsError> {
-        // Apply timing jitter if configured
-        if let Some(ref jitter_config) = self.config.timing_jitter {
-            jitter_config.apply();
-        }
-        
-        // Select template (either fixed or rotated)
-        let template_enum = if self.config.rotation_policy != TemplateRotationPolicy::None {
-            self.select_rotated_template()
-        } else {
-            self.config.template.clone()
+        let template = match self.template_for_hello()? {
+            Some(t) => t,
+            None => return Ok(()), // No template or routing disabled, skip customization
         };
         
-        // Resolve template to TemplateData
-        let template = match template_enum {
-            Some(t) => Self::resolve_template(&t),
-            None => {
-                // If no template, use the pre-resolved one from construction
-                match &self.template {
-                    Some(t) => t.clone(),
-                    None => return Ok(()), // No template, skip customization
-                }
-            }
-        };
+        // Advertise the template's cipher suites verbatim. Suites the provider does
+        // not implement are only advertised; the client rejects a server selecting one
+        if !template.cipher_suites.is_empty() {
+            cipher_suites.clone_from(&template.cipher_suites);
+        }
+        
+        Ok(())
+    }
+    
+    /// Phase 3: Pre-marshal structure modification hook.
+    ///
+    /// In this phase, we:
+    /// - Add a padding extension with a length from the template's distribution
+    /// - Put the extensions in template order, shuffled with grouped constraints
+    ///   unless randomization is off
+    /// - Inject GREASE cipher suites and extensions, unless randomization is off
+    /// - Replace the signature algorithms with the template's list
+    /// - Add a GREASE ECH extension when the template carries an ECH GREASE shape
+    /// - Reorder the QUIC transport parameters and add GREASE for HTTP/3 templates
+    /// - Report the shaped ClientHello to the event sink
+    /// - Perform final validation of ClientHello structure
+    /// - Make any final adjustments before serialization
+    ///
+    /// GREASE ECH is only added for TLS 1.3 hellos that do not already carry an
+    /// ECH extension, so real ECH and config-level GREASE always take precedence.
+    fn on_struct_ready(&self, payload: &mut crate::msgs::ClientHelloPayload) -> Result<(), RustlsError> {
+        let template = match self.template_for_hello()? {
+            Some(t) => t,
+            None => return Ok(()), // No template or routing disabled, skip customization
+        };
         
         // Get mutable access to randomizer
         #[cfg(feature = "std")]
@@ -706,55 +1349,88 @@ impl ClientHelloCustomizer for DefaultCustomizer {
         #[cfg(not(feature = "std"))]
         let mut randomizer = self.randomizer.borrow_mut();
         
-        // Apply randomization if level is not None
-        if randomizer.level() != RandomizationLevel::None {
-            // Shuffle extensions with grouped constraints
-            randomizer.shuffle_extensions(extensions, &template)?;
-            
-            // Inject GREASE values (no previous values tracking yet - will be added with cache integration)
-            let _ = randomizer.inject_grease(cipher_suites, extensions, &template, &[])?;
+        // Generate padding length from template distribution (no previous values tracking yet)
+        let padding_len = randomizer.generate_padding_len(&template, &[]);
+        
+        // Advertise the template's signature algorithms verbatim, including schemes
+        // the verifier cannot check
+        if !template.signature_algorithms.is_empty() && payload.signature_schemes.is_some() {
+            payload.signature_schemes = Some(template.signature_algorithms.clone());
         }
         
-        // TODO: Once cipher_suites and extensions have real implementations:
-        // - Apply template cipher suite ordering
-        // - Apply template extension ordering
-        // - Validate against naturalness filter
+        // Offer GREASE ECH the way the simulated browser does
+        if let Some(shape) = &template.ech_grease {
+            let offers_tls13 = payload
+                .supported_versions
+                .is_some_and(|versions| versions.tls13);
+            if offers_tls13 && payload.encrypted_client_hello.is_none() {
+                payload.encrypted_client_hello = randomizer.generate_ech_grease(shape);
+            }
+        }
         
-        Ok(())
-    }
-    
-    /// Phase 3: Pre-marshal structure modification hook.
-    ///
-    /// In this phase, we:
-    /// - Add padding extension with generated length
-    /// - Perform final validation of ClientHello structure
-    /// - Make any final adjustments before serialization
-    fn on_struct_ready(&self, _payload: &mut crate::msgs::ClientHelloPayload) -> Result<(), RustlsError> {
-        // Apply timing jitter if configured
-        if let Some(ref jitter_config) = self.config.timing_jitter {
-            jitter_config.apply();
+        // The application encodes its transport parameters; send them in the
+        // browser's order and with its GREASE parameter
+        if let (Some(profile), Some(encoded)) = (&template.quic, &payload.transport_parameters) {
+            let mut params = QuicTransportParameters::decode(encoded.bytes())?;
+            randomizer.shape_transport_parameters(&mut params, profile);
+            payload.transport_parameters = Some(Payload::new(params.encode()));
         }
         
-        // Select template (either fixed or rotated)
-        let template_enum = if self.config.rotation_policy != TemplateRotationPolicy::None {
-            self.select_rotated_template()
-        } else {
-            self.config.template.clone()
-        };
+        // A padding length of 0 means none
+        if padding_len > 0 {
+            payload.padding = Some(PaddingExtension::new(padding_len));
+        }
         
-        // Resolve template to TemplateData
-        let template = match template_enum {
-            Some(t) => Self::resolve_template(&t),
-            None => {
-                // If no template, use the pre-resolved one from construction
-                match &self.template {
-                    Some(t) => t.clone(),
-                    None => return Ok(()), // No template, skip customization
-                }
+        // No previous values tracking yet - will be added with cache integration
+        let mut order = shape_extension_order(&template, payload, &mut randomizer)?;
+        randomizer.inject_grease(&mut payload.cipher_suites, &mut order, &template, &[])?;
+        drop(randomizer);
+        payload.contiguous_extensions = order;
+        
+        let (target, name) = self.with_pending_hello(|pending| match pending {
+            Some(pending) => {
+                pending.outer_order = Some(payload.contiguous_extensions.clone());
+                (pending.target.clone(), pending.template.clone())
             }
+            None => (None, template.name.clone()),
+        })?;
+        // Report the hello as it will be encoded
+        let extension_order = payload.used_extensions_in_encoding_order();
+        let grease_values = payload
+            .cipher_suites
+            .iter()
+            .map(|suite| u16::from(*suite))
+            .chain(extension_order.iter().map(|ext| u16::from(*ext)))
+            .filter(|value| is_grease_value(*value))
+            .collect();
+        self.emit(CustlsEvent::ClientHelloShaped {
+            target,
+            template: name,
+            grease_values,
+            padding_length: payload.padding.as_ref().map(|padding| padding.length),
+            extension_order,
+        });
+        
+        Ok(())
+    }
+    
+    /// Phase 3 (ECH inner): Shape the protected inner ClientHello.
+    ///
+    /// The inner hello is derived from the outer one, which the template shaped in
+    /// `on_struct_ready`. Here its extensions are put in the order phase 3 gave the
+    /// outer hello, so both hellos look like the same browser and the protected one
+    /// still does once the server decrypts it. Without a shaped outer hello the
+    /// template order is used, shuffled like the outer hello's would be. The
+    /// template is the one phase 1 chose for this hello on this thread, so
+    /// concurrent connections never shape each other's inner hello. No padding
+    /// extension or GREASE is added: the inner hello is padded as the ECH
+    /// specification requires.
+    fn on_ech_inner_ready(&self, inner: &mut crate::msgs::ClientHelloPayload) -> Result<(), RustlsError> {
+        let template = match self.template_for_hello()? {
+            Some(t) => t,
+            None => return Ok(()), // No template or routing disabled, skip customization
         };
         
-        // Get mutable access to randomizer
         #[cfg(feature = "std")]
         let mut randomizer = self.randomizer.lock()
             .map_err(|e| RustlsError::General(alloc::format!("Failed to lock randomizer: {}", e)))?;
@@ -762,17 +1438,15 @@ impl ClientHelloCustomizer for DefaultCustomizer {
         #[cfg(not(feature = "std"))]
         let mut randomizer = self.randomizer.borrow_mut();
         
-        // Generate padding length from template distribution (no previous values tracking yet)
-        let padding_len = randomizer.generate_padding_len(&template, &[]);
-        
-        // TODO: Once ClientHelloPayload has real implementation:
-        // - Create PaddingExtension with generated length
-        // - Add to payload.extensions
-        // - Validate final structure
-        
-        // For now, just create the padding extension to validate the API
-        let _padding_ext = PaddingExtension::new(padding_len);
-        
+        let outer_order = self.with_pending_hello(|pending| {
+            pending
+                .as_ref()
+                .and_then(|pending| pending.outer_order.clone())
+        })?;
+        inner.contiguous_extensions = match outer_order {
+            Some(outer_order) => extension_order_following(&outer_order, inner),
+            None => shape_extension_order(&template, inner, &mut randomizer)?,
+        };
         Ok(())
     }
     
@@ -786,14 +1460,94 @@ impl ClientHelloCustomizer for DefaultCustomizer {
     /// - Additional obfuscation techniques
     /// - Final size adjustments
     fn transform_wire_bytes(&self, bytes: Vec<u8>) -> Result<Vec<u8>, RustlsError> {
-        // Apply timing jitter if configured
-        if let Some(ref jitter_config) = self.config.timing_jitter {
-            jitter_config.apply();
-        }
-        
         // Default implementation: no transformation
         Ok(bytes)
     }
+    
+    /// Resumption: offer a session only with the fingerprint it was recorded with.
+    ///
+    /// The session store is already partitioned by template; this check also
+    /// covers stores that ignore the partition. Without session tracking every
+    /// session is offered. A ClientHello without a template of ours may only
+    /// offer sessions the tracker has never seen.
+    fn on_resumption_offer(&self, session: &[u8], partition: Option<&[u8]>) -> Result<bool, RustlsError> {
+        let Some(tracker) = &self.session_tracker else {
+            return Ok(true);
+        };
+        let current = match partition {
+            Some(partition) => self.partition_fingerprint(partition)?,
+            None => None,
+        };
+        
+        #[cfg(feature = "std")]
+        let mut tracker = tracker.lock()
+            .map_err(|e| RustlsError::General(alloc::format!("Failed to lock session tracker: {}", e)))?;
+        
+        #[cfg(not(feature = "std"))]
+        let mut tracker = tracker.borrow_mut();
+        
+        let session = SessionId::new(session.to_vec());
+        Ok(match current {
+            Some(config) => tracker.check_resumption(&session, &config).is_ok(),
+            None => tracker.get_session_config(&session).is_none(),
+        })
+    }
+    
+    /// Resumption: record a stored session with its partition's fingerprint.
+    fn on_session_stored(&self, session: &[u8], partition: Option<&[u8]>) {
+        let Some(config) = partition.and_then(|partition| {
+            self.partition_fingerprint(partition).ok().flatten()
+        }) else {
+            return;
+        };
+        
+        let session = SessionId::new(session.to_vec());
+        self.record_session(&session, config);
+        self.mark_session_established(&session);
+    }
+}
+
+/// Encoding order of the extensions `payload` sends, as the template orders them
+/// and shuffled as far as the randomization level allows.
+fn shape_extension_order(
+    template: &TemplateData,
+    payload: &crate::msgs::ClientHelloPayload,
+    randomizer: &mut BrowserRandomizer,
+) -> Result<Vec<ExtensionType>, RustlsError> {
+    let mut order = extension_order_following(&template.extension_order, payload);
+    randomizer.shuffle_extensions(&mut order, template)?;
+    Ok(order)
+}
+
+/// Encoding order of the extensions `payload` sends, following `reference`.
+///
+/// This is the one ordering both the outer and the ECH inner hello go through:
+/// the outer hello follows the template, the inner hello the shaped outer hello.
+/// Extensions `reference` lists come in its order, after the ones it does not
+/// list, which keep the encoder's field order. A padding extension `reference`
+/// does not list goes last. ECH and PSK extensions are left out, since they are
+/// always encoded last.
+fn extension_order_following(
+    reference: &[ExtensionType],
+    payload: &crate::msgs::ClientHelloPayload,
+) -> Vec<ExtensionType> {
+    let mut used = payload.collect_used();
+    used.retain(|ext| !TRAILING_EXTENSIONS.contains(ext));
+    
+    let mut order: Vec<ExtensionType> = used
+        .iter()
+        .copied()
+        .filter(|ext| *ext != ExtensionType::Padding && !reference.contains(ext))
+        .collect();
+    for ext in reference {
+        if used.contains(ext) && !order.contains(ext) {
+            order.push(*ext);
+        }
+    }
+    if used.contains(&ExtensionType::Padding) && !order.contains(&ExtensionType::Padding) {
+        order.push(ExtensionType::Padding);
+    }
+    order
 }
 
 #[cfg(test)]
@@ -837,6 +1591,20 @@ mod tests {
         assert!(customizer.cache.is_some());
     }
     
+    #[test]
+    fn test_excluded_extensions_are_taken_out_of_the_template() {
+        let config = CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .with_excluded_extensions(vec![ExtensionType::EncryptedClientHello])
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        
+        let template = customizer.template_for_hello().unwrap().unwrap();
+        assert!(template.ech_grease.is_none());
+        assert!(!template.extension_order.contains(&ExtensionType::EncryptedClientHello));
+        assert!(chrome_130().ech_grease.is_some());
+    }
+    
     #[test]
     fn test_default_customizer_without_cache() {
         let config = CustlsConfig::builder()
@@ -851,25 +1619,25 @@ mod tests {
     
     #[test]
     fn test_resolve_template_chrome() {
-        let template = DefaultCustomizer::resolve_template(&BrowserTemplate::Chrome130);
+        let template = DefaultCustomizer::resolve_template(&BrowserTemplate::Chrome130).unwrap();
         assert_eq!(template.name, "Chrome 130+");
     }
     
     #[test]
     fn test_resolve_template_firefox() {
-        let template = DefaultCustomizer::resolve_template(&BrowserTemplate::Firefox135);
+        let template = DefaultCustomizer::resolve_template(&BrowserTemplate::Firefox135).unwrap();
         assert_eq!(template.name, "Firefox 135+");
     }
     
     #[test]
     fn test_resolve_template_safari() {
-        let template = DefaultCustomizer::resolve_template(&BrowserTemplate::Safari17);
+        let template = DefaultCustomizer::resolve_template(&BrowserTemplate::Safari17).unwrap();
         assert_eq!(template.name, "Safari 17+");
     }
     
     #[test]
     fn test_resolve_template_edge() {
-        let template = DefaultCustomizer::resolve_template(&BrowserTemplate::Edge130);
+        let template = DefaultCustomizer::resolve_template(&BrowserTemplate::Edge130).unwrap();
         assert_eq!(template.name, "Edge 130+");
     }
     
@@ -910,8 +1678,41 @@ mod tests {
         let customizer = DefaultCustomizer::new(config);
         let mut config_params = ConfigParams::new();
         
-        // Should succeed (currently a no-op)
         assert!(customizer.on_config_resolve(&mut config_params).is_ok());
+        assert_eq!(config_params.http2_profile, Some(chrome_130().http2_profile));
+    }
+    
+    #[test]
+    fn test_timing_jitter_reported_as_send_delay() {
+        let config = CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .with_timing_jitter(crate::custls::TimingJitterConfig::new(1_000, 2_000, 1.0).unwrap())
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        
+        let mut config_params = ConfigParams::new();
+        customizer.on_config_resolve(&mut config_params).unwrap();
+        let delay = config_params.send_delay.unwrap();
+        assert!(delay >= core::time::Duration::from_micros(1_000));
+        assert!(delay <= core::time::Duration::from_micros(2_000));
+        
+        // Without jitter configured there is no delay
+        let customizer = DefaultCustomizer::new(CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .build());
+        let mut config_params = ConfigParams::new();
+        customizer.on_config_resolve(&mut config_params).unwrap();
+        assert!(config_params.send_delay.is_none());
+    }
+    
+    #[test]
+    fn test_hook_on_config_resolve_without_template() {
+        let customizer = DefaultCustomizer::new(CustlsConfig::default());
+        let mut config_params = ConfigParams::new();
+        
+        assert!(customizer.on_config_resolve(&mut config_params).is_ok());
+        assert!(config_params.key_share_groups.is_none());
+        assert!(config_params.http2_profile.is_none());
     }
     
     #[test]
@@ -929,19 +1730,623 @@ mod tests {
         assert!(customizer.on_components_ready(&mut cipher_suites, &mut extensions).is_ok());
     }
     
+    #[cfg(feature = "std")]
     #[test]
-    fn test_hook_on_struct_ready() {
+    fn test_hook_on_struct_ready_adds_grease_and_padding() {
+        use crate::custls::templates::{PaddingDistribution, register_template};
+        
+        let mut template = chrome_130();
+        template.padding_distribution = PaddingDistribution {
+            pmf: vec![(128, 1.0)],
+            min_length: 128,
+            max_length: 128,
+            power_of_2_bias: 0.0,
+        };
+        template.grease_pattern.extension_positions = vec![0.0];
+        register_template("orchestrator_padded", template.clone());
+        let sink = Arc::new(RecordingSink::default());
+        
+        for level in [RandomizationLevel::None, RandomizationLevel::Light, RandomizationLevel::High] {
+            let config = CustlsConfig::builder()
+                .with_template(BrowserTemplate::Named("orchestrator_padded".to_string()))
+                .with_randomization_level(level)
+                .with_event_sink(sink.clone())
+                .build();
+            let customizer = DefaultCustomizer::new(config);
+            customizer.on_config_resolve(&mut ConfigParams::new()).unwrap();
+            
+            let mut payload = test_payload();
+            payload.cipher_suites = template.cipher_suites.clone();
+            customizer.on_struct_ready(&mut payload).unwrap();
+            
+            // Padding comes from the distribution at every level, and goes last
+            assert_eq!(payload.padding, Some(PaddingExtension::new(128)));
+            assert_eq!(payload.contiguous_extensions.last(), Some(&ExtensionType::Padding));
+            
+            // Chrome always sends GREASE, in front of its extensions
+            let grease_suites = payload.cipher_suites
+                .iter()
+                .filter(|suite| is_grease_value(u16::from(**suite)))
+                .count();
+            let grease_extensions = payload.contiguous_extensions
+                .iter()
+                .filter(|ext| is_grease_value(u16::from(**ext)))
+                .count();
+            match level {
+                RandomizationLevel::None => {
+                    assert_eq!(payload.cipher_suites, template.cipher_suites);
+                    assert_eq!((grease_suites, grease_extensions), (0, 0));
+                    assert_eq!(payload.contiguous_extensions, vec![
+                        ExtensionType::SupportedVersions,
+                        ExtensionType::EllipticCurves,
+                        ExtensionType::SignatureAlgorithms,
+                        ExtensionType::Padding,
+                    ]);
+                }
+                _ => {
+                    assert_eq!((grease_suites, grease_extensions), (1, 1));
+                    assert!(is_grease_value(u16::from(payload.contiguous_extensions[0])));
+                }
+            }
+            
+            // The event reports what the payload encodes
+            let sent_grease = payload.cipher_suites
+                .iter()
+                .map(|suite| u16::from(*suite))
+                .chain(payload.contiguous_extensions.iter().map(|ext| u16::from(*ext)))
+                .filter(|value| is_grease_value(*value))
+                .collect::<Vec<_>>();
+            let shaped = sink.take()
+                .into_iter()
+                .find_map(|event| match event {
+                    CustlsEvent::ClientHelloShaped { grease_values, padding_length, .. } => {
+                        Some((grease_values, padding_length))
+                    }
+                    _ => None,
+                });
+            assert_eq!(shaped, Some((sent_grease, Some(128))));
+        }
+    }
+    
+    #[test]
+    fn test_hook_on_ech_inner_ready_applies_template_order() {
+        use crate::crypto::SignatureScheme;
+        use crate::crypto::kx::NamedGroup;
+        use crate::msgs::{
+            ClientExtensions, ClientHelloPayload, Compression, EncryptedClientHello, Random,
+            SessionId, SupportedProtocolVersions,
+        };
+        
         let config = CustlsConfig::builder()
             .with_template(BrowserTemplate::Chrome130)
+            .with_randomization_level(RandomizationLevel::None)
             .build();
         
         let customizer = DefaultCustomizer::new(config);
         
-        // Note: We can't easily create a ClientHelloPayload for testing
-        // since it doesn't have a public constructor. This test would need
-        // integration with actual rustls ClientHello generation.
-        // For now, we just verify the customizer was created successfully.
-        assert!(customizer.template().is_some());
+        let mut inner = ClientHelloPayload {
+            client_version: crate::enums::ProtocolVersion::TLSv1_2,
+            random: Random([0u8; 32]),
+            session_id: SessionId::empty(),
+            cipher_suites: vec![CipherSuite::TLS13_AES_128_GCM_SHA256],
+            compression_methods: vec![Compression::Null],
+            extensions: alloc::boxed::Box::new(ClientExtensions::default()),
+        };
+        inner.signature_schemes = Some(vec![SignatureScheme::ED25519]);
+        inner.named_groups = Some(vec![NamedGroup::X25519]);
+        inner.supported_versions = Some(SupportedProtocolVersions { tls13: true, tls12: false });
+        inner.encrypted_client_hello = Some(EncryptedClientHello::Inner);
+        
+        customizer.on_ech_inner_ready(&mut inner).unwrap();
+        
+        // Chrome order, with ECH left to be encoded last
+        assert_eq!(inner.contiguous_extensions, vec![
+            ExtensionType::SupportedVersions,
+            ExtensionType::EllipticCurves,
+            ExtensionType::SignatureAlgorithms,
+        ]);
+        assert!(matches!(inner.encrypted_client_hello, Some(EncryptedClientHello::Inner)));
+    }
+    
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_ech_inner_hello_follows_outer_order() {
+        use crate::crypto::kx::NamedGroup;
+        use crate::msgs::KeyShareEntry;
+        
+        let config = CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .with_randomization_level(RandomizationLevel::High)
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        
+        for _ in 0..20 {
+            routed_cipher_suites(&customizer, "example.com");
+            let mut outer = test_payload();
+            outer.key_shares = Some(vec![KeyShareEntry::new(NamedGroup::X25519, &[1u8; 32][..])]);
+            outer.extended_master_secret_request = Some(());
+            outer.early_data_request = Some(());
+            let mut inner = outer.clone();
+            customizer.on_struct_ready(&mut outer).unwrap();
+            customizer.on_ech_inner_ready(&mut inner).unwrap();
+            
+            // The inner hello has no GREASE or padding, and is otherwise in the outer order
+            let mut outer_order = outer.contiguous_extensions.clone();
+            outer_order.retain(|ext| {
+                *ext != ExtensionType::Padding && !is_grease_value(u16::from(*ext))
+            });
+            assert_eq!(inner.contiguous_extensions, outer_order);
+        }
+    }
+    
+    #[test]
+    fn test_hooks_advertise_template_codepoints() {
+        use crate::crypto::SignatureScheme;
+        use crate::msgs::{ClientExtensions, ClientHelloPayload, Compression, Random, SessionId};
+        
+        let config = CustlsConfig::builder()
+            .with_template(BrowserTemplate::Named("curl_8".to_string()))
+            .build();
+        
+        let customizer = DefaultCustomizer::new(config);
+        let template = crate::custls::templates::curl_8();
+        
+        // Phase 2 replaces the provider's suites, keeping unimplemented codepoints
+        let mut cipher_suites = vec![
+            CipherSuite::TLS13_AES_128_GCM_SHA256,
+            CipherSuite::TLS_EMPTY_RENEGOTIATION_INFO_SCSV,
+        ];
+        let mut extensions = Vec::new();
+        customizer.on_components_ready(&mut cipher_suites, &mut extensions).unwrap();
+        assert_eq!(cipher_suites, template.cipher_suites);
+        assert!(cipher_suites.contains(&CipherSuite::TLS_DHE_RSA_WITH_AES_256_GCM_SHA384));
+        
+        // Phase 3 replaces the signature algorithms
+        let mut payload = ClientHelloPayload {
+            client_version: crate::enums::ProtocolVersion::TLSv1_2,
+            random: Random([0u8; 32]),
+            session_id: SessionId::empty(),
+            cipher_suites,
+            compression_methods: vec![Compression::Null],
+            extensions: alloc::boxed::Box::new(ClientExtensions::default()),
+        };
+        payload.signature_schemes = Some(vec![SignatureScheme::ED25519]);
+        customizer.on_struct_ready(&mut payload).unwrap();
+        assert_eq!(payload.signature_schemes, Some(template.signature_algorithms));
+    }
+    
+    #[test]
+    fn test_quic_connections_use_http3_template() {
+        use crate::custls::quic::{QuicTransportParameters, TransportParameter};
+        use crate::custls::templates::chrome_130_quic;
+        use crate::msgs::{ClientExtensions, ClientHelloPayload, Compression, Random, SessionId};
+        
+        let config = CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .with_randomization_level(RandomizationLevel::None)
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        let quic_template = chrome_130_quic();
+        
+        let mut params = ConfigParams::new();
+        params.quic = true;
+        customizer.on_config_resolve(&mut params).unwrap();
+        assert_eq!(params.session_partition, Some(quic_template.name.clone().into_bytes()));
+        assert_eq!(params.handshake_behavior, Some(quic_template.handshake_behavior));
+        
+        // Phase 2 offers the TLS 1.3 suites only
+        let mut cipher_suites = vec![CipherSuite::TLS13_AES_128_GCM_SHA256];
+        customizer.on_components_ready(&mut cipher_suites, &mut Vec::new()).unwrap();
+        assert_eq!(cipher_suites, quic_template.cipher_suites);
+        
+        // Phase 3 keeps the application's parameters and adds a GREASE parameter
+        let mut app_params = QuicTransportParameters::new();
+        app_params.push(TransportParameter::varint(TransportParameter::INITIAL_MAX_DATA, 1 << 20));
+        app_params.push(TransportParameter::varint(TransportParameter::MAX_IDLE_TIMEOUT, 30_000));
+        let mut payload = ClientHelloPayload {
+            client_version: crate::enums::ProtocolVersion::TLSv1_2,
+            random: Random([0u8; 32]),
+            session_id: SessionId::empty(),
+            cipher_suites,
+            compression_methods: vec![Compression::Null],
+            extensions: alloc::boxed::Box::new(ClientExtensions::default()),
+        };
+        payload.transport_parameters = Some(Payload::new(app_params.encode()));
+        customizer.on_struct_ready(&mut payload).unwrap();
+        
+        let sent = payload.transport_parameters.as_ref().unwrap();
+        let shaped = QuicTransportParameters::decode(sent.bytes()).unwrap();
+        assert!(shaped.has_grease());
+        assert_eq!(shaped.params().len(), 3);
+        for param in app_params.params() {
+            assert!(shaped.params().contains(param));
+        }
+        
+        // Over TCP the same customizer keeps the TCP template
+        let mut params = ConfigParams::new();
+        customizer.on_config_resolve(&mut params).unwrap();
+        assert_eq!(params.session_partition, Some(chrome_130().name.into_bytes()));
+    }
+    
+    fn routed_params(host: &str, alpn: &[&[u8]]) -> ConfigParams {
+        let mut params = ConfigParams::new();
+        params.target = Some(TargetKey::new(host.to_string(), 443));
+        params.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+        params
+    }
+    
+    fn routed_cipher_suites(customizer: &DefaultCustomizer, host: &str) -> (ConfigParams, Vec<CipherSuite>) {
+        let mut params = routed_params(host, &[b"h2"]);
+        customizer.on_config_resolve(&mut params).unwrap();
+        let mut cipher_suites = vec![CipherSuite::TLS13_AES_128_GCM_SHA256];
+        customizer.on_components_ready(&mut cipher_suites, &mut Vec::new()).unwrap();
+        (params, cipher_suites)
+    }
+    
+    #[test]
+    fn test_routing_rules_select_template_per_host() {
+        use crate::custls::{HostPattern, RoutingRule};
+        
+        let config = CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .with_randomization_level(RandomizationLevel::None)
+            .with_routing_rule(RoutingRule::new(
+                HostPattern::Suffix("internal.example".to_string()),
+                RouteAction::Disabled,
+            ))
+            .with_routing_rule(RoutingRule::new(
+                HostPattern::Glob("*.apple.com".to_string()),
+                RouteAction::Template(BrowserTemplate::Safari17),
+            ))
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        
+        let (params, suites) = routed_cipher_suites(&customizer, "cdn.apple.com");
+        assert_eq!(suites, crate::custls::templates::safari_17().cipher_suites);
+        assert_eq!(params.http2_profile, Some(crate::custls::templates::safari_17().http2_profile));
+        
+        // Disabled: no key shares, no profile, cipher suites left alone
+        let (params, suites) = routed_cipher_suites(&customizer, "api.internal.example");
+        assert!(params.key_share_groups.is_none());
+        assert!(params.http2_profile.is_none());
+        assert_eq!(suites, vec![CipherSuite::TLS13_AES_128_GCM_SHA256]);
+        
+        // No rule matches: the configured template, not the previous route
+        let (_, suites) = routed_cipher_suites(&customizer, "example.org");
+        assert_eq!(suites, chrome_130().cipher_suites);
+    }
+    
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_ech_inner_hello_uses_this_threads_template() {
+        use std::sync::Barrier;
+        
+        use crate::crypto::SignatureScheme;
+        use crate::crypto::kx::NamedGroup;
+        use crate::custls::{HostPattern, RoutingRule};
+        use crate::msgs::{
+            ClientExtensions, ClientHelloPayload, Compression, KeyShareEntry, Random, SessionId,
+            SupportedProtocolVersions,
+        };
+        
+        fn hello() -> ClientHelloPayload {
+            let mut hello = ClientHelloPayload {
+                client_version: crate::enums::ProtocolVersion::TLSv1_2,
+                random: Random([0u8; 32]),
+                session_id: SessionId::empty(),
+                cipher_suites: vec![CipherSuite::TLS13_AES_128_GCM_SHA256],
+                compression_methods: vec![Compression::Null],
+                extensions: alloc::boxed::Box::new(ClientExtensions::default()),
+            };
+            hello.signature_schemes = Some(vec![SignatureScheme::ED25519]);
+            hello.named_groups = Some(vec![NamedGroup::X25519]);
+            hello.key_shares = Some(vec![KeyShareEntry::new(NamedGroup::X25519, &[1u8; 32][..])]);
+            hello.supported_versions = Some(SupportedProtocolVersions { tls13: true, tls12: false });
+            hello
+        }
+        
+        let config = CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .with_randomization_level(RandomizationLevel::None)
+            .with_routing_rule(RoutingRule::new(
+                HostPattern::Suffix("mozilla.org".to_string()),
+                RouteAction::Template(BrowserTemplate::Firefox135),
+            ))
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        
+        // Both outer hellos are shaped before either inner hello, so a template
+        // shared between connections would shape one inner hello wrongly
+        let barrier = Barrier::new(2);
+        let shape = |host: &str| {
+            routed_cipher_suites(&customizer, host);
+            customizer.on_struct_ready(&mut hello()).unwrap();
+            barrier.wait();
+            let mut inner = hello();
+            customizer.on_ech_inner_ready(&mut inner).unwrap();
+            inner.contiguous_extensions
+        };
+        
+        let (firefox, chrome) = std::thread::scope(|s| {
+            let firefox = s.spawn(|| shape("www.mozilla.org"));
+            let chrome = s.spawn(|| shape("example.org"));
+            (firefox.join().unwrap(), chrome.join().unwrap())
+        });
+        
+        assert_eq!(firefox, vec![
+            ExtensionType::SupportedVersions,
+            ExtensionType::EllipticCurves,
+            ExtensionType::KeyShare,
+            ExtensionType::SignatureAlgorithms,
+        ]);
+        assert_eq!(chrome, vec![
+            ExtensionType::SupportedVersions,
+            ExtensionType::EllipticCurves,
+            ExtensionType::SignatureAlgorithms,
+            ExtensionType::KeyShare,
+        ]);
+    }
+    
+    #[test]
+    fn test_routing_rotation_is_sticky_per_target() {
+        use crate::custls::{HostPattern, RoutingRule};
+        
+        let set = vec![
+            BrowserTemplate::Chrome130,
+            BrowserTemplate::Firefox135,
+            BrowserTemplate::Safari17,
+        ];
+        let config = CustlsConfig::builder()
+            .with_randomization_level(RandomizationLevel::None)
+            .with_routing_rule(RoutingRule::new(HostPattern::Any, RouteAction::Rotate(set.clone()))
+                .with_alpn(b"h2"))
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        
+        let (_, first) = routed_cipher_suites(&customizer, "shop.example");
+        for _ in 0..5 {
+            assert_eq!(routed_cipher_suites(&customizer, "shop.example").1, first);
+        }
+        
+        let expected = sticky_choice(&set, &TargetKey::new("shop.example".to_string(), 443)).unwrap();
+        assert_eq!(first, DefaultCustomizer::resolve_template(expected).unwrap().cipher_suites);
+        
+        // Without h2 the rule does not apply and there is no template
+        let mut params = routed_params("shop.example", &[b"http/1.1"]);
+        customizer.on_config_resolve(&mut params).unwrap();
+        assert!(params.http2_profile.is_none());
+    }
+    
+    #[test]
+    fn test_weighted_choice() {
+        let weights = vec![
+            (BrowserTemplate::Chrome130, 3.0),
+            (BrowserTemplate::Named("okhttp_4".to_string()), 1.0),
+        ];
+        
+        assert_eq!(weighted_choice(&weights, 0.0), Some(BrowserTemplate::Chrome130));
+        assert_eq!(weighted_choice(&weights, 0.74), Some(BrowserTemplate::Chrome130));
+        assert_eq!(weighted_choice(&weights, 0.75), Some(BrowserTemplate::Named("okhttp_4".to_string())));
+        assert_eq!(weighted_choice(&weights, 1.0), Some(BrowserTemplate::Named("okhttp_4".to_string())));
+        assert_eq!(weighted_choice(&[], 0.5), None);
+        
+        assert_eq!(unit_from_entropy(0), 0.0);
+        assert!(unit_from_entropy(u64::MAX) < 1.0);
+    }
+    
+    #[test]
+    fn test_rotation_weights_configurable() {
+        // Configured weights replace the defaults; zero weights drop a template
+        let config = CustlsConfig::builder()
+            .with_rotation_policy(TemplateRotationPolicy::WeightedRandom)
+            .with_rotation_weight(BrowserTemplate::Named("curl_8".to_string()), 1.0)
+            .with_rotation_weight(BrowserTemplate::Chrome130, 0.0)
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        
+        for entropy in [0, u64::MAX / 2, u64::MAX] {
+            let mut params = ConfigParams::new();
+            params.entropy = Some(entropy);
+            customizer.on_config_resolve(&mut params).unwrap();
+            assert_eq!(params.http2_profile, Some(crate::custls::templates::curl_8().http2_profile));
+        }
+        
+        // Defaults only weight the configured rotation templates
+        let config = CustlsConfig::builder()
+            .with_rotation_policy(TemplateRotationPolicy::WeightedRandom)
+            .with_rotation_templates(vec![BrowserTemplate::Safari17, BrowserTemplate::Named("go_1_22".to_string())])
+            .build();
+        let weights = DefaultCustomizer::new(config).rotation_weights();
+        assert_eq!(weights, vec![
+            (BrowserTemplate::Safari17, 20.0),
+            (BrowserTemplate::Named("go_1_22".to_string()), 10.0),
+        ]);
+    }
+    
+    #[test]
+    fn test_weighted_rotation_uses_provider_entropy() {
+        let config = CustlsConfig::builder()
+            .with_randomization_level(RandomizationLevel::None)
+            .with_rotation_policy(TemplateRotationPolicy::WeightedRandom)
+            .with_rotation_weight(BrowserTemplate::Chrome130, 3.0)
+            .with_rotation_weight(BrowserTemplate::Firefox135, 1.0)
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        
+        // Chrome covers the first 75% of the distribution, Firefox the rest
+        for (entropy, expected) in [(0, chrome_130()), (u64::MAX, firefox_135())] {
+            let mut params = ConfigParams::new();
+            params.entropy = Some(entropy);
+            customizer.on_config_resolve(&mut params).unwrap();
+            assert_eq!(params.http2_profile, Some(expected.http2_profile.clone()));
+            
+            let mut cipher_suites = Vec::new();
+            customizer.on_components_ready(&mut cipher_suites, &mut Vec::new()).unwrap();
+            assert_eq!(cipher_suites, expected.cipher_suites);
+        }
+    }
+    
+    #[test]
+    fn test_sticky_weighted_rotation_per_target() {
+        let config = CustlsConfig::builder()
+            .with_rotation_policy(TemplateRotationPolicy::StickyWeightedRandom)
+            .with_rotation_weight(BrowserTemplate::Chrome130, 1.0)
+            .with_rotation_weight(BrowserTemplate::Firefox135, 1.0)
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        
+        let resolve = |host: &str, entropy: u64| {
+            let mut params = routed_params(host, &[]);
+            params.entropy = Some(entropy);
+            customizer.on_config_resolve(&mut params).unwrap();
+            params.http2_profile.unwrap()
+        };
+        
+        // The first draw decides; later draws for the same target are ignored
+        assert_eq!(resolve("a.example", 0), chrome_130().http2_profile);
+        assert_eq!(resolve("a.example", u64::MAX), chrome_130().http2_profile);
+        assert_eq!(resolve("b.example", u64::MAX), firefox_135().http2_profile);
+        assert_eq!(resolve("b.example", 0), firefox_135().http2_profile);
+        
+        // Without a target each connection draws afresh
+        let mut params = ConfigParams::new();
+        params.entropy = Some(u64::MAX);
+        customizer.on_config_resolve(&mut params).unwrap();
+        assert_eq!(params.http2_profile, Some(firefox_135().http2_profile));
+    }
+    
+    #[test]
+    fn test_sticky_assignments_evict_oldest() {
+        let mut assignments = StickyAssignments::default();
+        let target = |i: u16| TargetKey::new("host.example".to_string(), i);
+        
+        for i in 0..3 {
+            assignments.insert(target(i), BrowserTemplate::Chrome130, 2);
+        }
+        assert!(assignments.get(&target(0)).is_none());
+        assert!(assignments.get(&target(1)).is_some());
+        assert!(assignments.get(&target(2)).is_some());
+    }
+    
+    #[test]
+    fn test_rotation_advances_once_per_connection() {
+        let config = CustlsConfig::builder()
+            .with_randomization_level(RandomizationLevel::None)
+            .with_rotation_policy(TemplateRotationPolicy::RoundRobin)
+            .with_rotation_templates(vec![BrowserTemplate::Chrome130, BrowserTemplate::Firefox135])
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        
+        for expected in [chrome_130(), firefox_135(), chrome_130(), firefox_135()] {
+            let mut params = ConfigParams::new();
+            customizer.on_config_resolve(&mut params).unwrap();
+            assert_eq!(params.http2_profile, Some(expected.http2_profile.clone()));
+            
+            // Phase 2 shapes the hello with the template phase 1 chose
+            let mut cipher_suites = Vec::new();
+            customizer.on_components_ready(&mut cipher_suites, &mut Vec::new()).unwrap();
+            assert_eq!(cipher_suites, expected.cipher_suites);
+        }
+    }
+    
+    #[test]
+    fn test_session_partition_follows_template() {
+        let config = CustlsConfig::builder()
+            .with_randomization_level(RandomizationLevel::None)
+            .with_rotation_policy(TemplateRotationPolicy::RoundRobin)
+            .with_rotation_templates(vec![BrowserTemplate::Chrome130, BrowserTemplate::Firefox135])
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        
+        let mut chrome = ConfigParams::new();
+        customizer.on_config_resolve(&mut chrome).unwrap();
+        let mut firefox = ConfigParams::new();
+        customizer.on_config_resolve(&mut firefox).unwrap();
+        assert_eq!(chrome.session_partition, Some(chrome_130().name.into_bytes()));
+        assert_eq!(firefox.session_partition, Some(firefox_135().name.into_bytes()));
+        
+        let disabled = DefaultCustomizer::new(CustlsConfig::default());
+        let mut params = ConfigParams::new();
+        disabled.on_config_resolve(&mut params).unwrap();
+        assert_eq!(params.session_partition, None);
+    }
+    
+    #[test]
+    fn test_resumption_requires_recorded_fingerprint() {
+        let config = CustlsConfig::builder()
+            .with_randomization_level(RandomizationLevel::None)
+            .with_rotation_policy(TemplateRotationPolicy::RoundRobin)
+            .with_rotation_templates(vec![BrowserTemplate::Chrome130, BrowserTemplate::Firefox135])
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        
+        let mut params = ConfigParams::new();
+        customizer.on_config_resolve(&mut params).unwrap();
+        let chrome = params.session_partition.take().unwrap();
+        customizer.on_config_resolve(&mut params).unwrap();
+        let firefox = params.session_partition.take().unwrap();
+        
+        customizer.on_session_stored(b"ticket", Some(chrome.as_slice()));
+        assert!(customizer.get_session_config(&SessionId::new(b"ticket".to_vec())).is_some());
+        
+        // Offered with the fingerprint it was obtained with, even through a store
+        // that ignores partitions, and never with another one
+        assert!(customizer.on_resumption_offer(b"ticket", Some(chrome.as_slice())).unwrap());
+        assert!(!customizer.on_resumption_offer(b"ticket", Some(firefox.as_slice())).unwrap());
+        assert!(!customizer.on_resumption_offer(b"ticket", None).unwrap());
+        assert!(!customizer.on_resumption_offer(b"unknown", Some(chrome.as_slice())).unwrap());
+        
+        // Without session tracking nothing is recorded and every session is offered
+        let untracked = DefaultCustomizer::new(CustlsConfig::builder().with_cache(false).build());
+        untracked.on_session_stored(b"ticket", Some(chrome.as_slice()));
+        assert!(untracked.on_resumption_offer(b"ticket", Some(firefox.as_slice())).unwrap());
+    }
+    
+    #[test]
+    fn test_resolve_named_template() {
+        let okhttp = DefaultCustomizer::resolve_template(&BrowserTemplate::Named("okhttp_4".to_string()));
+        assert_eq!(okhttp.unwrap().name, crate::custls::templates::okhttp_4().name);
+        
+        // Unknown names are an error, not a silent Chrome hello
+        let unknown = BrowserTemplate::Named("lynx_2".to_string());
+        assert!(matches!(
+            DefaultCustomizer::resolve_template(&unknown),
+            Err(RustlsError::Customization { error: CustlsError::TemplateError(_), .. })
+        ));
+        
+        // ... and fail phase 1 of a connection that would use them
+        let customizer = DefaultCustomizer::new(
+            CustlsConfig::builder()
+                .with_template(unknown)
+                .build(),
+        );
+        assert!(customizer.template().is_none());
+        assert!(customizer.on_config_resolve(&mut ConfigParams::new()).is_err());
+    }
+    
+    #[test]
+    fn test_resolve_custom_template_is_an_error() {
+        let custom = BrowserTemplate::Custom(alloc::boxed::Box::new(crate::custls::CustomTemplate {
+            name: "my_browser".to_string(),
+            description: "no data".to_string(),
+        }));
+        
+        // Not a silent Chrome hello either
+        assert!(matches!(
+            DefaultCustomizer::resolve_template(&custom),
+            Err(RustlsError::Customization { error: CustlsError::TemplateError(_), .. })
+        ));
+        
+        let customizer = DefaultCustomizer::new(
+            CustlsConfig::builder()
+                .with_template(custom)
+                .build(),
+        );
+        assert!(customizer.template().is_none());
+        assert!(matches!(
+            customizer.on_config_resolve(&mut ConfigParams::new()),
+            Err(RustlsError::Customization { error: CustlsError::TemplateError(_), .. })
+        ));
     }
     
     #[test]
@@ -1238,6 +2643,195 @@ mod tests {
         assert_eq!(config1.padding_length, config2.padding_length);
         assert_eq!(config1.random_seed, config2.random_seed);
     }
+    
+    /// Collects the events a customizer reports
+    #[cfg(feature = "std")]
+    #[derive(Debug, Default)]
+    struct RecordingSink(Mutex<Vec<CustlsEvent>>);
+    
+    #[cfg(feature = "std")]
+    impl crate::custls::CustlsEventSink for RecordingSink {
+        fn on_event(&self, event: &CustlsEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+    
+    #[cfg(feature = "std")]
+    impl RecordingSink {
+        fn take(&self) -> Vec<CustlsEvent> {
+            core::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+    
+    #[cfg(feature = "std")]
+    fn test_payload() -> crate::msgs::ClientHelloPayload {
+        use crate::crypto::SignatureScheme;
+        use crate::crypto::kx::NamedGroup;
+        use crate::msgs::{
+            ClientExtensions, ClientHelloPayload, Compression, Random, SessionId,
+            SupportedProtocolVersions,
+        };
+        
+        let mut payload = ClientHelloPayload {
+            client_version: crate::enums::ProtocolVersion::TLSv1_2,
+            random: Random([0u8; 32]),
+            session_id: SessionId::empty(),
+            cipher_suites: vec![CipherSuite::TLS13_AES_128_GCM_SHA256],
+            compression_methods: vec![Compression::Null],
+            extensions: alloc::boxed::Box::new(ClientExtensions::default()),
+        };
+        payload.signature_schemes = Some(vec![SignatureScheme::ED25519]);
+        payload.named_groups = Some(vec![NamedGroup::X25519]);
+        payload.supported_versions = Some(SupportedProtocolVersions { tls13: true, tls12: false });
+        payload
+    }
+    
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_events_report_each_decision() {
+        let sink = Arc::new(RecordingSink::default());
+        let config = CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .with_randomization_level(RandomizationLevel::None)
+            .with_event_sink(sink.clone())
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        let target = TargetKey::new("example.com".to_string(), 443);
+        let name = chrome_130().name;
+        
+        routed_cipher_suites(&customizer, "example.com");
+        let mut payload = test_payload();
+        customizer.on_struct_ready(&mut payload).unwrap();
+        
+        let padding_length = chrome_130().padding_distribution.pmf
+            .first()
+            .map(|(len, _)| *len)
+            .filter(|len| *len > 0);
+        assert_eq!(sink.take(), vec![
+            CustlsEvent::TemplateSelected {
+                target: Some(target.clone()),
+                template: name.clone(),
+                source: TemplateSource::Configured,
+            },
+            CustlsEvent::CacheLookup { target: target.clone(), hit: false },
+            CustlsEvent::ClientHelloShaped {
+                target: Some(target.clone()),
+                template: name.clone(),
+                grease_values: Vec::new(),
+                padding_length,
+                extension_order: payload.used_extensions_in_encoding_order(),
+            },
+        ]);
+        
+        // A recorded success makes the next lookup a hit
+        let hello_config = ClientHelloConfig::from_template(BrowserTemplate::Chrome130, &chrome_130());
+        customizer.record_handshake_result(&target, hello_config, true);
+        assert_eq!(sink.take(), vec![CustlsEvent::HandshakeOutcome {
+            target: target.clone(),
+            template: name.clone(),
+            success: true,
+        }]);
+        
+        customizer.on_config_resolve(&mut routed_params("example.com", &[])).unwrap();
+        assert!(sink.take().contains(&CustlsEvent::CacheLookup { target: target.clone(), hit: true }));
+        
+        let stats = customizer.stats();
+        assert_eq!(stats.templates[&name].selected, 2);
+        assert_eq!(stats.templates[&name].outcomes.successes, 1);
+        assert_eq!(stats.targets[&target][&name].success_rate(), Some(1.0));
+        assert_eq!((stats.cache_hits, stats.cache_misses), (1, 1));
+        assert_eq!(stats.reputation_histogram[crate::custls::REPUTATION_BUCKETS - 1], 1);
+    }
+    
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_events_report_routing_decisions() {
+        use crate::custls::{HostPattern, RoutingRule};
+        
+        let sink = Arc::new(RecordingSink::default());
+        let config = CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .with_cache(false)
+            .with_routing_rule(RoutingRule::new(
+                HostPattern::Suffix("internal.example".to_string()),
+                RouteAction::Disabled,
+            ))
+            .with_routing_rule(RoutingRule::new(
+                HostPattern::Exact("apple.com".to_string()),
+                RouteAction::Template(BrowserTemplate::Safari17),
+            ))
+            .with_event_sink(sink.clone())
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        
+        customizer.on_config_resolve(&mut routed_params("api.internal.example", &[])).unwrap();
+        let mut payload = test_payload();
+        customizer.on_struct_ready(&mut payload).unwrap();
+        assert_eq!(sink.take(), vec![CustlsEvent::CustomizationDisabled {
+            target: Some(TargetKey::new("api.internal.example".to_string(), 443)),
+        }]);
+        
+        // Without a cache there is no lookup to report
+        customizer.on_config_resolve(&mut routed_params("apple.com", &[])).unwrap();
+        assert_eq!(sink.take(), vec![CustlsEvent::TemplateSelected {
+            target: Some(TargetKey::new("apple.com".to_string(), 443)),
+            template: safari_17().name,
+            source: TemplateSource::RoutingRule,
+        }]);
+        
+        let stats = customizer.stats();
+        assert_eq!(stats.disabled, 1);
+        assert_eq!(stats.reputation_histogram, [0; crate::custls::REPUTATION_BUCKETS]);
+    }
+    
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_quarantined_template_is_rotated_away_from() {
+        let sink = Arc::new(RecordingSink::default());
+        let config = CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .with_cache_policy(crate::custls::CachePolicy {
+                quarantine_after: Some(2),
+                ..Default::default()
+            })
+            .with_event_sink(sink.clone())
+            .build();
+        let customizer = DefaultCustomizer::new(config);
+        let target = TargetKey::new("example.com".to_string(), 443);
+        let chrome = ClientHelloConfig::from_template(BrowserTemplate::Chrome130, &chrome_130());
+        
+        customizer.record_handshake_result(&target, chrome.clone(), false);
+        customizer.record_handshake_result(&target, chrome, false);
+        let failure = CustlsEvent::HandshakeOutcome {
+            target: target.clone(),
+            template: chrome_130().name,
+            success: false,
+        };
+        assert_eq!(sink.take(), vec![
+            failure.clone(),
+            failure,
+            CustlsEvent::TargetQuarantined {
+                target: target.clone(),
+                template: chrome_130().name,
+            },
+        ]);
+        
+        customizer.on_config_resolve(&mut routed_params("example.com", &[])).unwrap();
+        assert_eq!(sink.take()[0], CustlsEvent::TemplateSelected {
+            target: Some(target),
+            template: firefox_135().name,
+            source: TemplateSource::Quarantine,
+        });
+        
+        // Other targets keep the configured template
+        customizer.on_config_resolve(&mut routed_params("other.example", &[])).unwrap();
+        assert_eq!(sink.take()[0], CustlsEvent::TemplateSelected {
+            target: Some(TargetKey::new("other.example".to_string(), 443)),
+            template: chrome_130().name,
+            source: TemplateSource::Configured,
+        });
+        assert_eq!(customizer.stats().quarantines, 1);
+    }
 }
 
 
@@ -0,0 +1,230 @@
+//! Browser profiles for `ClientConfig`
+//!
+//! Looking like a browser takes more than a customizer: ALPN, SNI, resumption,
+//! certificate compression and the crypto provider's cipher suites and key
+//! exchange groups all show up in the ClientHello. A browser profile derives all
+//! of these from one template:
+//!
+//! ```rust,ignore
+//! use rustls::ClientConfig;
+//! use rustls::custls::BrowserTemplate;
+//!
+//! let config = ClientConfig::builder(provider)
+//!     .with_browser_profile(BrowserTemplate::Chrome130)
+//!     .with_root_certificates(roots)
+//!     .with_no_client_auth()?;
+//!
+//! for mismatch in &config.browser_profile().unwrap().mismatches {
+//!     log::warn!("ClientHello differs from Chrome: {mismatch}");
+//! }
+//! ```
+//!
+//! The provider is narrowed and reordered to the template's cipher suites and
+//! groups. Whatever the provider cannot implement is reported as a
+//! [`ProfileMismatch`]; building fails only when no template cipher suite or
+//! group is left.
+
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::orchestrator::DefaultCustomizer;
+use super::templates::TemplateData;
+use super::{BrowserTemplate, CustlsConfig, CustlsError};
+use crate::client::{ClientConfig, Resumption, Tls12Resumption};
+use crate::compress;
+use crate::crypto::kx::NamedGroup;
+use crate::crypto::{CipherSuite, CryptoProvider};
+use crate::enums::{ApplicationProtocol, ProtocolVersion};
+use crate::error::Error;
+use crate::msgs::ExtensionType;
+
+/// The browser profile a `ClientConfig` was built with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrowserProfile {
+    /// Template the configuration was derived from
+    pub template: BrowserTemplate,
+
+    /// Ways the ClientHello differs from the template
+    pub mismatches: Vec<ProfileMismatch>,
+}
+
+impl BrowserProfile {
+    /// Whether the ClientHello matches the template in every respect
+    pub fn is_exact(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// A way a profiled ClientHello differs from its template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileMismatch {
+    /// The provider does not implement this cipher suite
+    ///
+    /// It is still advertised, but a server selecting it fails the handshake.
+    CipherSuiteNotImplemented(CipherSuite),
+
+    /// The provider does not implement this group
+    ///
+    /// It is missing from `supported_groups` and gets no key share.
+    GroupNotImplemented(NamedGroup),
+
+    /// The provider has no cipher suite for this version, so it is not offered
+    VersionNotImplemented(ProtocolVersion),
+
+    /// No certificate decompressor is built in, so `compress_certificate` is not sent
+    CertCompressionUnavailable,
+}
+
+impl fmt::Display for ProfileMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CipherSuiteNotImplemented(suite) => {
+                write!(f, "cipher suite {suite:?} is advertised but not implemented")
+            }
+            Self::GroupNotImplemented(group) => write!(f, "group {group:?} is not implemented"),
+            Self::VersionNotImplemented(version) => {
+                write!(f, "{version:?} has no implemented cipher suite")
+            }
+            Self::CertCompressionUnavailable => {
+                write!(f, "no certificate decompressor is available")
+            }
+        }
+    }
+}
+
+/// Template data behind `template`
+///
+/// Like the customizer, a profile refuses names that are not registered and
+/// custom templates.
+pub(crate) fn profile_template(template: &BrowserTemplate) -> Result<TemplateData, Error> {
+    match template {
+        BrowserTemplate::Custom(_) => Err(CustlsError::TemplateError(
+            "custom templates cannot be used as a browser profile".into(),
+        )
+        .into()),
+        template => DefaultCustomizer::resolve_template(template),
+    }
+}
+
+/// Narrow `provider` to the cipher suites and groups of `template`, in template order
+///
+/// TLS 1.2 is dropped when the template does not offer it or `tls12_allowed` is
+/// false (ECH requires a TLS 1.3-only configuration). Everything the template lists
+/// but the provider lacks is returned as a mismatch.
+pub(crate) fn tailor_provider(
+    template: &TemplateData,
+    provider: &CryptoProvider,
+    tls12_allowed: bool,
+) -> Result<(CryptoProvider, Vec<ProfileMismatch>), Error> {
+    let mut mismatches = Vec::new();
+    let offers = |version| template.supported_versions.contains(&version);
+
+    let mut tls13_cipher_suites = Vec::new();
+    let mut tls12_cipher_suites = Vec::new();
+    for suite in &template.cipher_suites {
+        let tls13 = provider
+            .tls13_cipher_suites
+            .iter()
+            .find(|cs| cs.common.suite == *suite);
+        let tls12 = provider
+            .tls12_cipher_suites
+            .iter()
+            .find(|cs| cs.common.suite == *suite);
+
+        match (tls13, tls12) {
+            (Some(cs), _) if offers(ProtocolVersion::TLSv1_3) => tls13_cipher_suites.push(*cs),
+            (_, Some(cs)) if offers(ProtocolVersion::TLSv1_2) && tls12_allowed => {
+                tls12_cipher_suites.push(*cs)
+            }
+            (None, None) => mismatches.push(ProfileMismatch::CipherSuiteNotImplemented(*suite)),
+            _ => {}
+        }
+    }
+
+    if offers(ProtocolVersion::TLSv1_3) && tls13_cipher_suites.is_empty() {
+        mismatches.push(ProfileMismatch::VersionNotImplemented(ProtocolVersion::TLSv1_3));
+    }
+    if offers(ProtocolVersion::TLSv1_2) && tls12_cipher_suites.is_empty() {
+        mismatches.push(ProfileMismatch::VersionNotImplemented(ProtocolVersion::TLSv1_2));
+    }
+
+    let mut kx_groups = Vec::new();
+    for group in &template.supported_groups {
+        match provider
+            .kx_groups
+            .iter()
+            .find(|kx| kx.name() == *group)
+        {
+            Some(kx) => kx_groups.push(*kx),
+            None => mismatches.push(ProfileMismatch::GroupNotImplemented(*group)),
+        }
+    }
+
+    if tls13_cipher_suites.is_empty() && tls12_cipher_suites.is_empty() {
+        return Err(CustlsError::TemplateError(format!(
+            "{} shares no cipher suite with the crypto provider",
+            template.name
+        ))
+        .into());
+    }
+    if kx_groups.is_empty() {
+        return Err(CustlsError::TemplateError(format!(
+            "{} shares no key exchange group with the crypto provider",
+            template.name
+        ))
+        .into());
+    }
+
+    let tailored = CryptoProvider {
+        tls12_cipher_suites: tls12_cipher_suites.into(),
+        tls13_cipher_suites: tls13_cipher_suites.into(),
+        kx_groups: kx_groups.into(),
+        ..provider.clone()
+    };
+    Ok((tailored, mismatches))
+}
+
+/// Set the `ClientConfig` fields `template` determines, and install its customizer
+pub(crate) fn apply_profile(
+    config: &mut ClientConfig,
+    browser: BrowserTemplate,
+    template: &TemplateData,
+    mut mismatches: Vec<ProfileMismatch>,
+) {
+    let sends = |ext| template.extension_order.contains(&ext);
+
+    config.alpn_protocols = template
+        .alpn_protocols
+        .iter()
+        .map(|protocol| ApplicationProtocol::from(protocol.clone()))
+        .collect();
+    config.enable_sni = sends(ExtensionType::ServerName);
+
+    config.resumption = match (
+        sends(ExtensionType::PSKKeyExchangeModes),
+        sends(ExtensionType::SessionTicket),
+    ) {
+        (false, false) => Resumption::disabled(),
+        (_, true) => Resumption::default(),
+        (_, false) => Resumption::default().tls12_resumption(Tls12Resumption::SessionIdOnly),
+    };
+
+    config.cert_decompressors = Vec::new();
+    if sends(ExtensionType::CompressCertificate) {
+        config.cert_decompressors = compress::default_cert_decompressors().to_vec();
+        if config.cert_decompressors.is_empty() {
+            mismatches.push(ProfileMismatch::CertCompressionUnavailable);
+        }
+    }
+
+    let custls_config = CustlsConfig::builder()
+        .with_template(browser.clone())
+        .build();
+    config.enable_custls(Arc::new(DefaultCustomizer::new(custls_config)));
+    config.browser_profile = Some(BrowserProfile {
+        template: browser,
+        mismatches,
+    });
+}
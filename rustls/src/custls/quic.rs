@@ -0,0 +1,385 @@
+//! QUIC ClientHello shaping
+//!
+//! A QUIC client carries its TLS 1.3 ClientHello in CRYPTO frames (RFC 9001), and
+//! the hello looks different from one sent over TCP:
+//!
+//! - only TLS 1.3 cipher suites, and never `TLS_AES_128_CCM_8_SHA256`
+//! - no TLS 1.2 extensions, no legacy session ID and no middlebox compatibility mode
+//! - ALPN `h3` instead of `h2` and `http/1.1`
+//! - a `quic_transport_parameters` extension, whose parameter order and GREASE
+//!   parameters identify the QUIC stack as well as the cipher suites do
+//!
+//! The QUIC templates (`chrome_130_quic()`, `firefox_135_quic()`) carry a
+//! [`QuicProfile`] describing the transport parameters. The application still
+//! encodes its own parameters and passes them to `quic::ClientConnection::new`;
+//! `DefaultCustomizer` decodes them with [`QuicTransportParameters`], reorders them
+//! and adds GREASE the way the browser does.
+//!
+//! ```rust,ignore
+//! use rustls::custls::quic::{QuicTransportParameters, TransportParameter};
+//!
+//! let mut params = QuicTransportParameters::new();
+//! params.push(TransportParameter::new(TransportParameter::MAX_IDLE_TIMEOUT, vec![0x80, 0x00, 0x75, 0x30]));
+//! params.push(TransportParameter::varint(TransportParameter::INITIAL_MAX_DATA, 15_728_640));
+//! let encoded = params.encode();
+//! ```
+//!
+//! Whatever a customizer does, rustls removes what QUIC forbids from the final
+//! hello: TLS 1.2 cipher suites and extensions, and a non-empty legacy session ID.
+
+use alloc::vec::Vec;
+
+use super::CustlsError;
+use crate::crypto::CipherSuite;
+use crate::msgs::ClientHelloPayload;
+
+/// Largest value a QUIC variable-length integer can hold (RFC 9000 Section 16)
+pub const MAX_VARINT: u64 = (1 << 62) - 1;
+
+/// One QUIC transport parameter (RFC 9000 Section 18)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportParameter {
+    /// Parameter ID
+    pub id: u64,
+
+    /// Encoded parameter value
+    pub value: Vec<u8>,
+}
+
+impl TransportParameter {
+    /// `original_destination_connection_id` (server only)
+    pub const ORIGINAL_DESTINATION_CONNECTION_ID: u64 = 0x00;
+    /// `max_idle_timeout`
+    pub const MAX_IDLE_TIMEOUT: u64 = 0x01;
+    /// `stateless_reset_token` (server only)
+    pub const STATELESS_RESET_TOKEN: u64 = 0x02;
+    /// `max_udp_payload_size`
+    pub const MAX_UDP_PAYLOAD_SIZE: u64 = 0x03;
+    /// `initial_max_data`
+    pub const INITIAL_MAX_DATA: u64 = 0x04;
+    /// `initial_max_stream_data_bidi_local`
+    pub const INITIAL_MAX_STREAM_DATA_BIDI_LOCAL: u64 = 0x05;
+    /// `initial_max_stream_data_bidi_remote`
+    pub const INITIAL_MAX_STREAM_DATA_BIDI_REMOTE: u64 = 0x06;
+    /// `initial_max_stream_data_uni`
+    pub const INITIAL_MAX_STREAM_DATA_UNI: u64 = 0x07;
+    /// `initial_max_streams_bidi`
+    pub const INITIAL_MAX_STREAMS_BIDI: u64 = 0x08;
+    /// `initial_max_streams_uni`
+    pub const INITIAL_MAX_STREAMS_UNI: u64 = 0x09;
+    /// `ack_delay_exponent`
+    pub const ACK_DELAY_EXPONENT: u64 = 0x0a;
+    /// `max_ack_delay`
+    pub const MAX_ACK_DELAY: u64 = 0x0b;
+    /// `disable_active_migration`
+    pub const DISABLE_ACTIVE_MIGRATION: u64 = 0x0c;
+    /// `active_connection_id_limit`
+    pub const ACTIVE_CONNECTION_ID_LIMIT: u64 = 0x0e;
+    /// `initial_source_connection_id`
+    pub const INITIAL_SOURCE_CONNECTION_ID: u64 = 0x0f;
+    /// `version_information` (RFC 9368)
+    pub const VERSION_INFORMATION: u64 = 0x11;
+    /// `max_datagram_frame_size` (RFC 9221)
+    pub const MAX_DATAGRAM_FRAME_SIZE: u64 = 0x20;
+    /// `grease_quic_bit` (RFC 9287)
+    pub const GREASE_QUIC_BIT: u64 = 0x2ab2;
+    /// `google_connection_options`, sent by Chrome
+    pub const GOOGLE_CONNECTION_OPTIONS: u64 = 0x3128;
+    /// `google_version`, sent by Chrome
+    pub const GOOGLE_VERSION: u64 = 0x4752;
+    /// `min_ack_delay` (draft-ietf-quic-ack-frequency)
+    pub const MIN_ACK_DELAY: u64 = 0xff04de1b;
+
+    /// A parameter with an already encoded value
+    pub fn new(id: u64, value: Vec<u8>) -> Self {
+        Self { id, value }
+    }
+
+    /// A parameter whose value is a single variable-length integer
+    pub fn varint(id: u64, value: u64) -> Self {
+        let mut encoded = Vec::new();
+        encode_varint(value, &mut encoded);
+        Self { id, value: encoded }
+    }
+
+    /// Whether this is a reserved GREASE parameter
+    pub fn is_grease(&self) -> bool {
+        is_grease_transport_parameter(self.id)
+    }
+}
+
+/// Whether `id` is reserved for GREASE, i.e. of the form `31 * N + 27`
+///
+/// RFC 9000 Section 18.1
+pub fn is_grease_transport_parameter(id: u64) -> bool {
+    id % 31 == 27
+}
+
+/// QUIC transport parameters in the order they are encoded
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuicTransportParameters {
+    params: Vec<TransportParameter>,
+}
+
+impl QuicTransportParameters {
+    /// An empty parameter list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode the contents of a `quic_transport_parameters` extension
+    ///
+    /// Parameter order and duplicates are kept as they are.
+    pub fn decode(mut bytes: &[u8]) -> Result<Self, CustlsError> {
+        let mut params = Vec::new();
+        while !bytes.is_empty() {
+            let id = read_varint(&mut bytes)?;
+            let len = read_varint(&mut bytes)?;
+            let len = usize::try_from(len)
+                .ok()
+                .filter(|len| *len <= bytes.len())
+                .ok_or_else(|| {
+                    CustlsError::ExtensionError(alloc::format!(
+                        "transport parameter {id:#x} is truncated"
+                    ))
+                })?;
+            let (value, rest) = bytes.split_at(len);
+            params.push(TransportParameter::new(id, value.to_vec()));
+            bytes = rest;
+        }
+        Ok(Self { params })
+    }
+
+    /// Encode the parameters for a `quic_transport_parameters` extension
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for param in &self.params {
+            encode_varint(param.id, &mut bytes);
+            encode_varint(param.value.len() as u64, &mut bytes);
+            bytes.extend_from_slice(&param.value);
+        }
+        bytes
+    }
+
+    /// Append a parameter
+    pub fn push(&mut self, param: TransportParameter) {
+        self.params.push(param);
+    }
+
+    /// The parameters in encoding order
+    pub fn params(&self) -> &[TransportParameter] {
+        &self.params
+    }
+
+    /// Parameter IDs in encoding order
+    pub fn ids(&self) -> Vec<u64> {
+        self.params
+            .iter()
+            .map(|param| param.id)
+            .collect()
+    }
+
+    /// Whether a GREASE parameter is present
+    pub fn has_grease(&self) -> bool {
+        self.params
+            .iter()
+            .any(TransportParameter::is_grease)
+    }
+
+    /// Move the parameters listed in `order` to the front, in that order
+    ///
+    /// Parameters `order` does not mention follow in their current order.
+    pub fn order_by(&mut self, order: &[u64]) {
+        self.params.sort_by_key(|param| {
+            order
+                .iter()
+                .position(|id| *id == param.id)
+                .unwrap_or(order.len())
+        });
+    }
+
+    pub(crate) fn params_mut(&mut self) -> &mut Vec<TransportParameter> {
+        &mut self.params
+    }
+}
+
+/// Whether a QUIC ClientHello may offer `suite`
+///
+/// QUIC runs TLS 1.3 only (RFC 9001 Section 4.2), and RFC 9001 Section 5.3 rules
+/// out `TLS_AES_128_CCM_8_SHA256`. GREASE values are allowed.
+pub fn quic_permits_cipher_suite(suite: CipherSuite) -> bool {
+    let value = u16::from(suite);
+    let tls13 = value >> 8 == 0x13 && suite != CipherSuite::TLS13_AES_128_CCM_8_SHA256;
+    tls13 || is_grease_codepoint(value)
+}
+
+/// Remove what QUIC forbids from a shaped ClientHello.
+///
+/// Cipher suites are kept if QUIC permits them or they are in `implemented`, the
+/// provider's QUIC-capable suites. The TLS 1.2 extensions (`session_ticket`,
+/// `extended_master_secret`, `renegotiation_info`, `ec_point_formats`) are dropped
+/// and the legacy session ID is emptied (RFC 9001 Section 8.4).
+pub(crate) fn drop_forbidden_in_quic(payload: &mut ClientHelloPayload, implemented: &[CipherSuite]) {
+    payload
+        .cipher_suites
+        .retain(|suite| quic_permits_cipher_suite(*suite) || implemented.contains(suite));
+
+    payload.session_ticket = None;
+    payload.extended_master_secret_request = None;
+    payload.renegotiation_info = None;
+    payload.ec_point_formats = None;
+    if let Some(versions) = &mut payload.supported_versions {
+        versions.tls12 = false;
+    }
+
+    let used = payload.collect_used();
+    payload
+        .contiguous_extensions
+        .retain(|ext| used.contains(ext));
+
+    payload.session_id = crate::msgs::SessionId::empty();
+}
+
+fn is_grease_codepoint(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+fn encode_varint(value: u64, bytes: &mut Vec<u8>) {
+    match value {
+        0..=0x3f => bytes.push(value as u8),
+        0x40..=0x3fff => bytes.extend_from_slice(&(value as u16 | 0x4000).to_be_bytes()),
+        0x4000..=0x3fff_ffff => bytes.extend_from_slice(&(value as u32 | 0x8000_0000).to_be_bytes()),
+        _ => bytes.extend_from_slice(&((value & MAX_VARINT) | 0xc000_0000_0000_0000).to_be_bytes()),
+    }
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, CustlsError> {
+    let truncated = || CustlsError::ExtensionError("truncated QUIC variable-length integer".into());
+    let first = *bytes.first().ok_or_else(truncated)?;
+    let len = 1usize << (first >> 6);
+    if bytes.len() < len {
+        return Err(truncated());
+    }
+
+    let (encoded, rest) = bytes.split_at(len);
+    let mut value = u64::from(first & 0x3f);
+    for byte in &encoded[1..] {
+        value = (value << 8) | u64::from(*byte);
+    }
+    *bytes = rest;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_varint_round_trip() {
+        // RFC 9000 Appendix A.1 examples
+        for (value, encoded) in [
+            (37u64, vec![0x25]),
+            (15_293, vec![0x7b, 0xbd]),
+            (494_878_333, vec![0x9d, 0x7f, 0x3e, 0x7d]),
+            (151_288_809_941_952_652, vec![0xc2, 0x19, 0x7c, 0x5e, 0xff, 0x14, 0xe8, 0x8c]),
+        ] {
+            let mut bytes = Vec::new();
+            encode_varint(value, &mut bytes);
+            assert_eq!(bytes, encoded);
+            assert_eq!(read_varint(&mut bytes.as_slice()).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_transport_parameters_round_trip() {
+        let mut params = QuicTransportParameters::new();
+        params.push(TransportParameter::varint(TransportParameter::INITIAL_MAX_DATA, 15_728_640));
+        params.push(TransportParameter::new(TransportParameter::GREASE_QUIC_BIT, Vec::new()));
+        params.push(TransportParameter::varint(TransportParameter::MIN_ACK_DELAY, 1000));
+
+        let decoded = QuicTransportParameters::decode(&params.encode()).unwrap();
+        assert_eq!(decoded, params);
+    }
+
+    #[test]
+    fn test_decode_rejects_truncated_parameters() {
+        // Length 4, but only two value bytes follow
+        assert!(QuicTransportParameters::decode(&[0x01, 0x04, 0x80, 0x00]).is_err());
+        // ID varint announces two bytes
+        assert!(QuicTransportParameters::decode(&[0x40]).is_err());
+    }
+
+    #[test]
+    fn test_order_by_keeps_unlisted_parameters_in_place() {
+        let mut params = QuicTransportParameters::new();
+        for id in [0x01, 0x04, 0x2ab2, 0x0f] {
+            params.push(TransportParameter::new(id, Vec::new()));
+        }
+
+        params.order_by(&[0x0f, 0x01]);
+        assert_eq!(params.ids(), vec![0x0f, 0x01, 0x04, 0x2ab2]);
+    }
+
+    #[test]
+    fn test_grease_transport_parameter_ids() {
+        assert!(is_grease_transport_parameter(27));
+        assert!(is_grease_transport_parameter(31 * 1000 + 27));
+        assert!(!is_grease_transport_parameter(TransportParameter::GREASE_QUIC_BIT));
+    }
+
+    #[test]
+    fn test_drop_forbidden_in_quic() {
+        use crate::msgs::{
+            ClientExtensions, ClientSessionTicket, Compression, ExtensionType, Random, SessionId,
+            SupportedEcPointFormats, SupportedProtocolVersions,
+        };
+
+        let mut payload = ClientHelloPayload {
+            client_version: crate::enums::ProtocolVersion::TLSv1_2,
+            random: Random([0u8; 32]),
+            session_id: SessionId::random_with_len(crate::crypto::TEST_PROVIDER.secure_random, 32)
+                .unwrap(),
+            cipher_suites: vec![
+                CipherSuite::from(0x0a0a),
+                CipherSuite::TLS13_AES_128_GCM_SHA256,
+                CipherSuite::TLS13_AES_128_CCM_8_SHA256,
+                CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+                CipherSuite::Unknown(0xff13),
+            ],
+            compression_methods: vec![Compression::Null],
+            extensions: alloc::boxed::Box::new(ClientExtensions::default()),
+        };
+        payload.session_ticket = Some(ClientSessionTicket::Request);
+        payload.extended_master_secret_request = Some(());
+        payload.ec_point_formats = Some(SupportedEcPointFormats::default());
+        payload.supported_versions = Some(SupportedProtocolVersions { tls13: true, tls12: true });
+        payload.contiguous_extensions = vec![
+            ExtensionType::ExtendedMasterSecret,
+            ExtensionType::SupportedVersions,
+        ];
+
+        drop_forbidden_in_quic(&mut payload, &[CipherSuite::Unknown(0xff13)]);
+
+        assert_eq!(payload.cipher_suites, vec![
+            CipherSuite::from(0x0a0a),
+            CipherSuite::TLS13_AES_128_GCM_SHA256,
+            CipherSuite::Unknown(0xff13),
+        ]);
+        assert!(payload.session_ticket.is_none());
+        assert!(payload.extended_master_secret_request.is_none());
+        assert!(payload.ec_point_formats.is_none());
+        assert!(payload.session_id.is_empty());
+        assert!(!payload.supported_versions.unwrap().tls12);
+        assert_eq!(payload.contiguous_extensions, vec![ExtensionType::SupportedVersions]);
+    }
+
+    #[test]
+    fn test_quic_permits_only_tls13_suites() {
+        assert!(quic_permits_cipher_suite(CipherSuite::TLS13_AES_128_GCM_SHA256));
+        assert!(quic_permits_cipher_suite(CipherSuite::from(0x3a3a)));
+        assert!(!quic_permits_cipher_suite(CipherSuite::TLS13_AES_128_CCM_8_SHA256));
+        assert!(!quic_permits_cipher_suite(CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256));
+        assert!(!quic_permits_cipher_suite(CipherSuite::TLS_EMPTY_RENEGOTIATION_INFO_SCSV));
+    }
+}
@@ -29,22 +29,41 @@
 //!     NaturalnessFilter::default(),
 //! );
 //!
-//! // Shuffle extensions with grouped constraints
+//! // Shuffle extension types, in encoding order, with grouped constraints
 //! randomizer.shuffle_extensions(&mut extensions, &template)?;
 //!
 //! // Inject GREASE values
-//! randomizer.inject_grease(&mut cipher_suites, &mut extensions, &template)?;
+//! randomizer.inject_grease(&mut cipher_suites, &mut extensions, &template, &[])?;
 //!
 //! // Generate padding length
 //! let padding_len = randomizer.generate_padding_len(&template);
 //! ```
 
+use alloc::vec;
 use alloc::vec::Vec;
 
-use crate::custls::{RandomizationLevel, ClientExtension};
+use crate::custls::RandomizationLevel;
+use crate::custls::capture::is_grease_value;
 use crate::crypto::CipherSuite;
-use crate::custls::templates::{TemplateData, NaturalnessFilter};
+use crate::crypto::cipher::Payload;
+use crate::custls::quic::{MAX_VARINT, QuicTransportParameters, TransportParameter};
+use crate::custls::templates::{EchGreaseShape, QuicProfile, TemplateData, NaturalnessFilter};
 use crate::error::Error as RustlsError;
+use crate::msgs::{EncryptedClientHello, EncryptedClientHelloOuter, ExtensionType, SizedPayload};
+
+/// Extensions the encoder places last, in this order
+pub(crate) const TRAILING_EXTENSIONS: [ExtensionType; 3] = [
+    ExtensionType::EncryptedClientHelloOuterExtensions,
+    ExtensionType::EncryptedClientHello,
+    ExtensionType::PreSharedKey,
+];
+
+/// Extensions [`BrowserRandomizer::shuffle_extensions`] never moves
+const PINNED_EXTENSIONS: [ExtensionType; 3] = [
+    ExtensionType::SupportedVersions,
+    ExtensionType::KeyShare,
+    ExtensionType::Padding,
+];
 
 // Simple pseudo-random number generator for custls
 // This is a placeholder until rand crate is added as a dependency
@@ -54,8 +73,18 @@ struct SimpleRng {
 
 impl SimpleRng {
     fn new() -> Self {
-        // Use a simple seed - in production this would use system entropy
-        Self { state: 0x123456789ABCDEF0 }
+        // Each randomizer gets its own seed, so connections made through different
+        // customizers or processes do not share one padding and GREASE sequence
+        Self::from_seed(super::utils::entropy_seed())
+    }
+    
+    fn from_seed(seed: u64) -> Self {
+        // xorshift never leaves the all-zero state
+        let state = match seed {
+            0 => 0x123456789ABCDEF0,
+            seed => seed,
+        };
+        Self { state }
     }
     
     fn next_u64(&mut self) -> u64 {
@@ -83,6 +112,13 @@ impl SimpleRng {
         (random as f64) / (u64::MAX as f64)
     }
     
+    fn fill(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let random = self.next_u64().to_be_bytes();
+            chunk.copy_from_slice(&random[..chunk.len()]);
+        }
+    }
+    
     fn choose<'a, T>(&mut self, slice: &'a [T]) -> Option<&'a T> {
         if slice.is_empty() {
             return None;
@@ -136,6 +172,32 @@ impl BrowserRandomizer {
         }
     }
     
+    /// Create a BrowserRandomizer whose generator starts from `seed`
+    ///
+    /// Randomizers created with the same seed produce the same sequence, so this
+    /// is only for reproducing a run; [`BrowserRandomizer::new`] seeds every
+    /// randomizer separately.
+    #[cfg(test)]
+    pub(crate) fn with_seed(
+        level: RandomizationLevel,
+        naturalness_filter: NaturalnessFilter,
+        seed: u64,
+    ) -> Self {
+        Self {
+            level,
+            rng: SimpleRng::from_seed(seed),
+            naturalness_filter,
+        }
+    }
+    
+    /// Fold fresh entropy into the generator state
+    ///
+    /// The orchestrator passes the crypto provider's entropy for every ClientHello,
+    /// so the sequence cannot be predicted from earlier connections.
+    pub(crate) fn mix_entropy(&mut self, entropy: u64) {
+        self.rng = SimpleRng::from_seed(self.rng.next_u64() ^ entropy);
+    }
+    
     /// Get the current randomization level
     pub fn level(&self) -> RandomizationLevel {
         self.level
@@ -148,28 +210,31 @@ impl BrowserRandomizer {
     
     /// Shuffle extensions with grouped constraints
     ///
+    /// `extensions` are the extension types of a ClientHello in encoding order.
     /// This method applies browser-style extension shuffling that respects grouped
     /// constraints. Extensions are categorized into:
-    /// - **Critical**: Must maintain specific positions (supported_versions, key_share, pre_shared_key)
-    /// - **Standard**: Common extensions that can be shuffled within their group
-    /// - **Optional**: Less common extensions that can be shuffled more freely
+    /// - **Critical**: Keep their position (supported_versions, key_share, padding)
+    /// - **Standard**: Extensions the template lists, shuffled among their positions
+    /// - **Optional**: Extensions the template does not list, shuffled among theirs
+    ///
+    /// Light and Medium randomization swap a quarter and a half as many neighbouring
+    /// pairs in each group as it has members; High shuffles each group completely.
     ///
     /// # Constraints
     ///
-    /// - PSK (pre_shared_key) extension MUST always appear last when present
-    /// - Critical extensions maintain browser-appropriate positions
-    /// - Shuffling respects template-specific ordering preferences
-    /// - Result is validated against naturalness filter
+    /// - PSK (pre_shared_key) and ECH extensions stay last when present
+    /// - GREASE extensions keep their position
+    /// - The set of extensions is unchanged
     ///
     /// # Parameters
     ///
     /// - `extensions`: Mutable reference to extension list to shuffle
-    /// - `template`: Browser template providing ordering preferences
+    /// - `template`: Browser template whose extension order defines the groups
     ///
     /// # Returns
     ///
-    /// - `Ok(())` if shuffling succeeded and passed naturalness filter
-    /// - `Err(error)` if shuffling failed or result was unnatural
+    /// `Ok(())`; the extension list cannot be rejected, since shuffling never
+    /// changes which extensions are present.
     ///
     /// # Example
     ///
@@ -180,30 +245,58 @@ impl BrowserRandomizer {
     /// ```
     pub fn shuffle_extensions(
         &mut self,
-        _extensions: &mut Vec<ClientExtension>,
-        _template: &TemplateData,
+        extensions: &mut [ExtensionType],
+        template: &TemplateData,
     ) -> Result<(), RustlsError> {
         // If no randomization, return immediately
         if self.level == RandomizationLevel::None {
             return Ok(());
         }
         
-        // TODO: Once ClientExtension has real implementation with extension_type() method:
-        // 1. Separate PSK extension if present (must be last)
-        // 2. Group remaining extensions into critical, standard, optional
-        // 3. Shuffle within groups based on randomization level
-        // 4. Reassemble: critical + standard + optional + PSK (if present)
-        // 5. Validate against naturalness filter
-        // 6. Retry if unnatural (up to 3 attempts)
-        
-        // For now, with placeholder types, we can't actually shuffle
-        // This will be implemented once ClientExtension integration is complete
+        let mut standard = Vec::new();
+        let mut optional = Vec::new();
+        for (index, ext) in extensions.iter().enumerate() {
+            let fixed = PINNED_EXTENSIONS.contains(ext)
+                || TRAILING_EXTENSIONS.contains(ext)
+                || is_grease_value(u16::from(*ext));
+            match (fixed, template.extension_order.contains(ext)) {
+                (true, _) => {}
+                (false, true) => standard.push(index),
+                (false, false) => optional.push(index),
+            }
+        }
         
-        // Placeholder implementation that does nothing
-        // This ensures the API is correct even though functionality is deferred
+        for positions in [standard, optional] {
+            self.shuffle_positions(extensions, &positions);
+        }
         Ok(())
     }
     
+    /// Shuffle the entries of `list` at `positions` among themselves
+    fn shuffle_positions(&mut self, list: &mut [ExtensionType], positions: &[usize]) {
+        if positions.len() < 2 {
+            return;
+        }
+        
+        let swaps = match self.level {
+            RandomizationLevel::None => 0,
+            RandomizationLevel::Light => (positions.len() / 4).max(1),
+            RandomizationLevel::Medium => (positions.len() / 2).max(1),
+            RandomizationLevel::High => {
+                for i in (1..positions.len()).rev() {
+                    let j = (self.rng.next_u64() % (i as u64 + 1)) as usize;
+                    list.swap(positions[i], positions[j]);
+                }
+                return;
+            }
+        };
+        
+        for _ in 0..swaps {
+            let i = (self.rng.next_u64() % (positions.len() as u64 - 1)) as usize;
+            list.swap(positions[i], positions[i + 1]);
+        }
+    }
+    
     /// Inject GREASE values into cipher suites and extensions
     ///
     /// GREASE (Generate Random Extensions And Sustain Extensibility) is defined in RFC 8701.
@@ -215,10 +308,14 @@ impl BrowserRandomizer {
     /// - **Firefox**: Distributes GREASE more evenly (positions 0.0, 0.25, 0.5, 0.75)
     /// - **Safari**: Conservative GREASE usage (80% probability)
     ///
+    /// A normalized position `p` puts the GREASE value at index `p * len` of the
+    /// list it goes into, counting only the extensions ahead of padding, ECH and
+    /// PSK, which stay last.
+    ///
     /// # Parameters
     ///
     /// - `cipher_suites`: Mutable reference to cipher suite list
-    /// - `extensions`: Mutable reference to extension list
+    /// - `extensions`: Mutable reference to extension types, in encoding order
     /// - `template`: Browser template providing GREASE pattern
     /// - `previous_grease_values`: Previously used GREASE values to avoid
     ///
@@ -238,8 +335,8 @@ impl BrowserRandomizer {
     /// ```
     pub fn inject_grease(
         &mut self,
-        _cipher_suites: &mut Vec<CipherSuite>,
-        _extensions: &mut Vec<ClientExtension>,
+        cipher_suites: &mut Vec<CipherSuite>,
+        extensions: &mut Vec<ExtensionType>,
         template: &TemplateData,
         previous_grease_values: &[u16],
     ) -> Result<Vec<u16>, RustlsError> {
@@ -260,26 +357,8 @@ impl BrowserRandomizer {
             ) {
                 used_grease_values.push(grease_value);
                 
-                // Select a position based on template's preferred positions
-                let _position = if !grease_pattern.cipher_suite_positions.is_empty() {
-                    let normalized_pos = self.rng.choose(&grease_pattern.cipher_suite_positions)
-                        .copied()
-                        .unwrap_or(0.0);
-                    
-                    // Convert normalized position (0.0-1.0) to actual index
-                    // let max_index = cipher_suites.len();
-                    // (normalized_pos * max_index as f64) as usize
-                    (normalized_pos * 10.0) as usize  // Placeholder
-                } else {
-                    0
-                };
-                
-                // TODO: Once CipherSuite has real implementation:
-                // Create GREASE cipher suite and insert at position
-                // For now, this is a placeholder
-                
-                // Placeholder: would insert GREASE cipher suite here
-                // cipher_suites.insert(position.min(cipher_suites.len()), grease_cipher_suite);
+                let position = self.grease_position(&grease_pattern.cipher_suite_positions, cipher_suites.len());
+                cipher_suites.insert(position, CipherSuite::from(grease_value));
             }
         }
         
@@ -297,32 +376,31 @@ impl BrowserRandomizer {
             ) {
                 used_grease_values.push(grease_value);
                 
-                // Select a position based on template's preferred positions
-                let _position = if !grease_pattern.extension_positions.is_empty() {
-                    let normalized_pos = self.rng.choose(&grease_pattern.extension_positions)
-                        .copied()
-                        .unwrap_or(0.0);
-                    
-                    // Convert normalized position (0.0-1.0) to actual index
-                    // let max_index = extensions.len();
-                    // (normalized_pos * max_index as f64) as usize
-                    (normalized_pos * 10.0) as usize  // Placeholder
-                } else {
-                    0
-                };
-                
-                // TODO: Once ClientExtension has real implementation:
-                // Create GREASE extension and insert at position
-                // For now, this is a placeholder
-                
-                // Placeholder: would insert GREASE extension here
-                // extensions.insert(position.min(extensions.len()), grease_extension);
+                let len = extensions
+                    .iter()
+                    .position(|ext| {
+                        *ext == ExtensionType::Padding || TRAILING_EXTENSIONS.contains(ext)
+                    })
+                    .unwrap_or(extensions.len());
+                let position = self.grease_position(&grease_pattern.extension_positions, len);
+                extensions.insert(position, ExtensionType::from(grease_value));
             }
         }
         
         Ok(used_grease_values)
     }
     
+    /// Index for a GREASE value in a list of `len` entries, from one of the
+    /// template's normalized positions, or the front if it has none
+    fn grease_position(&mut self, positions: &[f64], len: usize) -> usize {
+        let normalized = self
+            .rng
+            .choose(positions)
+            .copied()
+            .unwrap_or(0.0);
+        ((normalized * len as f64) as usize).min(len)
+    }
+    
     /// Generate padding length from template's distribution
     ///
     /// This method samples a padding length from the template's padding distribution,
@@ -430,6 +508,98 @@ impl BrowserRandomizer {
         }
     }
     
+    /// Generate a GREASE encrypted_client_hello extension following a template shape
+    ///
+    /// Produces an outer ECH extension whose cipher suite, config id, `enc` length
+    /// and payload length follow `shape`. The `enc` and payload bytes are random,
+    /// as required for GREASE ECH (draft-ietf-tls-esni Section 6.2).
+    ///
+    /// GREASE ECH is part of the browser's static shape rather than a randomized
+    /// perturbation, so it is generated regardless of the randomization level.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the shape lists no cipher suites or no payload lengths, or if the
+    /// selected AEAD has no tag length (EXPORT_ONLY).
+    pub(crate) fn generate_ech_grease(&mut self, shape: &EchGreaseShape) -> Option<EncryptedClientHello> {
+        let cipher_suite = *self.rng.choose(&shape.cipher_suites)?;
+        let tag_len = cipher_suite.aead_id.tag_len()?;
+        
+        let config_id = if shape.randomize_config_id {
+            (self.rng.next_u64() & 0xff) as u8
+        } else {
+            shape.config_id
+        };
+        
+        let payload_len = self.sample_pmf(&shape.payload_lengths)? as usize + tag_len;
+        
+        let mut enc = vec![0u8; shape.enc_len()];
+        self.rng.fill(&mut enc);
+        
+        let mut payload = vec![0u8; payload_len];
+        self.rng.fill(&mut payload);
+        
+        Some(EncryptedClientHello::Outer(EncryptedClientHelloOuter {
+            cipher_suite,
+            config_id,
+            enc: SizedPayload::from(Payload::new(enc)),
+            payload: SizedPayload::from(Payload::new(payload)),
+        }))
+    }
+    
+    /// Reorder QUIC transport parameters and add GREASE following a template's profile
+    ///
+    /// Like GREASE ECH, this is part of the browser's static shape and is applied
+    /// regardless of the randomization level. A GREASE parameter (RFC 9000 Section
+    /// 18.1) is only added when the application did not already encode one.
+    pub(crate) fn shape_transport_parameters(
+        &mut self,
+        params: &mut QuicTransportParameters,
+        profile: &QuicProfile,
+    ) {
+        if profile.shuffle_transport_parameters {
+            let list = params.params_mut();
+            for i in (1..list.len()).rev() {
+                let j = (self.rng.next_u64() % (i as u64 + 1)) as usize;
+                list.swap(i, j);
+            }
+        } else {
+            params.order_by(&profile.transport_parameter_order);
+        }
+        
+        if profile.grease_transport_parameter && !params.has_grease() {
+            let n = self.rng.next_u64() % ((MAX_VARINT - 27) / 31 + 1);
+            let len = self.rng.gen_range(0, u16::from(profile.grease_value_max_len));
+            let mut value = vec![0u8; usize::from(len)];
+            self.rng.fill(&mut value);
+            
+            let grease = TransportParameter::new(31 * n + 27, value);
+            let list = params.params_mut();
+            let position = match profile.shuffle_transport_parameters {
+                true => (self.rng.next_u64() % (list.len() as u64 + 1)) as usize,
+                false => list.len(),
+            };
+            list.insert(position, grease);
+        }
+    }
+    
+    /// Sample a value from a (value, probability) table using cumulative probabilities
+    fn sample_pmf(&mut self, pmf: &[(u16, f64)]) -> Option<u16> {
+        let (last, _) = pmf.last()?;
+        let total: f64 = pmf.iter().map(|(_, p)| p).sum();
+        let random_value = self.rng.gen_f64() * total;
+        
+        let mut cumulative = 0.0;
+        for (value, prob) in pmf {
+            cumulative += prob;
+            if random_value <= cumulative {
+                return Some(*value);
+            }
+        }
+        
+        Some(*last)
+    }
+    
     /// Find the nearest power of 2 to the given value, within the specified range
     ///
     /// Powers of 2 considered: 0, 1, 2, 4, 8, 16, 32, 64, 128, 256, 512, 1024, 2048
@@ -516,3 +686,7 @@ mod tests;
 #[cfg(test)]
 #[path = "randomizer_properties.rs"]
 mod properties;
+
+#[cfg(test)]
+#[path = "randomizer_statistics.rs"]
+mod statistics;
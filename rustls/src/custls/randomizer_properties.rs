@@ -10,68 +10,82 @@ mod property_tests {
     use crate::custls::RandomizationLevel;
     use crate::msgs::ExtensionType;
     use alloc::vec;
+    use alloc::vec::Vec;
+    use proptest::prelude::*;
     
-    // Feature: custls, Property 3: PSK Extension Always Last
-    // **Validates: Requirements 3.8**
-    //
-    // For any ClientHello that includes a PSK (pre_shared_key) extension,
-    // that extension SHALL appear as the last extension in the extensions list.
-    //
-    // Note: This test is currently a placeholder because ClientExtension is a placeholder type.
-    // Once ClientExtension integration is complete, this test should be updated to:
-    // 1. Generate arbitrary extension lists with and without PSK
-    // 2. Call shuffle_extensions
-    // 3. Verify PSK is always last when present
-    #[test]
-    fn property_psk_extension_always_last_placeholder() {
-        // Placeholder test that will be implemented once ClientExtension has real implementation
-        // For now, we just verify the randomizer can be created
-        let randomizer = BrowserRandomizer::new(
-            RandomizationLevel::Light,
-            NaturalnessFilter::default(),
-        );
-        
-        assert_eq!(randomizer.level(), RandomizationLevel::Light);
-        
-        // TODO: Once ClientExtension has real implementation with extension_type() method:
-        // - Generate extension lists with PSK at various positions
-        // - Shuffle extensions
-        // - Verify PSK is always last
-        // - Run with minimum 100 iterations
+    fn level_strategy() -> impl Strategy<Value = RandomizationLevel> {
+        prop_oneof![
+            Just(RandomizationLevel::Light),
+            Just(RandomizationLevel::Medium),
+            Just(RandomizationLevel::High),
+        ]
     }
     
-    // Feature: custls, Property 4: Critical Extension Positioning
-    // **Validates: Requirements 3.2**
-    //
-    // For any extension list after randomization, critical extensions
-    // (supported_versions, key_share, pre_shared_key) SHALL remain in
-    // browser-appropriate positions according to the template's grouped shuffle rules.
-    //
-    // Note: This test is currently a placeholder because ClientExtension is a placeholder type.
-    // Once ClientExtension integration is complete, this test should be updated to:
-    // 1. Generate arbitrary extension lists with critical extensions
-    // 2. Call shuffle_extensions with various templates
-    // 3. Verify critical extensions maintain appropriate positions
-    #[test]
-    fn property_critical_extension_positioning_placeholder() {
-        // Placeholder test that will be implemented once ClientExtension has real implementation
-        let mut randomizer = BrowserRandomizer::new(
-            RandomizationLevel::Medium,
-            NaturalnessFilter::default(),
-        );
-        
+    fn extensions_strategy() -> impl Strategy<Value = Vec<ExtensionType>> {
         let template = chrome_130();
+        proptest::sample::subsequence(template.extension_order.clone(), 0..=template.extension_order.len())
+            .prop_shuffle()
+    }
+    
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(100))]
         
-        // Verify template has expected critical extensions in extension_order
-        assert!(template.extension_order.contains(&ExtensionType::SupportedVersions));
-        assert!(template.extension_order.contains(&ExtensionType::KeyShare));
+        // Feature: custls, Property 3: PSK Extension Always Last
+        // **Validates: Requirements 3.8**
+        //
+        // For any ClientHello that includes a PSK (pre_shared_key) extension,
+        // that extension SHALL appear as the last extension in the extensions list.
+        #[test]
+        fn property_psk_extension_always_last(
+            level in level_strategy(),
+            mut extensions in extensions_strategy(),
+            with_psk in any::<bool>(),
+        ) {
+            let template = chrome_130();
+            let mut randomizer = BrowserRandomizer::new(level, NaturalnessFilter::default());
+            extensions.retain(|ext| *ext != ExtensionType::PreSharedKey);
+            if with_psk {
+                extensions.push(ExtensionType::PreSharedKey);
+            }
+            
+            let mut cipher_suites = Vec::new();
+            randomizer.shuffle_extensions(&mut extensions, &template).unwrap();
+            randomizer
+                .inject_grease(&mut cipher_suites, &mut extensions, &template, &[])
+                .unwrap();
+            
+            prop_assert_eq!(
+                extensions.last() == Some(&ExtensionType::PreSharedKey),
+                with_psk
+            );
+        }
         
-        // TODO: Once ClientExtension has real implementation:
-        // - Generate extension lists with critical extensions at various positions
-        // - Shuffle extensions
-        // - Verify critical extensions maintain browser-appropriate positions
-        // - Test with all browser templates
-        // - Run with minimum 100 iterations
+        // Feature: custls, Property 4: Critical Extension Positioning
+        // **Validates: Requirements 3.2**
+        //
+        // For any extension list after randomization, critical extensions
+        // (supported_versions, key_share, pre_shared_key) SHALL remain in
+        // browser-appropriate positions according to the template's grouped shuffle rules.
+        #[test]
+        fn property_critical_extension_positioning(
+            level in level_strategy(),
+            extensions in extensions_strategy(),
+        ) {
+            let template = chrome_130();
+            let mut randomizer = BrowserRandomizer::new(level, NaturalnessFilter::default());
+            
+            let mut shuffled = extensions.clone();
+            randomizer.shuffle_extensions(&mut shuffled, &template).unwrap();
+            
+            prop_assert_eq!(shuffled.len(), extensions.len());
+            for (i, ext) in extensions.iter().enumerate() {
+                if PINNED_EXTENSIONS.contains(ext) || TRAILING_EXTENSIONS.contains(ext) {
+                    prop_assert_eq!(shuffled[i], *ext);
+                } else {
+                    prop_assert!(shuffled.contains(ext));
+                }
+            }
+        }
     }
     
     // Feature: custls, Property 5: Naturalness Filter Rejection
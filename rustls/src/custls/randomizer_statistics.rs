@@ -0,0 +1,578 @@
+//! Statistical conformance tests for custls randomization
+//!
+//! The property tests check that sampled values stay in range; these check that
+//! they follow the configured distributions, since detectors catch skewed or
+//! repeating values. Each sampler runs from a fixed seed against Pearson's
+//! chi-square test or the Kolmogorov-Smirnov test at a significance level of
+//! 10^-4, once per built-in template where the distribution comes from one.
+//! Correlation between consecutive draws and between separately created
+//! generators is checked as well.
+
+#[cfg(test)]
+mod statistical_tests {
+    use super::super::*;
+    use crate::custls::quic::{QuicTransportParameters, TransportParameter};
+    use crate::custls::templates::{chrome_130, BUILTIN_TEMPLATES, NaturalnessFilter, TemplateData};
+    use crate::custls::RandomizationLevel;
+    use crate::msgs::EncryptedClientHello;
+    use alloc::collections::BTreeMap;
+    use alloc::format;
+    use alloc::vec::Vec;
+    use core::fmt::Debug;
+
+    /// Samples drawn for each distribution under test
+    const SAMPLES: usize = 20_000;
+
+    /// Upper 10^-4 quantile of the standard normal distribution
+    const Z_CRITICAL: f64 = 3.719;
+
+    /// Kolmogorov's asymptotic critical value, times sqrt(n), at 10^-4
+    const KS_CRITICAL: f64 = 2.2253;
+
+    const SEED: u64 = 0x5eed_c0ff_ee15_f00d;
+
+    const RANDOMIZED_LEVELS: [RandomizationLevel; 3] = [
+        RandomizationLevel::Light,
+        RandomizationLevel::Medium,
+        RandomizationLevel::High,
+    ];
+
+    fn templates() -> impl Iterator<Item = (&'static str, TemplateData)> {
+        BUILTIN_TEMPLATES
+            .iter()
+            .map(|(name, template)| (*name, template()))
+    }
+
+    fn seeded(level: RandomizationLevel) -> BrowserRandomizer {
+        BrowserRandomizer::with_seed(level, NaturalnessFilter::default(), SEED)
+    }
+
+    /// Equal weight for every value, counting repeated values once per occurrence
+    fn uniform<K: Ord>(values: impl IntoIterator<Item = K>) -> BTreeMap<K, f64> {
+        weighted(values.into_iter().map(|value| (value, 1.0)))
+    }
+
+    fn weighted<K: Ord>(pmf: impl IntoIterator<Item = (K, f64)>) -> BTreeMap<K, f64> {
+        let mut expected = BTreeMap::new();
+        for (value, weight) in pmf {
+            *expected.entry(value).or_insert(0.0) += weight;
+        }
+        expected
+    }
+
+    /// Chi-square critical value for `df` degrees of freedom (Wilson-Hilferty)
+    fn chi_square_critical(df: usize) -> f64 {
+        let df = df as f64;
+        let h = 2.0 / (9.0 * df);
+        df * (1.0 - h + Z_CRITICAL * h.sqrt()).powi(3)
+    }
+
+    /// Pearson's chi-square statistic and its degrees of freedom
+    ///
+    /// Neighbouring categories are pooled until each expects at least five
+    /// samples, as the chi-square approximation requires. A sample in a category
+    /// with no expected mass makes the statistic infinite.
+    fn chi_square(observed: &[u64], expected: &[f64]) -> (f64, usize) {
+        let mut pooled: Vec<(u64, f64)> = Vec::new();
+        let mut pending = (0u64, 0.0f64);
+        for (observed, expected) in observed.iter().zip(expected) {
+            if *expected <= 0.0 {
+                if *observed > 0 {
+                    return (f64::INFINITY, 0);
+                }
+                continue;
+            }
+
+            pending.0 += observed;
+            pending.1 += expected;
+            if pending.1 >= 5.0 {
+                pooled.push(pending);
+                pending = (0, 0.0);
+            }
+        }
+        match pooled.last_mut() {
+            Some(last) => {
+                last.0 += pending.0;
+                last.1 += pending.1;
+            }
+            None => pooled.push(pending),
+        }
+
+        let statistic = pooled
+            .iter()
+            .map(|(observed, expected)| {
+                let diff = *observed as f64 - expected;
+                diff * diff / expected
+            })
+            .sum();
+        (statistic, pooled.len() - 1)
+    }
+
+    /// Assert that `samples` follow the weights in `expected`
+    fn assert_distribution<K: Ord + Copy + Debug>(
+        what: &str,
+        samples: impl IntoIterator<Item = K>,
+        expected: &BTreeMap<K, f64>,
+    ) {
+        let mut counts = BTreeMap::new();
+        let mut n = 0u64;
+        for sample in samples {
+            *counts.entry(sample).or_insert(0u64) += 1;
+            n += 1;
+        }
+        if let Some(unexpected) = counts.keys().find(|value| !expected.contains_key(value)) {
+            panic!("{what}: sampled {unexpected:?}, which the distribution does not contain");
+        }
+
+        let total: f64 = expected.values().sum();
+        let (observed, expected): (Vec<u64>, Vec<f64>) = expected
+            .iter()
+            .map(|(value, weight)| {
+                let count = counts.get(value).copied().unwrap_or(0);
+                (count, weight / total * n as f64)
+            })
+            .unzip();
+        let (statistic, df) = chi_square(&observed, &expected);
+        assert!(
+            statistic.is_finite() && (df == 0 || statistic <= chi_square_critical(df)),
+            "{what}: chi-square {statistic:.1} exceeds {:.1} with {df} degrees of freedom",
+            chi_square_critical(df.max(1)),
+        );
+    }
+
+    /// Assert that `samples` follow the continuous distribution function `cdf`
+    fn assert_ks(what: &str, mut samples: Vec<f64>, cdf: impl Fn(f64) -> f64) {
+        samples.sort_by(f64::total_cmp);
+        let n = samples.len() as f64;
+        let statistic = samples
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let f = cdf(*x);
+                (f - i as f64 / n).max((i + 1) as f64 / n - f)
+            })
+            .fold(0.0, f64::max);
+        let critical = KS_CRITICAL / n.sqrt();
+        assert!(
+            statistic <= critical,
+            "{what}: Kolmogorov-Smirnov statistic {statistic:.4} exceeds {critical:.4}"
+        );
+    }
+
+    /// Assert that two generators' draws agree no more often than chance allows
+    ///
+    /// `collision` is the probability that two independent draws are equal.
+    fn assert_uncorrelated<T: PartialEq>(what: &str, first: &[T], second: &[T], collision: f64) {
+        let n = first.len() as f64;
+        let matches = first
+            .iter()
+            .zip(second)
+            .filter(|(a, b)| a == b)
+            .count();
+        let mean = n * collision;
+        let limit = mean + 5.0 * (mean * (1.0 - collision)).sqrt();
+        assert!(
+            matches as f64 <= limit,
+            "{what}: {matches} of {n} draws repeated across generators, {mean:.0} expected"
+        );
+    }
+
+    /// Distribution `generate_padding_len` should follow for `template` at `level`
+    ///
+    /// A share of samples (90%, 70% or 50% by level) comes from the template's
+    /// PMF, with any shortfall in its total falling to the first entry. The rest
+    /// is uniform over the length range, moved to the nearest power of 2 with
+    /// the template's bias.
+    fn padding_distribution(
+        randomizer: &BrowserRandomizer,
+        template: &TemplateData,
+        level: RandomizationLevel,
+    ) -> BTreeMap<u16, f64> {
+        let dist = &template.padding_distribution;
+        let from_pmf = match (level, dist.pmf.is_empty()) {
+            (_, true) => 0.0,
+            (RandomizationLevel::None, false) => 1.0,
+            (RandomizationLevel::Light, false) => 0.9,
+            (RandomizationLevel::Medium, false) => 0.7,
+            (RandomizationLevel::High, false) => 0.5,
+        };
+
+        let mut expected = BTreeMap::new();
+        let mut add = |len: u16, probability: f64| {
+            let len = len.clamp(dist.min_length, dist.max_length);
+            *expected.entry(len).or_insert(0.0) += probability;
+        };
+
+        let mut cumulative = 0.0f64;
+        for (len, probability) in &dist.pmf {
+            let next = (cumulative + probability).min(1.0);
+            add(*len, from_pmf * (next - cumulative).max(0.0));
+            cumulative = next;
+        }
+        if let Some((first, _)) = dist.pmf.first() {
+            add(*first, from_pmf * (1.0 - cumulative));
+        }
+
+        let width = f64::from(dist.max_length - dist.min_length) + 1.0;
+        let each = (1.0 - from_pmf) / width;
+        for len in dist.min_length..=dist.max_length {
+            let nearest = randomizer.nearest_power_of_2(len, dist.min_length, dist.max_length);
+            add(nearest, each * dist.power_of_2_bias);
+            add(len, each * (1.0 - dist.power_of_2_bias));
+        }
+
+        expected
+    }
+
+    /// Probability that two independent draws from `expected` are equal
+    fn collision_probability<K>(expected: &BTreeMap<K, f64>) -> f64 {
+        let total: f64 = expected.values().sum();
+        expected
+            .values()
+            .map(|weight| (weight / total) * (weight / total))
+            .sum()
+    }
+
+    #[test]
+    fn padding_lengths_follow_template_distributions() {
+        for (name, template) in templates() {
+            for level in RANDOMIZED_LEVELS {
+                let mut randomizer = seeded(level);
+                let expected = padding_distribution(&randomizer, &template, level);
+                assert_distribution(
+                    &format!("{name} padding at {level:?}"),
+                    (0..SAMPLES).map(|_| randomizer.generate_padding_len(&template, &[])),
+                    &expected,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unrandomized_padding_is_the_first_pmf_entry() {
+        for (name, template) in templates() {
+            let mut randomizer = seeded(RandomizationLevel::None);
+            let first = template
+                .padding_distribution
+                .pmf
+                .first()
+                .map_or(0, |(len, _)| *len);
+            for _ in 0..100 {
+                assert_eq!(randomizer.generate_padding_len(&template, &[]), first, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn grease_values_are_uniform_over_the_template_pool() {
+        for (name, template) in templates() {
+            let pool = &template.grease_pattern.grease_values;
+            if pool.is_empty() {
+                continue;
+            }
+
+            let mut randomizer = seeded(RandomizationLevel::Light);
+            assert_distribution(
+                &format!("{name} GREASE values"),
+                (0..SAMPLES).map(|_| randomizer.select_unused_grease(pool, &[]).unwrap()),
+                &uniform(pool.iter().copied()),
+            );
+
+            // Recently used values are avoided; the rest stay equally likely
+            let (previous, rest) = pool.split_at(pool.len() / 2);
+            assert_distribution(
+                &format!("{name} GREASE values avoiding recent ones"),
+                (0..SAMPLES).map(|_| randomizer.select_unused_grease(pool, previous).unwrap()),
+                &uniform(rest.iter().copied()),
+            );
+        }
+    }
+
+    /// A connection's GREASE value must not predict the next connection's
+    #[test]
+    fn consecutive_grease_values_are_independent() {
+        let pool = chrome_130().grease_pattern.grease_values;
+        let mut randomizer = seeded(RandomizationLevel::Light);
+        let draws = (0..2 * SAMPLES)
+            .map(|_| randomizer.select_unused_grease(&pool, &[]).unwrap())
+            .collect::<Vec<_>>();
+
+        let pairs = pool
+            .iter()
+            .flat_map(|first| pool.iter().map(move |second| (*first, *second)));
+        assert_distribution(
+            "consecutive GREASE values",
+            draws
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1])),
+            &uniform(pairs),
+        );
+    }
+
+    /// Randomizers created for different connections must not replay one
+    /// sequence, as they did when every generator started from the same seed
+    #[test]
+    fn separately_created_randomizers_are_uncorrelated() {
+        let draws = 256;
+        for (name, template) in templates() {
+            let level = RandomizationLevel::Light;
+            let mut first = BrowserRandomizer::new(level, NaturalnessFilter::default());
+            let mut second = BrowserRandomizer::new(level, NaturalnessFilter::default());
+
+            let collision = collision_probability(&padding_distribution(&first, &template, level));
+            if collision < 0.99 {
+                let a = (0..draws)
+                    .map(|_| first.generate_padding_len(&template, &[]))
+                    .collect::<Vec<_>>();
+                let b = (0..draws)
+                    .map(|_| second.generate_padding_len(&template, &[]))
+                    .collect::<Vec<_>>();
+                assert_uncorrelated(&format!("{name} padding"), &a, &b, collision);
+            }
+
+            let pool = &template.grease_pattern.grease_values;
+            if !pool.is_empty() {
+                let collision = collision_probability(&uniform(pool.iter().copied()));
+                let a = (0..draws)
+                    .map(|_| first.select_unused_grease(pool, &[]))
+                    .collect::<Vec<_>>();
+                let b = (0..draws)
+                    .map(|_| second.select_unused_grease(pool, &[]))
+                    .collect::<Vec<_>>();
+                assert_uncorrelated(&format!("{name} GREASE"), &a, &b, collision);
+            }
+        }
+    }
+
+    #[test]
+    fn ech_grease_follows_template_shapes() {
+        for (name, template) in templates() {
+            let Some(shape) = &template.ech_grease else {
+                continue;
+            };
+
+            let mut randomizer = seeded(RandomizationLevel::Light);
+            let mut payload_lengths = Vec::with_capacity(SAMPLES);
+            let mut config_ids = Vec::with_capacity(SAMPLES);
+            let mut suites = Vec::with_capacity(SAMPLES);
+            for _ in 0..SAMPLES {
+                let Some(EncryptedClientHello::Outer(outer)) = randomizer.generate_ech_grease(shape) else {
+                    panic!("{name}: no GREASE ECH generated");
+                };
+                let tag_len = outer.cipher_suite.aead_id.tag_len().unwrap();
+                payload_lengths.push(u16::try_from(outer.payload.bytes().len() - tag_len).unwrap());
+                config_ids.push(outer.config_id);
+                suites.push(
+                    shape
+                        .cipher_suites
+                        .iter()
+                        .position(|suite| *suite == outer.cipher_suite)
+                        .unwrap(),
+                );
+            }
+
+            assert_distribution(
+                &format!("{name} ECH payload lengths"),
+                payload_lengths,
+                &weighted(shape.payload_lengths.iter().copied()),
+            );
+            assert_distribution(
+                &format!("{name} ECH cipher suites"),
+                suites,
+                &uniform(0..shape.cipher_suites.len()),
+            );
+            let expected_ids = match shape.randomize_config_id {
+                true => uniform(0..=u8::MAX),
+                false => uniform([shape.config_id]),
+            };
+            assert_distribution(&format!("{name} ECH config ids"), config_ids, &expected_ids);
+        }
+    }
+
+    #[test]
+    fn quic_transport_parameters_are_shuffled_uniformly() {
+        for (name, template) in templates() {
+            let Some(profile) = &template.quic else {
+                continue;
+            };
+            if !profile.shuffle_transport_parameters || !profile.grease_transport_parameter {
+                continue;
+            }
+
+            let mut randomizer = seeded(RandomizationLevel::Light);
+            let mut grease_positions = Vec::with_capacity(SAMPLES);
+            let mut grease_lengths = Vec::with_capacity(SAMPLES);
+            let mut first_positions = Vec::with_capacity(SAMPLES);
+            for _ in 0..SAMPLES {
+                let mut params = QuicTransportParameters::new();
+                for id in 1..=6 {
+                    params.push(TransportParameter::varint(id, 0));
+                }
+                randomizer.shape_transport_parameters(&mut params, profile);
+
+                let list = params.params();
+                let grease = list
+                    .iter()
+                    .position(TransportParameter::is_grease)
+                    .unwrap();
+                grease_positions.push(grease);
+                grease_lengths.push(list[grease].value.len());
+                first_positions.push(
+                    list.iter()
+                        .filter(|param| !param.is_grease())
+                        .position(|param| param.id == 1)
+                        .unwrap(),
+                );
+            }
+
+            assert_distribution(
+                &format!("{name} GREASE transport parameter positions"),
+                grease_positions,
+                &uniform(0..=6),
+            );
+            assert_distribution(
+                &format!("{name} GREASE transport parameter lengths"),
+                grease_lengths,
+                &uniform(0..=usize::from(profile.grease_value_max_len)),
+            );
+            assert_distribution(
+                &format!("{name} shuffled transport parameter positions"),
+                first_positions,
+                &uniform(0..6),
+            );
+        }
+    }
+
+    #[cfg(feature = "std")]
+    mod utils_sampling {
+        use super::*;
+        use alloc::string::String;
+        use alloc::vec;
+        use crate::custls::utils::{
+            TimingJitterConfig, sample_from_pmf, sample_with_power_of_2_bias, seed_thread_rng,
+        };
+
+        #[test]
+        fn sample_from_pmf_follows_its_weights() {
+            seed_thread_rng(SEED);
+
+            let mut pmfs = vec![(
+                String::from("unnormalized weights"),
+                vec![(1u16, 2.0), (2, 1.0), (3, 0.5), (4, 0.5)],
+            )];
+            for (name, template) in templates() {
+                pmfs.push((format!("{name} padding PMF"), template.padding_distribution.pmf.clone()));
+                if let Some(shape) = &template.ech_grease {
+                    pmfs.push((format!("{name} ECH payload PMF"), shape.payload_lengths.clone()));
+                }
+            }
+
+            for (what, pmf) in pmfs {
+                assert_distribution(
+                    &what,
+                    (0..SAMPLES).map(|_| sample_from_pmf(&pmf).unwrap()),
+                    &weighted(pmf.iter().copied()),
+                );
+            }
+        }
+
+        #[test]
+        fn power_of_2_bias_matches_its_configuration() {
+            seed_thread_rng(SEED);
+
+            let fixed = [(0, 1500, 0.7), (16, 1500, 0.5), (0, 1500, 0.0), (100, 120, 1.0)];
+            let from_templates = templates().map(|(_, template)| {
+                let dist = template.padding_distribution;
+                (dist.min_length, dist.max_length, dist.power_of_2_bias)
+            });
+
+            for (min, max, bias) in fixed.into_iter().chain(from_templates) {
+                let powers = (0..16)
+                    .map(|shift| 1u16 << shift)
+                    .filter(|power| (min..=max).contains(power))
+                    .collect::<Vec<_>>();
+                let uniform_share = match powers.is_empty() {
+                    true => 1.0,
+                    false => 1.0 - bias,
+                };
+
+                let width = f64::from(max - min) + 1.0;
+                let mut expected = weighted((min..=max).map(|value| (value, uniform_share / width)));
+                for power in &powers {
+                    *expected.get_mut(power).unwrap() += bias / powers.len() as f64;
+                }
+
+                assert_distribution(
+                    &format!("power-of-2 bias {bias} over {min}..={max}"),
+                    (0..SAMPLES).map(|_| sample_with_power_of_2_bias(min, max, bias)),
+                    &expected,
+                );
+            }
+
+            // Without bias the values are uniform over the whole range
+            let samples = (0..SAMPLES)
+                .map(|_| (f64::from(sample_with_power_of_2_bias(0, 60_000, 0.0)) + 0.5) / 60_001.0)
+                .collect();
+            assert_ks("unbiased sampling", samples, |x| x.clamp(0.0, 1.0));
+        }
+
+        #[test]
+        fn timing_jitter_follows_its_configuration() {
+            seed_thread_rng(SEED);
+
+            let config = TimingJitterConfig::new(1_000, 1_001_000, 1.0).unwrap();
+            let samples = (0..SAMPLES)
+                .map(|_| {
+                    let delay = config.sample_delay().unwrap().as_micros() as f64;
+                    (delay - 1_000.0 + 0.5) / 1_000_001.0
+                })
+                .collect();
+            assert_ks("timing jitter delays", samples, |x| x.clamp(0.0, 1.0));
+
+            let config = TimingJitterConfig::new(0, 10, 0.3).unwrap();
+            assert_distribution(
+                "timing jitter application",
+                (0..SAMPLES).map(|_| config.sample_delay().is_some()),
+                &weighted([(true, 0.3), (false, 0.7)]),
+            );
+        }
+
+        /// Each thread's generator is seeded separately, so threads serving
+        /// different connections do not sample the same sequence
+        #[test]
+        fn threads_do_not_share_a_sampling_sequence() {
+            let pmf = (0..16u16)
+                .map(|value| (value, 1.0))
+                .collect::<Vec<_>>();
+            let draw = move || {
+                (0..256)
+                    .map(|_| sample_from_pmf(&pmf).unwrap())
+                    .collect::<Vec<_>>()
+            };
+
+            let first = std::thread::spawn(draw.clone()).join().unwrap();
+            let second = std::thread::spawn(draw).join().unwrap();
+            assert_uncorrelated("thread samples", &first, &second, 1.0 / 16.0);
+        }
+
+        /// Range reduction keeps the low bits of the generator, which must not
+        /// repeat from one draw to the next
+        #[test]
+        fn consecutive_uniform_samples_are_independent() {
+            seed_thread_rng(SEED);
+
+            let draws = (0..2 * SAMPLES)
+                .map(|_| sample_with_power_of_2_bias(0, 15, 0.0))
+                .collect::<Vec<_>>();
+
+            let pairs = (0..16u16).flat_map(|first| (0..16u16).map(move |second| (first, second)));
+            assert_distribution(
+                "consecutive uniform samples",
+                draws
+                    .chunks_exact(2)
+                    .map(|pair| (pair[0], pair[1])),
+                &uniform(pairs),
+            );
+        }
+    }
+}
@@ -8,6 +8,7 @@ mod tests {
     use crate::custls::templates::{chrome_130, firefox_135, safari_17, edge_130, NaturalnessFilter};
     use crate::custls::RandomizationLevel;
     use alloc::vec;
+    use alloc::vec::Vec;
     
     #[test]
     fn test_randomizer_creation() {
@@ -138,49 +139,130 @@ mod tests {
         assert_eq!(randomizer.nearest_power_of_2(300, 64, 256), 256);
     }
     
+    fn chrome_extensions() -> Vec<ExtensionType> {
+        vec![
+            ExtensionType::ServerName,
+            ExtensionType::ExtendedMasterSecret,
+            ExtensionType::RenegotiationInfo,
+            ExtensionType::EllipticCurves,
+            ExtensionType::ECPointFormats,
+            ExtensionType::SessionTicket,
+            ExtensionType::ALProtocolNegotiation,
+            ExtensionType::StatusRequest,
+            ExtensionType::SignatureAlgorithms,
+            ExtensionType::SCT,
+            ExtensionType::KeyShare,
+            ExtensionType::PSKKeyExchangeModes,
+            ExtensionType::SupportedVersions,
+            ExtensionType::Padding,
+            ExtensionType::EncryptedClientHello,
+            ExtensionType::PreSharedKey,
+        ]
+    }
+    
+    #[test]
+    fn test_shuffle_extensions_keeps_pinned_positions() {
+        let template = chrome_130();
+        
+        for level in [RandomizationLevel::Light, RandomizationLevel::Medium, RandomizationLevel::High] {
+            let mut randomizer = BrowserRandomizer::new(level, NaturalnessFilter::default());
+            let original = chrome_extensions();
+            
+            for _ in 0..50 {
+                let mut extensions = original.clone();
+                randomizer.shuffle_extensions(&mut extensions, &template).unwrap();
+                
+                // Same extensions, with the pinned and trailing ones where they were
+                let mut sorted = extensions.clone();
+                sorted.sort_by_key(|ext| u16::from(*ext));
+                let mut expected = original.clone();
+                expected.sort_by_key(|ext| u16::from(*ext));
+                assert_eq!(sorted, expected);
+                
+                for (i, ext) in original.iter().enumerate() {
+                    if PINNED_EXTENSIONS.contains(ext) || TRAILING_EXTENSIONS.contains(ext) {
+                        assert_eq!(extensions[i], *ext);
+                    }
+                }
+            }
+        }
+        
+        // Empty list is fine
+        let mut randomizer = BrowserRandomizer::new(RandomizationLevel::High, NaturalnessFilter::default());
+        let mut extensions = Vec::new();
+        randomizer.shuffle_extensions(&mut extensions, &template).unwrap();
+        assert!(extensions.is_empty());
+    }
+    
     #[test]
-    fn test_shuffle_extensions_placeholder() {
-        // Placeholder test for shuffle_extensions
-        // Once ClientExtension has real implementation, this should test:
-        // - Empty extension list
-        // - Extension list with PSK
-        // - Extension list without PSK
-        // - Different randomization levels produce different behavior
+    fn test_shuffle_extensions_high_changes_order() {
+        let template = chrome_130();
+        let mut randomizer = BrowserRandomizer::new(RandomizationLevel::High, NaturalnessFilter::default());
+        let original = chrome_extensions();
         
+        let changed = (0..20).any(|_| {
+            let mut extensions = original.clone();
+            randomizer.shuffle_extensions(&mut extensions, &template).unwrap();
+            extensions != original
+        });
+        assert!(changed);
+    }
+    
+    #[test]
+    fn test_inject_grease_inserts_template_values() {
         let mut randomizer = BrowserRandomizer::new(
             RandomizationLevel::Light,
             NaturalnessFilter::default(),
         );
         
-        let template = chrome_130();
-        let mut extensions = Vec::new();
+        let mut template = chrome_130();
+        template.grease_pattern.cipher_suite_positions = vec![0.0];
+        template.grease_pattern.extension_positions = vec![1.0];
+        let mut cipher_suites = vec![
+            CipherSuite::TLS13_AES_128_GCM_SHA256,
+            CipherSuite::TLS13_AES_256_GCM_SHA384,
+        ];
+        let mut extensions = chrome_extensions();
         
-        // Should not error on empty list
-        let result = randomizer.shuffle_extensions(&mut extensions, &template);
-        assert!(result.is_ok());
+        let used = randomizer
+            .inject_grease(&mut cipher_suites, &mut extensions, &template, &[])
+            .unwrap();
+        assert_eq!(used.len(), 2);
+        assert_ne!(used[0], used[1]);
+        assert!(used.iter().all(|value| template.grease_pattern.grease_values.contains(value)));
+        
+        // The cipher suite goes in front, the extension as late as it can: ahead of
+        // padding, ECH and PSK
+        assert_eq!(cipher_suites.len(), 3);
+        assert_eq!(cipher_suites[0], CipherSuite::from(used[0]));
+        assert_eq!(extensions.len(), chrome_extensions().len() + 1);
+        let padding = extensions
+            .iter()
+            .position(|ext| *ext == ExtensionType::Padding)
+            .unwrap();
+        assert_eq!(extensions[padding - 1], ExtensionType::from(used[1]));
+        assert_eq!(extensions.last(), Some(&ExtensionType::PreSharedKey));
     }
     
     #[test]
-    fn test_inject_grease_placeholder() {
-        // Placeholder test for inject_grease
-        // Once CipherSuite and ClientExtension have real implementations, this should test:
-        // - GREASE injection with different templates
-        // - GREASE values are from template's grease_values list
-        // - GREASE positions follow template's preferred positions
-        // - Chrome prefers front third for cipher suites
-        
+    fn test_inject_grease_avoids_previous_values() {
         let mut randomizer = BrowserRandomizer::new(
-            RandomizationLevel::Light,
+            RandomizationLevel::High,
             NaturalnessFilter::default(),
         );
-        
         let template = chrome_130();
-        let mut cipher_suites = Vec::new();
-        let mut extensions = Vec::new();
+        let previous = &template.grease_pattern.grease_values[..4];
         
-        // Should not error on empty lists
-        let result = randomizer.inject_grease(&mut cipher_suites, &mut extensions, &template, &[]);
-        assert!(result.is_ok());
+        for _ in 0..50 {
+            let mut cipher_suites = Vec::new();
+            let mut extensions = Vec::new();
+            let used = randomizer
+                .inject_grease(&mut cipher_suites, &mut extensions, &template, previous)
+                .unwrap();
+            assert!(used.iter().all(|value| !previous.contains(value)));
+            assert_eq!(cipher_suites.len(), 1);
+            assert_eq!(extensions.len(), 1);
+        }
     }
     
     #[test]
@@ -234,4 +316,122 @@ mod tests {
         let retrieved_filter = randomizer.naturalness_filter();
         assert_eq!(retrieved_filter.blacklist.len(), filter.blacklist.len());
     }
+    
+    #[test]
+    fn test_generate_ech_grease_chrome_shape() {
+        use crate::custls::templates::EchGreaseShape;
+        use crate::msgs::EncryptedClientHello;
+        
+        let mut randomizer = BrowserRandomizer::new(
+            RandomizationLevel::None,
+            NaturalnessFilter::default(),
+        );
+        let shape = EchGreaseShape::chrome();
+        
+        for _ in 0..50 {
+            let ext = randomizer.generate_ech_grease(&shape).unwrap();
+            let EncryptedClientHello::Outer(outer) = ext else {
+                panic!("GREASE ECH must be an outer extension");
+            };
+            
+            assert_eq!(outer.cipher_suite, shape.cipher_suites[0]);
+            assert_eq!(outer.enc.bytes().len(), 32);
+            assert!([144, 176, 208, 240].contains(&outer.payload.bytes().len()));
+        }
+    }
+    
+    #[test]
+    fn test_generate_ech_grease_fixed_config_id() {
+        use crate::custls::templates::EchGreaseShape;
+        use crate::msgs::EncryptedClientHello;
+        
+        let mut randomizer = BrowserRandomizer::new(
+            RandomizationLevel::High,
+            NaturalnessFilter::default(),
+        );
+        let mut shape = EchGreaseShape::chrome();
+        shape.randomize_config_id = false;
+        shape.config_id = 0x42;
+        
+        let Some(EncryptedClientHello::Outer(outer)) = randomizer.generate_ech_grease(&shape) else {
+            panic!("expected GREASE ECH extension");
+        };
+        assert_eq!(outer.config_id, 0x42);
+        
+        // A shape without payload lengths cannot produce an extension
+        shape.payload_lengths.clear();
+        assert!(randomizer.generate_ech_grease(&shape).is_none());
+    }
+    
+    fn sample_transport_parameters() -> crate::custls::quic::QuicTransportParameters {
+        use crate::custls::quic::{QuicTransportParameters, TransportParameter};
+        
+        let mut params = QuicTransportParameters::new();
+        for id in [
+            TransportParameter::INITIAL_SOURCE_CONNECTION_ID,
+            TransportParameter::MAX_IDLE_TIMEOUT,
+            TransportParameter::INITIAL_MAX_DATA,
+            TransportParameter::INITIAL_MAX_STREAM_DATA_BIDI_LOCAL,
+            TransportParameter::GREASE_QUIC_BIT,
+        ] {
+            params.push(TransportParameter::varint(id, 1));
+        }
+        params
+    }
+    
+    #[test]
+    fn test_shape_transport_parameters_firefox_order() {
+        use crate::custls::quic::TransportParameter;
+        use crate::custls::templates::QuicProfile;
+        
+        let mut randomizer = BrowserRandomizer::new(
+            RandomizationLevel::High,
+            NaturalnessFilter::default(),
+        );
+        let mut params = sample_transport_parameters();
+        randomizer.shape_transport_parameters(&mut params, &QuicProfile::firefox());
+        
+        assert_eq!(params.ids(), vec![
+            TransportParameter::INITIAL_MAX_STREAM_DATA_BIDI_LOCAL,
+            TransportParameter::INITIAL_MAX_DATA,
+            TransportParameter::MAX_IDLE_TIMEOUT,
+            TransportParameter::GREASE_QUIC_BIT,
+            TransportParameter::INITIAL_SOURCE_CONNECTION_ID,
+        ]);
+        assert!(!params.has_grease());
+    }
+    
+    #[test]
+    fn test_shape_transport_parameters_chrome_adds_one_grease() {
+        use crate::custls::quic::QuicTransportParameters;
+        use crate::custls::templates::QuicProfile;
+        
+        let mut randomizer = BrowserRandomizer::new(
+            RandomizationLevel::None,
+            NaturalnessFilter::default(),
+        );
+        let profile = QuicProfile::chrome();
+        let original = sample_transport_parameters();
+        
+        for _ in 0..50 {
+            let mut params = original.clone();
+            randomizer.shape_transport_parameters(&mut params, &profile);
+            
+            // Every original parameter is kept, plus exactly one GREASE parameter
+            let grease: Vec<_> = params.params().iter().filter(|p| p.is_grease()).collect();
+            assert_eq!(grease.len(), 1);
+            assert!(grease[0].value.len() <= usize::from(profile.grease_value_max_len));
+            for param in original.params() {
+                assert!(params.params().contains(param));
+            }
+            assert_eq!(params.params().len(), original.params().len() + 1);
+            
+            // The shaped parameters survive an encode/decode round trip
+            assert_eq!(QuicTransportParameters::decode(&params.encode()).unwrap(), params);
+            
+            // Shaping again does not add a second GREASE parameter
+            randomizer.shape_transport_parameters(&mut params, &profile);
+            assert_eq!(params.params().iter().filter(|p| p.is_grease()).count(), 1);
+        }
+    }
 }
@@ -0,0 +1,624 @@
+//! Retrying connections whose customized ClientHello was rejected
+//!
+//! Some servers and middleboxes reject a shaped ClientHello outright: a
+//! `handshake_failure` or `protocol_version` alert, or a connection reset before
+//! the server's first flight. A [`RetryPlanner`] turns such a rejection into the
+//! next attempt to make, stepping from the configured shape towards rustls's own
+//! ClientHello:
+//!
+//! - [`RetryStep::ChangeTemplate`]: another template from the rotation list
+//! - [`RetryStep::DropExtensions`]: the template without [`RISKY_EXTENSIONS`]
+//! - [`RetryStep::LowerRandomization`]: the template sent exactly, without GREASE or shuffling
+//! - [`RetryStep::Plain`]: no customization at all
+//!
+//! Each step is taken at most once per connection, in an order that depends on
+//! the rejection. Outcomes are recorded in the planner's [`FingerprintManager`],
+//! so the next connection to the same target starts from the template that last
+//! worked.
+//!
+//! The planner only produces `ClientConfig`s, so it serves the buffered and the
+//! unbuffered API alike. Classify a buffered connection's failure with
+//! [`HelloRejection::from_io_error`], and an unbuffered one's with
+//! [`HelloRejection::from_error`].
+//!
+//! ## Usage
+//!
+//! ```rust,ignore
+//! use std::sync::Arc;
+//! use rustls::custls::{HelloRejection, RetryPlanner, TargetKey};
+//!
+//! let planner = RetryPlanner::new(custls_config);
+//! let target = TargetKey::new("example.com".to_string(), 443);
+//! let mut attempt = planner.first_attempt(&target);
+//! loop {
+//!     let config = Arc::new(attempt.client_config(&base_config));
+//!     match connect(config) {
+//!         Ok(stream) => {
+//!             planner.record_success(&target, &attempt);
+//!             break Ok(stream);
+//!         }
+//!         Err(error) => {
+//!             let next = HelloRejection::from_io_error(&error)
+//!                 .and_then(|rejection| planner.next_attempt(&target, &attempt, rejection));
+//!             match next {
+//!                 Some(next) => attempt = next,
+//!                 None => break Err(error),
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+use super::orchestrator::{DefaultCustomizer, rotation_templates};
+use super::state::{ClientHelloConfig, FingerprintManager, TargetKey};
+use super::{BrowserTemplate, CustlsConfig, RandomizationLevel, TemplateRotationPolicy};
+use crate::client::ClientConfig;
+use crate::error::{AlertDescription, Error};
+use crate::msgs::ExtensionType;
+
+/// Extensions [`RetryStep::DropExtensions`] takes out of a template
+///
+/// ALPS and GREASE ECH are recent, browser-specific and the first thing a strict
+/// server or middlebox trips over.
+pub const RISKY_EXTENSIONS: [ExtensionType; 2] = [
+    ExtensionType::ApplicationSettings,
+    ExtensionType::EncryptedClientHello,
+];
+
+/// Attempts a [`RetryPlanner`] makes by default, including the first
+pub const DEFAULT_MAX_ATTEMPTS: usize = 5;
+
+/// How a server rejected a ClientHello
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelloRejection {
+    /// The server sent a `handshake_failure` alert
+    HandshakeFailure,
+
+    /// The server sent a `protocol_version` alert
+    ProtocolVersion,
+
+    /// The server could not parse or did not accept the hello's contents: an
+    /// `illegal_parameter`, `decode_error` or `unsupported_extension` alert
+    MalformedHello,
+
+    /// The connection was reset or closed before the server answered
+    ConnectionReset,
+}
+
+impl HelloRejection {
+    /// Classify the error a connection failed with
+    ///
+    /// Returns `None` for errors that are not a rejection of the ClientHello,
+    /// such as certificate errors.
+    pub fn from_error(error: &Error) -> Option<Self> {
+        match error {
+            Error::AlertReceived(AlertDescription::HandshakeFailure) => Some(Self::HandshakeFailure),
+            Error::AlertReceived(AlertDescription::ProtocolVersion) => Some(Self::ProtocolVersion),
+            Error::AlertReceived(
+                AlertDescription::IllegalParameter
+                | AlertDescription::DecodeError
+                | AlertDescription::UnsupportedExtension,
+            ) => Some(Self::MalformedHello),
+            _ => None,
+        }
+    }
+
+    /// Classify the I/O error a buffered connection failed with
+    ///
+    /// rustls errors carried by the I/O error are classified with
+    /// [`Self::from_error`]. Resets, aborts and an unexpected end of stream are a
+    /// [`Self::ConnectionReset`]; only classify those from the handshake, since
+    /// they are a rejection only before the server's first flight.
+    #[cfg(feature = "std")]
+    pub fn from_io_error(error: &std::io::Error) -> Option<Self> {
+        if let Some(error) = error
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<Error>())
+        {
+            return Self::from_error(error);
+        }
+
+        match error.kind() {
+            std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::UnexpectedEof => Some(Self::ConnectionReset),
+            _ => None,
+        }
+    }
+
+    /// Steps worth trying for this rejection, most promising first
+    fn ladder(self) -> [StepKind; 4] {
+        match self {
+            // Usually the fingerprint itself is blocked
+            Self::HandshakeFailure | Self::ConnectionReset => [
+                StepKind::ChangeTemplate,
+                StepKind::DropExtensions,
+                StepKind::LowerRandomization,
+                StepKind::Plain,
+            ],
+            // The server chokes on something in the hello; GREASE and newer
+            // extensions are the likely culprits
+            Self::MalformedHello => [
+                StepKind::DropExtensions,
+                StepKind::LowerRandomization,
+                StepKind::ChangeTemplate,
+                StepKind::Plain,
+            ],
+            Self::ProtocolVersion => [
+                StepKind::ChangeTemplate,
+                StepKind::LowerRandomization,
+                StepKind::DropExtensions,
+                StepKind::Plain,
+            ],
+        }
+    }
+}
+
+impl fmt::Display for HelloRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HandshakeFailure => write!(f, "handshake_failure alert"),
+            Self::ProtocolVersion => write!(f, "protocol_version alert"),
+            Self::MalformedHello => write!(f, "ClientHello rejected as malformed"),
+            Self::ConnectionReset => write!(f, "connection reset after ClientHello"),
+        }
+    }
+}
+
+/// A change from one attempt to the next
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RetryStep {
+    /// Shape the hello with this template instead
+    ChangeTemplate(BrowserTemplate),
+
+    /// Keep the template, with this randomization level
+    LowerRandomization(RandomizationLevel),
+
+    /// Keep the template without these extensions
+    DropExtensions(Vec<ExtensionType>),
+
+    /// Send rustls's own ClientHello
+    Plain,
+}
+
+impl RetryStep {
+    fn kind(&self) -> StepKind {
+        match self {
+            Self::ChangeTemplate(_) => StepKind::ChangeTemplate,
+            Self::LowerRandomization(_) => StepKind::LowerRandomization,
+            Self::DropExtensions(_) => StepKind::DropExtensions,
+            Self::Plain => StepKind::Plain,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepKind {
+    ChangeTemplate,
+    LowerRandomization,
+    DropExtensions,
+    Plain,
+}
+
+/// A connection attempt proposed by a [`RetryPlanner`]
+#[derive(Debug, Clone)]
+pub struct RetryAttempt {
+    /// 1 for the first attempt
+    pub number: usize,
+
+    /// Changes from the first attempt, in the order they were made
+    pub steps: Vec<RetryStep>,
+
+    /// Configuration for the attempt's customizer, or `None` to send rustls's
+    /// own ClientHello
+    pub config: Option<CustlsConfig>,
+}
+
+impl RetryAttempt {
+    /// `base` set up for this attempt
+    ///
+    /// The attempt gets a fresh [`DefaultCustomizer`], or custls disabled for a
+    /// plain hello. Everything else, including a browser profile's provider, is
+    /// kept from `base`.
+    pub fn client_config(&self, base: &ClientConfig) -> ClientConfig {
+        let mut config = base.clone();
+        match &self.config {
+            Some(custls) => config.enable_custls(DefaultCustomizer::new_arc(custls.clone())),
+            None => config.disable_custls(),
+        }
+        config
+    }
+
+    /// The change that produced this attempt, or `None` for the first
+    pub fn last_step(&self) -> Option<&RetryStep> {
+        self.steps.last()
+    }
+
+    /// Template the attempt shapes its hello with, if it has a fixed one
+    pub fn template(&self) -> Option<&BrowserTemplate> {
+        self.config
+            .as_ref()
+            .filter(|config| config.rotation_policy == TemplateRotationPolicy::None)
+            .and_then(|config| config.template.as_ref())
+    }
+
+    /// What the attempt's hello is recorded as in the fingerprint cache
+    fn fingerprint(&self) -> Option<ClientHelloConfig> {
+        let template = self.template()?;
+        Some(ClientHelloConfig::from_template(
+            template.clone(),
+            &DefaultCustomizer::resolve_template(template).ok()?,
+        ))
+    }
+
+    fn has_taken(&self, kind: StepKind) -> bool {
+        self.steps
+            .iter()
+            .any(|step| step.kind() == kind)
+    }
+}
+
+/// Proposes the next connection attempt after a rejected ClientHello
+///
+/// See the [module documentation](self) for the steps it takes.
+pub struct RetryPlanner {
+    config: CustlsConfig,
+    max_attempts: usize,
+    plain_fallback: bool,
+
+    #[cfg(feature = "std")]
+    cache: Mutex<FingerprintManager>,
+
+    #[cfg(not(feature = "std"))]
+    cache: core::cell::RefCell<FingerprintManager>,
+}
+
+impl RetryPlanner {
+    /// Create a planner whose first attempts use `config`
+    ///
+    /// Outcomes are kept in a fingerprint cache sized and timed by `config`'s
+    /// `max_cache_size`, `cache_policy` and `time_provider`.
+    pub fn new(config: CustlsConfig) -> Self {
+        let cache = FingerprintManager::with_policy(config.max_cache_size, config.cache_policy);
+        let cache = match &config.time_provider {
+            Some(time_provider) => cache.with_time_provider(Arc::clone(time_provider)),
+            None => cache,
+        };
+
+        Self {
+            config,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            plain_fallback: true,
+            #[cfg(feature = "std")]
+            cache: Mutex::new(cache),
+            #[cfg(not(feature = "std"))]
+            cache: core::cell::RefCell::new(cache),
+        }
+    }
+
+    /// Give up after this many attempts, including the first
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Whether the last resort is rustls's own ClientHello (the default)
+    pub fn with_plain_fallback(mut self, plain_fallback: bool) -> Self {
+        self.plain_fallback = plain_fallback;
+        self
+    }
+
+    /// The first attempt for `target`
+    ///
+    /// Uses the configured shape, with the template that last worked for the
+    /// target if there is one in the cache.
+    pub fn first_attempt(&self, target: &TargetKey) -> RetryAttempt {
+        let mut config = self.config.clone();
+        if let Some(working) = self.with_cache(|cache| cache.get_working_fingerprint(target)) {
+            config.template = Some(working.template);
+            config.rotation_policy = TemplateRotationPolicy::None;
+        }
+
+        RetryAttempt {
+            number: 1,
+            steps: Vec::new(),
+            config: Some(config),
+        }
+    }
+
+    /// Record that `failed` was rejected, and propose the attempt to make next
+    ///
+    /// Returns `None` when the attempts are used up or no step is left to take.
+    pub fn next_attempt(
+        &self,
+        target: &TargetKey,
+        failed: &RetryAttempt,
+        rejection: HelloRejection,
+    ) -> Option<RetryAttempt> {
+        self.record(target, failed, false);
+
+        if failed.number >= self.max_attempts {
+            return None;
+        }
+        let config = failed.config.as_ref()?;
+
+        rejection
+            .ladder()
+            .into_iter()
+            .filter(|kind| !failed.has_taken(*kind))
+            .find_map(|kind| self.take_step(kind, config))
+            .map(|(step, config)| {
+                let mut steps = failed.steps.clone();
+                steps.push(step);
+                RetryAttempt {
+                    number: failed.number + 1,
+                    steps,
+                    config,
+                }
+            })
+    }
+
+    /// Record that `attempt` got through to `target`
+    pub fn record_success(&self, target: &TargetKey, attempt: &RetryAttempt) {
+        self.record(target, attempt, true);
+    }
+
+    /// Apply `kind` to `config`, if it changes anything
+    fn take_step(
+        &self,
+        kind: StepKind,
+        config: &CustlsConfig,
+    ) -> Option<(RetryStep, Option<CustlsConfig>)> {
+        let mut next = config.clone();
+        let step = match kind {
+            StepKind::ChangeTemplate => {
+                let current = config.template.as_ref();
+                let template = rotation_templates(config)
+                    .into_iter()
+                    .find(|template| Some(template) != current)?;
+                next.template = Some(template.clone());
+                next.rotation_policy = TemplateRotationPolicy::None;
+                RetryStep::ChangeTemplate(template)
+            }
+            StepKind::LowerRandomization => {
+                if config.randomization_level == RandomizationLevel::None {
+                    return None;
+                }
+                next.randomization_level = RandomizationLevel::None;
+                RetryStep::LowerRandomization(RandomizationLevel::None)
+            }
+            StepKind::DropExtensions => {
+                let template = DefaultCustomizer::resolve_template(config.template.as_ref()?).ok()?;
+                let dropped = RISKY_EXTENSIONS
+                    .into_iter()
+                    .filter(|ext| !config.excluded_extensions.contains(ext))
+                    .filter(|ext| match ext {
+                        ExtensionType::EncryptedClientHello => template.ech_grease.is_some(),
+                        _ => template.extension_order.contains(ext),
+                    })
+                    .collect::<Vec<_>>();
+                if dropped.is_empty() {
+                    return None;
+                }
+                next.excluded_extensions
+                    .extend_from_slice(&dropped);
+                RetryStep::DropExtensions(dropped)
+            }
+            StepKind::Plain => {
+                if !self.plain_fallback {
+                    return None;
+                }
+                return Some((RetryStep::Plain, None));
+            }
+        };
+        Some((step, Some(next)))
+    }
+
+    fn record(&self, target: &TargetKey, attempt: &RetryAttempt, success: bool) {
+        if let Some(fingerprint) = attempt.fingerprint() {
+            self.with_cache(|cache| cache.record_result(target, fingerprint, success));
+        }
+    }
+
+    /// Template the planner would start `target` with, if one has worked before
+    pub fn working_template(&self, target: &TargetKey) -> Option<BrowserTemplate> {
+        self.with_cache(|cache| cache.get_working_fingerprint(target))
+            .map(|fingerprint| fingerprint.template)
+    }
+
+    /// Success and failure counts and reputation recorded for `target`
+    pub fn stats(&self, target: &TargetKey) -> Option<(u32, u32, f64)> {
+        self.with_cache(|cache| cache.get_stats(target))
+    }
+
+    #[cfg(feature = "std")]
+    fn with_cache<R: Default>(&self, f: impl FnOnce(&mut FingerprintManager) -> R) -> R {
+        match self.cache.lock() {
+            Ok(mut cache) => f(&mut cache),
+            Err(_) => R::default(),
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn with_cache<R: Default>(&self, f: impl FnOnce(&mut FingerprintManager) -> R) -> R {
+        f(&mut self.cache.borrow_mut())
+    }
+}
+
+impl fmt::Debug for RetryPlanner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPlanner")
+            .field("config", &self.config)
+            .field("max_attempts", &self.max_attempts)
+            .field("plain_fallback", &self.plain_fallback)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    fn target() -> TargetKey {
+        TargetKey::new("example.com".to_string(), 443)
+    }
+
+    fn chrome() -> CustlsConfig {
+        CustlsConfig::builder()
+            .with_template(BrowserTemplate::Chrome130)
+            .build()
+    }
+
+    #[test]
+    fn test_classifies_rejections() {
+        let alert = |alert| HelloRejection::from_error(&Error::AlertReceived(alert));
+        assert_eq!(
+            alert(AlertDescription::HandshakeFailure),
+            Some(HelloRejection::HandshakeFailure)
+        );
+        assert_eq!(
+            alert(AlertDescription::ProtocolVersion),
+            Some(HelloRejection::ProtocolVersion)
+        );
+        assert_eq!(
+            alert(AlertDescription::IllegalParameter),
+            Some(HelloRejection::MalformedHello)
+        );
+        assert_eq!(alert(AlertDescription::BadCertificate), None);
+        assert_eq!(HelloRejection::from_error(&Error::DecryptError), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_classifies_io_errors() {
+        use std::io;
+
+        let wrapped = io::Error::new(
+            io::ErrorKind::InvalidData,
+            Error::AlertReceived(AlertDescription::ProtocolVersion),
+        );
+        assert_eq!(
+            HelloRejection::from_io_error(&wrapped),
+            Some(HelloRejection::ProtocolVersion)
+        );
+        for kind in [io::ErrorKind::ConnectionReset, io::ErrorKind::UnexpectedEof] {
+            assert_eq!(
+                HelloRejection::from_io_error(&io::Error::from(kind)),
+                Some(HelloRejection::ConnectionReset)
+            );
+        }
+        assert_eq!(
+            HelloRejection::from_io_error(&io::Error::from(io::ErrorKind::TimedOut)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_handshake_failure_ladder() {
+        let planner = RetryPlanner::new(chrome());
+        let mut attempt = planner.first_attempt(&target());
+        let mut steps = Vec::new();
+        while let Some(next) = planner.next_attempt(&target(), &attempt, HelloRejection::HandshakeFailure) {
+            steps.push(next.last_step().unwrap().clone());
+            attempt = next;
+        }
+
+        // Firefox has neither ALPS nor GREASE ECH to drop
+        assert_eq!(
+            steps,
+            vec![
+                RetryStep::ChangeTemplate(BrowserTemplate::Firefox135),
+                RetryStep::LowerRandomization(RandomizationLevel::None),
+                RetryStep::Plain,
+            ]
+        );
+        assert_eq!(attempt.number, 4);
+        assert!(attempt.config.is_none());
+        assert_eq!(planner.stats(&target()).map(|(_, failures, _)| failures), Some(3));
+    }
+
+    #[test]
+    fn test_malformed_hello_drops_risky_extensions_first() {
+        let planner = RetryPlanner::new(chrome());
+        let first = planner.first_attempt(&target());
+        let second = planner
+            .next_attempt(&target(), &first, HelloRejection::MalformedHello)
+            .unwrap();
+        // Chrome offers GREASE ECH but no ALPS
+        let dropped = vec![ExtensionType::EncryptedClientHello];
+        assert_eq!(
+            second.last_step(),
+            Some(&RetryStep::DropExtensions(dropped.clone()))
+        );
+        let config = second.config.as_ref().unwrap();
+        assert_eq!(config.template, Some(BrowserTemplate::Chrome130));
+        assert_eq!(config.excluded_extensions, dropped);
+
+        let third = planner
+            .next_attempt(&target(), &second, HelloRejection::MalformedHello)
+            .unwrap();
+        assert_eq!(
+            third.steps,
+            vec![
+                RetryStep::DropExtensions(dropped),
+                RetryStep::LowerRandomization(RandomizationLevel::None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_attempts_are_bounded() {
+        let planner = RetryPlanner::new(chrome())
+            .with_max_attempts(2)
+            .with_plain_fallback(false);
+        let first = planner.first_attempt(&target());
+        let second = planner
+            .next_attempt(&target(), &first, HelloRejection::ConnectionReset)
+            .unwrap();
+        assert!(planner
+            .next_attempt(&target(), &second, HelloRejection::ConnectionReset)
+            .is_none());
+
+        // Without the plain fallback, the ladder ends with the last customized shape
+        let planner = RetryPlanner::new(chrome()).with_plain_fallback(false);
+        let mut attempt = planner.first_attempt(&target());
+        while let Some(next) = planner.next_attempt(&target(), &attempt, HelloRejection::ProtocolVersion) {
+            attempt = next;
+        }
+        assert!(attempt.config.is_some());
+        assert!(!attempt.steps.contains(&RetryStep::Plain));
+    }
+
+    #[test]
+    fn test_working_template_is_reused() {
+        let planner = RetryPlanner::new(chrome());
+        let first = planner.first_attempt(&target());
+        let second = planner
+            .next_attempt(&target(), &first, HelloRejection::HandshakeFailure)
+            .unwrap();
+        planner.record_success(&target(), &second);
+
+        assert_eq!(planner.working_template(&target()), Some(BrowserTemplate::Firefox135));
+        let again = planner.first_attempt(&target());
+        assert_eq!(again.template(), Some(&BrowserTemplate::Firefox135));
+        assert!(again.steps.is_empty());
+
+        // Other targets start from the configured template
+        let other = TargetKey::new("other.example".to_string(), 443);
+        assert_eq!(
+            planner.first_attempt(&other).template(),
+            Some(&BrowserTemplate::Chrome130)
+        );
+    }
+}
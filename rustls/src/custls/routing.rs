@@ -0,0 +1,313 @@
+//! Per-host template routing for custls.
+//!
+//! A [`CustlsConfig`](super::CustlsConfig) can carry an ordered list of
+//! [`RoutingRule`]s. In phase 1 the customizer matches the connection's target
+//! (server name and port) and offered ALPN protocols against the rules; the first
+//! rule that matches decides how the ClientHello is shaped:
+//!
+//! - [`RouteAction::Template`]: always use one template
+//! - [`RouteAction::Rotate`]: pick one template of a set, sticky per [`TargetKey`]
+//! - [`RouteAction::Disabled`]: leave the ClientHello to rustls
+//!
+//! Connections no rule matches fall back to the configured template and rotation
+//! policy.
+//!
+//! ## Usage
+//!
+//! ```rust,ignore
+//! use rustls::custls::{BrowserTemplate, CustlsConfig, HostPattern, RouteAction, RoutingRule};
+//!
+//! let config = CustlsConfig::builder()
+//!     .with_template(BrowserTemplate::Chrome130)
+//!     .with_routing_rule(RoutingRule::new(
+//!         HostPattern::Suffix("internal.example".into()),
+//!         RouteAction::Disabled,
+//!     ))
+//!     .with_routing_rule(RoutingRule::new(
+//!         HostPattern::Glob("*.apple.com".into()),
+//!         RouteAction::Template(BrowserTemplate::Safari17),
+//!     ))
+//!     .build();
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::BrowserTemplate;
+use super::state::TargetKey;
+
+/// Host matching pattern of a [`RoutingRule`]
+///
+/// Matching is ASCII case-insensitive.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HostPattern {
+    /// Matches every host
+    Any,
+
+    /// Matches exactly this host
+    Exact(String),
+
+    /// Matches this domain and all of its subdomains
+    ///
+    /// `Suffix("example.com")` matches `example.com` and `cdn.example.com`, but
+    /// not `badexample.com`. A leading dot is ignored.
+    Suffix(String),
+
+    /// Shell-style glob: `*` matches any run of characters, `?` exactly one
+    ///
+    /// `*.apple.com` matches `www.apple.com` and `a.b.apple.com`, but not
+    /// `apple.com`.
+    Glob(String),
+}
+
+impl HostPattern {
+    /// Whether `host` matches this pattern
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.');
+        match self {
+            Self::Any => true,
+            Self::Exact(expected) => host.eq_ignore_ascii_case(expected.trim_end_matches('.')),
+            Self::Suffix(suffix) => {
+                let suffix = suffix.trim_start_matches('.').trim_end_matches('.');
+                if host.len() == suffix.len() {
+                    return host.eq_ignore_ascii_case(suffix);
+                }
+                host.len() > suffix.len()
+                    && host.as_bytes()[host.len() - suffix.len() - 1] == b'.'
+                    && host[host.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+            }
+            Self::Glob(pattern) => glob_matches(pattern.as_bytes(), host.as_bytes()),
+        }
+    }
+}
+
+/// Iterative glob matching with single-star backtracking
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == b'?' || c.eq_ignore_ascii_case(&text[t]) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// What to do with connections a [`RoutingRule`] matches
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RouteAction {
+    /// Shape the ClientHello with this template
+    Template(BrowserTemplate),
+
+    /// Shape the ClientHello with one template of this set
+    ///
+    /// The template is chosen from the target, so a given host and port always
+    /// gets the same one while different hosts spread over the set. An empty set
+    /// falls back to the configured template.
+    Rotate(Vec<BrowserTemplate>),
+
+    /// Do not customize the ClientHello at all
+    Disabled,
+}
+
+/// One entry of the per-host routing table in [`CustlsConfig`](super::CustlsConfig)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RoutingRule {
+    /// Hosts this rule applies to
+    pub host: HostPattern,
+
+    /// Port this rule applies to, or any port when `None`
+    pub port: Option<u16>,
+
+    /// ALPN protocol the connection must offer, or any when `None`
+    pub alpn: Option<Vec<u8>>,
+
+    /// Action taken for matching connections
+    pub action: RouteAction,
+}
+
+impl RoutingRule {
+    /// Create a rule matching `host` on any port and ALPN
+    pub fn new(host: HostPattern, action: RouteAction) -> Self {
+        Self {
+            host,
+            port: None,
+            alpn: None,
+            action,
+        }
+    }
+
+    /// Restrict the rule to one port
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Restrict the rule to connections offering this ALPN protocol
+    pub fn with_alpn(mut self, protocol: &[u8]) -> Self {
+        self.alpn = Some(protocol.to_vec());
+        self
+    }
+
+    /// Whether this rule applies to a connection to `target` offering `alpn_protocols`
+    pub fn matches(&self, target: &TargetKey, alpn_protocols: &[Vec<u8>]) -> bool {
+        self.host.matches(&target.host)
+            && self.port.is_none_or(|port| port == target.port)
+            && self
+                .alpn
+                .as_ref()
+                .is_none_or(|protocol| alpn_protocols.contains(protocol))
+    }
+}
+
+/// Find the action of the first rule matching the connection
+pub fn route<'a>(
+    rules: &'a [RoutingRule],
+    target: &TargetKey,
+    alpn_protocols: &[Vec<u8>],
+) -> Option<&'a RouteAction> {
+    rules
+        .iter()
+        .find(|rule| rule.matches(target, alpn_protocols))
+        .map(|rule| &rule.action)
+}
+
+/// Pick the template of a rotation set assigned to `target`
+///
+/// The choice is a stable hash of the lowercased host and the port, so it does not
+/// depend on connection order and needs no per-target state.
+pub fn sticky_choice<'a>(
+    templates: &'a [BrowserTemplate],
+    target: &TargetKey,
+) -> Option<&'a BrowserTemplate> {
+    if templates.is_empty() {
+        return None;
+    }
+
+    // FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let host = target.host.trim_end_matches('.');
+    for byte in host
+        .bytes()
+        .map(|b| b.to_ascii_lowercase())
+        .chain(target.port.to_be_bytes())
+    {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    templates.get((hash % templates.len() as u64) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    fn target(host: &str, port: u16) -> TargetKey {
+        TargetKey::new(host.to_string(), port)
+    }
+
+    #[test]
+    fn test_host_patterns() {
+        assert!(HostPattern::Any.matches("anything.example"));
+
+        let exact = HostPattern::Exact("api.example.com".into());
+        assert!(exact.matches("API.example.com"));
+        assert!(exact.matches("api.example.com."));
+        assert!(!exact.matches("v2.api.example.com"));
+
+        let suffix = HostPattern::Suffix(".example.com".into());
+        assert!(suffix.matches("example.com"));
+        assert!(suffix.matches("cdn.Example.com"));
+        assert!(!suffix.matches("badexample.com"));
+        assert!(!suffix.matches("example.org"));
+
+        let glob = HostPattern::Glob("*.apple.com".into());
+        assert!(glob.matches("www.apple.com"));
+        assert!(glob.matches("a.b.apple.com"));
+        assert!(!glob.matches("apple.com"));
+        assert!(!glob.matches("www.apple.com.evil"));
+
+        let glob = HostPattern::Glob("edge-??.*.akamai*.net".into());
+        assert!(glob.matches("edge-01.fra.akamaiedge.net"));
+        assert!(!glob.matches("edge-1.fra.akamaiedge.net"));
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let rules = vec![
+            RoutingRule::new(HostPattern::Suffix("internal.example".into()), RouteAction::Disabled),
+            RoutingRule::new(
+                HostPattern::Suffix("apple.com".into()),
+                RouteAction::Template(BrowserTemplate::Safari17),
+            )
+            .with_port(443),
+            RoutingRule::new(HostPattern::Any, RouteAction::Template(BrowserTemplate::Firefox135))
+                .with_alpn(b"h2"),
+        ];
+        let h2 = [b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        assert_eq!(
+            route(&rules, &target("db.internal.example", 443), &h2),
+            Some(&RouteAction::Disabled)
+        );
+        assert_eq!(
+            route(&rules, &target("cdn.apple.com", 443), &[]),
+            Some(&RouteAction::Template(BrowserTemplate::Safari17))
+        );
+        // Wrong port for the Apple rule, so the ALPN rule applies
+        assert_eq!(
+            route(&rules, &target("cdn.apple.com", 8443), &h2),
+            Some(&RouteAction::Template(BrowserTemplate::Firefox135))
+        );
+        assert_eq!(route(&rules, &target("example.org", 443), &[]), None);
+    }
+
+    #[test]
+    fn test_sticky_choice_is_stable_per_target() {
+        let set = vec![
+            BrowserTemplate::Chrome130,
+            BrowserTemplate::Firefox135,
+            BrowserTemplate::Safari17,
+            BrowserTemplate::Edge130,
+        ];
+
+        let first = sticky_choice(&set, &target("shop.example", 443));
+        for _ in 0..10 {
+            assert_eq!(sticky_choice(&set, &target("shop.example", 443)), first);
+        }
+        assert_eq!(sticky_choice(&set, &target("SHOP.example.", 443)), first);
+
+        // Different targets spread over the set
+        let mut seen = Vec::new();
+        for i in 0..64 {
+            let host = alloc::format!("host{}.example", i);
+            let choice = sticky_choice(&set, &target(&host, 443)).unwrap();
+            if !seen.contains(&choice) {
+                seen.push(choice);
+            }
+        }
+        assert_eq!(seen.len(), set.len());
+
+        assert_eq!(sticky_choice(&[], &target("shop.example", 443)), None);
+    }
+}
@@ -51,11 +51,13 @@
 //! ```
 
 use alloc::collections::BTreeMap;
+use alloc::format;
 use alloc::vec::Vec;
 
 use crate::error::Error as RustlsError;
 use crate::enums::ProtocolVersion;
 
+use super::CustlsError;
 use super::state::ClientHelloConfig;
 
 /// RFC 8446 downgrade protection canary for TLS 1.2
@@ -347,6 +349,44 @@ impl SessionStateTracker {
         }
     }
     
+    /// Check that resuming a session keeps the fingerprint it was established with.
+    ///
+    /// A session may only be offered in a ClientHello presenting the same
+    /// fingerprint (see [`ClientHelloConfig::same_fingerprint`]) as the handshake
+    /// that created it; otherwise the resumption links two identities. Sessions
+    /// that are not tracked have no recorded fingerprint and are refused as well.
+    ///
+    /// On success the resumption is counted.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - The session identifier
+    /// * `config` - Configuration of the ClientHello that would offer the session
+    ///
+    /// # Errors
+    ///
+    /// Returns `CustlsError::ValidationError` if the session is unknown or was
+    /// established with a different fingerprint.
+    pub fn check_resumption(
+        &mut self,
+        session_id: &SessionId,
+        config: &ClientHelloConfig,
+    ) -> Result<(), CustlsError> {
+        let state = self.sessions.get_mut(session_id).ok_or_else(|| {
+            CustlsError::ValidationError("session has no recorded fingerprint".into())
+        })?;
+        
+        if !state.config.same_fingerprint(config) {
+            return Err(CustlsError::ValidationError(format!(
+                "session was established as {:?}, not {:?}",
+                state.config.template, config.template
+            )));
+        }
+        
+        state.increment_resume_count();
+        Ok(())
+    }
+    
     /// Get session statistics.
     ///
     /// # Arguments
@@ -732,6 +772,32 @@ mod tests {
         assert_eq!(config1.padding_length, config2.padding_length);
         assert_eq!(config1.padding_length, 512);
     }
+    
+    #[test]
+    fn test_check_resumption_enforces_recorded_fingerprint() {
+        let mut tracker = SessionStateTracker::new(100);
+        let session_id = SessionId::new(vec![1, 2, 3]);
+        let chrome = ClientHelloConfig::from_template(
+            BrowserTemplate::Chrome130,
+            &crate::custls::templates::chrome_130(),
+        );
+        let firefox = ClientHelloConfig::from_template(
+            BrowserTemplate::Firefox135,
+            &crate::custls::templates::firefox_135(),
+        );
+        tracker.record_session(&session_id, chrome.clone());
+        
+        // Per-connection variation does not change the fingerprint
+        let mut varied = chrome.clone();
+        varied.padding_length = 300;
+        varied.random_seed = 7;
+        assert!(tracker.check_resumption(&session_id, &varied).is_ok());
+        assert_eq!(tracker.get_session_stats(&session_id), Some((false, 1, false)));
+        
+        assert!(tracker.check_resumption(&session_id, &firefox).is_err());
+        assert!(tracker.check_resumption(&SessionId::new(vec![9]), &chrome).is_err());
+        assert_eq!(tracker.get_session_stats(&session_id), Some((false, 1, false)));
+    }
 }
 
 
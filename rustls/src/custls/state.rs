@@ -19,6 +19,10 @@
 //! - Small random variations applied on retrieval
 //! - Size-limited to prevent unbounded growth
 //!
+//! A [`CachePolicy`] adds wall-clock behavior on top: entries expire after a
+//! time-to-live, old results fade with a half-life, and a target that keeps
+//! failing has its template quarantined so the customizer rotates away from it.
+//!
 //! ## Usage
 //!
 //! ```rust,ignore
@@ -37,8 +41,11 @@
 
 use alloc::collections::BTreeMap;
 use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::time::Duration;
 
+use pki_types::UnixTime;
 #[cfg(feature = "std")]
 use std::time::Instant;
 
@@ -47,6 +54,10 @@ use crate::crypto::kx::NamedGroup;
 use crate::msgs::ExtensionType;
 
 use super::BrowserTemplate;
+use super::templates::TemplateData;
+#[cfg(feature = "std")]
+use crate::time_provider::DefaultTimeProvider;
+use crate::time_provider::TimeProvider;
 
 /// Key for identifying a target server.
 ///
@@ -106,6 +117,92 @@ pub struct ClientHelloConfig {
     pub signature_algorithms: Vec<SignatureScheme>,
 }
 
+impl ClientHelloConfig {
+    /// Snapshot of the ClientHello `data` produces, before any randomization
+    pub fn from_template(template: BrowserTemplate, data: &TemplateData) -> Self {
+        Self {
+            template,
+            cipher_suites: data.cipher_suites.clone(),
+            extension_order: data.extension_order.clone(),
+            extension_data: BTreeMap::new(),
+            grease_cipher_positions: Vec::new(),
+            grease_extension_positions: Vec::new(),
+            padding_length: 0,
+            random_seed: 0,
+            supported_groups: data.supported_groups.clone(),
+            signature_algorithms: data.signature_algorithms.clone(),
+        }
+    }
+
+    /// Whether `other` presents the same fingerprint
+    ///
+    /// Compares what identifies the client (cipher suites, extensions, groups and
+    /// signature algorithms) and ignores the per-connection variation: GREASE
+    /// positions, padding, the random seed and extension contents.
+    pub fn same_fingerprint(&self, other: &Self) -> bool {
+        self.cipher_suites == other.cipher_suites
+            && self.extension_order == other.extension_order
+            && self.supported_groups == other.supported_groups
+            && self.signature_algorithms == other.signature_algorithms
+    }
+}
+
+/// Time- and failure-aware behavior of the fingerprint cache
+///
+/// The default keeps entries until they are evicted for space, weighs every
+/// recorded result equally and never quarantines, which is how the cache behaves
+/// without a policy. Everything time-based needs a clock: the system clock under
+/// `std`, or a [`TimeProvider`] given to the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachePolicy {
+    /// Drop entries not used or updated for this long
+    pub ttl: Option<Duration>,
+    
+    /// Halve the weight of recorded results every time this much time passes
+    ///
+    /// Faded results count as neutral, up to one handshake's worth, so an entry
+    /// whose history has faded drifts back towards a score of 0.5.
+    pub half_life: Option<Duration>,
+    
+    /// Quarantine a target's template after this many consecutive failures
+    pub quarantine_after: Option<u32>,
+    
+    /// How long a quarantine lasts
+    ///
+    /// Without a clock, a quarantine lasts until the target is invalidated.
+    pub quarantine_duration: Duration,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            ttl: None,
+            half_life: None,
+            quarantine_after: None,
+            quarantine_duration: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+/// A template a target has stopped accepting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quarantine {
+    /// The template that kept failing
+    pub template: BrowserTemplate,
+    
+    /// When the quarantine ends, or `None` if it was started without a clock
+    pub until: Option<UnixTime>,
+}
+
+impl Quarantine {
+    fn active(&self, now: Option<UnixTime>) -> bool {
+        match (self.until, now) {
+            (Some(until), Some(now)) => now.as_secs() < until.as_secs(),
+            _ => true,
+        }
+    }
+}
+
 /// Cache entry for a working fingerprint.
 ///
 /// Tracks the configuration along with success/failure statistics and
@@ -125,8 +222,27 @@ pub struct FingerprintEntry {
     ///
     /// Calculated as: success_count / (success_count + failure_count)
     /// Used for eviction policy - low reputation entries are evicted first.
+    /// With a half-life, it is calculated from the faded weights instead.
     pub reputation_score: f64,
     
+    /// Successes, with older ones faded by the cache policy's half-life
+    pub success_weight: f64,
+    
+    /// Failures, with older ones faded by the cache policy's half-life
+    pub failure_weight: f64,
+    
+    /// Faded results, counted as neutral (at most 1.0)
+    pub faded_weight: f64,
+    
+    /// Failures recorded since the last success
+    pub consecutive_failures: u32,
+    
+    /// Wall-clock time of the last lookup or recorded result, if there is a clock
+    pub last_seen: Option<UnixTime>,
+    
+    /// Set while the target's template is quarantined
+    pub quarantine: Option<Quarantine>,
+    
     /// Last time this entry was used
     #[cfg(feature = "std")]
     pub last_used: Instant,
@@ -153,6 +269,12 @@ impl FingerprintEntry {
             success_count: 0,
             failure_count: 0,
             reputation_score: 0.5, // Start with neutral reputation
+            success_weight: 0.0,
+            failure_weight: 0.0,
+            faded_weight: 0.0,
+            consecutive_failures: 0,
+            last_seen: None,
+            quarantine: None,
             last_used: Instant::now(),
             previous_grease_values: Vec::new(),
             previous_padding_lengths: Vec::new(),
@@ -167,6 +289,12 @@ impl FingerprintEntry {
             success_count: 0,
             failure_count: 0,
             reputation_score: 0.5,
+            success_weight: 0.0,
+            failure_weight: 0.0,
+            faded_weight: 0.0,
+            consecutive_failures: 0,
+            last_seen: None,
+            quarantine: None,
             last_used: Duration::from_secs(0),
             previous_grease_values: Vec::new(),
             previous_padding_lengths: Vec::new(),
@@ -183,6 +311,30 @@ impl FingerprintEntry {
         }
     }
     
+    /// Reputation score from the faded weights
+    ///
+    /// Equal to [`Self::update_reputation`]'s score until results start to fade.
+    pub fn weighted_reputation(&self) -> f64 {
+        let total = self.success_weight + self.failure_weight + self.faded_weight;
+        if total > 0.0 {
+            (self.success_weight + self.faded_weight / 2.0) / total
+        } else {
+            0.5
+        }
+    }
+    
+    /// Scale the success and failure weights by `factor` (0.0 to 1.0)
+    ///
+    /// The weight lost is added to the neutral weight, capped at one result.
+    pub fn decay(&mut self, factor: f64) {
+        let before = self.success_weight + self.failure_weight;
+        self.success_weight *= factor;
+        self.failure_weight *= factor;
+        let faded = before - self.success_weight - self.failure_weight;
+        self.faded_weight = (self.faded_weight * factor + faded).min(1.0);
+        self.reputation_score = self.weighted_reputation();
+    }
+    
     /// Update last used timestamp
     #[cfg(feature = "std")]
     pub fn touch(&mut self) {
@@ -248,6 +400,9 @@ impl FingerprintEntry {
     }
 }
 
+/// Number of buckets in [`FingerprintManager::reputation_histogram`]
+pub const REPUTATION_BUCKETS: usize = 10;
+
 /// Fingerprint cache manager.
 ///
 /// Manages a cache of working ClientHello configurations indexed by target.
@@ -263,6 +418,12 @@ pub struct FingerprintManager {
     
     /// Maximum number of cache entries
     max_size: usize,
+    
+    /// Expiry, decay and quarantine settings
+    policy: CachePolicy,
+    
+    /// Wall clock for the policy
+    time_provider: Option<Arc<dyn TimeProvider>>,
 }
 
 impl FingerprintManager {
@@ -279,10 +440,132 @@ impl FingerprintManager {
     /// let manager = FingerprintManager::new(1000);
     /// ```
     pub fn new(max_size: usize) -> Self {
+        Self::with_policy(max_size, CachePolicy::default())
+    }
+    
+    /// Create a fingerprint manager that applies `policy`.
+    ///
+    /// Under `std` the policy is timed by the system clock; use
+    /// [`Self::with_time_provider`] to supply another one.
+    pub fn with_policy(max_size: usize, policy: CachePolicy) -> Self {
+        #[cfg(feature = "std")]
+        let time_provider: Option<Arc<dyn TimeProvider>> = Some(Arc::new(DefaultTimeProvider));
+        #[cfg(not(feature = "std"))]
+        let time_provider: Option<Arc<dyn TimeProvider>> = None;
+        
         Self {
             cache: BTreeMap::new(),
             max_size,
+            policy,
+            time_provider,
+        }
+    }
+    
+    /// Time the cache policy with `time_provider`
+    pub fn with_time_provider(mut self, time_provider: Arc<dyn TimeProvider>) -> Self {
+        self.time_provider = Some(time_provider);
+        self
+    }
+    
+    /// Get the cache policy
+    pub fn policy(&self) -> &CachePolicy {
+        &self.policy
+    }
+    
+    fn now(&self) -> Option<UnixTime> {
+        self.time_provider
+            .as_ref()
+            .and_then(|provider| provider.current_time())
+    }
+    
+    /// Whether `entry` has outlived the TTL at `now`
+    fn expired(&self, entry: &FingerprintEntry, now: Option<UnixTime>) -> bool {
+        match (self.policy.ttl, entry.last_seen, now) {
+            (Some(ttl), Some(last_seen), Some(now)) => {
+                now.as_secs().saturating_sub(last_seen.as_secs()) > ttl.as_secs()
+            }
+            _ => false,
+        }
+    }
+    
+    /// Fraction of its weight `entry` keeps at `now` under the half-life
+    fn decay_factor(&self, entry: &FingerprintEntry, now: Option<UnixTime>) -> f64 {
+        match (self.policy.half_life, entry.last_seen, now) {
+            (Some(half_life), Some(last_seen), Some(now)) if half_life.as_secs() > 0 => {
+                let elapsed = now.as_secs().saturating_sub(last_seen.as_secs());
+                half_life_factor(elapsed as f64 / half_life.as_secs() as f64)
+            }
+            _ => 1.0,
+        }
+    }
+    
+    /// Reputation of `entry` as it stands at `now`
+    fn reputation_at(&self, entry: &FingerprintEntry, now: Option<UnixTime>) -> f64 {
+        let factor = self.decay_factor(entry, now);
+        if factor >= 1.0 {
+            return entry.reputation_score;
+        }
+        let mut entry = entry.clone();
+        entry.decay(factor);
+        entry.reputation_score
+    }
+    
+    /// Bring `target`'s entry up to `now`: drop it if expired, fade its results
+    /// and lift a quarantine that has ended.
+    ///
+    /// Returns the entry if it is still cached.
+    fn refresh(&mut self, target: &TargetKey, now: Option<UnixTime>) -> Option<&mut FingerprintEntry> {
+        let entry = self.cache.get(target)?;
+        if self.expired(entry, now) {
+            self.cache.remove(target);
+            return None;
+        }
+        
+        let factor = self.decay_factor(entry, now);
+        let entry = self.cache.get_mut(target)?;
+        if factor < 1.0 {
+            entry.decay(factor);
+        }
+        if now.is_some() {
+            entry.last_seen = now;
+        }
+        if entry
+            .quarantine
+            .as_ref()
+            .is_some_and(|quarantine| !quarantine.active(now))
+        {
+            entry.quarantine = None;
+        }
+        Some(entry)
+    }
+    
+    /// Remove every entry that has outlived the TTL
+    ///
+    /// Returns the number of entries removed.
+    pub fn purge_expired(&mut self) -> usize {
+        let now = self.now();
+        let before = self.cache.len();
+        let expired = self
+            .cache
+            .iter()
+            .filter(|(_, entry)| self.expired(entry, now))
+            .map(|(target, _)| target.clone())
+            .collect::<Vec<_>>();
+        for target in expired {
+            self.cache.remove(&target);
         }
+        before - self.cache.len()
+    }
+    
+    /// Template quarantined for `target`, if there is an active quarantine
+    pub fn quarantined_template(&self, target: &TargetKey) -> Option<&BrowserTemplate> {
+        let now = self.now();
+        self.cache
+            .get(target)
+            .filter(|entry| !self.expired(entry, now))
+            .and_then(|entry| entry.quarantine.as_ref())
+            .filter(|quarantine| quarantine.active(now))
+            .map(|quarantine| &quarantine.template)
     }
     
     /// Get the current cache size
@@ -320,7 +603,8 @@ impl FingerprintManager {
     
     /// Insert or update a cache entry
     ///
-    /// If the cache is full, evicts the lowest reputation entry before inserting.
+    /// If the cache is full, drops expired entries, then evicts the lowest
+    /// reputation entry before inserting.
     ///
     /// # Arguments
     ///
@@ -340,13 +624,15 @@ impl FingerprintManager {
     
     /// Evict the entry with the lowest reputation score.
     ///
-    /// If multiple entries have the same lowest reputation, evicts the least
-    /// recently used one (LRU policy).
+    /// Expired entries go first. Otherwise, the reputation is taken as it stands
+    /// now, after fading; if multiple entries have the same lowest reputation,
+    /// evicts the least recently used one (LRU policy).
     #[allow(dead_code)]
     fn evict_lowest_reputation(&mut self) {
-        if self.cache.is_empty() {
+        if self.purge_expired() > 0 || self.cache.is_empty() {
             return;
         }
+        let now = self.now();
         
         // Find entry with lowest reputation score
         // If tied, prefer least recently used
@@ -366,9 +652,10 @@ impl FingerprintManager {
             #[cfg(not(feature = "std"))]
             let is_older = entry.last_used < oldest_time;
             
-            if entry.reputation_score < lowest_score || 
-               (entry.reputation_score == lowest_score && is_older) {
-                lowest_score = entry.reputation_score;
+            let score = self.reputation_at(entry, now);
+            if score < lowest_score || 
+               (score == lowest_score && is_older) {
+                lowest_score = score;
                 lowest_key = Some(key.clone());
                 oldest_time = entry.last_used;
             }
@@ -395,6 +682,9 @@ impl FingerprintManager {
     /// `Some(config)` if a cached fingerprint exists, `None` otherwise.
     /// The returned config has small variations applied.
     ///
+    /// Expired entries are dropped instead, and a fingerprint whose template is
+    /// quarantined is not returned.
+    ///
     /// # Note
     ///
     /// This method updates the `last_used` timestamp for the cache entry.
@@ -402,11 +692,21 @@ impl FingerprintManager {
         &mut self,
         target: &TargetKey,
     ) -> Option<ClientHelloConfig> {
+        let now = self.now();
+        
         // Look up cached entry
-        if let Some(entry) = self.cache.get_mut(target) {
+        if let Some(entry) = self.refresh(target, now) {
             // Update last used timestamp
             entry.touch();
             
+            if entry
+                .quarantine
+                .as_ref()
+                .is_some_and(|quarantine| quarantine.template == entry.config.template)
+            {
+                return None;
+            }
+            
             // Clone the config for variation
             // Note: Actual variation logic will be applied by the randomizer
             // in the calling code. We just return the cached config here.
@@ -421,6 +721,9 @@ impl FingerprintManager {
     /// Updates the cache entry's success/failure counts and recalculates the
     /// reputation score. If no entry exists for the target, creates a new one.
     ///
+    /// Under a policy with `quarantine_after`, that many consecutive failures
+    /// quarantine the template of `config` for the target.
+    ///
     /// # Arguments
     ///
     /// * `target` - The target that was connected to
@@ -442,8 +745,11 @@ impl FingerprintManager {
         config: ClientHelloConfig,
         success: bool,
     ) {
-        // Check if entry exists
-        let entry_exists = self.cache.contains_key(target);
+        let now = self.now();
+        let policy = self.policy;
+        
+        // Check if entry exists, dropping it if it has expired
+        let entry_exists = self.refresh(target, now).is_some();
         
         // If entry doesn't exist and cache is full, evict first
         if !entry_exists && self.cache.len() >= self.max_size {
@@ -453,16 +759,35 @@ impl FingerprintManager {
         // Get or create entry
         let entry = self.cache.entry(target.clone())
             .or_insert_with(|| FingerprintEntry::new(config.clone()));
+        entry.last_seen = now;
         
         // Update counts
         if success {
             entry.success_count += 1;
+            entry.success_weight += 1.0;
+            entry.consecutive_failures = 0;
         } else {
             entry.failure_count += 1;
+            entry.failure_weight += 1.0;
+            entry.consecutive_failures += 1;
         }
         
         // Update reputation score
-        entry.update_reputation();
+        entry.reputation_score = entry.weighted_reputation();
+        
+        if let Some(limit) = policy.quarantine_after {
+            if entry.consecutive_failures >= limit.max(1) {
+                entry.consecutive_failures = 0;
+                entry.quarantine = Some(Quarantine {
+                    template: config.template.clone(),
+                    until: now.and_then(|now| {
+                        now.as_secs()
+                            .checked_add(policy.quarantine_duration.as_secs())
+                            .map(|secs| UnixTime::since_unix_epoch(Duration::from_secs(secs)))
+                    }),
+                });
+            }
+        }
         
         // Update last used timestamp
         entry.touch();
@@ -479,12 +804,34 @@ impl FingerprintManager {
     ///
     /// # Returns
     ///
-    /// `Some((success_count, failure_count, reputation_score))` if entry exists,
-    /// `None` otherwise.
+    /// `Some((success_count, failure_count, reputation_score))` if an unexpired
+    /// entry exists, `None` otherwise. The score is as it stands now, after fading.
     pub fn get_stats(&self, target: &TargetKey) -> Option<(u32, u32, f64)> {
-        self.cache.get(target).map(|entry| {
-            (entry.success_count, entry.failure_count, entry.reputation_score)
-        })
+        let now = self.now();
+        self.cache
+            .get(target)
+            .filter(|entry| !self.expired(entry, now))
+            .map(|entry| {
+                (entry.success_count, entry.failure_count, self.reputation_at(entry, now))
+            })
+    }
+    
+    /// Count the cached fingerprints by reputation score
+    ///
+    /// Bucket `i` counts scores in `[i / REPUTATION_BUCKETS, (i + 1) / REPUTATION_BUCKETS)`;
+    /// a perfect score of 1.0 is counted in the last bucket. Expired entries are
+    /// not counted.
+    pub fn reputation_histogram(&self) -> [usize; REPUTATION_BUCKETS] {
+        let now = self.now();
+        let mut histogram = [0; REPUTATION_BUCKETS];
+        for entry in self.cache.values() {
+            if self.expired(entry, now) {
+                continue;
+            }
+            let bucket = (self.reputation_at(entry, now) * REPUTATION_BUCKETS as f64) as usize;
+            histogram[bucket.min(REPUTATION_BUCKETS - 1)] += 1;
+        }
+        histogram
     }
     
     /// Get all cached targets
@@ -563,10 +910,38 @@ impl FingerprintManager {
     }
 }
 
+/// `2^-halvings`: the weight kept after `halvings` half-lives
+///
+/// `core` has no `powf`, so whole half-lives halve the weight and the remainder
+/// is a Taylor series of `e^(-x ln 2)`, accurate to well below `f64` precision
+/// for the `x < 1` it is given.
+fn half_life_factor(halvings: f64) -> f64 {
+    if halvings.is_nan() || halvings <= 0.0 {
+        return 1.0;
+    }
+    if halvings >= 1100.0 {
+        return 0.0;
+    }
+    
+    let whole = halvings as u32;
+    let exponent = -(halvings - whole as f64) * core::f64::consts::LN_2;
+    let mut term = 1.0;
+    let mut factor = 1.0;
+    for k in 1..20 {
+        term *= exponent / k as f64;
+        factor += term;
+    }
+    for _ in 0..whole {
+        factor /= 2.0;
+    }
+    factor
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use alloc::string::ToString;
+    use alloc::vec;
     
     // Helper function to create a test config
     fn create_test_config() -> ClientHelloConfig {
@@ -699,6 +1074,28 @@ mod tests {
         assert!((stats.2 - 0.666).abs() < 0.01); // reputation_score ~= 10/15
     }
     
+    #[test]
+    fn test_reputation_histogram() {
+        let mut manager = FingerprintManager::new(100);
+        assert_eq!(manager.reputation_histogram(), [0; REPUTATION_BUCKETS]);
+        
+        let config = create_test_config();
+        let perfect = TargetKey::new("perfect.example".to_string(), 443);
+        manager.record_result(&perfect, config.clone(), true);
+        let failing = TargetKey::new("failing.example".to_string(), 443);
+        manager.record_result(&failing, config.clone(), false);
+        let mixed = TargetKey::new("mixed.example".to_string(), 443);
+        for success in [true, true, false] {
+            manager.record_result(&mixed, config.clone(), success);
+        }
+        
+        let mut expected = [0; REPUTATION_BUCKETS];
+        expected[0] = 1; // 0.0
+        expected[6] = 1; // 0.667
+        expected[9] = 1; // 1.0 goes in the last bucket
+        assert_eq!(manager.reputation_histogram(), expected);
+    }
+    
     #[test]
     fn test_cache_eviction_policy() {
         let mut manager = FingerprintManager::new(3);
@@ -826,6 +1223,162 @@ mod tests {
         let targets = manager.get_all_targets();
         assert!(targets.is_empty());
     }
+    
+    /// Clock the tests advance by hand
+    #[derive(Debug, Default)]
+    struct TestClock(core::sync::atomic::AtomicU64);
+    
+    impl TestClock {
+        fn advance(&self, secs: u64) {
+            self.0.fetch_add(secs, core::sync::atomic::Ordering::Relaxed);
+        }
+    }
+    
+    impl TimeProvider for TestClock {
+        fn current_time(&self) -> Option<UnixTime> {
+            let secs = 1_700_000_000 + self.0.load(core::sync::atomic::Ordering::Relaxed);
+            Some(UnixTime::since_unix_epoch(Duration::from_secs(secs)))
+        }
+    }
+    
+    fn managed(policy: CachePolicy) -> (FingerprintManager, Arc<TestClock>) {
+        let clock = Arc::new(TestClock::default());
+        let manager = FingerprintManager::with_policy(100, policy).with_time_provider(clock.clone());
+        (manager, clock)
+    }
+    
+    #[test]
+    fn test_half_life_factor() {
+        assert_eq!(half_life_factor(0.0), 1.0);
+        assert_eq!(half_life_factor(-1.0), 1.0);
+        assert!((half_life_factor(1.0) - 0.5).abs() < 1e-12);
+        assert!((half_life_factor(2.5) - 0.176_776_695_296_636_87).abs() < 1e-12);
+        assert!((half_life_factor(0.25) - 0.840_896_415_253_714_5).abs() < 1e-12);
+        assert_eq!(half_life_factor(2000.0), 0.0);
+    }
+    
+    #[test]
+    fn test_entries_expire_after_ttl() {
+        let (mut manager, clock) = managed(CachePolicy {
+            ttl: Some(Duration::from_secs(60)),
+            ..CachePolicy::default()
+        });
+        let target = TargetKey::new("example.com".to_string(), 443);
+        let other = TargetKey::new("other.com".to_string(), 443);
+        manager.record_result(&target, create_test_config(), true);
+        manager.record_result(&other, create_test_config(), true);
+        
+        // A lookup keeps the entry alive
+        clock.advance(45);
+        assert!(manager.get_working_fingerprint(&target).is_some());
+        clock.advance(45);
+        assert!(manager.get_stats(&target).is_some());
+        assert!(manager.get_stats(&other).is_none());
+        assert_eq!(manager.reputation_histogram().iter().sum::<usize>(), 1);
+        
+        assert_eq!(manager.purge_expired(), 1);
+        assert_eq!(manager.get_all_targets(), vec![target.clone()]);
+        
+        clock.advance(61);
+        assert!(manager.get_working_fingerprint(&target).is_none());
+        assert!(manager.is_empty());
+    }
+    
+    #[test]
+    fn test_results_fade_with_half_life() {
+        let (mut manager, clock) = managed(CachePolicy {
+            half_life: Some(Duration::from_secs(3600)),
+            ..CachePolicy::default()
+        });
+        let old = TargetKey::new("old.com".to_string(), 443);
+        let new = TargetKey::new("new.com".to_string(), 443);
+        for _ in 0..10 {
+            manager.record_result(&old, create_test_config(), true);
+        }
+        assert_eq!(manager.get_stats(&old).unwrap().2, 1.0);
+        
+        // After one half-life, half the successes are left and one neutral result
+        clock.advance(3600);
+        let (successes, failures, score) = manager.get_stats(&old).unwrap();
+        assert_eq!((successes, failures), (10, 0));
+        assert!((score - 5.5 / 6.0).abs() < 1e-9);
+        
+        // Long-faded history is close to neutral, and a fresh success outranks it
+        clock.advance(30 * 24 * 3600);
+        let score = manager.get_stats(&old).unwrap().2;
+        assert!((score - 0.5).abs() < 1e-6);
+        manager.record_result(&new, create_test_config(), true);
+        assert!(manager.get_stats(&new).unwrap().2 > score);
+        
+        // Fresh failures count in full against the faded history
+        manager.record_result(&old, create_test_config(), false);
+        manager.record_result(&old, create_test_config(), false);
+        assert!((manager.get_stats(&old).unwrap().2 - 0.5 / 3.0).abs() < 1e-6);
+    }
+    
+    #[test]
+    fn test_eviction_uses_faded_reputation() {
+        let clock = Arc::new(TestClock::default());
+        let mut manager = FingerprintManager::with_policy(
+            2,
+            CachePolicy {
+                half_life: Some(Duration::from_secs(60)),
+                ..CachePolicy::default()
+            },
+        )
+        .with_time_provider(clock.clone());
+        let old = TargetKey::new("old.com".to_string(), 443);
+        let recent = TargetKey::new("recent.com".to_string(), 443);
+        manager.record_result(&old, create_test_config(), true);
+        manager.record_result(&old, create_test_config(), true);
+        
+        clock.advance(3600);
+        manager.record_result(&recent, create_test_config(), true);
+        manager.record_result(&recent, create_test_config(), false);
+        manager.record_result(&recent, create_test_config(), true);
+        
+        // Without fading, the old entry's perfect score would keep it
+        let new = TargetKey::new("new.com".to_string(), 443);
+        manager.record_result(&new, create_test_config(), true);
+        assert!(manager.get_stats(&old).is_none());
+        assert!(manager.get_stats(&recent).is_some());
+    }
+    
+    #[test]
+    fn test_consecutive_failures_quarantine_template() {
+        let (mut manager, clock) = managed(CachePolicy {
+            quarantine_after: Some(3),
+            quarantine_duration: Duration::from_secs(60),
+            ..CachePolicy::default()
+        });
+        let target = TargetKey::new("example.com".to_string(), 443);
+        
+        // A success resets the run of failures
+        for success in [false, false, true, false, false] {
+            manager.record_result(&target, create_test_config(), success);
+        }
+        assert!(manager.quarantined_template(&target).is_none());
+        assert!(manager.get_working_fingerprint(&target).is_some());
+        
+        manager.record_result(&target, create_test_config(), false);
+        assert_eq!(manager.quarantined_template(&target), Some(&BrowserTemplate::Chrome130));
+        assert!(manager.get_working_fingerprint(&target).is_none());
+        
+        // A fingerprint from another template is served during the quarantine
+        let mut firefox = create_test_config();
+        firefox.template = BrowserTemplate::Firefox135;
+        manager.record_result(&target, firefox, true);
+        assert_eq!(
+            manager.get_working_fingerprint(&target).map(|config| config.template),
+            Some(BrowserTemplate::Firefox135)
+        );
+        assert!(manager.quarantined_template(&target).is_some());
+        
+        clock.advance(61);
+        assert!(manager.quarantined_template(&target).is_none());
+        manager.record_result(&target, create_test_config(), true);
+        assert!(manager.get_working_fingerprint(&target).is_some());
+    }
 }
 
 #[cfg(test)]
@@ -20,7 +20,9 @@
 //! - GREASE injection patterns (positions and probabilities)
 //! - Padding length distributions
 //! - ALPN protocol lists
-//! - HTTP/2 pseudo-header ordering (for multi-layer coordination)
+//! - HTTP/2 connection profile: SETTINGS, WINDOW_UPDATE, priorities and header order
+//!   (for multi-layer coordination)
+//! - QUIC transport parameter order and GREASE, for the HTTP/3 templates
 //!
 //! ## Usage
 //!
@@ -35,11 +37,14 @@ use alloc::string::{String, ToString};
 use alloc::vec;
 use alloc::vec::Vec;
 use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
 
 use crate::crypto::{CipherSuite, SignatureScheme};
+use crate::crypto::hpke::{HpkeAead, HpkeKdf, HpkeKem, HpkeSymmetricCipherSuite};
 use crate::crypto::kx::NamedGroup;
 use crate::enums::ProtocolVersion;
 use crate::msgs::ExtensionType;
+use super::utils::Http2Profile;
 
 /// Complete template data for browser simulation.
 ///
@@ -57,7 +62,11 @@ pub struct TemplateData {
     /// Cipher suites in browser order
     ///
     /// The order matters for fingerprinting. This list should match the exact order
-    /// used by the target browser.
+    /// used by the target browser, and is sent verbatim in the ClientHello.
+    ///
+    /// Entries need not be implemented by the crypto provider: deprecated codepoints
+    /// such as 3DES suites can be listed with `CipherSuite::from(0x000a)` purely to be
+    /// advertised. A server selecting one of those fails the handshake.
     pub cipher_suites: Vec<CipherSuite>,
     
     /// Extension types in browser order
@@ -73,7 +82,10 @@ pub struct TemplateData {
     
     /// Signature algorithms in browser order
     ///
-    /// Used for the signature_algorithms extension. Order matters.
+    /// Used verbatim for the signature_algorithms extension. Order matters, and
+    /// duplicates are kept. Schemes the certificate verifier does not support (for
+    /// example `rsa_pkcs1_sha1`) are advertised only; a server signing with one is
+    /// rejected before its signature reaches the verifier.
     pub signature_algorithms: Vec<SignatureScheme>,
     
     /// GREASE injection pattern
@@ -100,6 +112,13 @@ pub struct TemplateData {
     /// pseudo-headers (:method, :path, :authority, :scheme).
     pub http2_pseudo_header_order: Vec<String>,
     
+    /// HTTP/2 connection profile of the simulated client
+    ///
+    /// SETTINGS order and values, the initial WINDOW_UPDATE, PRIORITY frames and
+    /// header ordering. Reported by the connection once `h2` is negotiated so the
+    /// HTTP layer can match the TLS fingerprint.
+    pub http2_profile: Http2Profile,
+    
     /// Supported TLS versions
     ///
     /// List of protocol versions to advertise in the supported_versions extension.
@@ -110,6 +129,220 @@ pub struct TemplateData {
     /// Which groups to include in the key_share extension. Typically a subset
     /// of supported_groups.
     pub key_share_groups: Vec<NamedGroup>,
+    
+    /// GREASE encrypted_client_hello (0xfe0d) shape
+    ///
+    /// When set, a GREASE ECH extension matching this shape is added to the
+    /// outer ClientHello whenever no real ECH configuration is in use. `None`
+    /// means the browser does not send GREASE ECH.
+    pub ech_grease: Option<EchGreaseShape>,
+
+    /// Session and compatibility behavior of the browser's handshake
+    ///
+    /// Legacy session ID length, PSK modes, 0-RTT, TLS 1.2 ticket requests,
+    /// extended master secret and middlebox compatibility mode.
+    pub handshake_behavior: HandshakeBehavior,
+
+    /// QUIC transport parameter shape, for templates of HTTP/3 ClientHellos
+    ///
+    /// `None` for templates of ClientHellos sent over TCP.
+    pub quic: Option<QuicProfile>,
+}
+
+/// How a client handles the session-related and compatibility parts of a handshake.
+///
+/// These are not visible in the extension list alone: two clients sending the same
+/// extensions can still differ in the legacy session ID they send, whether they
+/// offer early data when resuming, or whether a ChangeCipherSpec record follows
+/// the ClientHello.
+///
+/// The default matches Chrome and most other TLS 1.3 clients, which run in
+/// middlebox compatibility mode (RFC 8446 Appendix D.4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandshakeBehavior {
+    /// Length of a fresh random legacy_session_id, at most 32
+    ///
+    /// 0 sends an empty session ID. Resuming a TLS 1.2 session by ID sends that ID
+    /// instead, and QUIC always sends an empty one (RFC 9001 Section 8.4).
+    pub legacy_session_id_len: u8,
+
+    /// Offer the `psk_dhe_ke` PSK key exchange mode
+    pub psk_dhe_ke: bool,
+
+    /// Offer the `psk_ke` PSK key exchange mode
+    ///
+    /// Advertised only: rustls always resumes with a key exchange, so a server
+    /// selecting PSK-only resumption fails the handshake. With neither mode the
+    /// `psk_key_exchange_modes` extension is omitted and no TLS 1.3 ticket is offered.
+    pub psk_ke: bool,
+
+    /// Offer `early_data` when resuming
+    ///
+    /// Early data is still only offered when `ClientConfig::enable_early_data` is set
+    /// and the ticket allows it.
+    pub early_data: bool,
+
+    /// Send an empty `session_ticket` extension when TLS 1.2 is offered without a ticket
+    ///
+    /// Only applies when the configuration allows TLS 1.2 ticket resumption.
+    pub session_ticket_request: bool,
+
+    /// Send `extended_master_secret`
+    ///
+    /// Always sent when `ClientConfig::require_ems` is set.
+    pub extended_master_secret: bool,
+
+    /// Send the TLS 1.3 middlebox compatibility ChangeCipherSpec record
+    pub middlebox_compat_ccs: bool,
+}
+
+impl Default for HandshakeBehavior {
+    fn default() -> Self {
+        Self {
+            legacy_session_id_len: 32,
+            psk_dhe_ke: true,
+            psk_ke: false,
+            early_data: false,
+            session_ticket_request: true,
+            extended_master_secret: true,
+            middlebox_compat_ccs: true,
+        }
+    }
+}
+
+/// Shape of the GREASE encrypted_client_hello extension sent by a browser.
+///
+/// GREASE ECH (draft-ietf-tls-esni Section 6.2) is an outer ECH extension with
+/// random contents. Browsers differ in the HPKE suites they claim, whether the
+/// config id is random, and how the fake payload length is chosen, so these
+/// characteristics are captured per template.
+#[derive(Debug, Clone)]
+pub struct EchGreaseShape {
+    /// HPKE KEM whose encapsulated key size is used for the `enc` field
+    ///
+    /// DHKEM(X25519) yields a 32-byte `enc`, DHKEM(P-256) a 65-byte one.
+    pub kem: HpkeKem,
+    
+    /// HPKE symmetric cipher suites to choose from
+    ///
+    /// One suite is picked per connection. Chrome always claims
+    /// HKDF-SHA256 with AES-128-GCM.
+    pub cipher_suites: Vec<HpkeSymmetricCipherSuite>,
+    
+    /// Whether the config id is randomized on every connection
+    pub randomize_config_id: bool,
+    
+    /// Config id to use when `randomize_config_id` is false
+    pub config_id: u8,
+    
+    /// Probability mass function over the plaintext payload length
+    ///
+    /// The AEAD tag length is added on top of the sampled value. Chrome
+    /// picks uniformly from 128, 160, 192 and 224 bytes.
+    pub payload_lengths: Vec<(u16, f64)>,
+}
+
+impl EchGreaseShape {
+    /// Length of the encapsulated key for this shape's KEM
+    pub fn enc_len(&self) -> usize {
+        match self.kem {
+            HpkeKem::DHKEM_P256_HKDF_SHA256 => 65,
+            HpkeKem::DHKEM_P384_HKDF_SHA384 => 97,
+            HpkeKem::DHKEM_P521_HKDF_SHA512 => 133,
+            HpkeKem::DHKEM_X448_HKDF_SHA512 => 56,
+            _ => 32,
+        }
+    }
+    
+    /// Chrome-style GREASE ECH shape
+    ///
+    /// Matches BoringSSL's `ssl_setup_grease_ech`: X25519 `enc`, HKDF-SHA256 with
+    /// AES-128-GCM, a random config id, and a payload of 128 + 32*k bytes (k in 0..=3)
+    /// plus the 16-byte tag.
+    pub fn chrome() -> Self {
+        Self {
+            kem: HpkeKem::DHKEM_X25519_HKDF_SHA256,
+            cipher_suites: vec![HpkeSymmetricCipherSuite {
+                kdf_id: HpkeKdf::HKDF_SHA256,
+                aead_id: HpkeAead::AES_128_GCM,
+            }],
+            randomize_config_id: true,
+            config_id: 0,
+            payload_lengths: vec![
+                (128, 0.25),
+                (160, 0.25),
+                (192, 0.25),
+                (224, 0.25),
+            ],
+        }
+    }
+}
+
+/// Shape of the `quic_transport_parameters` extension sent by a browser.
+///
+/// The application encodes the transport parameter values; the profile only
+/// decides their order and whether a GREASE parameter (RFC 9000 Section 18.1) is
+/// added, which is what differs between QUIC stacks.
+#[derive(Debug, Clone)]
+pub struct QuicProfile {
+    /// Transport parameter IDs in the order the browser sends them
+    ///
+    /// Parameters the application sets but the list does not mention follow in
+    /// their original order. Ignored when `shuffle_transport_parameters` is set.
+    pub transport_parameter_order: Vec<u64>,
+    
+    /// Whether the parameters are sent in a fresh random order on every connection
+    pub shuffle_transport_parameters: bool,
+    
+    /// Whether a GREASE transport parameter with a random ID is added
+    pub grease_transport_parameter: bool,
+    
+    /// Largest length of the GREASE parameter's random value
+    pub grease_value_max_len: u8,
+}
+
+impl QuicProfile {
+    /// Chrome-style transport parameters
+    ///
+    /// quiche serializes the parameters in random order and adds one GREASE
+    /// parameter with a random value of up to 16 bytes.
+    pub fn chrome() -> Self {
+        Self {
+            transport_parameter_order: Vec::new(),
+            shuffle_transport_parameters: true,
+            grease_transport_parameter: true,
+            grease_value_max_len: 16,
+        }
+    }
+    
+    /// Firefox-style transport parameters
+    ///
+    /// neqo sends the parameters in a fixed order without a GREASE parameter.
+    pub fn firefox() -> Self {
+        use super::quic::TransportParameter as Tp;
+        
+        Self {
+            transport_parameter_order: vec![
+                Tp::INITIAL_MAX_STREAM_DATA_BIDI_LOCAL,
+                Tp::INITIAL_MAX_STREAM_DATA_BIDI_REMOTE,
+                Tp::INITIAL_MAX_STREAM_DATA_UNI,
+                Tp::INITIAL_MAX_DATA,
+                Tp::INITIAL_MAX_STREAMS_BIDI,
+                Tp::INITIAL_MAX_STREAMS_UNI,
+                Tp::MAX_IDLE_TIMEOUT,
+                Tp::MAX_UDP_PAYLOAD_SIZE,
+                Tp::ACTIVE_CONNECTION_ID_LIMIT,
+                Tp::MAX_DATAGRAM_FRAME_SIZE,
+                Tp::GREASE_QUIC_BIT,
+                Tp::VERSION_INFORMATION,
+                Tp::MIN_ACK_DELAY,
+                Tp::INITIAL_SOURCE_CONNECTION_ID,
+            ],
+            shuffle_transport_parameters: false,
+            grease_transport_parameter: false,
+            grease_value_max_len: 0,
+        }
+    }
 }
 
 /// GREASE injection pattern for a browser template.
@@ -316,6 +549,10 @@ impl TemplateData {
             http2_pseudo_header_order: Vec::new(),
             supported_versions: Vec::new(),
             key_share_groups: Vec::new(),
+            ech_grease: None,
+            http2_profile: Http2Profile::default(),
+            handshake_behavior: HandshakeBehavior::default(),
+            quic: None,
         }
     }
 }
@@ -363,6 +600,7 @@ impl Default for PaddingDistribution {
 /// - Padding typically 0-512 bytes, favoring powers of 2
 /// - HTTP/2 support with h2 ALPN
 /// - Post-quantum key exchange support (X25519Kyber768Draft00)
+/// - GREASE ECH offered when no real ECH config is available
 ///
 /// ## Validation
 ///
@@ -413,6 +651,7 @@ pub fn chrome_130() -> TemplateData {
             ExtensionType::KeyShare,                      // 0x0033
             ExtensionType::PSKKeyExchangeModes,           // 0x002d
             ExtensionType::CompressCertificate,           // 0x001b
+            ExtensionType::EncryptedClientHello,          // 0xfe0d (GREASE ECH)
             ExtensionType::Padding,                       // 0x0015
         ],
         
@@ -490,6 +729,14 @@ pub fn chrome_130() -> TemplateData {
         key_share_groups: vec![
             NamedGroup::X25519,
         ],
+        
+        // GREASE ECH shape (BoringSSL)
+        ech_grease: Some(EchGreaseShape::chrome()),
+        http2_profile: Http2Profile::chrome(),
+
+        // Compatibility mode with a 32-byte session ID; no TLS 0-RTT
+        handshake_behavior: HandshakeBehavior::default(),
+        quic: None,
     }
 }
 
@@ -637,6 +884,17 @@ pub fn firefox_135() -> TemplateData {
             NamedGroup::X25519,
             NamedGroup::secp256r1,
         ],
+        
+        // No GREASE ECH
+        ech_grease: None,
+        http2_profile: Http2Profile::firefox(),
+
+        // NSS offers 0-RTT when resuming a ticket that allows it
+        handshake_behavior: HandshakeBehavior {
+            early_data: true,
+            ..HandshakeBehavior::default()
+        },
+        quic: None,
     }
 }
 
@@ -657,6 +915,7 @@ pub fn firefox_135() -> TemplateData {
 /// - Minimal padding (typically 0 bytes)
 /// - HTTP/2 support with h2 ALPN
 /// - Prefers secp256r1 curve
+/// - No ALPS (application_settings) extension
 ///
 /// ## Validation
 ///
@@ -679,12 +938,6 @@ pub fn safari_17() -> TemplateData {
             CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
             CipherSuite::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
             CipherSuite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
-            CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA,
-            CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA,
-            CipherSuite::TLS_RSA_WITH_AES_256_GCM_SHA384,
-            CipherSuite::TLS_RSA_WITH_AES_128_GCM_SHA256,
-            CipherSuite::TLS_RSA_WITH_AES_256_CBC_SHA,
-            CipherSuite::TLS_RSA_WITH_AES_128_CBC_SHA,
         ],
         
         // Extension order matching Safari
@@ -715,14 +968,11 @@ pub fn safari_17() -> TemplateData {
         // Signature algorithms in Safari order
         signature_algorithms: vec![
             SignatureScheme::ECDSA_NISTP256_SHA256,
-            SignatureScheme::RSA_PSS_SHA256,
-            SignatureScheme::RSA_PKCS1_SHA256,
             SignatureScheme::ECDSA_NISTP384_SHA384,
             SignatureScheme::ECDSA_NISTP521_SHA512,
+            SignatureScheme::RSA_PSS_SHA256,
             SignatureScheme::RSA_PSS_SHA384,
             SignatureScheme::RSA_PSS_SHA512,
-            SignatureScheme::RSA_PKCS1_SHA384,
-            SignatureScheme::RSA_PKCS1_SHA512,
         ],
         
         // Safari GREASE pattern
@@ -776,6 +1026,82 @@ pub fn safari_17() -> TemplateData {
             NamedGroup::secp256r1,
             NamedGroup::X25519,
         ],
+        
+        // No GREASE ECH
+        ech_grease: None,
+        http2_profile: Http2Profile::safari(),
+        handshake_behavior: HandshakeBehavior::default(),
+        quic: None,
+    }
+}
+
+/// Safari 17 template with the legacy cipher suites and signature schemes
+///
+/// Some Safari 17 builds still advertise the CBC-SHA, static RSA and 3DES suites
+/// and the SHA-1 signature schemes that [`safari_17`] leaves out. Use this
+/// template where a server or fingerprint database expects those.
+///
+/// ## Source
+///
+/// Derived from [`safari_17`], with the cipher suite and signature scheme lists
+/// Safari sent before it dropped 3DES; not captured from a device.
+///
+/// ## Characteristics
+///
+/// - Same extensions, groups, GREASE and padding as Safari 17
+/// - Advertises 3DES suites and `rsa_pkcs1_sha1`, which the provider does not
+///   implement; a server selecting one fails the handshake
+/// - Repeats `rsa_pss_rsae_sha384` in the signature algorithms, as Safari does
+///
+/// ## Validation
+///
+/// Not yet validated against a capture.
+pub fn safari_17_legacy() -> TemplateData {
+    TemplateData {
+        name: "Safari 17+ (legacy suites)".to_string(),
+        description: "Safari 17+ on macOS/iOS, advertising 3DES and SHA-1".to_string(),
+        
+        // Cipher suites in Safari order, ending with the legacy 3DES suites
+        cipher_suites: vec![
+            CipherSuite::TLS13_AES_128_GCM_SHA256,
+            CipherSuite::TLS13_AES_256_GCM_SHA384,
+            CipherSuite::TLS13_CHACHA20_POLY1305_SHA256,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+            CipherSuite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA,
+            CipherSuite::TLS_RSA_WITH_AES_256_GCM_SHA384,
+            CipherSuite::TLS_RSA_WITH_AES_128_GCM_SHA256,
+            CipherSuite::TLS_RSA_WITH_AES_256_CBC_SHA,
+            CipherSuite::TLS_RSA_WITH_AES_128_CBC_SHA,
+            CipherSuite::from(0xc008),                    // TLS_ECDHE_ECDSA_WITH_3DES_EDE_CBC_SHA
+            CipherSuite::from(0xc012),                    // TLS_ECDHE_RSA_WITH_3DES_EDE_CBC_SHA
+            CipherSuite::from(0x000a),                    // TLS_RSA_WITH_3DES_EDE_CBC_SHA
+        ],
+        
+        // Signature algorithms in Safari order, including the SHA-1 schemes
+        // and the repeated rsa_pss_rsae_sha384 that Safari sends
+        signature_algorithms: vec![
+            SignatureScheme::ECDSA_NISTP256_SHA256,
+            SignatureScheme::RSA_PSS_SHA256,
+            SignatureScheme::RSA_PKCS1_SHA256,
+            SignatureScheme::ECDSA_NISTP384_SHA384,
+            SignatureScheme::ECDSA_SHA1_Legacy,
+            SignatureScheme::RSA_PSS_SHA384,
+            SignatureScheme::RSA_PSS_SHA384,
+            SignatureScheme::RSA_PKCS1_SHA384,
+            SignatureScheme::RSA_PSS_SHA512,
+            SignatureScheme::RSA_PKCS1_SHA512,
+            SignatureScheme::RSA_PKCS1_SHA1,
+        ],
+        
+        ..safari_17()
     }
 }
 
@@ -796,6 +1122,7 @@ pub fn safari_17() -> TemplateData {
 /// - Padding typically 0-512 bytes, favoring powers of 2
 /// - HTTP/2 support with h2 ALPN
 /// - Identical extension ordering to Chrome in most cases
+/// - GREASE ECH offered, same shape as Chrome
 ///
 /// ## Validation
 ///
@@ -842,6 +1169,7 @@ pub fn edge_130() -> TemplateData {
             ExtensionType::KeyShare,                      // 0x0033
             ExtensionType::PSKKeyExchangeModes,           // 0x002d
             ExtensionType::CompressCertificate,           // 0x001b
+            ExtensionType::EncryptedClientHello,          // 0xfe0d (GREASE ECH)
             ExtensionType::Padding,                       // 0x0015
         ],
         
@@ -915,106 +1243,810 @@ pub fn edge_130() -> TemplateData {
         key_share_groups: vec![
             NamedGroup::X25519,
         ],
+        
+        // GREASE ECH shape (BoringSSL)
+        ech_grease: Some(EchGreaseShape::chrome()),
+        http2_profile: Http2Profile::chrome(),
+
+        // Compatibility mode with a 32-byte session ID; no TLS 0-RTT
+        handshake_behavior: HandshakeBehavior::default(),
+        quic: None,
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use alloc::vec;
-    
-    #[test]
-    fn test_extension_set_is_subset() {
-        let set = ExtensionSet::new(vec![
-            ExtensionType::ServerName,
-            ExtensionType::SupportedVersions,
-        ]);
-        
-        let extensions = vec![
-            ExtensionType::ServerName,
-            ExtensionType::SupportedVersions,
-            ExtensionType::KeyShare,
-        ];
-        
-        assert!(set.is_subset_of(&extensions));
-        
-        let incomplete = vec![ExtensionType::ServerName];
-        assert!(!set.is_subset_of(&incomplete));
+/// Post-quantum groups, ahead of the classical ones, as later releases send them
+///
+/// The hybrid share comes first; the X25519 share that follows it reuses the
+/// hybrid's classical component.
+fn with_x25519mlkem768(mut template: TemplateData) -> TemplateData {
+    template.supported_groups.insert(0, NamedGroup::X25519MLKEM768);
+    template.key_share_groups.insert(0, NamedGroup::X25519MLKEM768);
+    template
+}
+
+/// Chrome 131+ template
+///
+/// Chrome 131 replaced the X25519Kyber768Draft00 hybrid with X25519MLKEM768 and
+/// sends its key share ahead of X25519. Everything else is as in [`chrome_130`].
+///
+/// ## Validation
+///
+/// Not yet validated against a capture. Until one is added to
+/// `test_data/browser_captures`, use [`chrome_130`] where the fingerprint has to
+/// match a recorded browser.
+pub fn chrome_131() -> TemplateData {
+    TemplateData {
+        name: "Chrome 131+".to_string(),
+        description: "Chrome 131+ on Windows/macOS/Linux, with X25519MLKEM768".to_string(),
+        ..with_x25519mlkem768(chrome_130())
     }
-    
-    #[test]
-    fn test_naturalness_filter_blacklist() {
-        let mut filter = NaturalnessFilter::new();
-        filter.blacklist.push(ExtensionSet::new(vec![
-            ExtensionType::ServerName,
-            ExtensionType::EncryptedClientHello,
-        ]));
-        
-        // Should reject if both blacklisted extensions present
-        let bad_extensions = vec![
-            ExtensionType::ServerName,
-            ExtensionType::EncryptedClientHello,
-        ];
-        assert!(!filter.is_natural(&bad_extensions));
-        
-        // Should accept if only one present
-        let good_extensions = vec![ExtensionType::ServerName];
-        assert!(filter.is_natural(&good_extensions));
+}
+
+/// Firefox 136+ template
+///
+/// Firefox offers X25519MLKEM768 ahead of X25519 and sends key shares for both,
+/// plus secp256r1. Everything else is as in [`firefox_135`].
+///
+/// ## Validation
+///
+/// Not yet validated against a capture. Until one is added to
+/// `test_data/browser_captures`, use [`firefox_135`] where the fingerprint has to
+/// match a recorded browser.
+pub fn firefox_136() -> TemplateData {
+    TemplateData {
+        name: "Firefox 136+".to_string(),
+        description: "Firefox 136+ on Windows/macOS/Linux, with X25519MLKEM768".to_string(),
+        ..with_x25519mlkem768(firefox_135())
     }
-    
-    #[test]
-    fn test_naturalness_filter_dependencies() {
-        let mut filter = NaturalnessFilter::new();
-        filter.dependencies.insert(
-            ExtensionType::CompressCertificate,
-            vec![ExtensionType::SignatureAlgorithms],
-        );
-        
-        // Should reject if dependency missing
-        let bad_extensions = vec![ExtensionType::CompressCertificate];
-        assert!(!filter.is_natural(&bad_extensions));
-        
-        // Should accept if dependency present
-        let good_extensions = vec![
-            ExtensionType::CompressCertificate,
-            ExtensionType::SignatureAlgorithms,
-        ];
-        assert!(filter.is_natural(&good_extensions));
-        
-        // Should accept if neither present
-        let neither = vec![ExtensionType::ServerName];
-        assert!(filter.is_natural(&neither));
+}
+
+/// Edge 131+ template
+///
+/// Edge follows Chromium, so this is [`edge_130`] with the X25519MLKEM768 key
+/// share of [`chrome_131`].
+///
+/// ## Validation
+///
+/// Not yet validated against a capture. Until one is added to
+/// `test_data/browser_captures`, use [`edge_130`] where the fingerprint has to
+/// match a recorded browser.
+pub fn edge_131() -> TemplateData {
+    TemplateData {
+        name: "Edge 131+".to_string(),
+        description: "Edge 131+ on Windows, with X25519MLKEM768".to_string(),
+        ..with_x25519mlkem768(edge_130())
     }
-    
-    #[test]
-    fn test_template_data_creation() {
-        let template = TemplateData::new(
-            "Test Template".to_string(),
-            "A test template".to_string(),
-        );
-        
-        assert_eq!(template.name, "Test Template");
-        assert_eq!(template.description, "A test template");
-        assert!(template.cipher_suites.is_empty());
-        assert!(template.extension_order.is_empty());
+}
+
+/// GREASE pattern for clients that never send GREASE
+fn no_grease() -> GreasePattern {
+    GreasePattern {
+        cipher_suite_probability: 0.0,
+        cipher_suite_positions: Vec::new(),
+        extension_probability: 0.0,
+        extension_positions: Vec::new(),
+        grease_values: Vec::new(),
     }
-    
-    #[test]
-    fn test_grease_pattern_default() {
-        let pattern = GreasePattern::default();
-        
-        assert_eq!(pattern.cipher_suite_probability, 1.0);
-        assert_eq!(pattern.extension_probability, 1.0);
-        assert_eq!(pattern.grease_values.len(), 16); // All standard GREASE values
+}
+
+/// Padding distribution for clients that never send the padding extension
+fn no_padding() -> PaddingDistribution {
+    PaddingDistribution {
+        pmf: vec![(0, 1.0)],
+        min_length: 0,
+        max_length: 0,
+        power_of_2_bias: 0.0,
     }
-    
-    #[test]
-    fn test_padding_distribution_default() {
-        let dist = PaddingDistribution::default();
-        
-        assert_eq!(dist.min_length, 0);
-        assert_eq!(dist.max_length, 1500);
-        assert!(dist.power_of_2_bias > 0.0 && dist.power_of_2_bias <= 1.0);
+}
+
+/// OkHttp 4 on Android template
+///
+/// This template simulates OkHttp 4.12 running on Android 14, where TLS is provided
+/// by Conscrypt (BoringSSL). Most Android apps share this ClientHello.
+///
+/// ## Source
+///
+/// Written from Conscrypt's default cipher suite, group and signature scheme
+/// lists; not captured from a device.
+///
+/// ## Characteristics
+///
+/// - TLS 1.3 with TLS 1.2 fallback support
+/// - No GREASE: Conscrypt leaves BoringSSL's GREASE disabled
+/// - No padding extension
+/// - Single X25519 key share
+/// - Advertises `rsa_pkcs1_sha1` last
+///
+/// ## Validation
+///
+/// Not yet validated against a capture.
+pub fn okhttp_4() -> TemplateData {
+    TemplateData {
+        name: "OkHttp 4".to_string(),
+        description: "OkHttp 4 with Conscrypt on Android".to_string(),
+        
+        // Cipher suites in Conscrypt order
+        cipher_suites: vec![
+            CipherSuite::TLS13_AES_128_GCM_SHA256,
+            CipherSuite::TLS13_AES_256_GCM_SHA384,
+            CipherSuite::TLS13_CHACHA20_POLY1305_SHA256,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+            CipherSuite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA,
+            CipherSuite::TLS_RSA_WITH_AES_128_GCM_SHA256,
+            CipherSuite::TLS_RSA_WITH_AES_256_GCM_SHA384,
+            CipherSuite::TLS_RSA_WITH_AES_128_CBC_SHA,
+            CipherSuite::TLS_RSA_WITH_AES_256_CBC_SHA,
+        ],
+        
+        // Extension order matching Conscrypt
+        extension_order: vec![
+            ExtensionType::ServerName,                    // 0x0000
+            ExtensionType::ExtendedMasterSecret,          // 0x0017
+            ExtensionType::RenegotiationInfo,             // 0xff01
+            ExtensionType::EllipticCurves,                // 0x000a (supported_groups)
+            ExtensionType::ECPointFormats,                // 0x000b
+            ExtensionType::SessionTicket,                 // 0x0023
+            ExtensionType::ALProtocolNegotiation,         // 0x0010 (ALPN)
+            ExtensionType::StatusRequest,                 // 0x0005 (OCSP)
+            ExtensionType::SignatureAlgorithms,           // 0x000d
+            ExtensionType::KeyShare,                      // 0x0033
+            ExtensionType::PSKKeyExchangeModes,           // 0x002d
+            ExtensionType::SupportedVersions,             // 0x002b
+        ],
+        
+        // Supported groups in Conscrypt order
+        supported_groups: vec![
+            NamedGroup::X25519,
+            NamedGroup::secp256r1,
+            NamedGroup::secp384r1,
+        ],
+        
+        // Signature algorithms in BoringSSL's default order
+        signature_algorithms: vec![
+            SignatureScheme::ECDSA_NISTP256_SHA256,
+            SignatureScheme::RSA_PSS_SHA256,
+            SignatureScheme::RSA_PKCS1_SHA256,
+            SignatureScheme::ECDSA_NISTP384_SHA384,
+            SignatureScheme::RSA_PSS_SHA384,
+            SignatureScheme::RSA_PKCS1_SHA384,
+            SignatureScheme::RSA_PSS_SHA512,
+            SignatureScheme::RSA_PKCS1_SHA512,
+            SignatureScheme::RSA_PKCS1_SHA1,
+        ],
+        
+        grease_pattern: no_grease(),
+        padding_distribution: no_padding(),
+        
+        // ALPN protocols set by OkHttp
+        alpn_protocols: vec![
+            b"h2".to_vec(),           // HTTP/2
+            b"http/1.1".to_vec(),     // HTTP/1.1
+        ],
+        
+        // HTTP/2 pseudo-header order for OkHttp
+        http2_pseudo_header_order: vec![
+            ":method".to_string(),
+            ":path".to_string(),
+            ":authority".to_string(),
+            ":scheme".to_string(),
+        ],
+        
+        // Supported TLS versions
+        supported_versions: vec![
+            ProtocolVersion::TLSv1_3,
+            ProtocolVersion::TLSv1_2,
+        ],
+        
+        // Key share groups (subset of supported_groups)
+        key_share_groups: vec![
+            NamedGroup::X25519,
+        ],
+        
+        // No GREASE ECH
+        ech_grease: None,
+        http2_profile: Http2Profile::okhttp(),
+        handshake_behavior: HandshakeBehavior::default(),
+        quic: None,
+    }
+}
+
+/// iOS 17 NSURLSession template
+///
+/// This template simulates apps using `URLSession` on iOS 17. Apple's networking
+/// stack produces the same ClientHello as Safari, so this template is derived from
+/// [`safari_17`] and differs only in identification.
+///
+/// ## Source
+///
+/// Derived from [`safari_17`]; not captured from a device.
+///
+/// ## Characteristics
+///
+/// - Byte-identical TLS parameters to Safari 17
+/// - No ALPS and no GREASE ECH
+///
+/// ## Validation
+///
+/// Not yet validated against a capture.
+pub fn ios_17() -> TemplateData {
+    TemplateData {
+        name: "iOS 17 URLSession".to_string(),
+        description: "NSURLSession apps on iOS 17".to_string(),
+        ..safari_17()
+    }
+}
+
+/// curl 8 with OpenSSL 3 template
+///
+/// This template simulates the curl 8 command line tool linked against OpenSSL 3,
+/// as shipped by most Linux distributions.
+///
+/// ## Source
+///
+/// Data captured from curl 7.88.1 with OpenSSL 3.0.19 on Debian 12. curl leaves
+/// the ClientHello to OpenSSL, so this stands in for curl 8 on OpenSSL 3.0.
+/// Capture date: October 2026.
+///
+/// ## Characteristics
+///
+/// - TLS 1.3 with TLS 1.2 fallback support
+/// - Long OpenSSL cipher list including DHE and CBC-SHA256 suites, ending with
+///   the renegotiation SCSV
+/// - encrypt_then_mac and post_handshake_auth extensions, which browsers never send
+/// - FFDHE groups and X448 advertised
+/// - Legacy SHA-224 and DSA signature schemes advertised
+/// - No GREASE and no session ticket
+///
+/// ## Validation
+///
+/// Cipher suites, extension order, groups and signature schemes are checked
+/// against `test_data/browser_captures/curl_8`. The hello custls sends still
+/// differs from OpenSSL's: encrypt_then_mac and post_handshake_auth are not
+/// sent, supported_versions does not offer TLS 1.1 and 1.0, and OpenSSL's
+/// padding of the hello to 512 bytes is not reproduced.
+pub fn curl_8() -> TemplateData {
+    TemplateData {
+        name: "curl 8".to_string(),
+        description: "curl 8 with OpenSSL 3 on Linux".to_string(),
+        
+        // Cipher suites in OpenSSL 3 default order
+        cipher_suites: vec![
+            CipherSuite::TLS13_AES_256_GCM_SHA384,
+            CipherSuite::TLS13_CHACHA20_POLY1305_SHA256,
+            CipherSuite::TLS13_AES_128_GCM_SHA256,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+            CipherSuite::TLS_DHE_RSA_WITH_AES_256_GCM_SHA384,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
+            CipherSuite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+            CipherSuite::TLS_DHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+            CipherSuite::TLS_DHE_RSA_WITH_AES_128_GCM_SHA256,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA384,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA384,
+            CipherSuite::TLS_DHE_RSA_WITH_AES_256_CBC_SHA256,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA256,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA256,
+            CipherSuite::TLS_DHE_RSA_WITH_AES_128_CBC_SHA256,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA,
+            CipherSuite::TLS_DHE_RSA_WITH_AES_256_CBC_SHA,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA,
+            CipherSuite::TLS_DHE_RSA_WITH_AES_128_CBC_SHA,
+            CipherSuite::TLS_RSA_WITH_AES_256_GCM_SHA384,
+            CipherSuite::TLS_RSA_WITH_AES_128_GCM_SHA256,
+            CipherSuite::TLS_RSA_WITH_AES_256_CBC_SHA256,
+            CipherSuite::TLS_RSA_WITH_AES_128_CBC_SHA256,
+            CipherSuite::TLS_RSA_WITH_AES_256_CBC_SHA,
+            CipherSuite::TLS_RSA_WITH_AES_128_CBC_SHA,
+            CipherSuite::TLS_EMPTY_RENEGOTIATION_INFO_SCSV,
+        ],
+        
+        // Extension order matching OpenSSL 3
+        extension_order: vec![
+            ExtensionType::ServerName,                    // 0x0000
+            ExtensionType::ECPointFormats,                // 0x000b
+            ExtensionType::EllipticCurves,                // 0x000a (supported_groups)
+            ExtensionType::ALProtocolNegotiation,         // 0x0010 (ALPN)
+            ExtensionType::from(0x0016),                  // 0x0016 (encrypt_then_mac)
+            ExtensionType::ExtendedMasterSecret,          // 0x0017
+            ExtensionType::PostHandshakeAuth,             // 0x0031
+            ExtensionType::SignatureAlgorithms,           // 0x000d
+            ExtensionType::SupportedVersions,             // 0x002b
+            ExtensionType::PSKKeyExchangeModes,           // 0x002d
+            ExtensionType::KeyShare,                      // 0x0033
+        ],
+        
+        // Supported groups in OpenSSL 3 default order
+        supported_groups: vec![
+            NamedGroup::X25519,
+            NamedGroup::secp256r1,
+            NamedGroup::X448,
+            NamedGroup::secp521r1,
+            NamedGroup::secp384r1,
+            NamedGroup::FFDHE2048,
+            NamedGroup::FFDHE3072,
+            NamedGroup::FFDHE4096,
+            NamedGroup::FFDHE6144,
+            NamedGroup::FFDHE8192,
+        ],
+        
+        // Signature algorithms in OpenSSL 3 default order
+        signature_algorithms: vec![
+            SignatureScheme::ECDSA_NISTP256_SHA256,
+            SignatureScheme::ECDSA_NISTP384_SHA384,
+            SignatureScheme::ECDSA_NISTP521_SHA512,
+            SignatureScheme::ED25519,
+            SignatureScheme::ED448,
+            SignatureScheme::from(0x0809),            // rsa_pss_pss_sha256
+            SignatureScheme::from(0x080a),            // rsa_pss_pss_sha384
+            SignatureScheme::from(0x080b),            // rsa_pss_pss_sha512
+            SignatureScheme::RSA_PSS_SHA256,
+            SignatureScheme::RSA_PSS_SHA384,
+            SignatureScheme::RSA_PSS_SHA512,
+            SignatureScheme::RSA_PKCS1_SHA256,
+            SignatureScheme::RSA_PKCS1_SHA384,
+            SignatureScheme::RSA_PKCS1_SHA512,
+            SignatureScheme::from(0x0303),            // ecdsa_sha224
+            SignatureScheme::from(0x0301),            // rsa_pkcs1_sha224
+            SignatureScheme::from(0x0302),            // dsa_sha224
+            SignatureScheme::from(0x0402),            // dsa_sha256
+            SignatureScheme::from(0x0502),            // dsa_sha384
+            SignatureScheme::from(0x0602),            // dsa_sha512
+        ],
+        
+        grease_pattern: no_grease(),
+        padding_distribution: no_padding(),
+        
+        // ALPN protocols set by curl when built with nghttp2
+        alpn_protocols: vec![
+            b"h2".to_vec(),           // HTTP/2
+            b"http/1.1".to_vec(),     // HTTP/1.1
+        ],
+        
+        // HTTP/2 pseudo-header order for curl (nghttp2)
+        http2_pseudo_header_order: vec![
+            ":method".to_string(),
+            ":path".to_string(),
+            ":scheme".to_string(),
+            ":authority".to_string(),
+        ],
+        
+        // Supported TLS versions
+        supported_versions: vec![
+            ProtocolVersion::TLSv1_3,
+            ProtocolVersion::TLSv1_2,
+        ],
+        
+        // Key share groups (subset of supported_groups)
+        key_share_groups: vec![
+            NamedGroup::X25519,
+        ],
+        
+        // No GREASE ECH
+        ech_grease: None,
+        http2_profile: Http2Profile::curl(),
+        handshake_behavior: HandshakeBehavior::default(),
+        quic: None,
+    }
+}
+
+/// Go 1.22 `crypto/tls` template
+///
+/// This template simulates Go's `net/http` client using `crypto/tls` from Go 1.22 on
+/// a platform with AES hardware support.
+///
+/// ## Source
+///
+/// Written from the defaults in Go 1.22's `crypto/tls`; not captured from a
+/// running client.
+///
+/// ## Characteristics
+///
+/// - TLS 1.2 suites listed ahead of TLS 1.3 suites
+/// - No RSA key exchange suites, which Go 1.22 removed from its defaults
+/// - signature_algorithms_cert sent alongside signature_algorithms
+/// - No GREASE, no padding, single X25519 key share
+///
+/// ## Validation
+///
+/// Not yet validated against a capture.
+pub fn go_1_22() -> TemplateData {
+    TemplateData {
+        name: "Go 1.22".to_string(),
+        description: "Go 1.22 crypto/tls net/http client".to_string(),
+        
+        // Cipher suites in Go order (AES hardware preferred)
+        cipher_suites: vec![
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
+            CipherSuite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA,
+            CipherSuite::TLS13_AES_128_GCM_SHA256,
+            CipherSuite::TLS13_AES_256_GCM_SHA384,
+            CipherSuite::TLS13_CHACHA20_POLY1305_SHA256,
+        ],
+        
+        // Extension order matching crypto/tls
+        extension_order: vec![
+            ExtensionType::ServerName,                    // 0x0000
+            ExtensionType::StatusRequest,                 // 0x0005 (OCSP)
+            ExtensionType::EllipticCurves,                // 0x000a (supported_groups)
+            ExtensionType::ECPointFormats,                // 0x000b
+            ExtensionType::SessionTicket,                 // 0x0023
+            ExtensionType::SignatureAlgorithms,           // 0x000d
+            ExtensionType::SignatureAlgorithmsCert,       // 0x0032
+            ExtensionType::RenegotiationInfo,             // 0xff01
+            ExtensionType::ALProtocolNegotiation,         // 0x0010 (ALPN)
+            ExtensionType::SCT,                           // 0x0012 (signed_certificate_timestamp)
+            ExtensionType::ExtendedMasterSecret,          // 0x0017
+            ExtensionType::SupportedVersions,             // 0x002b
+            ExtensionType::KeyShare,                      // 0x0033
+            ExtensionType::PSKKeyExchangeModes,           // 0x002d
+        ],
+        
+        // Supported groups in Go order
+        supported_groups: vec![
+            NamedGroup::X25519,
+            NamedGroup::secp256r1,
+            NamedGroup::secp384r1,
+            NamedGroup::secp521r1,
+        ],
+        
+        // Signature algorithms in Go order
+        signature_algorithms: vec![
+            SignatureScheme::RSA_PSS_SHA256,
+            SignatureScheme::ECDSA_NISTP256_SHA256,
+            SignatureScheme::ED25519,
+            SignatureScheme::RSA_PSS_SHA384,
+            SignatureScheme::RSA_PSS_SHA512,
+            SignatureScheme::RSA_PKCS1_SHA256,
+            SignatureScheme::RSA_PKCS1_SHA384,
+            SignatureScheme::RSA_PKCS1_SHA512,
+            SignatureScheme::ECDSA_NISTP384_SHA384,
+            SignatureScheme::ECDSA_NISTP521_SHA512,
+            SignatureScheme::RSA_PKCS1_SHA1,
+            SignatureScheme::ECDSA_SHA1_Legacy,
+        ],
+        
+        grease_pattern: no_grease(),
+        padding_distribution: no_padding(),
+        
+        // ALPN protocols set by net/http
+        alpn_protocols: vec![
+            b"h2".to_vec(),           // HTTP/2
+            b"http/1.1".to_vec(),     // HTTP/1.1
+        ],
+        
+        // HTTP/2 pseudo-header order for golang.org/x/net/http2
+        http2_pseudo_header_order: vec![
+            ":authority".to_string(),
+            ":method".to_string(),
+            ":path".to_string(),
+            ":scheme".to_string(),
+        ],
+        
+        // Supported TLS versions
+        supported_versions: vec![
+            ProtocolVersion::TLSv1_3,
+            ProtocolVersion::TLSv1_2,
+        ],
+        
+        // Key share groups (subset of supported_groups)
+        key_share_groups: vec![
+            NamedGroup::X25519,
+        ],
+        
+        // No GREASE ECH
+        ech_grease: None,
+        http2_profile: Http2Profile::go(),
+        handshake_behavior: HandshakeBehavior::default(),
+        quic: None,
+    }
+}
+
+/// Handshake behavior QUIC requires: no legacy session ID, no TLS 1.2
+/// extensions and no compatibility mode (RFC 9001 Section 8.4)
+fn quic_handshake_behavior(tcp: HandshakeBehavior) -> HandshakeBehavior {
+    HandshakeBehavior {
+        legacy_session_id_len: 0,
+        session_ticket_request: false,
+        extended_master_secret: false,
+        middlebox_compat_ccs: false,
+        ..tcp
+    }
+}
+
+/// Chrome 130+ HTTP/3 template
+///
+/// This template simulates the ClientHello Chrome 130 and later sends in the
+/// QUIC Initial of an HTTP/3 connection. It is derived from [`chrome_130`].
+///
+/// ## Characteristics
+///
+/// - TLS 1.3 only: the TLS 1.2 cipher suites and extensions are left out
+/// - ALPN `h3`
+/// - No legacy session ID, no padding extension (QUIC pads the Initial packet)
+/// - GREASE cipher suites, extensions and GREASE ECH as over TCP
+/// - Transport parameters in random order with a GREASE transport parameter
+pub fn chrome_130_quic() -> TemplateData {
+    let tcp = chrome_130();
+    let cipher_suites = tcp
+        .cipher_suites
+        .iter()
+        .copied()
+        .filter(|suite| super::quic::quic_permits_cipher_suite(*suite))
+        .collect();
+    
+    TemplateData {
+        name: "Chrome 130+ (HTTP/3)".to_string(),
+        description: "Chrome 130+ HTTP/3 over QUIC".to_string(),
+        cipher_suites,
+        
+        // Extension order matching Chrome over QUIC
+        extension_order: vec![
+            ExtensionType::ServerName,                    // 0x0000
+            ExtensionType::SupportedVersions,             // 0x002b
+            ExtensionType::EllipticCurves,                // 0x000a (supported_groups)
+            ExtensionType::ALProtocolNegotiation,         // 0x0010 (ALPN)
+            ExtensionType::SignatureAlgorithms,           // 0x000d
+            ExtensionType::KeyShare,                      // 0x0033
+            ExtensionType::PSKKeyExchangeModes,           // 0x002d
+            ExtensionType::CompressCertificate,           // 0x001b
+            ExtensionType::ApplicationSettings,           // 0x4469 (ALPS)
+            ExtensionType::TransportParameters,           // 0x0039
+            ExtensionType::EncryptedClientHello,          // 0xfe0d (GREASE ECH)
+        ],
+        
+        // No padding extension
+        padding_distribution: PaddingDistribution {
+            pmf: vec![(0, 1.0)],
+            min_length: 0,
+            max_length: 0,
+            power_of_2_bias: 0.0,
+        },
+        
+        alpn_protocols: vec![b"h3".to_vec()],
+        supported_versions: vec![ProtocolVersion::TLSv1_3],
+        handshake_behavior: quic_handshake_behavior(tcp.handshake_behavior),
+        quic: Some(QuicProfile::chrome()),
+        ..tcp
+    }
+}
+
+/// Firefox 135+ HTTP/3 template
+///
+/// This template simulates the ClientHello Firefox 135 and later sends in the
+/// QUIC Initial of an HTTP/3 connection (neqo on NSS). It is derived from
+/// [`firefox_135`].
+///
+/// ## Characteristics
+///
+/// - TLS 1.3 only: the TLS 1.2 cipher suites and extensions are left out
+/// - ALPN `h3`
+/// - No legacy session ID, no padding extension
+/// - 0-RTT offered when resuming, as over TCP
+/// - Transport parameters in a fixed order without GREASE
+pub fn firefox_135_quic() -> TemplateData {
+    let tcp = firefox_135();
+    let cipher_suites = tcp
+        .cipher_suites
+        .iter()
+        .copied()
+        .filter(|suite| super::quic::quic_permits_cipher_suite(*suite))
+        .collect();
+    
+    TemplateData {
+        name: "Firefox 135+ (HTTP/3)".to_string(),
+        description: "Firefox 135+ HTTP/3 over QUIC".to_string(),
+        cipher_suites,
+        
+        // Extension order matching Firefox over QUIC
+        extension_order: vec![
+            ExtensionType::ServerName,                    // 0x0000
+            ExtensionType::EllipticCurves,                // 0x000a (supported_groups)
+            ExtensionType::ALProtocolNegotiation,         // 0x0010 (ALPN)
+            ExtensionType::KeyShare,                      // 0x0033
+            ExtensionType::SupportedVersions,             // 0x002b
+            ExtensionType::SignatureAlgorithms,           // 0x000d
+            ExtensionType::PSKKeyExchangeModes,           // 0x002d
+            ExtensionType::TransportParameters,           // 0x0039
+        ],
+        
+        // No padding extension
+        padding_distribution: PaddingDistribution {
+            pmf: vec![(0, 1.0)],
+            min_length: 0,
+            max_length: 0,
+            power_of_2_bias: 0.0,
+        },
+        
+        alpn_protocols: vec![b"h3".to_vec()],
+        supported_versions: vec![ProtocolVersion::TLSv1_3],
+        handshake_behavior: quic_handshake_behavior(tcp.handshake_behavior),
+        quic: Some(QuicProfile::firefox()),
+        ..tcp
+    }
+}
+
+/// Names of the templates that ship with custls, with their constructors
+pub(crate) const BUILTIN_TEMPLATES: &[(&str, fn() -> TemplateData)] = &[
+    ("chrome_130", chrome_130),
+    ("firefox_135", firefox_135),
+    ("safari_17", safari_17),
+    ("safari_17_legacy", safari_17_legacy),
+    ("edge_130", edge_130),
+    ("chrome_131", chrome_131),
+    ("firefox_136", firefox_136),
+    ("edge_131", edge_131),
+    ("okhttp_4", okhttp_4),
+    ("ios_17", ios_17),
+    ("curl_8", curl_8),
+    ("go_1_22", go_1_22),
+    ("chrome_130_quic", chrome_130_quic),
+    ("firefox_135_quic", firefox_135_quic),
+];
+
+fn registry() -> &'static RwLock<HashMap<String, TemplateData>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, TemplateData>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        RwLock::new(
+            BUILTIN_TEMPLATES
+                .iter()
+                .map(|(name, template)| (name.to_string(), template()))
+                .collect(),
+        )
+    })
+}
+
+/// Register a template under `name`, replacing any template of that name.
+///
+/// Registered templates can be selected with [`BrowserTemplate::Named`], so new
+/// client families can be added without changing the `BrowserTemplate` enum.
+/// The built-in templates are pre-registered under their function names
+/// (`"chrome_130"`, `"okhttp_4"`, ...) and may be overridden.
+///
+/// [`BrowserTemplate::Named`]: crate::custls::BrowserTemplate::Named
+pub fn register_template(name: impl Into<String>, template: TemplateData) {
+    registry()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.into(), template);
+}
+
+/// Look up a registered template by name
+pub fn lookup_template(name: &str) -> Option<TemplateData> {
+    registry()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .cloned()
+}
+
+/// Names of all registered templates, sorted
+pub fn registered_template_names() -> Vec<String> {
+    let mut names: Vec<String> = registry()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .keys()
+        .cloned()
+        .collect();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use crate::custls::utils::akamai_fingerprint_of_preface;
+    
+    #[test]
+    fn test_extension_set_is_subset() {
+        let set = ExtensionSet::new(vec![
+            ExtensionType::ServerName,
+            ExtensionType::SupportedVersions,
+        ]);
+        
+        let extensions = vec![
+            ExtensionType::ServerName,
+            ExtensionType::SupportedVersions,
+            ExtensionType::KeyShare,
+        ];
+        
+        assert!(set.is_subset_of(&extensions));
+        
+        let incomplete = vec![ExtensionType::ServerName];
+        assert!(!set.is_subset_of(&incomplete));
+    }
+    
+    #[test]
+    fn test_naturalness_filter_blacklist() {
+        let mut filter = NaturalnessFilter::new();
+        filter.blacklist.push(ExtensionSet::new(vec![
+            ExtensionType::ServerName,
+            ExtensionType::EncryptedClientHello,
+        ]));
+        
+        // Should reject if both blacklisted extensions present
+        let bad_extensions = vec![
+            ExtensionType::ServerName,
+            ExtensionType::EncryptedClientHello,
+        ];
+        assert!(!filter.is_natural(&bad_extensions));
+        
+        // Should accept if only one present
+        let good_extensions = vec![ExtensionType::ServerName];
+        assert!(filter.is_natural(&good_extensions));
+    }
+    
+    #[test]
+    fn test_naturalness_filter_dependencies() {
+        let mut filter = NaturalnessFilter::new();
+        filter.dependencies.insert(
+            ExtensionType::CompressCertificate,
+            vec![ExtensionType::SignatureAlgorithms],
+        );
+        
+        // Should reject if dependency missing
+        let bad_extensions = vec![ExtensionType::CompressCertificate];
+        assert!(!filter.is_natural(&bad_extensions));
+        
+        // Should accept if dependency present
+        let good_extensions = vec![
+            ExtensionType::CompressCertificate,
+            ExtensionType::SignatureAlgorithms,
+        ];
+        assert!(filter.is_natural(&good_extensions));
+        
+        // Should accept if neither present
+        let neither = vec![ExtensionType::ServerName];
+        assert!(filter.is_natural(&neither));
+    }
+    
+    #[test]
+    fn test_template_data_creation() {
+        let template = TemplateData::new(
+            "Test Template".to_string(),
+            "A test template".to_string(),
+        );
+        
+        assert_eq!(template.name, "Test Template");
+        assert_eq!(template.description, "A test template");
+        assert!(template.cipher_suites.is_empty());
+        assert!(template.extension_order.is_empty());
+    }
+    
+    #[test]
+    fn test_grease_pattern_default() {
+        let pattern = GreasePattern::default();
+        
+        assert_eq!(pattern.cipher_suite_probability, 1.0);
+        assert_eq!(pattern.extension_probability, 1.0);
+        assert_eq!(pattern.grease_values.len(), 16); // All standard GREASE values
+    }
+    
+    #[test]
+    fn test_padding_distribution_default() {
+        let dist = PaddingDistribution::default();
+        
+        assert_eq!(dist.min_length, 0);
+        assert_eq!(dist.max_length, 1500);
+        assert!(dist.power_of_2_bias > 0.0 && dist.power_of_2_bias <= 1.0);
         
         // PMF should have some entries
         assert!(!dist.pmf.is_empty());
@@ -1140,6 +2172,10 @@ mod tests {
             firefox_135(),
             safari_17(),
             edge_130(),
+            okhttp_4(),
+            ios_17(),
+            curl_8(),
+            go_1_22(),
         ];
         
         for template in templates {
@@ -1174,6 +2210,10 @@ mod tests {
             firefox_135(),
             safari_17(),
             edge_130(),
+            okhttp_4(),
+            ios_17(),
+            curl_8(),
+            go_1_22(),
         ];
         
         for template in templates {
@@ -1207,6 +2247,10 @@ mod tests {
             firefox_135(),
             safari_17(),
             edge_130(),
+            okhttp_4(),
+            ios_17(),
+            curl_8(),
+            go_1_22(),
         ];
         
         let expected_headers = [":method", ":path", ":authority", ":scheme"];
@@ -1226,4 +2270,203 @@ mod tests {
                 "Template '{}' missing standard HTTP/2 headers", template.name);
         }
     }
+    
+    #[test]
+    fn test_http2_profiles_match_templates() {
+        let templates = vec![
+            chrome_130(),
+            firefox_135(),
+            safari_17(),
+            edge_130(),
+            okhttp_4(),
+            ios_17(),
+            curl_8(),
+            go_1_22(),
+        ];
+        
+        for template in templates {
+            let profile = &template.http2_profile;
+            assert_eq!(profile.pseudo_header_order, template.http2_pseudo_header_order,
+                "Template '{}' has inconsistent pseudo-header order", template.name);
+            assert!(profile.connection_window_update.is_some());
+            assert!(!profile.settings.is_empty());
+        }
+        
+        // Prefaces carry the settings exactly as listed
+        let firefox = firefox_135().http2_profile;
+        assert_eq!(
+            akamai_fingerprint_of_preface(&firefox.connection_preface()).unwrap(),
+            "1:65536;2:0;4:131072;5:16384|12517377|3:0:0:201,5:0:0:101,7:0:0:1,9:0:7:1,11:0:3:1,13:0:0:241"
+        );
+        
+        // Edge is Chromium, iOS is Safari
+        assert_eq!(edge_130().http2_profile, chrome_130().http2_profile);
+        assert_eq!(ios_17().http2_profile, safari_17().http2_profile);
+        assert_ne!(chrome_130().http2_profile, firefox_135().http2_profile);
+    }
+    
+    #[test]
+    fn test_ech_grease_shapes() {
+        // Chromium-based browsers send GREASE ECH, Firefox and Safari do not
+        assert!(chrome_130().ech_grease.is_some());
+        assert!(edge_130().ech_grease.is_some());
+        assert!(firefox_135().ech_grease.is_none());
+        assert!(safari_17().ech_grease.is_none());
+        
+        let shape = EchGreaseShape::chrome();
+        assert_eq!(shape.enc_len(), 32);
+        assert!(!shape.cipher_suites.is_empty());
+        
+        let total: f64 = shape.payload_lengths.iter().map(|(_, p)| p).sum();
+        assert!((total - 1.0).abs() < 0.01);
+    }
+    
+    #[test]
+    fn test_post_quantum_key_shares() {
+        // The newer Chromium-based and Firefox templates lead with the hybrid share
+        for (template, previous) in [
+            (chrome_131(), chrome_130()),
+            (edge_131(), edge_130()),
+            (firefox_136(), firefox_135()),
+        ] {
+            assert_eq!(template.key_share_groups[0], NamedGroup::X25519MLKEM768);
+            assert_eq!(template.key_share_groups[1..], previous.key_share_groups[..]);
+            assert_eq!(template.supported_groups[0], NamedGroup::X25519MLKEM768);
+            assert_eq!(template.supported_groups[1..], previous.supported_groups[..]);
+            assert_eq!(template.cipher_suites, previous.cipher_suites);
+            assert_eq!(template.extension_order, previous.extension_order);
+            
+            // The captured versions are left as recorded
+            assert!(!previous.supported_groups.contains(&NamedGroup::X25519MLKEM768));
+        }
+        
+        // Safari 17 does not offer post-quantum groups
+        let safari = safari_17();
+        assert!(!safari.supported_groups.contains(&NamedGroup::X25519MLKEM768));
+        assert!(!safari.key_share_groups.contains(&NamedGroup::X25519MLKEM768));
+    }
+    
+    #[test]
+    fn test_safari_matches_recorded_codepoints() {
+        let template = safari_17();
+        let suites: Vec<u16> = template.cipher_suites.iter().map(|cs| u16::from(*cs)).collect();
+        let schemes: Vec<u16> = template.signature_algorithms.iter().map(|s| u16::from(*s)).collect();
+        
+        // As in test_data/browser_captures/safari_17/analysis.json
+        assert_eq!(suites, [0x1301, 0x1302, 0x1303, 0xc02c, 0xc02b, 0xc030, 0xc02f, 0xcca9, 0xcca8]);
+        assert_eq!(schemes, [0x0403, 0x0503, 0x0603, 0x0804, 0x0805, 0x0806]);
+        
+        // Safari never sends ALPS
+        assert!(!template.extension_order.contains(&ExtensionType::ApplicationSettings));
+    }
+    
+    #[test]
+    fn test_safari_legacy_advertises_legacy_codepoints() {
+        let template = safari_17_legacy();
+        let suites: Vec<u16> = template.cipher_suites.iter().map(|cs| u16::from(*cs)).collect();
+        
+        // 3DES suites trail the list
+        assert_eq!(&suites[suites.len() - 3..], &[0xc008, 0xc012, 0x000a]);
+        
+        // rsa_pkcs1_sha1 is the last signature scheme
+        assert_eq!(
+            template.signature_algorithms.last(),
+            Some(&SignatureScheme::RSA_PKCS1_SHA1)
+        );
+        assert_eq!(
+            template.signature_algorithms.iter()
+                .filter(|s| **s == SignatureScheme::RSA_PSS_SHA384)
+                .count(),
+            2
+        );
+        
+        // Everything else is Safari 17's
+        let safari = safari_17();
+        assert_eq!(template.extension_order, safari.extension_order);
+        assert_eq!(template.supported_groups, safari.supported_groups);
+        assert_eq!(template.key_share_groups, safari.key_share_groups);
+        assert_eq!(template.alpn_protocols, safari.alpn_protocols);
+    }
+    
+    #[test]
+    fn test_non_browser_templates() {
+        for template in [okhttp_4(), ios_17(), curl_8(), go_1_22()] {
+            assert!(!template.cipher_suites.is_empty());
+            assert!(template.supported_versions.contains(&ProtocolVersion::TLSv1_3));
+            for key_share in &template.key_share_groups {
+                assert!(template.supported_groups.contains(key_share));
+            }
+            assert!(template.ech_grease.is_none());
+        }
+        
+        // OkHttp, curl and Go send a single X25519 share and never GREASE or pad;
+        // iOS shares Safari's ClientHello
+        for template in [okhttp_4(), curl_8(), go_1_22()] {
+            assert_eq!(template.key_share_groups, vec![NamedGroup::X25519]);
+            assert_eq!(template.grease_pattern.cipher_suite_probability, 0.0);
+            assert_eq!(template.grease_pattern.extension_probability, 0.0);
+            assert_eq!(template.padding_distribution.max_length, 0);
+        }
+        
+        let ios = ios_17();
+        let safari = safari_17();
+        assert_ne!(ios.name, safari.name);
+        assert_eq!(ios.cipher_suites, safari.cipher_suites);
+        assert_eq!(ios.extension_order, safari.extension_order);
+        assert_eq!(ios.signature_algorithms, safari.signature_algorithms);
+        
+        // curl ends with the renegotiation SCSV and lists encrypt_then_mac
+        let curl = curl_8();
+        assert_eq!(curl.cipher_suites.last(), Some(&CipherSuite::TLS_EMPTY_RENEGOTIATION_INFO_SCSV));
+        assert!(curl.extension_order.contains(&ExtensionType::from(0x0016)));
+        
+        // Go lists TLS 1.2 suites before TLS 1.3 suites
+        let go = go_1_22();
+        assert_eq!(go.cipher_suites.last(), Some(&CipherSuite::TLS13_CHACHA20_POLY1305_SHA256));
+        assert!(go.extension_order.contains(&ExtensionType::SignatureAlgorithmsCert));
+    }
+    
+    #[test]
+    fn test_template_registry() {
+        for name in ["chrome_130", "firefox_135", "safari_17", "safari_17_legacy", "edge_130",
+                     "chrome_131", "firefox_136", "edge_131", "okhttp_4", "ios_17", "curl_8", "go_1_22",
+                     "chrome_130_quic", "firefox_135_quic"] {
+            assert!(registered_template_names().iter().any(|n| n == name), "{name} not registered");
+        }
+        assert_eq!(lookup_template("okhttp_4").unwrap().name, okhttp_4().name);
+        assert!(lookup_template("netscape_4").is_none());
+        
+        let mut template = go_1_22();
+        template.name = "Registry Test Client".to_string();
+        register_template("registry_test_client", template);
+        assert_eq!(
+            lookup_template("registry_test_client").unwrap().name,
+            "Registry Test Client"
+        );
+    }
+    
+    #[test]
+    fn test_quic_templates_are_tls13_only() {
+        for (tcp, quic) in [(chrome_130(), chrome_130_quic()), (firefox_135(), firefox_135_quic())] {
+            assert!(tcp.quic.is_none());
+            assert!(quic.quic.is_some());
+            assert!(!quic.cipher_suites.is_empty());
+            assert!(quic.cipher_suites.iter().all(|suite| u16::from(*suite) >> 8 == 0x13));
+            assert_eq!(quic.alpn_protocols, vec![b"h3".to_vec()]);
+            assert_eq!(quic.supported_versions, vec![ProtocolVersion::TLSv1_3]);
+            assert!(quic.extension_order.contains(&ExtensionType::TransportParameters));
+            assert!(!quic.extension_order.contains(&ExtensionType::SessionTicket));
+            assert_eq!(quic.handshake_behavior.legacy_session_id_len, 0);
+            assert!(!quic.handshake_behavior.middlebox_compat_ccs);
+            
+            // Everything else follows the TCP template
+            assert_eq!(quic.supported_groups, tcp.supported_groups);
+            assert_eq!(quic.key_share_groups, tcp.key_share_groups);
+            assert_eq!(quic.signature_algorithms, tcp.signature_algorithms);
+            assert_eq!(quic.handshake_behavior.early_data, tcp.handshake_behavior.early_data);
+        }
+        
+        assert!(chrome_130_quic().quic.unwrap().grease_transport_parameter);
+        assert!(!firefox_135_quic().quic.unwrap().shuffle_transport_parameters);
+    }
 }
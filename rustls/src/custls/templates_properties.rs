@@ -23,6 +23,10 @@ mod property_tests {
             Just(firefox_135()),
             Just(safari_17()),
             Just(edge_130()),
+            Just(okhttp_4()),
+            Just(ios_17()),
+            Just(curl_8()),
+            Just(go_1_22()),
         ]
     }
     
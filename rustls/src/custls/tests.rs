@@ -30,14 +30,13 @@ fn test_custls_error_to_rustls_error() {
     let custls_err = CustlsError::HookError("test error".into());
     let rustls_err: RustlsError = custls_err.into();
     
-    match rustls_err {
-        RustlsError::General(msg) => {
-            assert!(msg.contains("custls error"));
-            assert!(msg.contains("Hook error"));
-            assert!(msg.contains("test error"));
+    assert_eq!(
+        rustls_err,
+        RustlsError::Customization {
+            phase: None,
+            error: CustlsError::HookError("test error".into()),
         }
-        _ => panic!("Expected General error variant"),
-    }
+    );
 }
 
 #[test]
@@ -1,12 +1,18 @@
 //! Utility functions for custls
 //!
 //! This module provides helper functions for:
-//! - HTTP/2 SETTINGS encoding
+//! - HTTP/2 SETTINGS encoding and connection prefaces
 //! - Probability distribution sampling
 //! - Timing jitter injection
 //! - Extension ordering validation
 
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
 use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::hash::{BuildHasher, Hasher};
+use core::sync::atomic::{AtomicUsize, Ordering};
 use core::time::Duration;
 
 use crate::msgs::ExtensionType;
@@ -20,15 +26,19 @@ struct SimpleRng {
 
 impl SimpleRng {
     fn new() -> Self {
-        // Seed with a simple time-based value or constant
-        // In production, this should use a better entropy source
-        Self { state: 0x123456789ABCDEF0 }
+        Self::from_seed(entropy_seed())
+    }
+    
+    const fn from_seed(seed: u64) -> Self {
+        Self { state: seed }
     }
     
     fn next_u64(&mut self) -> u64 {
         // LCG parameters from Numerical Recipes
         self.state = self.state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
-        self.state
+        // The low bits of an LCG state have short periods, and `gen_range_*`
+        // reduce modulo small ranges, so mix the state before handing it out
+        mix64(self.state)
     }
     
     fn gen_range_u16(&mut self, min: u16, max: u16) -> u16 {
@@ -55,6 +65,37 @@ impl SimpleRng {
     }
 }
 
+/// Finalizer of SplitMix64: a bijection whose output bits all depend on every input bit
+const fn mix64(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// A seed for a custls generator, different on every call
+///
+/// With `std` this mixes the per-process random keys of the standard library's
+/// hasher with a counter, so generators created one after another, on other
+/// threads or in other processes never replay the same sequence. A constant seed
+/// would make every process send the same padding and GREASE sequence, which
+/// links its connections together. Without `std` only the counter varies.
+pub(crate) fn entropy_seed() -> u64 {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed) as u64;
+    
+    #[cfg(feature = "std")]
+    let base = {
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u64(count);
+        hasher.finish()
+    };
+    
+    #[cfg(not(feature = "std"))]
+    let base = 0x123456789ABCDEF0;
+    
+    mix64(base ^ count.wrapping_mul(0x9e3779b97f4a7c15))
+}
+
 // Thread-local RNG instance
 #[cfg(feature = "std")]
 use std::cell::RefCell;
@@ -65,7 +106,7 @@ std::thread_local! {
 }
 
 #[cfg(not(feature = "std"))]
-static mut RNG: SimpleRng = SimpleRng { state: 0x123456789ABCDEF0 };
+static mut RNG: SimpleRng = SimpleRng::from_seed(0x123456789ABCDEF0);
 
 fn with_rng<F, R>(f: F) -> R
 where
@@ -82,6 +123,17 @@ where
     }
 }
 
+/// Restart this thread's generator from `seed`, for reproducible statistical tests
+#[cfg(all(test, feature = "std"))]
+pub(crate) fn seed_thread_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SimpleRng::from_seed(seed));
+}
+
+/// Uniform value in [0, 1] from the custls internal generator
+pub(crate) fn random_unit() -> f64 {
+    with_rng(|rng| rng.gen_f64())
+}
+
 /// HTTP/2 SETTINGS frame parameters for browser coordination
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Http2Settings {
@@ -229,6 +281,413 @@ impl PrioritySpec {
     }
 }
 
+/// SETTINGS_HEADER_TABLE_SIZE identifier
+pub const SETTINGS_HEADER_TABLE_SIZE: u16 = 0x1;
+/// SETTINGS_ENABLE_PUSH identifier
+pub const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+/// SETTINGS_MAX_CONCURRENT_STREAMS identifier
+pub const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+/// SETTINGS_INITIAL_WINDOW_SIZE identifier
+pub const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+/// SETTINGS_MAX_FRAME_SIZE identifier
+pub const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+/// SETTINGS_MAX_HEADER_LIST_SIZE identifier
+pub const SETTINGS_MAX_HEADER_LIST_SIZE: u16 = 0x6;
+/// SETTINGS_NO_RFC7540_PRIORITIES identifier (RFC 9218)
+pub const SETTINGS_NO_RFC7540_PRIORITIES: u16 = 0x9;
+
+/// HTTP/2 connection behaviour of the client a template imitates
+///
+/// Unlike [`Http2Settings`], which always emits the six RFC 7540 settings in
+/// identifier order, a profile records exactly what a given client sends: which
+/// settings appear and in what order, the connection-level WINDOW_UPDATE, any
+/// PRIORITY frames sent before the first request, the priority carried on the
+/// first HEADERS frame, and the order of pseudo-headers and regular headers.
+///
+/// Passive fingerprinters combine these with the TLS fingerprint, so an HTTP/2
+/// client should take them from the profile of the template that shaped the
+/// ClientHello (see `ClientConnection::http2_profile`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Http2Profile {
+    /// SETTINGS entries as `(identifier, value)`, in the order sent
+    pub settings: Vec<(u16, u32)>,
+
+    /// Increment of the WINDOW_UPDATE frame sent on stream 0, if any
+    pub connection_window_update: Option<u32>,
+
+    /// PRIORITY frames sent after the preface, as `(stream id, priority)`
+    pub priority_frames: Vec<(u32, PrioritySpec)>,
+
+    /// Priority carried on the HEADERS frame of the first request
+    pub headers_priority: Option<PrioritySpec>,
+
+    /// Pseudo-header order for requests
+    pub pseudo_header_order: Vec<Cow<'static, str>>,
+
+    /// Order hint for regular request headers (lowercase names)
+    ///
+    /// Headers the application sends that are not listed here should follow
+    /// the listed ones.
+    pub header_order: Vec<Cow<'static, str>>,
+}
+
+impl Http2Profile {
+    /// Encode the SETTINGS frame payload, preserving the profile's order
+    pub fn encode_settings(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(self.settings.len() * 6);
+        for (id, value) in &self.settings {
+            payload.extend_from_slice(&id.to_be_bytes());
+            payload.extend_from_slice(&value.to_be_bytes());
+        }
+        payload
+    }
+
+    /// Value of a setting, if the profile sends it
+    pub fn setting(&self, id: u16) -> Option<u32> {
+        self.settings
+            .iter()
+            .find(|(setting, _)| *setting == id)
+            .map(|(_, value)| *value)
+    }
+
+    /// Encode the client connection preface for this profile
+    ///
+    /// Returns the bytes a client writes first on a fresh HTTP/2 connection: the
+    /// connection preface magic, a SETTINGS frame with the profile's settings in
+    /// order, the connection-level WINDOW_UPDATE (if any), then the profile's
+    /// PRIORITY frames. The HEADERS frame of the first request is not included.
+    pub fn connection_preface(&self) -> Vec<u8> {
+        let mut preface = Vec::with_capacity(
+            HTTP2_PREFACE_MAGIC.len()
+                + FRAME_HEADER_LEN * (2 + self.priority_frames.len())
+                + self.settings.len() * 6
+                + 4
+                + self.priority_frames.len() * 5,
+        );
+        preface.extend_from_slice(HTTP2_PREFACE_MAGIC);
+
+        encode_frame(&mut preface, FRAME_TYPE_SETTINGS, 0, &self.encode_settings());
+
+        if let Some(increment) = self.connection_window_update {
+            encode_frame(
+                &mut preface,
+                FRAME_TYPE_WINDOW_UPDATE,
+                0,
+                &(increment & 0x7FFF_FFFF).to_be_bytes(),
+            );
+        }
+
+        for (stream_id, priority) in &self.priority_frames {
+            encode_frame(&mut preface, FRAME_TYPE_PRIORITY, *stream_id, &priority.encode());
+        }
+
+        preface
+    }
+
+    /// Akamai-style HTTP/2 fingerprint of this profile
+    ///
+    /// The format is `SETTINGS|WINDOW_UPDATE|PRIORITY|PSEUDO_HEADERS` as described
+    /// in Akamai's "Passive Fingerprinting of HTTP/2 Clients": settings as `id:value`
+    /// joined by `;`, the WINDOW_UPDATE increment (`00` when none is sent), PRIORITY
+    /// frames as `stream:exclusive:dependency:weight` joined by `,` (`0` when none are
+    /// sent), and pseudo-headers abbreviated to their first letter.
+    pub fn akamai_fingerprint(&self) -> String {
+        let pseudo_headers = self
+            .pseudo_header_order
+            .iter()
+            .filter_map(|header| header.strip_prefix(':')?.get(..1))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{}|{}",
+            akamai_frames_fingerprint(
+                &self.settings,
+                self.connection_window_update,
+                &self.priority_frames
+            ),
+            pseudo_headers
+        )
+    }
+
+    /// Chrome (and other Chromium browsers)
+    pub fn chrome() -> Self {
+        Self {
+            settings: alloc::vec![
+                (SETTINGS_HEADER_TABLE_SIZE, 65536),
+                (SETTINGS_ENABLE_PUSH, 0),
+                (SETTINGS_INITIAL_WINDOW_SIZE, 6291456),
+                (SETTINGS_MAX_HEADER_LIST_SIZE, 262144),
+            ],
+            connection_window_update: Some(15663105),
+            priority_frames: Vec::new(),
+            headers_priority: Some(PrioritySpec {
+                stream_dependency: 0,
+                weight: 256,
+                exclusive: true,
+            }),
+            pseudo_header_order: header_names(&[":method", ":authority", ":scheme", ":path"]),
+            header_order: header_names(&[
+                "sec-ch-ua",
+                "sec-ch-ua-mobile",
+                "sec-ch-ua-platform",
+                "upgrade-insecure-requests",
+                "user-agent",
+                "accept",
+                "sec-fetch-site",
+                "sec-fetch-mode",
+                "sec-fetch-user",
+                "sec-fetch-dest",
+                "accept-encoding",
+                "accept-language",
+                "priority",
+            ]),
+        }
+    }
+
+    /// Firefox, including its RFC 7540 priority tree
+    pub fn firefox() -> Self {
+        let priority = |stream_dependency, weight| PrioritySpec {
+            stream_dependency,
+            weight,
+            exclusive: false,
+        };
+        Self {
+            settings: alloc::vec![
+                (SETTINGS_HEADER_TABLE_SIZE, 65536),
+                (SETTINGS_ENABLE_PUSH, 0),
+                (SETTINGS_INITIAL_WINDOW_SIZE, 131072),
+                (SETTINGS_MAX_FRAME_SIZE, 16384),
+            ],
+            connection_window_update: Some(12517377),
+            priority_frames: alloc::vec![
+                (3, priority(0, 201)),
+                (5, priority(0, 101)),
+                (7, priority(0, 1)),
+                (9, priority(7, 1)),
+                (11, priority(3, 1)),
+                (13, priority(0, 241)),
+            ],
+            headers_priority: Some(priority(13, 42)),
+            pseudo_header_order: header_names(&[":method", ":path", ":authority", ":scheme"]),
+            header_order: header_names(&[
+                "user-agent",
+                "accept",
+                "accept-language",
+                "accept-encoding",
+                "upgrade-insecure-requests",
+                "sec-fetch-dest",
+                "sec-fetch-mode",
+                "sec-fetch-site",
+                "sec-fetch-user",
+                "priority",
+                "te",
+            ]),
+        }
+    }
+
+    /// Safari on macOS and iOS
+    pub fn safari() -> Self {
+        Self {
+            settings: alloc::vec![
+                (SETTINGS_ENABLE_PUSH, 0),
+                (SETTINGS_MAX_CONCURRENT_STREAMS, 100),
+                (SETTINGS_INITIAL_WINDOW_SIZE, 2097152),
+                (SETTINGS_NO_RFC7540_PRIORITIES, 1),
+            ],
+            connection_window_update: Some(10420225),
+            priority_frames: Vec::new(),
+            headers_priority: None,
+            pseudo_header_order: header_names(&[":method", ":scheme", ":path", ":authority"]),
+            header_order: header_names(&[
+                "accept",
+                "sec-fetch-site",
+                "sec-fetch-dest",
+                "accept-language",
+                "sec-fetch-mode",
+                "user-agent",
+                "accept-encoding",
+                "priority",
+            ]),
+        }
+    }
+
+    /// OkHttp 4
+    pub fn okhttp() -> Self {
+        Self {
+            settings: alloc::vec![(SETTINGS_INITIAL_WINDOW_SIZE, 16777216)],
+            connection_window_update: Some(16711681),
+            priority_frames: Vec::new(),
+            headers_priority: None,
+            pseudo_header_order: header_names(&[":method", ":path", ":authority", ":scheme"]),
+            header_order: header_names(&["accept-encoding", "user-agent"]),
+        }
+    }
+
+    /// curl with nghttp2
+    pub fn curl() -> Self {
+        Self {
+            settings: alloc::vec![
+                (SETTINGS_MAX_CONCURRENT_STREAMS, 100),
+                (SETTINGS_INITIAL_WINDOW_SIZE, 33554432),
+                (SETTINGS_ENABLE_PUSH, 0),
+            ],
+            connection_window_update: Some(33488897),
+            priority_frames: Vec::new(),
+            headers_priority: None,
+            pseudo_header_order: header_names(&[":method", ":path", ":scheme", ":authority"]),
+            header_order: header_names(&["user-agent", "accept"]),
+        }
+    }
+
+    /// Go's net/http
+    pub fn go() -> Self {
+        Self {
+            settings: alloc::vec![
+                (SETTINGS_ENABLE_PUSH, 0),
+                (SETTINGS_INITIAL_WINDOW_SIZE, 4194304),
+                (SETTINGS_MAX_HEADER_LIST_SIZE, 10485760),
+            ],
+            connection_window_update: Some(1073741824),
+            priority_frames: Vec::new(),
+            headers_priority: None,
+            pseudo_header_order: header_names(&[":authority", ":method", ":path", ":scheme"]),
+            header_order: header_names(&["user-agent", "accept-encoding"]),
+        }
+    }
+}
+
+/// Header names as stored in an `Http2Profile`
+fn header_names(names: &[&'static str]) -> Vec<Cow<'static, str>> {
+    names.iter().map(|name| Cow::Borrowed(*name)).collect()
+}
+
+/// Client connection preface magic (RFC 9113 Section 3.4)
+pub const HTTP2_PREFACE_MAGIC: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const FRAME_HEADER_LEN: usize = 9;
+const FRAME_TYPE_PRIORITY: u8 = 0x2;
+const FRAME_TYPE_SETTINGS: u8 = 0x4;
+const FRAME_TYPE_WINDOW_UPDATE: u8 = 0x8;
+
+/// Append an HTTP/2 frame with no flags set
+fn encode_frame(out: &mut Vec<u8>, frame_type: u8, stream_id: u32, payload: &[u8]) {
+    let len = payload.len() as u32;
+    out.extend_from_slice(&len.to_be_bytes()[1..]);
+    out.push(frame_type);
+    out.push(0);
+    out.extend_from_slice(&(stream_id & 0x7FFF_FFFF).to_be_bytes());
+    out.extend_from_slice(payload);
+}
+
+fn akamai_frames_fingerprint(
+    settings: &[(u16, u32)],
+    window_update: Option<u32>,
+    priority_frames: &[(u32, PrioritySpec)],
+) -> String {
+    let settings = settings
+        .iter()
+        .map(|(id, value)| format!("{}:{}", id, value))
+        .collect::<Vec<_>>()
+        .join(";");
+    let window_update = match window_update {
+        Some(increment) => format!("{}", increment),
+        None => String::from("00"),
+    };
+    let priorities = match priority_frames.is_empty() {
+        true => String::from("0"),
+        false => priority_frames
+            .iter()
+            .map(|(stream_id, spec)| {
+                format!(
+                    "{}:{}:{}:{}",
+                    stream_id,
+                    spec.exclusive as u8,
+                    spec.stream_dependency,
+                    spec.weight
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(","),
+    };
+    format!("{}|{}|{}", settings, window_update, priorities)
+}
+
+/// Compute the frame part of an Akamai HTTP/2 fingerprint from preface bytes
+///
+/// Parses a client connection preface as produced by
+/// [`Http2Profile::connection_preface`] or captured from a real client, and returns
+/// `SETTINGS|WINDOW_UPDATE|PRIORITY`. The pseudo-header component is omitted
+/// because it comes from the first HEADERS frame, which is not part of the preface.
+/// Parsing stops at the first frame that is not SETTINGS, WINDOW_UPDATE on stream
+/// 0, or PRIORITY, so trailing request frames are ignored.
+pub fn akamai_fingerprint_of_preface(preface: &[u8]) -> Result<String, CustlsError> {
+    let mut rest = preface
+        .strip_prefix(HTTP2_PREFACE_MAGIC)
+        .ok_or_else(|| CustlsError::ValidationError("missing HTTP/2 preface magic".into()))?;
+
+    let mut settings = None;
+    let mut window_update = None;
+    let mut priority_frames = Vec::new();
+
+    while rest.len() >= FRAME_HEADER_LEN {
+        let len = u32::from_be_bytes([0, rest[0], rest[1], rest[2]]) as usize;
+        let frame_type = rest[3];
+        let stream_id = u32::from_be_bytes([rest[5], rest[6], rest[7], rest[8]]) & 0x7FFF_FFFF;
+        let Some(payload) = rest.get(FRAME_HEADER_LEN..FRAME_HEADER_LEN + len) else {
+            return Err(CustlsError::ValidationError("truncated HTTP/2 frame".into()));
+        };
+
+        match frame_type {
+            FRAME_TYPE_SETTINGS if settings.is_none() => {
+                if len % 6 != 0 {
+                    return Err(CustlsError::ValidationError(
+                        "malformed SETTINGS frame".into(),
+                    ));
+                }
+                settings = Some(
+                    payload
+                        .chunks(6)
+                        .map(|s| {
+                            (
+                                u16::from_be_bytes([s[0], s[1]]),
+                                u32::from_be_bytes([s[2], s[3], s[4], s[5]]),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                );
+            }
+            FRAME_TYPE_WINDOW_UPDATE if stream_id == 0 && len == 4 => {
+                window_update = Some(
+                    u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]])
+                        & 0x7FFF_FFFF,
+                );
+            }
+            FRAME_TYPE_PRIORITY if len == 5 => {
+                let dependency = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]);
+                priority_frames.push((
+                    stream_id,
+                    PrioritySpec {
+                        stream_dependency: dependency & 0x7FFF_FFFF,
+                        weight: payload[4] as u16 + 1,
+                        exclusive: dependency & 0x8000_0000 != 0,
+                    },
+                ));
+            }
+            _ => break,
+        }
+
+        rest = &rest[FRAME_HEADER_LEN + len..];
+    }
+
+    let settings = settings
+        .ok_or_else(|| CustlsError::ValidationError("preface has no SETTINGS frame".into()))?;
+    Ok(akamai_frames_fingerprint(
+        &settings,
+        window_update,
+        &priority_frames,
+    ))
+}
+
 /// Timing jitter configuration for anti-fingerprinting
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct TimingJitterConfig {
@@ -253,22 +712,30 @@ impl Default for TimingJitterConfig {
 }
 
 impl TimingJitterConfig {
-    /// Apply timing jitter based on configuration
+    /// Sample a send delay based on configuration
     ///
-    /// This function will sleep for a random duration between min_delay_micros
-    /// and max_delay_micros with probability apply_probability.
-    #[cfg(feature = "std")]
-    pub fn apply(&self) {
+    /// Returns a random duration between min_delay_micros and max_delay_micros
+    /// with probability apply_probability, and `None` otherwise. Nothing sleeps:
+    /// `DefaultCustomizer` reports the sample as the delay before the first flight,
+    /// which the connection exposes through `wants_write_after()`.
+    pub fn sample_delay(&self) -> Option<Duration> {
         let should_apply = with_rng(|rng| rng.gen_f64() < self.apply_probability);
+        if !should_apply {
+            return None;
+        }
         
-        if should_apply {
-            let delay = with_rng(|rng| {
-                rng.gen_range_u16(
-                    self.min_delay_micros as u16,
-                    self.max_delay_micros as u16
-                ) as u64
-            });
-            std::thread::sleep(Duration::from_micros(delay));
+        let span = self.max_delay_micros.saturating_sub(self.min_delay_micros);
+        let offset = with_rng(|rng| (rng.gen_f64() * (span as f64 + 1.0)) as u64).min(span);
+        Some(Duration::from_micros(self.min_delay_micros + offset))
+    }
+    
+    /// Blocking adapter: sleep for a sampled delay on the current thread
+    ///
+    /// For simple synchronous callers only; never call this from an async task.
+    #[cfg(feature = "std")]
+    pub fn apply(&self) {
+        if let Some(delay) = self.sample_delay() {
+            std::thread::sleep(delay);
         }
     }
     
@@ -497,6 +964,123 @@ mod tests {
         assert_eq!(encoded[4], 255);
     }
     
+    #[test]
+    fn test_http2_profile_encode_settings_keeps_order() {
+        let profile = Http2Profile::safari();
+        let encoded = profile.encode_settings();
+
+        assert_eq!(encoded.len(), profile.settings.len() * 6);
+        // Safari leads with ENABLE_PUSH and ends with NO_RFC7540_PRIORITIES
+        assert_eq!(&encoded[0..6], &[0x00, 0x02, 0, 0, 0, 0]);
+        assert_eq!(&encoded[18..24], &[0x00, 0x09, 0, 0, 0, 1]);
+        assert_eq!(profile.setting(SETTINGS_MAX_CONCURRENT_STREAMS), Some(100));
+        assert_eq!(profile.setting(SETTINGS_HEADER_TABLE_SIZE), None);
+    }
+
+    #[test]
+    fn test_http2_profiles_are_distinct() {
+        let profiles = [
+            Http2Profile::chrome(),
+            Http2Profile::firefox(),
+            Http2Profile::safari(),
+            Http2Profile::okhttp(),
+            Http2Profile::curl(),
+            Http2Profile::go(),
+        ];
+
+        for (i, a) in profiles.iter().enumerate() {
+            assert_eq!(a.pseudo_header_order.len(), 4);
+            assert!(!a.settings.is_empty());
+            for b in &profiles[i + 1..] {
+                assert_ne!(a.settings, b.settings);
+            }
+        }
+
+        let firefox = Http2Profile::firefox();
+        assert_eq!(firefox.priority_frames.len(), 6);
+        assert_eq!(firefox.headers_priority.unwrap().stream_dependency, 13);
+    }
+
+    #[test]
+    fn test_http2_connection_preface_frames() {
+        let profile = Http2Profile::chrome();
+        let preface = profile.connection_preface();
+
+        assert!(preface.starts_with(HTTP2_PREFACE_MAGIC));
+        let frames = &preface[HTTP2_PREFACE_MAGIC.len()..];
+
+        // SETTINGS: 4 settings, type 0x4, no flags, stream 0
+        assert_eq!(&frames[..9], &[0, 0, 24, 0x4, 0, 0, 0, 0, 0]);
+        assert_eq!(&frames[9..33], profile.encode_settings().as_slice());
+
+        // WINDOW_UPDATE on stream 0
+        assert_eq!(&frames[33..42], &[0, 0, 4, 0x8, 0, 0, 0, 0, 0]);
+        assert_eq!(&frames[42..46], &15663105u32.to_be_bytes());
+        assert_eq!(frames.len(), 46);
+
+        // Firefox follows with six PRIORITY frames of 14 bytes each
+        let firefox = Http2Profile::firefox().connection_preface();
+        let settings_len = 9 + 4 * 6;
+        assert_eq!(
+            firefox.len(),
+            HTTP2_PREFACE_MAGIC.len() + settings_len + 13 + 6 * 14
+        );
+        let first_priority = &firefox[HTTP2_PREFACE_MAGIC.len() + settings_len + 13..][..14];
+        assert_eq!(&first_priority[..9], &[0, 0, 5, 0x2, 0, 0, 0, 0, 3]);
+        assert_eq!(first_priority[13], 200);
+    }
+
+    #[test]
+    fn test_http2_akamai_fingerprints() {
+        assert_eq!(
+            Http2Profile::chrome().akamai_fingerprint(),
+            "1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p"
+        );
+        assert_eq!(
+            Http2Profile::firefox().akamai_fingerprint(),
+            "1:65536;2:0;4:131072;5:16384|12517377|3:0:0:201,5:0:0:101,7:0:0:1,9:0:7:1,11:0:3:1,13:0:0:241|m,p,a,s"
+        );
+        assert_eq!(
+            Http2Profile::safari().akamai_fingerprint(),
+            "2:0;3:100;4:2097152;9:1|10420225|0|m,s,p,a"
+        );
+
+        let mut no_window_update = Http2Profile::okhttp();
+        no_window_update.connection_window_update = None;
+        assert_eq!(no_window_update.akamai_fingerprint(), "4:16777216|00|0|m,p,a,s");
+    }
+
+    #[test]
+    fn test_http2_preface_roundtrips_to_akamai_fingerprint() {
+        for profile in [
+            Http2Profile::chrome(),
+            Http2Profile::firefox(),
+            Http2Profile::safari(),
+            Http2Profile::okhttp(),
+            Http2Profile::curl(),
+            Http2Profile::go(),
+        ] {
+            let mut preface = profile.connection_preface();
+            let expected = profile.akamai_fingerprint();
+            let frames_part = expected.rsplit_once('|').unwrap().0;
+            assert_eq!(akamai_fingerprint_of_preface(&preface).unwrap(), frames_part);
+
+            // A following HEADERS frame stops parsing
+            preface.extend_from_slice(&[0, 0, 0, 0x1, 0x4, 0, 0, 0, 1]);
+            assert_eq!(akamai_fingerprint_of_preface(&preface).unwrap(), frames_part);
+        }
+    }
+
+    #[test]
+    fn test_akamai_fingerprint_of_malformed_preface() {
+        assert!(akamai_fingerprint_of_preface(b"GET / HTTP/1.1\r\n").is_err());
+        assert!(akamai_fingerprint_of_preface(HTTP2_PREFACE_MAGIC).is_err());
+
+        let preface = Http2Profile::chrome().connection_preface();
+        // Cut inside the SETTINGS payload
+        assert!(akamai_fingerprint_of_preface(&preface[..40]).is_err());
+    }
+
     #[test]
     fn test_timing_jitter_config_validation() {
         // Valid config
@@ -515,6 +1099,22 @@ mod tests {
         assert!(config.is_err());
     }
     
+    #[test]
+    fn test_timing_jitter_sample_delay_within_bounds() {
+        let always = TimingJitterConfig::new(100_000, 200_000, 1.0).unwrap();
+        for _ in 0..100 {
+            let delay = always.sample_delay().unwrap();
+            assert!(delay >= Duration::from_micros(100_000));
+            assert!(delay <= Duration::from_micros(200_000));
+        }
+        
+        let fixed = TimingJitterConfig::new(750, 750, 1.0).unwrap();
+        assert_eq!(fixed.sample_delay(), Some(Duration::from_micros(750)));
+        
+        let never = TimingJitterConfig::new(100, 1000, 0.0).unwrap();
+        assert!((0..100).all(|_| never.sample_delay().is_none()));
+    }
+    
     #[test]
     fn test_sample_from_pmf() {
         let pmf = vec![
@@ -12,6 +12,7 @@ use webpki::ExtendedKeyUsage;
 
 use crate::crypto::kx::KeyExchangeAlgorithm;
 use crate::crypto::{GetRandomFailed, InconsistentKeys};
+use crate::custls::{CustlsError, HookPhase};
 use crate::enums::{ContentType, HandshakeType};
 use crate::msgs::{Codec, EchConfigPayload};
 
@@ -125,6 +126,17 @@ pub enum Error {
     /// See [`RejectedEch::can_retry()`] and [`crate::client::EchConfig::for_retry()`].
     RejectedEch(RejectedEch),
 
+    /// A custls customizer failed while shaping a ClientHello.
+    ///
+    /// `phase` names the hook that failed. It is `None` for failures outside the
+    /// hooks, such as enabling a profile with an unknown template.
+    Customization {
+        /// The hook the error was returned from
+        phase: Option<HookPhase>,
+        /// What went wrong
+        error: CustlsError,
+    },
+
     /// Errors of this variant should never be produced by the library.
     ///
     /// Please file a bug if you see one.
@@ -172,6 +184,9 @@ impl TryFrom<&Error> for AlertDescription {
             Error::PeerIncompatible(e) => Self::from(*e),
             Error::PeerSentOversizedRecord => Self::RecordOverflow,
             Error::RejectedEch(_) => Self::EncryptedClientHelloRequired,
+            // Only reaches the peer when shaping a hello after a HelloRetryRequest;
+            // a failure shaping the first hello is returned before anything is sent
+            Error::Customization { .. } => Self::InternalError,
 
             _ => return Err(()),
         })
@@ -236,6 +251,13 @@ impl fmt::Display for Error {
                     if why.can_retry() { "with" } else { "without" }
                 )
             }
+            Self::Customization {
+                phase: Some(phase),
+                error,
+            } => write!(f, "ClientHello customization failed in {phase}: {error}"),
+            Self::Customization { phase: None, error } => {
+                write!(f, "ClientHello customization failed: {error}")
+            }
             Self::General(err) => write!(f, "unexpected error: {err}"),
             Self::Unreachable(err) => write!(
                 f,
@@ -1098,7 +1120,9 @@ pub enum PeerIncompatible {
     NoServerNameProvided,
     NullCompressionRequired,
     ServerDoesNotSupportTls12Or13,
+    ServerSelectedAdvertiseOnlyCipherSuite,
     ServerSentHelloRetryRequestWithUnknownExtension,
+    ServerSignedWithAdvertiseOnlySignatureScheme,
     ServerTlsVersionIsDisabledByOurConfig,
     SignatureAlgorithmsExtensionRequired,
     SupportedVersionsExtensionRequired,
@@ -9,6 +9,7 @@ use super::{
     UnixTime,
 };
 use crate::crypto::GetRandomFailed;
+use crate::custls::{CustlsError, HookPhase};
 use crate::msgs::test_enum8_display;
 
 #[test]
@@ -257,6 +258,11 @@ fn smoke() {
         Error::InconsistentKeys(InconsistentKeys::Unknown),
         Error::InvalidCertRevocationList(CertRevocationListError::BadSignature),
         Error::Unreachable("smoke"),
+        Error::Customization {
+            phase: Some(HookPhase::StructReady),
+            error: CustlsError::ValidationError("smoke".into()),
+        },
+        CustlsError::TemplateError("smoke".into()).into(),
         super::ApiMisuse::ExporterAlreadyUsed.into(),
         Error::Other(OtherError::new(TestError)),
     ];
@@ -303,6 +309,48 @@ fn rand_error_mapping() {
     assert_eq!(err, Error::FailedToGetRandomBytes);
 }
 
+#[test]
+fn customization_error_mapping() {
+    let err: Error = CustlsError::CacheError("full".into()).into();
+    assert_eq!(
+        HookPhase::ConfigResolve.attribute(err),
+        Error::Customization {
+            phase: Some(HookPhase::ConfigResolve),
+            error: CustlsError::CacheError("full".into()),
+        }
+    );
+
+    // A phase already recorded is kept
+    let err = HookPhase::StructReady.attribute(Error::Customization {
+        phase: Some(HookPhase::EchInnerReady),
+        error: CustlsError::HookError("inner".into()),
+    });
+    assert_eq!(
+        err,
+        Error::Customization {
+            phase: Some(HookPhase::EchInnerReady),
+            error: CustlsError::HookError("inner".into()),
+        }
+    );
+    assert_eq!(
+        AlertDescription::try_from(&err),
+        Ok(AlertDescription::InternalError)
+    );
+
+    // Hooks reporting catch-all errors are recognised as hook failures
+    assert_eq!(
+        HookPhase::ComponentsReady.attribute(Error::General("aborted".into())),
+        Error::Customization {
+            phase: Some(HookPhase::ComponentsReady),
+            error: CustlsError::HookError("aborted".into()),
+        }
+    );
+    assert_eq!(
+        HookPhase::ComponentsReady.attribute(Error::FailedToGetRandomBytes),
+        Error::FailedToGetRandomBytes
+    );
+}
+
 #[cfg(feature = "std")]
 #[test]
 fn time_error_mapping() {
@@ -17,6 +17,8 @@ use crate::crypto::cipher::Payload;
 use crate::crypto::hpke::HpkeSymmetricCipherSuite;
 use crate::crypto::kx::NamedGroup;
 use crate::crypto::{CipherSuite, SignatureScheme};
+use crate::custls::capture::is_grease_value;
+use crate::custls::extensions::PaddingExtension;
 use crate::enums::{
     ApplicationProtocol, CertificateCompressionAlgorithm, CertificateType, EchClientHelloType,
     ProtocolVersion,
@@ -81,6 +83,21 @@ impl ClientHelloPayload {
             compressed.clear(*e);
         }
 
+        // If the compressed extensions sit inside an explicitly ordered block, the
+        // marker takes their place so the decoded inner hello keeps the same order.
+        if let Some(pos) = compressed
+            .contiguous_extensions
+            .iter()
+            .position(|e| *e == to_compress[0])
+        {
+            compressed
+                .contiguous_extensions
+                .retain(|e| !to_compress.contains(e));
+            compressed
+                .contiguous_extensions
+                .insert(pos, ExtensionType::EncryptedClientHelloOuterExtensions);
+        }
+
         // Replace with the marker noting which extensions were elided.
         compressed.encrypted_client_hello_outer = Some(to_compress);
 
@@ -241,11 +258,17 @@ extension_struct! {
         /// Encrypted client hello outer extensions (draft-ietf-tls-esni)
         ExtensionType::EncryptedClientHelloOuterExtensions =>
             pub(crate) encrypted_client_hello_outer: Option<Vec<ExtensionType>>,
+
+        /// Padding to a chosen length (RFC7685)
+        ExtensionType::Padding =>
+            pub(crate) padding: Option<PaddingExtension>,
     } + {
         /// Order randomization seed.
         pub(crate) order_seed: u16,
 
         /// Extensions that must appear contiguously.
+        ///
+        /// GREASE extension types (RFC8701) listed here are sent with an empty body.
         pub(crate) contiguous_extensions: Vec<ExtensionType>,
     }
 }
@@ -275,6 +298,7 @@ impl ClientExtensions<'_> {
             renegotiation_info,
             encrypted_client_hello,
             encrypted_client_hello_outer,
+            padding,
             order_seed,
             contiguous_extensions,
         } = self;
@@ -305,6 +329,7 @@ impl ClientExtensions<'_> {
             renegotiation_info: renegotiation_info.map(|x| x.into_owned()),
             encrypted_client_hello,
             encrypted_client_hello_outer,
+            padding,
             order_seed,
             contiguous_extensions,
         }
@@ -317,6 +342,9 @@ impl ClientExtensions<'_> {
         if self
             .encrypted_client_hello_outer
             .is_some()
+            && !self
+                .contiguous_extensions
+                .contains(&ExtensionType::EncryptedClientHelloOuterExtensions)
         {
             exts.push(ExtensionType::EncryptedClientHelloOuterExtensions);
         }
@@ -329,6 +357,16 @@ impl ClientExtensions<'_> {
         exts
     }
 
+    /// Returns true if extension `typ` is present in both `self` and `other`, with
+    /// identical encodings.
+    pub(crate) fn has_identical(&self, other: &Self, typ: ExtensionType) -> bool {
+        let mut ours = Vec::new();
+        self.encode_one(typ, &mut ours);
+        let mut theirs = Vec::new();
+        other.encode_one(typ, &mut theirs);
+        !ours.is_empty() && ours == theirs
+    }
+
     /// Returns extensions which don't need a specific order, in randomized order.
     ///
     /// Extensions are encoded in three portions:
@@ -374,7 +412,13 @@ impl<'a> Codec<'a> for ClientExtensions<'a> {
 
         let body = LengthPrefixedBuffer::new(ListLength::U16, bytes);
         for item in order {
-            self.encode_one(item, body.buf);
+            match is_grease_value(u16::from(item)) {
+                true => {
+                    item.encode(body.buf);
+                    0u16.encode(body.buf);
+                }
+                false => self.encode_one(item, body.buf),
+            }
         }
     }
 
@@ -132,6 +132,16 @@ impl SessionId {
         Ok(Self { data, len: 32 })
     }
 
+    /// A random session ID of `len` bytes, capped at 32; empty when `len` is 0.
+    pub(crate) fn random_with_len(
+        secure_random: &dyn SecureRandom,
+        len: usize,
+    ) -> Result<Self, GetRandomFailed> {
+        let mut id = Self::random(secure_random)?;
+        id.len = len.min(32);
+        Ok(id)
+    }
+
     pub(crate) fn empty() -> Self {
         Self {
             data: [0u8; 32],
@@ -372,6 +382,9 @@ pub(crate) struct ClientExtensionsInput {
 
     /// ALPN protocols
     pub(crate) protocols: Option<Vec<ApplicationProtocol<'static>>>,
+
+    /// Destination port reported to a custls customizer, if the caller gave one
+    pub(crate) target_port: Option<u16>,
 }
 
 impl ClientExtensionsInput {
@@ -384,6 +397,7 @@ impl ClientExtensionsInput {
         Self {
             transport_parameters: None,
             protocols,
+            target_port: None,
         }
     }
 }
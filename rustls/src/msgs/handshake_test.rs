@@ -32,6 +32,7 @@ use crate::crypto::cipher::Payload;
 use crate::crypto::hpke::{HpkeAead, HpkeKdf, HpkeKem, HpkeSymmetricCipherSuite};
 use crate::crypto::kx::NamedGroup;
 use crate::crypto::{CipherSuite, SignatureScheme};
+use crate::custls::extensions::PaddingExtension;
 use crate::enums::{
     ApplicationProtocol, CertificateCompressionAlgorithm, CertificateType, HandshakeType,
     ProtocolVersion,
@@ -465,6 +466,35 @@ fn client_extensions_ordering() {
     }
 }
 
+#[test]
+fn client_extensions_encode_grease_and_padding() {
+    let exts = ClientExtensions {
+        extended_master_secret_request: Some(()),
+        padding: Some(PaddingExtension::new(3)),
+        contiguous_extensions: vec![
+            ExtensionType::Unknown(0x1a1a),
+            ExtensionType::ExtendedMasterSecret,
+            ExtensionType::Padding,
+        ],
+        ..Default::default()
+    };
+    assert_eq!(
+        exts.get_encoding(),
+        b"\x00\x0f\
+          \x1a\x1a\x00\x00\
+          \x00\x17\x00\x00\
+          \x00\x15\x00\x03\x00\x00\x00"
+    );
+
+    // GREASE is dropped on reading, like any unknown extension
+    let read = ClientExtensions::read_bytes(&exts.get_encoding()).unwrap();
+    assert_eq!(
+        read.collect_used(),
+        vec![ExtensionType::ExtendedMasterSecret, ExtensionType::Padding]
+    );
+    assert_eq!(read.padding, Some(PaddingExtension::new(3)));
+}
+
 #[test]
 fn test_truncated_psk_offer() {
     let ext = PresharedKeyOffer {
@@ -921,6 +951,7 @@ fn sample_client_hello_payload() -> ClientHelloPayload {
             certificate_compression_algorithms: Some(vec![CertificateCompressionAlgorithm::Brotli]),
             encrypted_client_hello: Some(EncryptedClientHello::Inner),
             encrypted_client_hello_outer: Some(vec![ExtensionType::SCT]),
+            padding: Some(PaddingExtension::new(4)),
             ..Default::default()
         }),
     }
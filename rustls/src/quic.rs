@@ -184,6 +184,25 @@ mod connection {
             self.inner.tls13_tickets_received
         }
 
+        /// Return the HTTP/2 profile of the custls template that shaped this connection.
+        ///
+        /// See [`crate::client::ClientConnection::http2_profile`]. Like there, this is
+        /// `Some` only if `h2` was negotiated; an HTTP/3 connection gets `None`.
+        pub fn http2_profile(&self) -> Option<&crate::custls::Http2Profile> {
+            self.inner
+                .core
+                .side
+                .negotiated_http2_profile(&self.inner.core.common_state)
+        }
+
+        /// Returns the ClientHellos this connection has sent, oldest first.
+        ///
+        /// The bytes are the handshake messages carried in CRYPTO frames. See
+        /// [`crate::client::ClientConnection::sent_client_hellos`].
+        pub fn sent_client_hellos(&self) -> &[crate::custls::SentClientHello] {
+            &self.inner.core.side.sent_client_hellos
+        }
+
         /// Returns an object that can derive key material from the agreed connection secrets.
         ///
         /// See [RFC5705][] for more details on what this is for.
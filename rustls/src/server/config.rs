@@ -17,7 +17,8 @@ use crate::crypto::{
 };
 use crate::enums::{ApplicationProtocol, CertificateType, ProtocolVersion};
 use crate::error::{Error, PeerMisbehaved};
-use crate::msgs::ServerNamePayload;
+use crate::custls::ClientHelloView;
+use crate::msgs::{Message, MessagePayload, ServerNamePayload};
 use crate::sync::Arc;
 #[cfg(feature = "std")]
 use crate::time_provider::DefaultTimeProvider;
@@ -267,6 +268,7 @@ impl ServerConfig {
         ConfigBuilder {
             state: WantsVerifier {
                 client_ech_mode: None,
+                client_browser_profile: None,
             },
             provider,
             time_provider,
@@ -400,6 +402,7 @@ pub struct ClientHello<'a> {
     /// [certificate_authorities]: https://datatracker.ietf.org/doc/html/rfc8446#section-4.2.4
     pub(super) certificate_authorities: Option<&'a [DistinguishedName]>,
     pub(super) named_groups: Option<&'a [NamedGroup]>,
+    pub(super) bytes: &'a [u8],
 }
 
 impl<'a> ClientHello<'a> {
@@ -433,6 +436,7 @@ impl<'a> ClientHello<'a> {
                 .client_hello
                 .named_groups
                 .as_deref(),
+            bytes: client_hello_bytes(input.message),
         }
     }
 
@@ -523,6 +527,34 @@ impl<'a> ClientHello<'a> {
     pub fn named_groups(&self) -> Option<&'a [NamedGroup]> {
         self.named_groups
     }
+
+    /// Get the ClientHello handshake message as received, including its 4-byte header.
+    ///
+    /// This is the message as it enters the transcript, after reassembly from
+    /// records (or CRYPTO frames for QUIC).
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Parse the received ClientHello into a [`ClientHelloView`].
+    ///
+    /// Unlike the other accessors, the view keeps everything a fingerprinting server
+    /// looks at: the extension list in wire order, GREASE values, key share groups,
+    /// the legacy version and the padding length.
+    ///
+    /// The view also computes the JA3 and JA4 fingerprints of the client
+    /// ([`ClientHelloView::ja3_string`], [`ClientHelloView::ja4`]).
+    pub fn view(&self) -> Result<ClientHelloView, Error> {
+        Ok(ClientHelloView::parse(self.bytes)?)
+    }
+}
+
+/// The encoding of a received ClientHello message.
+pub(super) fn client_hello_bytes<'a>(message: &'a Message<'_>) -> &'a [u8] {
+    match &message.payload {
+        MessagePayload::Handshake { encoded, .. } => encoded.bytes(),
+        _ => &[],
+    }
 }
 
 /// A policy describing how an invalid Server Name Indication (SNI) value from a client is handled by the server.
@@ -11,7 +11,7 @@ use std::io;
 use pki_types::DnsName;
 
 #[cfg(feature = "std")]
-use super::config::ClientHello;
+use super::config::{ClientHello, client_hello_bytes};
 use super::config::ServerConfig;
 use super::hs;
 #[cfg(feature = "std")]
@@ -530,6 +530,7 @@ impl Accepted {
                 .certificate_authority_names
                 .as_deref(),
             named_groups: payload.named_groups.as_deref(),
+            bytes: client_hello_bytes(&self.input.message),
         };
 
         trace!("Accepted::client_hello(): {ch:#?}");
@@ -241,6 +241,7 @@ mod sni_resolver {
                         cipher_suites: &[],
                         certificate_authorities: None,
                         named_groups: None,
+                        bytes: &[],
                     })
                     .is_err()
             );
@@ -263,6 +264,7 @@ mod sni_resolver {
                         cipher_suites: &[],
                         certificate_authorities: None,
                         named_groups: None,
+                        bytes: &[],
                     })
                     .is_err()
             );